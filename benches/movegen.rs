@@ -0,0 +1,166 @@
+//! Baselines for move generation and the grid operations it relies on,
+//! measured on mid-game positions.
+//!
+//! Run with `cargo bench`, or `cargo bench -- all_moves` for a single group
+
+use anansii::engine::engines::{Engine, RandomEngine};
+use anansii::engine::transposition::TranspositionTable;
+use anansii::engine::Searcher;
+use anansii::game::GameDebugger;
+use anansii::generator::debug::*;
+use anansii::hex_grid::{HexGrid, PieceColor};
+use anansii::uhp::GameType;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// Plies played into each benchmarked game, where most pieces have been
+/// placed and the queens are under pressure
+const PLIES: [usize; 2] = [16, 30];
+
+/// The games played by two random engines for each of PLIES, so that the
+/// positions are the same on every run
+fn positions() -> Vec<(String, GameDebugger)> {
+    PLIES
+        .iter()
+        .map(|&plies| {
+            let mut game = GameDebugger::from_move_list(&[], GameType::MLP).unwrap();
+            let mut engines = [RandomEngine::new(1), RandomEngine::new(2)];
+            for ply in 0..plies {
+                let Some(position) = engines[ply % 2].best_move(&mut game) else {
+                    break;
+                };
+                game.append_position(&position).unwrap();
+            }
+            (format!("{} plies", plies), game)
+        })
+        .collect()
+}
+
+fn generator(game: &GameDebugger) -> ReferenceGenerator {
+    ReferenceGenerator::from_hex_grid(game.position(), game.game_type(), game.last_move())
+}
+
+/// The destinations of one piece of each type on the board
+fn bench_destinations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("destinations");
+    for (name, game) in positions() {
+        let generator = generator(&game);
+        let mut seen = vec![];
+        for (location, stack) in game.position().occupied() {
+            let piece_type = stack.last().unwrap().piece_type;
+            if seen.contains(&piece_type) {
+                continue;
+            }
+            seen.push(piece_type);
+            let id = BenchmarkId::new(format!("{:?}", piece_type), &name);
+            group.bench_function(id, |b| {
+                b.iter(|| generator.destinations(black_box(location)))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Every legal position of the player to move, including placements and
+/// pillbug swaps
+fn bench_all_moves(c: &mut Criterion) {
+    let mut group = c.benchmark_group("all_moves");
+    for (name, game) in positions() {
+        let color = game.player_to_move();
+        group.bench_function(&name, |b| {
+            b.iter(|| generator(black_box(&game)).generate_positions_for(color))
+        });
+    }
+    group.finish();
+}
+
+fn bench_clone(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clone");
+    for (name, game) in positions() {
+        group.bench_function(&name, |b| b.iter(|| black_box(game.position()).clone()));
+    }
+    group.finish();
+}
+
+fn bench_pinned(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pinned");
+    for (name, game) in positions() {
+        group.bench_function(&name, |b| b.iter(|| black_box(game.position()).pinned()));
+    }
+    group.finish();
+}
+
+/// The neighbor, slide and stack queries move generation makes for every
+/// piece, over every occupied location and the locations around it
+fn bench_neighbors(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbors");
+    for (name, game) in positions() {
+        let grid = game.position();
+        let locations = grid
+            .occupied()
+            .flat_map(|(location, _)| grid.neighbors(location).map(|(neighbor, _)| neighbor))
+            .collect::<Vec<_>>();
+        group.bench_function(BenchmarkId::new("get_neighbors", &name), |b| {
+            b.iter(|| {
+                for &location in &locations {
+                    black_box(grid.get_neighbors(location));
+                    black_box(grid.get_empty_neighbors(location));
+                }
+            })
+        });
+        group.bench_function(BenchmarkId::new("slidable", &name), |b| {
+            b.iter(|| {
+                for &location in &locations {
+                    black_box(grid.slidable_locations_3d(location));
+                }
+            })
+        });
+        group.bench_function(BenchmarkId::new("peek", &name), |b| {
+            b.iter(|| {
+                for &location in &locations {
+                    black_box(grid.peek(location));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_canonical(c: &mut Criterion) {
+    let mut group = c.benchmark_group("canonical");
+    for (name, game) in positions() {
+        group.bench_function(&name, |b| {
+            b.iter(|| black_box(game.position()).canonical_transform())
+        });
+    }
+    group.finish();
+}
+
+/// A search of the opening with a fresh transposition table, looking the
+/// positions of the first plies up by their canonical form or not, see
+/// Searcher::set_symmetry_plies()
+fn bench_symmetric_search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("symmetric_search");
+    for plies in [0, 3] {
+        group.bench_function(BenchmarkId::new("opening", plies), |b| {
+            b.iter(|| {
+                let mut searcher = Searcher::new(GameType::Standard);
+                searcher.set_transposition_table(Some(TranspositionTable::new(1 << 16)));
+                searcher.set_symmetry_plies(plies);
+                searcher.analyze(&HexGrid::new(), None, PieceColor::White, 3)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_destinations,
+    bench_all_moves,
+    bench_clone,
+    bench_pinned,
+    bench_neighbors,
+    bench_canonical,
+    bench_symmetric_search
+);
+criterion_main!(benches);
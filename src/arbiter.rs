@@ -0,0 +1,721 @@
+use crate::clock::{Clock, TimeControl};
+use crate::engine::evaluation::Score;
+use crate::engine::handle::{search, SearchLimits};
+use crate::engine::search::Searcher;
+use crate::game::{GameDebugger, GameDebuggerError, GameEvent, GameResult};
+use crate::piece::PieceColor;
+use crate::uhp::GameType;
+use crate::uhp_client::{BestMoveLimit, EngineLimits, Result, UhpClient, UhpClientError};
+use crate::zobrist;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// How long past the whole clock an engine may take to answer before it is
+/// cut off, as the answer takes time to reach the arbiter
+const TIMEOUT_GRACE: Duration = Duration::from_millis(500);
+
+/// Why a player lost without the game ending on the board
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Forfeit {
+    /// The player chose a move that is not legal by the rules of the arbiter
+    IllegalMove { move_string: String, reason: String },
+    /// The player's clock ran out
+    Time,
+    /// The engine exited, failed to answer or answered with an error
+    Failed(String),
+    /// The engine process crashed or was killed, see EngineLimits
+    Crashed(String),
+}
+
+impl Forfeit {
+    /// The reason to forfeit for an engine that failed with *error*
+    fn failed(error: UhpClientError) -> Forfeit {
+        match error {
+            UhpClientError::Crashed(..) => Forfeit::Crashed(error.to_string()),
+            error => Forfeit::Failed(error.to_string()),
+        }
+    }
+}
+
+/// Why the arbiter decided a game neither player could finish, see
+/// ArbiterConfig
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Adjudication {
+    /// The same board was reached this many times, whoever was to move
+    Shuffling(usize),
+    /// The arbiter's search scored the position this much for the winner
+    /// for EvalAdjudication::moves moves in a row
+    Evaluation(Score),
+}
+
+/// How a game run by the Arbiter ended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The game ended on the board
+    Finished(GameResult),
+    Forfeit {
+        loser: PieceColor,
+        reason: Forfeit,
+    },
+    /// An engine refused a move the arbiter accepted, so the game cannot go on
+    Disagreement(String),
+    /// Neither player won within the move limit, see ArbiterConfig
+    MoveLimit,
+    /// Stopped by the arbiter, with no winner if it called the game a draw
+    Adjudicated {
+        winner: Option<PieceColor>,
+        reason: Adjudication,
+    },
+}
+
+impl Outcome {
+    pub fn winner(&self) -> Option<PieceColor> {
+        match self {
+            Outcome::Finished(result) => result.winner(),
+            Outcome::Forfeit { loser, .. } => Some(loser.opposite()),
+            Outcome::Adjudicated { winner, .. } => *winner,
+            Outcome::Disagreement(_) | Outcome::MoveLimit => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Finished(result) => write!(f, "{:?}", result),
+            Outcome::Forfeit { loser, reason } => {
+                write!(f, "{} forfeits, ", loser.to_str())?;
+                match reason {
+                    Forfeit::IllegalMove {
+                        move_string,
+                        reason,
+                    } => write!(f, "illegal move {}: {}", move_string, reason),
+                    Forfeit::Time => write!(f, "out of time"),
+                    Forfeit::Failed(message) | Forfeit::Crashed(message) => {
+                        write!(f, "{}", message)
+                    }
+                }
+            }
+            Outcome::Disagreement(message) => write!(f, "aborted, {}", message),
+            Outcome::MoveLimit => write!(f, "move limit reached"),
+            Outcome::Adjudicated { winner, reason } => {
+                match winner {
+                    Some(winner) => write!(f, "{} wins by adjudication, ", winner.to_str())?,
+                    None => write!(f, "drawn by adjudication, ")?,
+                }
+                match reason {
+                    Adjudication::Shuffling(times) => {
+                        write!(f, "the same board was reached {} times", times)
+                    }
+                    Adjudication::Evaluation(score) => write!(f, "evaluated at {}", score),
+                }
+            }
+        }
+    }
+}
+
+/// Adjudicates a win once the arbiter's own search scores the position at
+/// least *threshold* for the same player after *moves* moves in a row
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EvalAdjudication {
+    pub threshold: Score,
+    pub moves: usize,
+    /// The depth of the search after every move, in plies
+    pub depth: usize,
+}
+
+impl Default for EvalAdjudication {
+    fn default() -> Self {
+        EvalAdjudication {
+            threshold: 500,
+            moves: 8,
+            depth: 2,
+        }
+    }
+}
+
+/// The rules of the games run by an Arbiter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArbiterConfig {
+    pub game_type: GameType,
+    /// The clock of each player, with or without byo-yomi
+    pub time_control: TimeControl,
+    /// The number of moves after which the game is stopped undecided
+    pub max_moves: usize,
+    /// Draws the game once the same board was reached this many times,
+    /// whoever is to move, to stop engines shuffling pieces back and forth
+    /// without repeating the exact state the rules draw on
+    pub shuffle_repetitions: Option<usize>,
+    /// Decides games one player is clearly winning, see EvalAdjudication
+    pub eval_adjudication: Option<EvalAdjudication>,
+    /// The resources each engine process may use
+    pub limits: EngineLimits,
+    /// Asks the player to move for its valid moves every turn and records
+    /// every difference to the moves the arbiter considers legal
+    pub check_valid_moves: bool,
+}
+
+impl Default for ArbiterConfig {
+    fn default() -> Self {
+        ArbiterConfig {
+            game_type: GameType::MLP,
+            time_control: TimeControl::default(),
+            max_moves: 300,
+            shuffle_repetitions: None,
+            eval_adjudication: None,
+            limits: EngineLimits::default(),
+            check_valid_moves: false,
+        }
+    }
+}
+
+/// Follows a game move by move for the adjudication rules of an
+/// ArbiterConfig
+#[derive(Clone, Debug)]
+struct Adjudicator {
+    shuffle_repetitions: Option<usize>,
+    eval_adjudication: Option<EvalAdjudication>,
+    /// How often each board was reached, by its Zobrist hash
+    boards: HashMap<u64, usize>,
+    /// The player the search favored after the last moves, and for how
+    /// many moves in a row
+    leader: Option<(PieceColor, usize)>,
+}
+
+impl Adjudicator {
+    fn new(config: &ArbiterConfig) -> Adjudicator {
+        Adjudicator {
+            shuffle_repetitions: config.shuffle_repetitions,
+            eval_adjudication: config.eval_adjudication,
+            boards: HashMap::new(),
+            leader: None,
+        }
+    }
+
+    /// Applies the rules to the position *game* reached with its last move,
+    /// returning the outcome if the game is to be stopped
+    fn update(&mut self, game: &GameDebugger) -> Option<Outcome> {
+        let times = self
+            .boards
+            .entry(zobrist::hash(game.position()))
+            .or_default();
+        *times += 1;
+        if self
+            .shuffle_repetitions
+            .is_some_and(|limit| *times >= limit)
+        {
+            return Some(Outcome::Adjudicated {
+                winner: None,
+                reason: Adjudication::Shuffling(*times),
+            });
+        }
+
+        let rule = self.eval_adjudication?;
+        let searcher = Searcher::new(game.game_type());
+        let score = search(game, searcher, SearchLimits::depth(rule.depth))?.score;
+        let (leader, score) = match score >= 0 {
+            true => (game.player_to_move(), score),
+            false => (game.player_to_move().opposite(), -score),
+        };
+        if score < rule.threshold {
+            self.leader = None;
+            return None;
+        }
+        let moves = match self.leader {
+            Some((color, moves)) if color == leader => moves + 1,
+            _ => 1,
+        };
+        self.leader = Some((leader, moves));
+        (moves >= rule.moves).then_some(Outcome::Adjudicated {
+            winner: Some(leader),
+            reason: Adjudication::Evaluation(score),
+        })
+    }
+}
+
+/// A game played by two engines under an Arbiter
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    /// The names the engines reported for themselves
+    pub white: String,
+    pub black: String,
+    pub game_type: GameType,
+    /// The moves played, as UHP MoveStrings
+    pub moves: Vec<String>,
+    /// The thinking time spent on each move
+    pub times: Vec<Duration>,
+    pub outcome: Outcome,
+    /// Every disagreement between an engine and the arbiter about which
+    /// moves are legal, see ArbiterConfig::check_valid_moves
+    pub discrepancies: Vec<String>,
+}
+
+impl GameRecord {
+    /// The UHP GameString of the final position, forfeited and adjudicated
+    /// games are left InProgress as UHP has no state for them
+    pub fn game_string(&self) -> String {
+        let state = match &self.outcome {
+            _ if self.moves.is_empty() => "NotStarted",
+            Outcome::Finished(result) => result.game_state(),
+            _ => "InProgress",
+        };
+        let color = match self.moves.len() % 2 {
+            0 => PieceColor::White,
+            _ => PieceColor::Black,
+        };
+        let mut game_string = format!(
+            "{};{};{}[{}]",
+            self.game_type.to_str(),
+            state,
+            color.to_str(),
+            self.moves.len() / 2 + 1
+        );
+        for move_string in self.moves.iter() {
+            game_string += ";";
+            game_string += move_string;
+        }
+        game_string
+    }
+}
+
+impl std::fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "White: {}", self.white)?;
+        writeln!(f, "Black: {}", self.black)?;
+        writeln!(f, "Result: {}", self.outcome)?;
+        for discrepancy in self.discrepancies.iter() {
+            writeln!(f, "Discrepancy: {}", discrepancy)?;
+        }
+        write!(f, "{}", self.game_string())
+    }
+}
+
+/// Referees games between two external UHP engines.
+///
+/// Every move an engine chooses is checked with the rules of this crate
+/// before it is passed on, and each engine's clock is enforced, so that a
+/// misbehaving engine loses the game instead of derailing it. Disagreements
+/// about the rules are recorded, which makes the arbiter useful for
+/// cross-checking the move generator against other engines
+#[derive(Clone, Debug, Default)]
+pub struct Arbiter {
+    pub config: ArbiterConfig,
+}
+
+impl Arbiter {
+    pub fn new(config: ArbiterConfig) -> Arbiter {
+        Arbiter { config }
+    }
+
+    /// Starts the engines run by the commands *white* and *black* and plays
+    /// a game between them.
+    ///
+    /// Fails only if the game cannot be set up, engines failing during the
+    /// game forfeit it instead
+    pub fn play(&self, white: &str, black: &str) -> Result<GameRecord> {
+        self.play_observed(white, black, |_| {})
+    }
+
+    /// Like play(), but calls *observer* with every move of the game and the
+    /// time left to its player after it, see GameDebugger::subscribe()
+    pub fn play_observed<F>(&self, white: &str, black: &str, observer: F) -> Result<GameRecord>
+    where
+        F: Fn(&GameEvent) + Send + Sync + 'static,
+    {
+        let limits = &self.config.limits;
+        let mut engines = [
+            UhpClient::spawn_with(white, limits)?,
+            UhpClient::spawn_with(black, limits)?,
+        ];
+        for engine in engines.iter_mut() {
+            engine.new_game(self.config.game_type.to_str())?;
+        }
+
+        let mut game = GameDebugger::from_move_list(&[], self.config.game_type)
+            .expect("The empty game should be valid");
+        game.subscribe(observer);
+        let mut record = GameRecord {
+            white: engines[0].name().to_string(),
+            black: engines[1].name().to_string(),
+            game_type: self.config.game_type,
+            moves: vec![],
+            times: vec![],
+            outcome: Outcome::MoveLimit,
+            discrepancies: vec![],
+        };
+        let mut clocks = [Clock::new(self.config.time_control); 2];
+        let mut adjudicator = Adjudicator::new(&self.config);
+
+        while record.moves.len() < self.config.max_moves {
+            if let Some(result) = game.game_result() {
+                record.outcome = Outcome::Finished(result);
+                return Ok(record);
+            }
+            let color = game.player_to_move();
+            let index = match color {
+                PieceColor::White => 0,
+                PieceColor::Black => 1,
+            };
+            let forfeit = |reason| Outcome::Forfeit {
+                loser: color,
+                reason,
+            };
+
+            if self.config.check_valid_moves {
+                match engines[index].valid_moves() {
+                    Ok(listed) => {
+                        let name = engines[index].name();
+                        record
+                            .discrepancies
+                            .extend(check_valid_moves(&game, name, &listed));
+                    }
+                    Err(e) => {
+                        record.outcome = forfeit(Forfeit::failed(e));
+                        return Ok(record);
+                    }
+                }
+            }
+
+            // The engine is asked to think for its share of the clock, and
+            // only cut off once the whole clock and a grace for answering
+            // are spent, the clock itself being enforced by spend()
+            let limit = BestMoveLimit::Time(clocks[index].move_time());
+            let command = format!("bestmove {}", limit.to_uhp());
+            let timeout = clocks[index].available() + TIMEOUT_GRACE;
+            let start = Instant::now();
+            let response = engines[index].send(&command, Some(timeout));
+            let elapsed = start.elapsed();
+            let move_string = match response {
+                Err(UhpClientError::Timeout(_)) => {
+                    record.outcome = forfeit(Forfeit::Time);
+                    return Ok(record);
+                }
+                Err(e) => {
+                    record.outcome = forfeit(Forfeit::failed(e));
+                    return Ok(record);
+                }
+                Ok(lines) => lines.concat(),
+            };
+            if !clocks[index].spend(elapsed) {
+                record.outcome = forfeit(Forfeit::Time);
+                return Ok(record);
+            }
+
+            // Moves are passed on the way this crate writes them, so that
+            // both engines also have to understand its notation
+            let before = game.clone();
+            if let Err(e) = game.make_move(&move_string) {
+                let reason = match e {
+                    GameDebuggerError::IllegalMove(reason) => reason.to_string(),
+                    e => e.to_string(),
+                };
+                record.outcome = forfeit(Forfeit::IllegalMove {
+                    move_string,
+                    reason,
+                });
+                return Ok(record);
+            }
+            let move_string = before.move_string(game.position()).unwrap_or(move_string);
+
+            for (other, engine) in engines.iter_mut().enumerate() {
+                match engine.play(&move_string) {
+                    Ok(_) => {}
+                    Err(e @ UhpClientError::Rejected { .. }) => {
+                        record.outcome = Outcome::Disagreement(e.to_string());
+                        return Ok(record);
+                    }
+                    Err(e) => {
+                        record.outcome = Outcome::Forfeit {
+                            loser: [PieceColor::White, PieceColor::Black][other],
+                            reason: Forfeit::failed(e),
+                        };
+                        return Ok(record);
+                    }
+                }
+            }
+            game.update_clock(color, clocks[index].available());
+            record.moves.push(move_string);
+            record.times.push(elapsed);
+
+            if game.game_result().is_none() {
+                if let Some(outcome) = adjudicator.update(&game) {
+                    record.outcome = outcome;
+                    return Ok(record);
+                }
+            }
+        }
+
+        if let Some(result) = game.game_result() {
+            record.outcome = Outcome::Finished(result);
+        }
+        Ok(record)
+    }
+}
+
+/// Compares the moves an engine lists as valid with the legal moves of the
+/// current position of *game*, describing every difference
+fn check_valid_moves(game: &GameDebugger, engine: &str, listed: &[String]) -> Vec<String> {
+    let mut game = game.clone();
+    let legal = game.legal_positions();
+    let mut discrepancies = vec![];
+    let mut found = HashSet::new();
+    for move_string in listed {
+        let mut after = game.clone();
+        match after.make_move(move_string) {
+            Ok(()) => {
+                found.insert(after.position().clone());
+            }
+            Err(e) => discrepancies.push(format!(
+                "{} lists {} after {} moves, which is illegal: {}",
+                engine,
+                move_string,
+                game.positions().count() - 1,
+                e
+            )),
+        }
+    }
+    for position in legal.iter().filter(|position| !found.contains(*position)) {
+        let move_string = game
+            .move_string(position)
+            .unwrap_or_else(|_| "unknown".to_string());
+        discrepancies.push(format!(
+            "{} misses the legal move {} after {} moves",
+            engine,
+            move_string,
+            game.positions().count() - 1
+        ));
+    }
+    discrepancies
+}
+
+/// Plays a game between the engines run by *white* and *black*, printing
+/// its record and writing its GameString to *output* if given
+pub fn print_match(
+    white: &str,
+    black: &str,
+    config: ArbiterConfig,
+    output: Option<&std::path::Path>,
+) {
+    let record = match Arbiter::new(config).play(white, black) {
+        Ok(record) => record,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    println!("{}", record);
+    if let Some(path) = output {
+        if let Err(e) = std::fs::write(path, record.game_string() + "\n") {
+            println!("Could not write {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::testing::temp_path;
+    #[cfg(unix)]
+    use std::path::PathBuf;
+
+    /// A shell script run as an engine, removed once dropped
+    #[cfg(unix)]
+    struct Script {
+        path: PathBuf,
+        command: String,
+    }
+
+    #[cfg(unix)]
+    impl Script {
+        fn new(script: &str) -> Script {
+            let path = temp_path("engine.sh");
+            std::fs::write(&path, script).unwrap();
+            let command = format!("sh {}", path.display());
+            Script { path, command }
+        }
+    }
+
+    #[cfg(unix)]
+    impl Drop for Script {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    /// An engine that answers every bestmove with *move_string* after
+    /// *delay* seconds, and everything else with just "ok"
+    #[cfg(unix)]
+    fn scripted_engine(move_string: &str, delay: &str) -> Script {
+        Script::new(&format!(
+            "echo id scripted; echo ok; while read line; do case $line in bestmove*) sleep {}; echo {};; esac; echo ok; done",
+            delay, move_string
+        ))
+    }
+
+    #[test]
+    #[cfg(unix)]
+    pub fn test_arbiter() {
+        let config = ArbiterConfig {
+            game_type: GameType::Standard,
+            time_control: TimeControl::fischer(Duration::from_secs(5), Duration::ZERO),
+            ..Default::default()
+        };
+        let arbiter = Arbiter::new(config.clone());
+
+        // Black repeats white's first move, which is illegal for it
+        let script = scripted_engine("wA1", "0");
+        let engine = script.command.as_str();
+        let (sender, events) = std::sync::mpsc::channel();
+        let record = arbiter
+            .play_observed(engine, engine, move |event| {
+                sender.send(event.clone()).unwrap();
+            })
+            .unwrap();
+        let events = events.try_iter().collect::<Vec<_>>();
+        assert!(matches!(
+            &events[..],
+            [
+                GameEvent::MoveApplied { move_string, .. },
+                GameEvent::Clock {
+                    color: PieceColor::White,
+                    remaining
+                }
+            ] if move_string == "wA1" && *remaining <= config.time_control.main
+        ));
+        assert_eq!(record.white, "scripted");
+        assert_eq!(record.moves, vec!["wA1"]);
+        assert!(matches!(
+            record.outcome,
+            Outcome::Forfeit {
+                loser: PieceColor::Black,
+                reason: Forfeit::IllegalMove { .. }
+            }
+        ));
+        assert_eq!(record.outcome.winner(), Some(PieceColor::White));
+        assert_eq!(record.game_string(), "Base;InProgress;Black[1];wA1");
+
+        let slow = scripted_engine("wA1", "2");
+        let slow = slow.command.as_str();
+        let arbiter = Arbiter::new(ArbiterConfig {
+            time_control: TimeControl::fischer(Duration::from_secs(1), Duration::ZERO),
+            ..config.clone()
+        });
+        let record = arbiter.play(slow, engine).unwrap();
+        assert_eq!(
+            record.outcome,
+            Outcome::Forfeit {
+                loser: PieceColor::White,
+                reason: Forfeit::Time
+            }
+        );
+
+        assert!(matches!(
+            arbiter.play("", engine),
+            Err(UhpClientError::EmptyCommand)
+        ));
+
+        // Black's engine crashes instead of moving
+        let crashing = Script::new("echo id crashing; echo ok; while read line; do case $line in bestmove*) kill -SEGV $$;; esac; echo ok; done");
+        let record = Arbiter::new(config)
+            .play(engine, &crashing.command)
+            .unwrap();
+        assert!(matches!(
+            record.outcome,
+            Outcome::Forfeit {
+                loser: PieceColor::Black,
+                reason: Forfeit::Crashed(_)
+            }
+        ));
+        assert_eq!(
+            record.outcome.to_string(),
+            "Black forfeits, crashing crashed: segmentation fault"
+        );
+    }
+
+    #[test]
+    pub fn test_adjudicator() {
+        let config = ArbiterConfig {
+            shuffle_repetitions: Some(2),
+            eval_adjudication: Some(EvalAdjudication {
+                moves: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        // The queens go back and forth to the board after the fourth move
+        let moves = [
+            "wA1", "bA1 wA1-", "wQ -wA1", "bQ bA1-", r"wQ \wA1", "bQ bA1/", "wQ -wA1", "bQ bA1-",
+        ];
+        let mut adjudicator = Adjudicator::new(&config);
+        let mut outcomes = vec![];
+        for turn in 1..=moves.len() {
+            let game = GameDebugger::from_move_list(&moves[..turn], GameType::Standard).unwrap();
+            outcomes.push(adjudicator.update(&game));
+        }
+        assert!(outcomes[..7].iter().all(Option::is_none));
+        let shuffled = outcomes[7].clone().unwrap();
+        assert_eq!(
+            shuffled,
+            Outcome::Adjudicated {
+                winner: None,
+                reason: Adjudication::Shuffling(2)
+            }
+        );
+        assert_eq!(
+            shuffled.to_string(),
+            "drawn by adjudication, the same board was reached 2 times"
+        );
+
+        // White surrounds the black queen next move, once is not enough
+        let moves = [
+            "wP",
+            "bL wP-",
+            r"wB1 \wP",
+            "bQ bL/",
+            "wA1 /wB1",
+            r"bA1 \bQ",
+            r"wQ wA1\",
+            "bB1 bQ/",
+            "wB1 wP",
+            r"bG1 bB1\",
+            r"wA1 bQ\",
+            "bG2 bG1/",
+        ];
+        let game = GameDebugger::from_move_list(&moves, GameType::MLP).unwrap();
+        let mut adjudicator = Adjudicator::new(&ArbiterConfig {
+            shuffle_repetitions: None,
+            ..config
+        });
+        assert_eq!(adjudicator.update(&game), None);
+        let outcome = adjudicator.update(&game).unwrap();
+        assert_eq!(outcome.winner(), Some(PieceColor::White));
+        assert!(matches!(
+            outcome,
+            Outcome::Adjudicated {
+                reason: Adjudication::Evaluation(score),
+                ..
+            } if score >= 500
+        ));
+    }
+
+    #[test]
+    pub fn test_check_valid_moves() {
+        let game = GameDebugger::from_move_list(&["wA1"], GameType::Standard).unwrap();
+        let mut legal = game.clone();
+        let listed = legal
+            .legal_positions()
+            .iter()
+            .map(|position| game.move_string(position).unwrap())
+            .collect::<Vec<_>>();
+        assert!(check_valid_moves(&game, "engine", &listed).is_empty());
+
+        let discrepancies = check_valid_moves(&game, "engine", &listed[1..]);
+        assert_eq!(discrepancies.len(), 1);
+        assert!(discrepancies[0].contains("misses the legal move"));
+        let listed = [listed, vec!["bQ".to_string()]].concat();
+        let discrepancies = check_valid_moves(&game, "engine", &listed);
+        assert_eq!(discrepancies.len(), 1);
+        assert!(discrepancies[0].contains("lists bQ"));
+    }
+}
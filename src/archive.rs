@@ -0,0 +1,517 @@
+//! Compressed archives of many games, such as self-play corpora, with any
+//! game readable by its id without decompressing the others.
+//!
+//! Games are numbered from 0 in the order they are added, and stored in
+//! blocks of consecutive games, each compressed with zstd on its own:
+//!
+//! ```text
+//! "AGA2"
+//! for each block, a zstd frame holding for each game of the block
+//!     its length (u32) and the game (see encoding::encode_game())
+//! the index: the number of blocks (u64), then for each block
+//!     its offset from the start of the archive (u64)
+//!     its compressed length (u64)
+//!     its number of games (u32)
+//!     the SHA-256 digest of the frame (32 bytes)
+//! the length of the signature of the index (u16) and the signature, empty
+//!     if the archive is unsigned (see ArchiveWriter::finish_signed())
+//! the offset of the index (u64)
+//! ```
+//!
+//! Numbers are little endian. Every block read is checked against its
+//! digest, and as the index holds the digests, signing the digest of the
+//! index (see Archive::digest()) vouches for every game. Archives starting
+//! with "AGAR" have neither digests nor a signature
+use crate::encoding::{self, Digest, EncodingError, Signer, Verifier};
+use crate::game::{GameDebugger, GameDebuggerError, GameResult};
+use crate::hex_grid::HexGrid;
+use crate::moves::Move;
+use crate::uhp::GameType;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use thiserror::Error;
+
+/// The first bytes of an archive
+const MAGIC: &[u8; 4] = b"AGA2";
+
+/// The first bytes of archives without digests. They can still be read
+const MAGIC_1: &[u8; 4] = b"AGAR";
+
+/// The number of games compressed together unless told otherwise, see
+/// ArchiveWriter::with_block_size()
+pub const DEFAULT_BLOCK_SIZE: usize = 256;
+
+/// The zstd compression level of the blocks
+const LEVEL: i32 = 9;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Could not access the archive: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a game archive")]
+    NotAnArchive,
+    #[error("The archive holds no game {0}")]
+    UnknownGame(u64),
+    #[error("Corrupt archive, {0}")]
+    Encoding(#[from] EncodingError),
+    #[error("Game {0} cannot be replayed: {1}")]
+    InvalidGame(u64, GameDebuggerError),
+    #[error("Block {0} of the archive does not match its digest, it was corrupted or changed")]
+    DigestMismatch(usize),
+    #[error("The signature of the archive is missing or invalid")]
+    InvalidSignature,
+}
+
+pub type Result<T> = std::result::Result<T, ArchiveError>;
+
+/// Where a block is stored, see the layout above
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Block {
+    offset: u64,
+    length: u64,
+    games: u32,
+    /// None in archives without digests
+    digest: Option<Digest>,
+}
+
+/// The index of the archive with *blocks*, without the signature
+fn index_bytes(blocks: &[Block]) -> Vec<u8> {
+    let mut index = (blocks.len() as u64).to_le_bytes().to_vec();
+    for block in blocks {
+        index.extend(block.offset.to_le_bytes());
+        index.extend(block.length.to_le_bytes());
+        index.extend(block.games.to_le_bytes());
+        index.extend(block.digest.iter().flatten());
+    }
+    index
+}
+
+/// Writes an archive, see the layout above. Nothing can be read back until
+/// finish() wrote the index
+#[derive(Debug)]
+pub struct ArchiveWriter<W: Write> {
+    output: W,
+    offset: u64,
+    block_size: usize,
+    /// The encoded games of the block being filled
+    pending: Vec<u8>,
+    pending_games: u32,
+    blocks: Vec<Block>,
+    games: u64,
+}
+
+impl ArchiveWriter<BufWriter<File>> {
+    /// Creates (or truncates) the archive at *path*
+    pub fn create(path: &Path) -> Result<Self> {
+        ArchiveWriter::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(output: W) -> Result<Self> {
+        ArchiveWriter::with_block_size(output, DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Compresses *block_size* games together. Larger blocks compress
+    /// better, smaller ones are faster to read a single game from
+    pub fn with_block_size(mut output: W, block_size: usize) -> Result<Self> {
+        output.write_all(MAGIC)?;
+        Ok(ArchiveWriter {
+            output,
+            offset: MAGIC.len() as u64,
+            block_size: block_size.max(1),
+            pending: vec![],
+            pending_games: 0,
+            blocks: vec![],
+            games: 0,
+        })
+    }
+
+    /// Adds the game of *game_type* made of *moves*, ended by the players
+    /// with *ending* if given (see encoding::encode_game()), returning its id
+    pub fn add(
+        &mut self,
+        game_type: GameType,
+        moves: &[Move],
+        ending: Option<&GameResult>,
+    ) -> Result<u64> {
+        let game = encoding::encode_game(game_type, moves, ending);
+        self.pending.extend((game.len() as u32).to_le_bytes());
+        self.pending.extend(game);
+        self.pending_games += 1;
+        if self.pending_games as usize >= self.block_size {
+            self.flush_block()?;
+        }
+        self.games += 1;
+        Ok(self.games - 1)
+    }
+
+    /// Adds every move played in *game* and how it ended, returning its id
+    pub fn add_game(&mut self, game: &GameDebugger) -> Result<u64> {
+        let moves = game
+            .positions()
+            .zip(game.positions().skip(1))
+            .map(|(before, after)| before.diff(after).unwrap_or(Move::Pass))
+            .collect::<Vec<_>>();
+        self.add(game.game_type(), &moves, game.game_result().as_ref())
+    }
+
+    /// The number of games added so far
+    pub fn len(&self) -> u64 {
+        self.games
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games == 0
+    }
+
+    /// Writes the last block and the index, returning the output
+    pub fn finish(self) -> Result<W> {
+        self.finish_with(None)
+    }
+
+    /// Like finish(), signing the digest of the index with *signer*
+    pub fn finish_signed(self, signer: &dyn Signer) -> Result<W> {
+        self.finish_with(Some(signer))
+    }
+
+    fn finish_with(mut self, signer: Option<&dyn Signer>) -> Result<W> {
+        self.flush_block()?;
+        let mut index = index_bytes(&self.blocks);
+        let digest = encoding::digest(&index);
+        let signature = signer.map_or(vec![], |signer| signer.sign(&digest));
+        index.extend((signature.len() as u16).to_le_bytes());
+        index.extend(signature);
+        index.extend(self.offset.to_le_bytes());
+        self.output.write_all(&index)?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    fn flush_block(&mut self) -> Result<()> {
+        if self.pending_games == 0 {
+            return Ok(());
+        }
+        let compressed = zstd::encode_all(self.pending.as_slice(), LEVEL)?;
+        self.output.write_all(&compressed)?;
+        self.blocks.push(Block {
+            offset: self.offset,
+            length: compressed.len() as u64,
+            games: self.pending_games,
+            digest: Some(encoding::digest(&compressed)),
+        });
+        self.offset += compressed.len() as u64;
+        self.pending.clear();
+        self.pending_games = 0;
+        Ok(())
+    }
+}
+
+/// Reads games from an archive made by an ArchiveWriter, loading only the
+/// index up front
+#[derive(Debug)]
+pub struct Archive<R: Read + Seek> {
+    input: R,
+    blocks: Vec<Block>,
+    /// The id of the first game of each block
+    first_ids: Vec<u64>,
+    games: u64,
+    /// The signature of the index, empty if the archive is unsigned
+    signature: Vec<u8>,
+    /// The index and decompressed games of the block read last, as reads
+    /// of nearby games are common
+    cached: Option<(usize, Vec<u8>)>,
+}
+
+impl Archive<BufReader<File>> {
+    /// Opens the archive at *path*
+    pub fn open(path: &Path) -> Result<Self> {
+        Archive::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> Archive<R> {
+    /// Reads the index of the archive *input*
+    pub fn new(mut input: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        input.seek(SeekFrom::Start(0))?;
+        input
+            .read_exact(&mut magic)
+            .map_err(|_| ArchiveError::NotAnArchive)?;
+        let digests = match &magic {
+            MAGIC => true,
+            MAGIC_1 => false,
+            _ => return Err(ArchiveError::NotAnArchive),
+        };
+        input.seek(SeekFrom::End(-8))?;
+        let index_offset = read_u64(&mut input)?;
+        input.seek(SeekFrom::Start(index_offset))?;
+
+        let mut blocks = vec![];
+        let mut first_ids = vec![];
+        let mut games = 0;
+        for _ in 0..read_u64(&mut input)? {
+            let block = Block {
+                offset: read_u64(&mut input)?,
+                length: read_u64(&mut input)?,
+                games: read_u32(&mut input)?,
+                digest: match digests {
+                    true => Some(read_bytes(&mut input)?),
+                    false => None,
+                },
+            };
+            first_ids.push(games);
+            games += block.games as u64;
+            blocks.push(block);
+        }
+        let signature = match digests {
+            true => {
+                let length = u16::from_le_bytes(read_bytes(&mut input)?);
+                let mut signature = vec![0; length as usize];
+                input.read_exact(&mut signature)?;
+                signature
+            }
+            false => vec![],
+        };
+        Ok(Archive {
+            input,
+            blocks,
+            first_ids,
+            games,
+            signature,
+            cached: None,
+        })
+    }
+
+    /// The number of games in the archive
+    pub fn len(&self) -> u64 {
+        self.games
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games == 0
+    }
+
+    /// The digest of the index, which covers every game of archives with
+    /// digests, for publishing along with the archive
+    pub fn digest(&self) -> Digest {
+        encoding::digest(&index_bytes(&self.blocks))
+    }
+
+    /// Reads every block, checking it against its digest and, if a
+    /// *verifier* is given, the signature of the index
+    pub fn verify(&mut self, verifier: Option<&dyn Verifier>) -> Result<()> {
+        if let Some(verifier) = verifier {
+            let digest = self.digest();
+            if self.signature.is_empty() || !verifier.verify(&digest, &self.signature) {
+                return Err(ArchiveError::InvalidSignature);
+            }
+        }
+        for block in 0..self.blocks.len() {
+            self.block(block)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the game type, the moves and the ending decided by the
+    /// players (see encoding::decode_game()) of the game numbered *id*
+    pub fn get(&mut self, id: u64) -> Result<(GameType, Vec<Move>, Option<GameResult>)> {
+        if id >= self.games {
+            return Err(ArchiveError::UnknownGame(id));
+        }
+        let block = self.first_ids.partition_point(|first| *first <= id) - 1;
+        let first = self.first_ids[block];
+        let games = self.block(block)?;
+
+        let mut offset = 0;
+        for _ in first..id {
+            offset += 4 + game_length(games, offset)?;
+        }
+        let length = game_length(games, offset)?;
+        let game = games
+            .get(offset + 4..offset + 4 + length)
+            .ok_or(EncodingError::Truncated)?;
+        Ok(encoding::decode_game(game)?)
+    }
+
+    /// Replays the game numbered *id*, checking every move to be legal
+    pub fn game(&mut self, id: u64) -> Result<GameDebugger> {
+        let (game_type, moves, ending) = self.get(id)?;
+        let mut positions = vec![HexGrid::new()];
+        for mv in moves {
+            positions.push(mv.apply(positions.last().unwrap()));
+        }
+        let mut game = GameDebugger::from_positions_custom(&positions, game_type)
+            .map_err(|e| ArchiveError::InvalidGame(id, e))?;
+        if let Some(ending) = ending {
+            game.apply_game_state(ending.game_state())
+                .map_err(|e| ArchiveError::InvalidGame(id, e))?;
+        }
+        Ok(game)
+    }
+
+    /// The decompressed games of the block numbered *block*
+    fn block(&mut self, block: usize) -> Result<&[u8]> {
+        if self.cached.as_ref().map(|(cached, _)| *cached) != Some(block) {
+            let Block {
+                offset,
+                length,
+                digest,
+                ..
+            } = self.blocks[block];
+            let mut compressed = vec![0; length as usize];
+            self.input.seek(SeekFrom::Start(offset))?;
+            self.input.read_exact(&mut compressed)?;
+            if digest.is_some_and(|digest| digest != encoding::digest(&compressed)) {
+                return Err(ArchiveError::DigestMismatch(block));
+            }
+            self.cached = Some((block, zstd::decode_all(compressed.as_slice())?));
+        }
+        Ok(&self.cached.as_ref().unwrap().1)
+    }
+}
+
+/// Reads the length of the game encoded at *offset* of a block
+fn game_length(games: &[u8], offset: usize) -> Result<usize> {
+    let length = games
+        .get(offset..offset + 4)
+        .ok_or(EncodingError::Truncated)?;
+    Ok(u32::from_le_bytes(length.try_into().unwrap()) as usize)
+}
+
+fn read_bytes<const N: usize>(input: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u64(input: &mut impl Read) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(input)?))
+}
+
+fn read_u32(input: &mut impl Read) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(input)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameAction;
+    use crate::piece::PieceColor;
+    use crate::testing::fuzz::play_moves;
+    use crate::testing::KeyedSigner;
+    use std::io::Cursor;
+
+    #[test]
+    pub fn test_archive() {
+        let games = (0..7u8)
+            .map(|seed| {
+                let choices = (0..20)
+                    .map(|i| seed.wrapping_mul(31) ^ (i * 7))
+                    .collect::<Vec<_>>();
+                play_moves(&[&[seed][..], &choices].concat())
+            })
+            .collect::<Vec<_>>();
+
+        let mut writer = ArchiveWriter::with_block_size(Cursor::new(vec![]), 3).unwrap();
+        for (id, game) in games.iter().enumerate() {
+            assert_eq!(writer.add_game(game).unwrap(), id as u64);
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive = Archive::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(archive.len(), 7);
+        assert_eq!(archive.blocks.len(), 3);
+        // Out of order, across blocks
+        for id in [6, 0, 4, 1, 5, 3, 2] {
+            let game = archive.game(id).unwrap();
+            let expected = &games[id as usize];
+            assert_eq!(game.game_type(), expected.game_type());
+            assert!(game.positions().eq(expected.positions()));
+        }
+        assert!(matches!(archive.get(7), Err(ArchiveError::UnknownGame(7))));
+
+        // Endings decided by the players are kept
+        let mut resigned = games[0].clone();
+        resigned.act(PieceColor::White, GameAction::Resign).unwrap();
+        let mut writer = ArchiveWriter::new(Cursor::new(vec![])).unwrap();
+        writer.add_game(&resigned).unwrap();
+        let mut archive = Archive::new(Cursor::new(writer.finish().unwrap().into_inner())).unwrap();
+        let game = archive.game(0).unwrap();
+        assert_eq!(
+            game.game_result(),
+            Some(GameResult::Resigned(PieceColor::White))
+        );
+        assert!(game.positions().eq(resigned.positions()));
+
+        let empty = ArchiveWriter::new(Cursor::new(vec![])).unwrap();
+        let empty = Archive::new(Cursor::new(empty.finish().unwrap().into_inner())).unwrap();
+        assert!(empty.is_empty());
+        assert!(matches!(
+            Archive::new(Cursor::new(b"APDB and more".to_vec())),
+            Err(ArchiveError::NotAnArchive)
+        ));
+        assert!(Archive::new(Cursor::new(bytes[..bytes.len() - 30].to_vec())).is_err());
+    }
+
+    #[test]
+    pub fn test_archive_integrity() {
+        let key = KeyedSigner(*b"key1");
+        let game = play_moves(&[3, 1, 4, 1, 5, 9, 2, 6]);
+        let mut writer = ArchiveWriter::with_block_size(Cursor::new(vec![]), 1).unwrap();
+        for _ in 0..3 {
+            writer.add_game(&game).unwrap();
+        }
+        let signed = writer.finish_signed(&key).unwrap().into_inner();
+        let mut archive = Archive::new(Cursor::new(signed.clone())).unwrap();
+        archive.verify(Some(&key)).unwrap();
+        assert!(matches!(
+            archive.verify(Some(&KeyedSigner(*b"key2"))),
+            Err(ArchiveError::InvalidSignature)
+        ));
+        let digest = archive.digest();
+
+        // A bit flipped in the second block, caught before decompressing it
+        let second = archive.blocks[1].offset as usize;
+        let mut corrupted = signed.clone();
+        corrupted[second + 5] ^= 1;
+        let mut archive = Archive::new(Cursor::new(corrupted)).unwrap();
+        assert_eq!(archive.digest(), digest);
+        assert!(archive.game(0).is_ok());
+        assert!(matches!(
+            archive.game(1),
+            Err(ArchiveError::DigestMismatch(1))
+        ));
+        assert!(matches!(
+            archive.verify(None),
+            Err(ArchiveError::DigestMismatch(1))
+        ));
+
+        // Unsigned archives only verify without a verifier
+        let mut writer = ArchiveWriter::new(Cursor::new(vec![])).unwrap();
+        writer.add_game(&game).unwrap();
+        let unsigned = writer.finish().unwrap().into_inner();
+        let mut archive = Archive::new(Cursor::new(unsigned)).unwrap();
+        archive.verify(None).unwrap();
+        assert!(matches!(
+            archive.verify(Some(&key)),
+            Err(ArchiveError::InvalidSignature)
+        ));
+
+        // Archives written before digests are still read
+        let encoded = encoding::encode_game(game.game_type(), &[], None);
+        let games = [&(encoded.len() as u32).to_le_bytes()[..], &encoded].concat();
+        let block = zstd::encode_all(games.as_slice(), LEVEL).unwrap();
+        let mut old = MAGIC_1.to_vec();
+        old.extend(&block);
+        old.extend(1u64.to_le_bytes());
+        old.extend((MAGIC_1.len() as u64).to_le_bytes());
+        old.extend((block.len() as u64).to_le_bytes());
+        old.extend(1u32.to_le_bytes());
+        old.extend(((MAGIC_1.len() + block.len()) as u64).to_le_bytes());
+        let mut archive = Archive::new(Cursor::new(old)).unwrap();
+        archive.verify(None).unwrap();
+        assert_eq!(archive.get(0).unwrap(), (game.game_type(), vec![], None));
+    }
+}
@@ -29,7 +29,7 @@ const GRID_SIZE: usize = GRID_WIDTH * GRID_HEIGHT;
 /// TODO: conventional?? Isn't negative x going in the wrong direction? Perhaps
 /// compare it to HexGridLocation instead
 ///
-/// ```
+/// ```text
 ///    48 47 46 45 44 43 42
 ///    41 40 39 38 37 36 35
 ///    34 33 32 31 30 29 28
@@ -59,14 +59,14 @@ impl GridBounds {
             self.bottom_right.board_index % GRID_WIDTH,
         );
 
-        let board_difference = max_board_x as i8 - min_board_x as i8;
-        let extra_padding = (board_difference - 1) * BITBOARD_WIDTH as i8;
+        let board_difference = max_board_x as Coordinate - min_board_x as Coordinate;
+        let extra_padding = (board_difference - 1) * BITBOARD_WIDTH as Coordinate;
 
         // Contribution from left width of rightmost bitboard
-        let mut width = (BITBOARD_WIDTH - right_x) as i8;
+        let mut width = (BITBOARD_WIDTH - right_x) as Coordinate;
 
-        width += extra_padding as i8;
-        width += left_x as i8 + 1;
+        width += extra_padding as Coordinate;
+        width += left_x as Coordinate + 1;
 
         width as usize
     }
@@ -82,14 +82,14 @@ impl GridBounds {
             self.bottom_right.board_index / GRID_HEIGHT,
         );
 
-        let board_difference = max_board_y as i8 - min_board_y as i8;
-        let extra_padding = (board_difference - 1) * BITBOARD_HEIGHT as i8;
+        let board_difference = max_board_y as Coordinate - min_board_y as Coordinate;
+        let extra_padding = (board_difference - 1) * BITBOARD_HEIGHT as Coordinate;
 
         // Contribution from top height of bottommost bitboard
-        let mut height = (BITBOARD_HEIGHT - bottom_y) as i8;
+        let mut height = (BITBOARD_HEIGHT - bottom_y) as Coordinate;
 
-        height += extra_padding as i8;
-        height += top_y as i8 + 1;
+        height += extra_padding as Coordinate;
+        height += top_y as Coordinate + 1;
 
         height as usize
     }
@@ -137,10 +137,10 @@ impl BasicBitGrid {
     /// Deterministically chooses a HexLocation that contains to a single piece
     /// on the board. Returns none if no piece exists within the bounds of an equivalent HexGrid
     fn find_one_hex(&self) -> Option<HexLocation> {
-        let left = -((HEX_GRID_SIZE / 2) as i8);
-        let right = (HEX_GRID_SIZE / 2) as i8;
-        let top = -((HEX_GRID_SIZE / 2) as i8);
-        let bottom = (HEX_GRID_SIZE / 2) as i8;
+        let left = -((HEX_GRID_SIZE / 2) as Coordinate);
+        let right = (HEX_GRID_SIZE / 2) as Coordinate;
+        let top = -((HEX_GRID_SIZE / 2) as Coordinate);
+        let bottom = (HEX_GRID_SIZE / 2) as Coordinate;
 
         for row in top..=bottom {
             for col in left..=right {
@@ -420,9 +420,9 @@ impl BitGridLocation {
         use Direction::*;
         let x = self.bitboard_index % BITBOARD_WIDTH;
         let y = self.bitboard_index / BITBOARD_HEIGHT;
-        let board = self.board_index as i8;
+        let board = self.board_index as Coordinate;
 
-        let (x, y) = (x as i8, y as i8);
+        let (x, y) = (x as Coordinate, y as Coordinate);
         let (x, y) = match direction {
             E => (x - 1, y),
             W => (x + 1, y),
@@ -437,12 +437,12 @@ impl BitGridLocation {
 
     /// Deals with overflow and underflow of the x and y coordinates
     /// of a given board
-    fn wrap(&self, board: i8, x: i8, y: i8) -> BitGridLocation {
-        let width = BITBOARD_WIDTH as i8;
-        let height = BITBOARD_HEIGHT as i8;
+    fn wrap(&self, board: Coordinate, x: Coordinate, y: Coordinate) -> BitGridLocation {
+        let width = BITBOARD_WIDTH as Coordinate;
+        let height = BITBOARD_HEIGHT as Coordinate;
 
-        let board_x = board % GRID_WIDTH as i8;
-        let board_y = board / GRID_HEIGHT as i8;
+        let board_x = board % GRID_WIDTH as Coordinate;
+        let board_y = board / GRID_HEIGHT as Coordinate;
 
         let dx = match x {
             x if x < 0 => -1,
@@ -456,10 +456,10 @@ impl BitGridLocation {
             _ => 0,
         };
 
-        let board_x = (board_x + dx).rem_euclid(GRID_WIDTH as i8);
-        let board_y = (board_y + dy).rem_euclid(GRID_HEIGHT as i8);
+        let board_x = (board_x + dx).rem_euclid(GRID_WIDTH as Coordinate);
+        let board_y = (board_y + dy).rem_euclid(GRID_HEIGHT as Coordinate);
 
-        let new_board_index = board_y * GRID_HEIGHT as i8 + board_x;
+        let new_board_index = board_y * GRID_HEIGHT as Coordinate + board_x;
         let new_bitboard_index = y.rem_euclid(height) * height + x.rem_euclid(width);
 
         BitGridLocation::new(new_board_index as usize, new_bitboard_index as usize)
@@ -468,24 +468,26 @@ impl BitGridLocation {
 
 impl FromHex for BitGridLocation {
     fn from_hex(hex: HexLocation) -> Self {
-        let center_x = (CENTER_BITBOARD_INDEX % BITBOARD_WIDTH) as i8;
-        let center_y = (CENTER_BITBOARD_INDEX / BITBOARD_HEIGHT) as i8;
-        let board_center_x = (CENTER_BOARD_INDEX % GRID_WIDTH) as i8;
-        let board_center_y = (CENTER_BOARD_INDEX / GRID_HEIGHT) as i8;
+        let center_x = (CENTER_BITBOARD_INDEX % BITBOARD_WIDTH) as Coordinate;
+        let center_y = (CENTER_BITBOARD_INDEX / BITBOARD_HEIGHT) as Coordinate;
+        let board_center_x = (CENTER_BOARD_INDEX % GRID_WIDTH) as Coordinate;
+        let board_center_y = (CENTER_BOARD_INDEX / GRID_HEIGHT) as Coordinate;
 
-        let bit_x = (center_x - hex.x + BITBOARD_WIDTH as i8).rem_euclid(BITBOARD_WIDTH as i8);
-        let bit_y = (center_y - hex.y + BITBOARD_HEIGHT as i8).rem_euclid(BITBOARD_HEIGHT as i8);
+        let bit_x = (center_x - hex.x + BITBOARD_WIDTH as Coordinate)
+            .rem_euclid(BITBOARD_WIDTH as Coordinate);
+        let bit_y = (center_y - hex.y + BITBOARD_HEIGHT as Coordinate)
+            .rem_euclid(BITBOARD_HEIGHT as Coordinate);
 
-        let board_x = -hex.x + (board_center_x * BITBOARD_WIDTH as i8) + center_x;
-        let board_x = board_x.rem_euclid((BITBOARD_WIDTH * GRID_WIDTH) as i8);
-        let board_x = board_x / BITBOARD_WIDTH as i8;
+        let board_x = -hex.x + (board_center_x * BITBOARD_WIDTH as Coordinate) + center_x;
+        let board_x = board_x.rem_euclid((BITBOARD_WIDTH * GRID_WIDTH) as Coordinate);
+        let board_x = board_x / BITBOARD_WIDTH as Coordinate;
 
-        let board_y = -hex.y + (board_center_y * BITBOARD_HEIGHT as i8) + center_y;
-        let board_y = board_y.rem_euclid((BITBOARD_HEIGHT * GRID_HEIGHT) as i8);
-        let board_y = board_y / BITBOARD_HEIGHT as i8;
+        let board_y = -hex.y + (board_center_y * BITBOARD_HEIGHT as Coordinate) + center_y;
+        let board_y = board_y.rem_euclid((BITBOARD_HEIGHT * GRID_HEIGHT) as Coordinate);
+        let board_y = board_y / BITBOARD_HEIGHT as Coordinate;
 
-        let board_index = (board_y * GRID_HEIGHT as i8 + board_x) as usize;
-        let bitboard_index = (bit_y * BITBOARD_HEIGHT as i8 + bit_x) as usize;
+        let board_index = (board_y * GRID_HEIGHT as Coordinate + board_x) as usize;
+        let bitboard_index = (bit_y * BITBOARD_HEIGHT as Coordinate + bit_x) as usize;
 
         BitGridLocation::new(board_index, bitboard_index)
     }
@@ -667,7 +669,7 @@ mod tests {
     use super::BasicBitGrid;
     use super::BitGridLocation;
     use super::*;
-    use crate::testing_utils::is_localized;
+    use crate::testing::is_localized;
     pub const MAX_WRAP_BEFORE_COLLISION: usize = 28;
 
     #[test]
@@ -23,7 +23,7 @@ const SOUTH_WITHOUT_CORNER: AxialBitboard = AxialBitboard(0x000000000000007f);
 /// Assumes that the bitboard represents its individual bits as
 /// follows (00 is the least significant bit, 63 is the most)
 ///
-/// ```
+/// ```text
 ///     63 62 61 60 59 58 57 56
 ///     55 54 53 52 51 50 49 48
 ///     47 46 45 44 43 42 41 40
@@ -37,7 +37,7 @@ const SOUTH_WITHOUT_CORNER: AxialBitboard = AxialBitboard(0x000000000000007f);
 /// Then directions are represented as follows:
 /// (where some location is represented by X)
 ///  
-/// ```
+/// ```text
 ///     .  .  .  .  .  .  .  .
 ///     .  .  .  .  .  .  .  .
 ///     .  .  .  NW NE .  .  .
@@ -233,6 +233,18 @@ impl AxialBitboard {
         self.0 == 0
     }
 
+    /// Returns the number of set bits
+    #[inline(always)]
+    pub fn count_ones(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// Returns the least significant set bit alone, 0 if no bit is set
+    #[inline(always)]
+    pub fn lowest_bit(&self) -> u64 {
+        self.0 & self.0.wrapping_neg()
+    }
+
     /// Returns the smallest bounding box of the bitboard, containing all
     /// set bits in the bitboard if there are any
     pub fn bounding_box(&self) -> Option<BitboardBounds> {
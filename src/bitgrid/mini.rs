@@ -1,7 +1,7 @@
 use super::*;
 use crate::hex_grid::{HexGrid, HexGridConvertible};
 use crate::generator::change::Change;
-use crate::location::{Direction, FromHex, HexLocation, Shiftable};
+use crate::location::{Coordinate, Direction, FromHex, HexLocation, Shiftable};
 use crate::piece::{IntoPieces, Piece, PieceColor, PieceType};
 use std::collections::HashSet;
 use std::fmt::{self, Display};
@@ -12,8 +12,8 @@ const BOTTOM_OVERFLOW_MASK: u64 = 0x00000000000000FF;
 const TOP_OVERFLOW_MASK: u64 = 0xFF00000000000000;
 
 const CENTER_BOARD_INDEX: usize = 0;
-const CENTER_BIT_X: i8 = 4;
-const CENTER_BIT_Y: i8 = 3;
+const CENTER_BIT_X: Coordinate = 4;
+const CENTER_BIT_Y: Coordinate = 3;
 const CENTER_BIT_INDEX: usize = CENTER_BIT_X as usize + CENTER_BIT_Y as usize * BITBOARD_WIDTH;
 const GRID_SIZE: usize = GRID_WIDTH * GRID_HEIGHT;
 const GRID_WIDTH: usize = 2;
@@ -40,7 +40,7 @@ pub type MiniGrid = [AxialBitboard; 4];
 ///
 /// TODO: conventional?? Isn't negative x going in the wrong direction? Perhaps
 /// compare it to HexGridLocation instead
-/// ```
+/// ```text
 ///     3 2
 ///     1 0
 /// ```
@@ -127,47 +127,50 @@ impl MiniBitGrid {
 
     /// Returns true only if the current grid follows the One Hive rule
     fn is_one_hive(&self) -> bool {
-        if self.is_empty() {
-            return true;
+        match first_location(&self.all_pieces) {
+            Some(start) => connected(start, &self.all_pieces) == self.all_pieces,
+            None => true,
         }
+    }
 
-        fn dfs(
-            grid: &MiniBitGrid,
-            visited: &mut HashSet<MiniBitGridLocation>,
-            loc: MiniBitGridLocation,
-        ) {
-            if visited.contains(&loc) {
-                return;
-            }
-
-            if grid.presence(loc) == false {
-                return;
-            }
-
-            visited.insert(loc);
+    /// Returns the locations whose pieces are pinned, that is, the locations
+    /// that would split the hive in two if their stacks were removed
+    ///
+    /// Each occupied location is removed in turn and the rest of the hive
+    /// flooded a ring of neighbors at a time, every board at once
+    pub fn pinned(&self) -> MiniGrid {
+        let mut pinned = [AxialBitboard::empty(); 4];
+        for board_index in 0..4 {
+            for coords in self.all_pieces[board_index] {
+                let mask = 1 << coords.index();
+                let mut rest = self.all_pieces;
+                rest[board_index] &= !mask;
+
+                // A piece touching less than two others cannot split the hive
+                let around = adjacent(&single(board_index, mask));
+                let touching: u32 = (0..4)
+                    .map(|index| (around[index] & rest[index]).count_ones())
+                    .sum();
+                if touching < 2 {
+                    continue;
+                }
 
-            for direction in Direction::all() {
-                dfs(grid, visited, loc.apply(direction));
+                let start = first_location(&rest).unwrap();
+                if connected(start, &rest) != rest {
+                    pinned[board_index] |= mask;
+                }
             }
         }
-
-        let mut found_locations = HashSet::new();
-        dfs(
-            &self,
-            &mut found_locations,
-            self.find_one_hex().unwrap().into(),
-        );
-
-        found_locations.len() == self.pieces().len()
+        pinned
     }
 
     /// Deterministically chooses a HexLocation that contains at least one piece
     /// on the board. Returns none if the board is empty
     fn find_one_hex(&self) -> Option<HexLocation> {
-        let left = -(BITBOARD_WIDTH as i8);
-        let right = BITBOARD_WIDTH as i8;
-        let top = -(BITBOARD_HEIGHT as i8);
-        let bottom = BITBOARD_HEIGHT as i8;
+        let left = -(BITBOARD_WIDTH as Coordinate);
+        let right = BITBOARD_WIDTH as Coordinate;
+        let top = -(BITBOARD_HEIGHT as Coordinate);
+        let bottom = BITBOARD_HEIGHT as Coordinate;
 
         for row in top..=bottom {
             for col in left..=right {
@@ -181,36 +184,11 @@ impl MiniBitGrid {
         None
     }
 
-    fn stamp(&mut self, neighborhood: &mut Neighborhood, board_index: usize) {
-        debug_assert!(board_index < 4);
-        let vertical_index = (board_index + 2) % 4;
-        let horizontal_index = board_index ^ 1;
-        let diagonal_index = ((board_index + 2) % 4) ^ 1;
-
-        let vertical = &mut self.outside[vertical_index];
-        *vertical |= *neighborhood.top() | *neighborhood.bottom();
-        *vertical &= !self.all_pieces[vertical_index];
-
-        let horizontal = &mut self.outside[horizontal_index];
-        *horizontal |= *neighborhood.center_left() | *neighborhood.center_right();
-        *horizontal &= !self.all_pieces[horizontal_index];
-
-        let diagonal = &mut self.outside[diagonal_index];
-        *diagonal |= *neighborhood.top_right() | *neighborhood.bottom_left();
-        *diagonal &= !self.all_pieces[diagonal_index];
-
-        let center = &mut self.outside[board_index];
-        *center |= *neighborhood.center();
-        *center &= !self.all_pieces[board_index];
-    }
-
     fn update_outside(&mut self) {
-        for board_index in 0..4 {
-            self.outside[board_index] = AxialBitboard::empty();
-        }
-        for board_index in 0..4 {
-            let mut neighborhood = self.all_pieces[board_index].neighborhood();
-            self.stamp(&mut neighborhood, board_index);
+        let adjacent = adjacent(&self.all_pieces);
+        let boards = self.outside.iter_mut().zip(adjacent).zip(self.all_pieces);
+        for ((outside, adjacent), occupied) in boards {
+            *outside = adjacent & !occupied;
         }
     }
 
@@ -528,6 +506,55 @@ impl MiniBitGrid {
     }
 }
 
+/// Returns the locations next to the locations set in *grid*, found for every
+/// location at once by shifting each board in the six directions, with what
+/// overflows a board carried to the board it wraps to
+fn adjacent(grid: &MiniGrid) -> MiniGrid {
+    let mut adjacent = [AxialBitboard::empty(); 4];
+    for board_index in 0..4 {
+        let mut neighborhood = grid[board_index].neighborhood();
+        let vertical_index = (board_index + 2) % 4;
+        let horizontal_index = board_index ^ 1;
+        let diagonal_index = ((board_index + 2) % 4) ^ 1;
+
+        adjacent[vertical_index] |= *neighborhood.top() | *neighborhood.bottom();
+        adjacent[horizontal_index] |= *neighborhood.center_left() | *neighborhood.center_right();
+        adjacent[diagonal_index] |= *neighborhood.top_right() | *neighborhood.bottom_left();
+        adjacent[board_index] |= *neighborhood.center();
+    }
+    adjacent
+}
+
+/// Returns the locations set in *within* that are connected to *start*
+/// through other locations set in *within*
+fn connected(start: MiniBitGridLocation, within: &MiniGrid) -> MiniGrid {
+    let mut reached = single(start.board_index, start.mask);
+    loop {
+        let adjacent = adjacent(&reached);
+        let mut grown = reached;
+        for board_index in 0..4 {
+            grown[board_index] |= adjacent[board_index] & within[board_index];
+        }
+        if grown == reached {
+            return reached;
+        }
+        reached = grown;
+    }
+}
+
+/// Returns the lowest location set in *grid*, if any
+fn first_location(grid: &MiniGrid) -> Option<MiniBitGridLocation> {
+    let board_index = grid.iter().position(|board| !board.is_empty())?;
+    Some(MiniBitGridLocation::from_u64(board_index, grid[board_index].lowest_bit()))
+}
+
+/// Returns a grid with only *mask* set on the board *board_index*
+fn single(board_index: usize, mask: u64) -> MiniGrid {
+    let mut grid = [AxialBitboard::empty(); 4];
+    grid[board_index] = AxialBitboard::from_u64(mask);
+    grid
+}
+
 impl IntoPieces for MiniBitGrid {
     fn pieces(&self) -> Vec<(Vec<Piece>, HexLocation)> {
         // We use the fact that the equivalence of
@@ -787,23 +814,23 @@ impl Shiftable for MiniBitGridLocation {
 
 impl FromHex for MiniBitGridLocation {
     fn from_hex(hex: HexLocation) -> MiniBitGridLocation {
-        let wrap = |x: i8, y: i8| -> usize {
-            let board_x = (x + CENTER_BIT_X).div_euclid(BITBOARD_WIDTH as i8);
-            let board_y = (y + CENTER_BIT_Y).div_euclid(BITBOARD_HEIGHT as i8);
+        let wrap = |x: Coordinate, y: Coordinate| -> usize {
+            let board_x = (x + CENTER_BIT_X).div_euclid(BITBOARD_WIDTH as Coordinate);
+            let board_y = (y + CENTER_BIT_Y).div_euclid(BITBOARD_HEIGHT as Coordinate);
 
-            let board_x = board_x.rem_euclid(GRID_WIDTH as i8);
-            let board_y = board_y.rem_euclid(GRID_HEIGHT as i8);
+            let board_x = board_x.rem_euclid(GRID_WIDTH as Coordinate);
+            let board_y = board_y.rem_euclid(GRID_HEIGHT as Coordinate);
 
-            let board_index = board_x + board_y * GRID_WIDTH as i8;
+            let board_index = board_x + board_y * GRID_WIDTH as Coordinate;
 
             board_index as usize
         };
 
-        let bit_x = (-hex.x + CENTER_BIT_X).rem_euclid(BITBOARD_WIDTH as i8);
-        let bit_y = (-hex.y + CENTER_BIT_Y).rem_euclid(BITBOARD_HEIGHT as i8);
+        let bit_x = (-hex.x + CENTER_BIT_X).rem_euclid(BITBOARD_WIDTH as Coordinate);
+        let bit_y = (-hex.y + CENTER_BIT_Y).rem_euclid(BITBOARD_HEIGHT as Coordinate);
 
-        let bit_index = bit_x + bit_y * BITBOARD_WIDTH as i8;
-        let bit_index = bit_index.rem_euclid(BITBOARD_SIZE as i8);
+        let bit_index = bit_x + bit_y * BITBOARD_WIDTH as Coordinate;
+        let bit_index = bit_index.rem_euclid(BITBOARD_SIZE as Coordinate);
         let bit_index = bit_index as usize;
         let mask = 1 << bit_index as usize;
 
@@ -921,7 +948,7 @@ impl TryFrom<HexGrid> for MiniBitGrid {
 pub mod tests {
     use super::*;
     use crate::hex_grid::HexGrid;
-    use crate::testing_utils::is_localized;
+    use crate::testing::is_localized;
 
     #[test]
     pub fn size_sanity_check() {
@@ -1159,6 +1186,58 @@ pub mod tests {
         assert!(!grid.should_promote());
     }
 
+    #[test]
+    pub fn test_pinned_and_outside() {
+        let grids = [
+            concat!(
+                " . . a . . .\n",
+                ". a a . a .\n",
+                " . . a a . .\n",
+                ". . a . a .\n",
+                " . . . . . .\n\n",
+                "start - [0 0]\n\n",
+            ),
+            concat!(
+                " a . . . . .\n",
+                ". a a . . .\n",
+                " a . a a . .\n",
+                ". . . . a a\n",
+                " . . . . . a\n\n",
+                "start - [0 0]\n\n",
+            ),
+            // A ring pins nothing
+            concat!(
+                " . . . . . .\n",
+                ". . a a . .\n",
+                " . a . a . .\n",
+                ". . a a . .\n",
+                " . . . . . .\n\n",
+                "start - [0 0]\n\n",
+            ),
+        ];
+        let to_grid = |locations: Vec<HexLocation>| {
+            let mut grid = [AxialBitboard::empty(); 4];
+            for location in locations {
+                let location: MiniBitGridLocation = location.into();
+                grid[location.board_index] |= location.mask;
+            }
+            grid
+        };
+
+        for dsl in grids {
+            let hex_grid = HexGrid::from_dsl(dsl);
+            let mut mini: MiniBitGrid = hex_grid.clone().try_into().unwrap();
+            assert!(mini.is_one_hive());
+            assert_eq!(mini.pinned(), to_grid(hex_grid.pinned()));
+            assert_eq!(mini.outside, to_grid(hex_grid.outside().into_iter().collect()));
+
+            if let Some(location) = hex_grid.pinned().first() {
+                mini.remove_top((*location).into());
+                assert!(!mini.is_one_hive());
+            }
+        }
+    }
+
     #[test]
     pub fn test_conversion() {
         let hex_grid = HexGrid::from_dsl(concat!(
@@ -0,0 +1,306 @@
+use crate::generator::debug::Position;
+use crate::hex_grid::Height;
+use crate::location::{Direction, HexLocation};
+use crate::piece::{IntoPieces, Piece};
+use smallvec::SmallVec;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// The pieces of a stack from bottom to top, stacks of up to two
+/// pieces are stored without allocating
+pub type Stack = SmallVec<[Piece; 2]>;
+
+/// Locations around a location, of which there are at most six, so they
+/// are returned without allocating
+pub type Neighbors = SmallVec<[HexLocation; 6]>;
+
+/// A board representation the move generator can run on.
+///
+/// Implementors only need to provide access to the stacks, every
+/// other query is derived from those and can be overridden when the
+/// representation can answer it faster (e.g. HexGrid keeps track of
+/// the locations outside of the hive as pieces move)
+pub trait Board: Position + IntoPieces + Clone + Eq + Hash {
+    /// The pieces at a given location stacked from bottom to top,
+    /// empty if the location is not occupied
+    fn stack(&self, location: HexLocation) -> &[Piece];
+
+    /// Adds a piece to the top of the stack at the given location
+    fn add(&mut self, piece: Piece, location: HexLocation);
+
+    /// Removes the top-most piece from the stack at the given location
+    fn remove(&mut self, location: HexLocation) -> Option<Piece>;
+
+    /// Iterates over every occupied location and the stack of pieces there,
+    /// stacked from bottom to top, in no particular order
+    fn occupied(&self) -> impl Iterator<Item = (HexLocation, &[Piece])>;
+
+    /// Access a copy of the pieces at a given location
+    /// stacked from bottom to top
+    fn peek(&self, location: HexLocation) -> Stack {
+        Stack::from_slice(self.stack(location))
+    }
+
+    /// Acces the top most piece at a given location
+    fn top(&self, location: HexLocation) -> Option<Piece> {
+        self.stack(location).last().copied()
+    }
+
+    /// The number of pieces stacked at a given location, 0 if it is empty
+    fn height(&self, location: HexLocation) -> usize {
+        self.stack(location).len()
+    }
+
+    /// Returns true if the stack at a given location has more than one piece,
+    /// that is, if the pieces below the top piece are covered
+    fn is_covered(&self, location: HexLocation) -> bool {
+        self.height(location) > 1
+    }
+
+    fn is_empty(&self) -> bool {
+        self.occupied().next().is_none()
+    }
+
+    fn num_pieces(&self) -> usize {
+        self.occupied().map(|(_, stack)| stack.len()).sum()
+    }
+
+    /// Returns the first occurrence of a specified piece in board order,
+    /// with stacks searched from bottom to top
+    fn find(&self, piece: Piece) -> Option<(HexLocation, Height)> {
+        self.pieces().into_iter().find_map(|(stack, location)| {
+            let height = stack.iter().position(|&stack_piece| stack_piece == piece)?;
+            Some((location, height))
+        })
+    }
+
+    /// Returns the six locations surrounding a given location, in the order
+    /// of Direction::all(), each with its pieces stacked from bottom to top.
+    ///
+    /// Empty locations are included with no pieces, the location itself
+    /// does not need to be occupied
+    fn neighbors(&self, location: HexLocation) -> impl Iterator<Item = (HexLocation, &[Piece])> {
+        Direction::all().into_iter().map(move |direction| {
+            let neighbor = location.apply(direction);
+            (neighbor, self.stack(neighbor))
+        })
+    }
+
+    /// Returns the empty locations surrounding a given location,
+    /// see neighbors()
+    fn get_empty_neighbors(&self, location: HexLocation) -> Neighbors {
+        self.neighbors(location)
+            .filter(|(_, stack)| stack.is_empty())
+            .map(|(neighbor, _)| neighbor)
+            .collect()
+    }
+
+    /// Returns the non-empty locations surrounding a given location,
+    /// see neighbors()
+    fn get_neighbors(&self, location: HexLocation) -> Neighbors {
+        self.neighbors(location)
+            .filter(|(_, stack)| !stack.is_empty())
+            .map(|(neighbor, _)| neighbor)
+            .collect()
+    }
+
+    /// Returns the locations that are neighbors of the current pieces of hive, but
+    /// that location contains no pieces
+    fn outside(&self) -> HashSet<HexLocation> {
+        self.occupied()
+            .flat_map(|(location, _)| self.get_empty_neighbors(location))
+            .collect()
+    }
+
+    /// Whether *location* is empty and next to a piece of the hive, that is,
+    /// whether it is part of outside()
+    fn is_outside(&self, location: HexLocation) -> bool {
+        self.height(location) == 0 && self.neighbors(location).any(|(_, stack)| !stack.is_empty())
+    }
+
+    /// Returns the locations in the hive that are "pinned",
+    /// in other words, removing the pieces in that stack would violate the One Hive rule
+    ///
+    /// returns in board order, that is, first top-to-bottom then left-to-right
+    /// Assumes that the pieces on the board already form "One Hive"
+    fn pinned(&self) -> Vec<HexLocation> {
+        let mut pinned = vec![];
+        let hive = self
+            .pieces()
+            .into_iter()
+            .map(|(_, location)| location)
+            .collect::<Vec<_>>();
+
+        for &candidate in hive.iter() {
+            let mut visited = HashSet::new();
+            let neighbors = self.get_neighbors(candidate);
+            if !neighbors.is_empty() {
+                dfs(self, &mut visited, candidate, neighbors[0])
+            }
+
+            visited.insert(candidate);
+            if visited.len() != hive.len() {
+                pinned.push(candidate);
+            }
+        }
+        pinned
+    }
+
+    /// Returns locations that are neighbors of an given location but are
+    /// "slidable", that is, they do not form gates that are inaccessible for
+    /// sliding pieces and maintains contact with at least one of its original neighbors
+    ///
+    /// Specifies the effective height of the piece, to see if the piece can jump over the gate
+    fn slidable_locations_3d_height(
+        &self,
+        location: HexLocation,
+        effective_height: usize,
+    ) -> Neighbors {
+        let mut slidable = Neighbors::new();
+
+        for direction in Direction::all().iter() {
+            let destination = location.apply(*direction);
+            let destination_height = self.height(destination);
+            let final_height = destination_height + 1;
+            let effective_height = final_height.max(effective_height);
+
+            let (left_dir, right_dir) = direction.adjacent();
+            let (left, right) = (location.apply(left_dir), location.apply(right_dir));
+
+            // Must be high enough to step over and through gate
+            let gate_requirement = self.height(left).min(self.height(right));
+            if effective_height <= gate_requirement {
+                continue;
+            }
+
+            // maintains contact if the destination has a piece
+            // or if the location has a piece under it
+            let mut maintains_contact = destination_height > 0;
+            maintains_contact = maintains_contact || effective_height > 1;
+
+            // or if a piece touches both, and the only locations next to
+            // both are the two that form the gate
+            maintains_contact =
+                maintains_contact || self.height(left) > 0 || self.height(right) > 0;
+
+            if maintains_contact {
+                slidable.push(destination);
+            }
+        }
+        slidable
+    }
+
+    /// Returns locations that are neighbors of an given location but are
+    /// "slidable", that is, they do not form gates that are inaccessible for
+    /// sliding pieces and maintains contact with at least one of its original neighbors
+    ///
+    /// "3D" because it allows climbing up the hive
+    fn slidable_locations_3d(&self, location: HexLocation) -> Neighbors {
+        let effective_height = self.height(location);
+        self.slidable_locations_3d_height(location, effective_height)
+    }
+
+    /// Returns locations that are neighbors of an given location but are
+    /// "slidable", that is, they do not form gates that are inaccessible for
+    /// sliding pieces and maintains contact with at least one of its original neighbors
+    ///
+    /// "2D" because it ignores the height of the pieces, disallowing climbing up the hive
+    fn slidable_locations_2d(&self, location: HexLocation) -> Neighbors {
+        debug_assert!(self.height(location) <= 1); // Cannot climb up the hive
+        let all_locations = self.slidable_locations_3d_height(location, 1);
+        all_locations
+            .into_iter()
+            .filter(|&loc| self.height(loc) == 0)
+            .collect()
+    }
+}
+
+/// Depth first search on the stack with a location not allowed to be visited
+/// to determine if the location contains a pinned piece
+fn dfs<B: Board>(
+    board: &B,
+    visited: &mut HashSet<HexLocation>,
+    disallowed: HexLocation,
+    current_location: HexLocation,
+) {
+    if visited.contains(&current_location) {
+        return;
+    }
+    if current_location == disallowed {
+        return;
+    }
+    visited.insert(current_location);
+    for neighbor in board.get_neighbors(current_location) {
+        dfs(board, visited, disallowed, neighbor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::debug::{FromHexGrid, PositionGenerator, ReferenceGenerator};
+    use crate::hex_grid::HexGrid;
+    use crate::piece::PieceColor;
+    use crate::sparse_hex_grid::SparseHexGrid;
+
+    #[test]
+    pub fn test_board_queries_agree() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . . .\n",
+            " . . g a . .\n",
+            ". . 2 q b .\n",
+            " . A G Q . .\n",
+            ". . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ a B ]\n",
+        ));
+        let sparse = SparseHexGrid::from(&grid);
+
+        assert_eq!(Board::pinned(&sparse), grid.pinned());
+        assert_eq!(Board::outside(&sparse), grid.outside());
+        assert_eq!(Board::num_pieces(&sparse), grid.num_pieces());
+
+        let locations = grid
+            .occupied()
+            .map(|(location, _)| location)
+            .chain(grid.outside())
+            .collect::<Vec<_>>();
+        for location in locations {
+            assert_eq!(sparse.is_outside(location), grid.is_outside(location));
+            assert_eq!(
+                sparse.slidable_locations_3d(location),
+                grid.slidable_locations_3d(location)
+            );
+            assert_eq!(
+                sparse.slidable_locations_3d_height(location, 1),
+                grid.slidable_locations_3d_height(location, 1)
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_generator_on_sparse_board() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . . .\n",
+            " . . g a . .\n",
+            ". . 2 q b .\n",
+            " . A G Q . .\n",
+            ". . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ a B ]\n",
+        ));
+
+        for color in [PieceColor::White, PieceColor::Black] {
+            let mut generator = ReferenceGenerator::from_default(&grid);
+            let mut sparse_generator = ReferenceGenerator::<SparseHexGrid>::from_default(&grid);
+            let expected = generator.generate_positions_for(color);
+            let actual = sparse_generator
+                .generate_positions_for(color)
+                .into_iter()
+                .map(HexGrid::from)
+                .collect::<Vec<_>>();
+            // In the same order on every board
+            assert_eq!(expected, actual);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use crate::hex_grid::*;
+use crate::moves::Move;
+
+/// A single hex of a BoardView, with the pieces on it from bottom to top
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackView {
+    pub location: HexLocation,
+    pub pieces: Vec<Piece>,
+}
+
+impl StackView {
+    pub fn height(&self) -> usize {
+        self.pieces.len()
+    }
+
+    pub fn top(&self) -> Option<Piece> {
+        self.pieces.last().cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    /// The character representing this hex in the DSL, "." for an empty hex,
+    /// the piece for a single piece, or the height of a stack
+    pub fn symbol(&self) -> String {
+        match self.pieces.len() {
+            0 => ".".to_owned(),
+            1 => self.pieces[0].to_str(),
+            height => height.to_string(),
+        }
+    }
+}
+
+/// How a hex is marked when rendered with RenderOptions
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mark {
+    /// The hex the last moved piece came from
+    Origin,
+    /// The hex the last moved or placed piece went to
+    Destination,
+}
+
+/// Options for rendering a BoardView beyond the DSL
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// The move to highlight, see GameDebugger::last_move_played()
+    pub last_move: Option<Move>,
+}
+
+impl RenderOptions {
+    /// Returns how the hex at *location* should be marked, if at all
+    pub fn mark(&self, location: HexLocation) -> Option<Mark> {
+        let last_move = self.last_move?;
+        if last_move.destination() == Some(location) {
+            return Some(Mark::Destination);
+        }
+        if last_move.origin() == Some(location) {
+            return Some(Mark::Origin);
+        }
+        None
+    }
+}
+
+/// A row of hexes in a BoardView
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowView {
+    /// Odd rows are shifted half a hex to the right
+    pub indented: bool,
+    pub cells: Vec<StackView>,
+}
+
+/// A structured view of a HexGrid, see HexGrid::view()
+///
+/// Covers the bounding box of the hive with an extra empty hex on every side,
+/// in the odd-r layout used by the DSL, rows from top to bottom and cells
+/// from left to right
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardView {
+    pub rows: Vec<RowView>,
+}
+
+impl BoardView {
+    /// The location of the top left hex of the view
+    pub fn start(&self) -> HexLocation {
+        self.rows[0].cells[0].location
+    }
+
+    /// Every hex of the view in board order
+    pub fn cells(&self) -> impl Iterator<Item = &StackView> {
+        self.rows.iter().flat_map(|row| row.cells.iter())
+    }
+
+    /// The hexes holding more than one piece in board order
+    pub fn stacks(&self) -> impl Iterator<Item = &StackView> {
+        self.cells().filter(|cell| cell.height() > 1)
+    }
+
+    /// Renders the board part of the DSL, see HexGrid::board_string()
+    pub fn board_string(&self) -> String {
+        let mut board = String::new();
+        for row in self.rows.iter() {
+            if row.indented {
+                board.push(' ');
+            }
+            let symbols = row.cells.iter().map(|cell| cell.symbol());
+            board.push_str(&symbols.collect::<Vec<_>>().join(" "));
+            board.push('\n');
+        }
+        board
+    }
+
+    /// Renders the start part of the DSL, see HexGrid::start_string()
+    pub fn start_string(&self) -> String {
+        let start = self.start();
+        format!("start - [ {} {} ]", start.x, start.y)
+    }
+
+    /// Renders the stack part of the DSL, see HexGrid::stacks_string()
+    pub fn stacks_string(&self) -> String {
+        let mut stack_string = String::new();
+        for stack in self.stacks() {
+            stack_string.push_str(&format!("{} - [ ", stack.height()));
+            for piece in stack.pieces.iter() {
+                stack_string.push_str(&piece.to_str());
+                stack_string.push(' ');
+            }
+            stack_string.push_str("]\n");
+        }
+        stack_string
+    }
+
+    /// Renders the last move of *options* as a DSL comment, which is ignored
+    /// when parsed, or an empty string if there is no last move
+    ///
+    /// ```text
+    /// # last move - [ 1 0 ] -> [ 2 -1 ]
+    /// # last move - [ 0 0 ]
+    /// # last move - pass
+    /// ```
+    pub fn comment_string(&self, options: &RenderOptions) -> String {
+        let bracket = |location: HexLocation| format!("[ {} {} ]", location.x, location.y);
+        match options.last_move {
+            None => String::new(),
+            Some(Move::Pass) => "# last move - pass\n".to_owned(),
+            Some(Move::Place { to, .. }) => format!("# last move - {}\n", bracket(to)),
+            Some(Move::Movement { from, to, .. }) => {
+                format!("# last move - {} -> {}\n", bracket(from), bracket(to))
+            }
+        }
+    }
+
+    /// Renders the board part of the DSL for a terminal, with the origin of
+    /// the last move in yellow and its destination in bold green
+    #[cfg(feature = "render")]
+    pub fn ansi_string(&self, options: &RenderOptions) -> String {
+        let mut board = String::new();
+        for row in self.rows.iter() {
+            if row.indented {
+                board.push(' ');
+            }
+            let symbols = row.cells.iter().map(|cell| {
+                let symbol = cell.symbol();
+                match options.mark(cell.location) {
+                    None => symbol,
+                    Some(Mark::Origin) => format!("\x1b[33m{}\x1b[0m", symbol),
+                    Some(Mark::Destination) => format!("\x1b[1;32m{}\x1b[0m", symbol),
+                }
+            });
+            board.push_str(&symbols.collect::<Vec<_>>().join(" "));
+            board.push('\n');
+        }
+        board
+    }
+
+    /// Renders the view as an SVG image of pointy-topped hexes, labelled with
+    /// the DSL symbol of their top piece, where the origin of the last move
+    /// has a dashed outline and its destination a solid one
+    #[cfg(feature = "render")]
+    pub fn svg_string(&self, options: &RenderOptions) -> String {
+        const SIZE: f64 = 30.0;
+        let width = SIZE * 3f64.sqrt();
+        let columns = self
+            .rows
+            .iter()
+            .map(|row| row.cells.len())
+            .max()
+            .unwrap_or(0);
+        let image_width = width * (columns as f64 + 0.5);
+        let image_height = SIZE * (1.5 * self.rows.len() as f64 + 0.5);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\">\n",
+            image_width, image_height
+        );
+        for (row_index, row) in self.rows.iter().enumerate() {
+            let indent = if row.indented { 0.5 } else { 0.0 };
+            for (column, cell) in row.cells.iter().enumerate() {
+                let cx = width * (column as f64 + 0.5 + indent);
+                let cy = SIZE * (1.5 * row_index as f64 + 1.0);
+                let points = (0..6)
+                    .map(|corner| {
+                        let angle =
+                            std::f64::consts::PI / 3.0 * corner as f64 - std::f64::consts::PI / 6.0;
+                        format!(
+                            "{:.1},{:.1}",
+                            cx + SIZE * angle.cos(),
+                            cy + SIZE * angle.sin()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let (fill, text) = match cell.top().map(|piece| piece.color) {
+                    None => ("none", "#000000"),
+                    Some(PieceColor::White) => ("#f4ecd8", "#000000"),
+                    Some(PieceColor::Black) => ("#303030", "#ffffff"),
+                };
+                let outline = match options.mark(cell.location) {
+                    None => "stroke=\"#c0c0c0\" stroke-width=\"1\"",
+                    Some(Mark::Origin) => {
+                        "stroke=\"#e0a000\" stroke-width=\"3\" stroke-dasharray=\"6 4\""
+                    }
+                    Some(Mark::Destination) => "stroke=\"#e0a000\" stroke-width=\"3\"",
+                };
+                svg.push_str(&format!(
+                    "<polygon points=\"{}\" fill=\"{}\" {}/>\n",
+                    points, fill, outline
+                ));
+                if let Some(piece) = cell.top() {
+                    let label = match cell.height() {
+                        1 => piece.piece_type.to_str().to_string(),
+                        height => format!("{}{}", piece.piece_type.to_str(), height),
+                    };
+                    svg.push_str(&format!(
+                        "<text x=\"{:.1}\" y=\"{:.1}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                        cx, cy, text, label
+                    ));
+                }
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameDebugger;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_board_view() {
+        let dsl = concat!(
+            " . . . .\n",
+            ". Q 2 .\n",
+            " . b . .\n",
+            ". . . .\n\n",
+            "start - [ 0 -1 ]\n\n",
+            "2 - [ a B ]\n",
+        );
+        let grid = HexGrid::from_dsl(dsl);
+        let view = grid.view();
+
+        assert_eq!(view.rows.len(), 4);
+        assert!(view.rows.iter().all(|row| row.cells.len() == 4));
+        assert!(view.rows[0].indented && !view.rows[1].indented);
+        assert_eq!(view.start(), HexLocation::new(0, -1));
+
+        for cell in view.cells() {
+            assert_eq!(cell.pieces, grid.peek(cell.location).to_vec());
+        }
+        let stacks = view.stacks().collect::<Vec<_>>();
+        assert_eq!(stacks.len(), 1);
+        assert_eq!(
+            stacks[0].top(),
+            Some(Piece::new(PieceType::Beetle, PieceColor::White))
+        );
+
+        assert_eq!(view.board_string(), grid.board_string());
+        assert_eq!(grid.to_dsl(), dsl);
+    }
+
+    #[test]
+    pub fn test_render_last_move() {
+        let moves = ["wS1", "bS1 wS1-", "wQ -wS1", "bQ bS1-", r"wQ \wS1"];
+        let game = GameDebugger::from_move_list(&moves, GameType::Standard).unwrap();
+        let after = game.position();
+        let options = game.render_options();
+        let Some(Move::Movement { from, to, .. }) = options.last_move else {
+            panic!("Expected a movement");
+        };
+        assert_eq!(options.mark(from), Some(Mark::Origin));
+        assert_eq!(options.mark(to), Some(Mark::Destination));
+        assert_eq!(RenderOptions::default().mark(to), None);
+
+        let view = after.view();
+        let comment = view.comment_string(&options);
+        assert_eq!(
+            comment,
+            format!(
+                "# last move - [ {} {} ] -> [ {} {} ]\n",
+                from.x, from.y, to.x, to.y
+            )
+        );
+        // Comments are ignored by the parser
+        assert_eq!(&HexGrid::from_dsl(&(after.to_dsl() + &comment)), after);
+
+        #[cfg(feature = "render")]
+        {
+            let ansi = view.ansi_string(&options);
+            assert!(ansi.contains("\x1b[33m.\x1b[0m"));
+            assert!(ansi.contains("\x1b[1;32mQ\x1b[0m"));
+            assert_eq!(
+                view.ansi_string(&RenderOptions::default()),
+                view.board_string()
+            );
+
+            let svg = view.svg_string(&options);
+            assert_eq!(svg.matches("<polygon").count(), view.cells().count());
+            assert_eq!(svg.matches("<text").count(), 4);
+            assert_eq!(svg.matches("stroke-width=\"3\"").count(), 2);
+            assert_eq!(svg.matches("stroke-dasharray").count(), 1);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Opening books read straight from a memory mapped file, so that even
+//! large books open instantly and only the pages looked at are loaded.
+//!
+//! A book is compiled from a PositionDb (see build()), and like the
+//! database stores positions in their canonical form (see
+//! HexGrid::canonical()), with moves relative to that form. Entries are
+//! sorted by key, then by weight from highest, so that the moves of a
+//! position are found with a binary search:
+//!
+//! ```text
+//! "ABOK" number of entries (u64)
+//! for each entry:
+//!     the key of the canonical position and the player to move, see
+//!     PositionDb::key() (u64)
+//!     the weight of the move (u32)
+//!     the move (see encoding::write_move()), padded with zeros to 10 bytes
+//! ```
+//!
+//! Numbers are little endian
+use crate::encoding::{self, EncodingError};
+use crate::hex_grid::HexGrid;
+use crate::moves::Move;
+use crate::piece::PieceColor;
+use crate::position_db::PositionDb;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+use thiserror::Error;
+
+/// The first bytes of a book
+const MAGIC: &[u8; 4] = b"ABOK";
+
+const HEADER_SIZE: usize = MAGIC.len() + 8;
+
+/// The size of the longest move, a movement
+const MOVE_SIZE: usize = 10;
+
+const ENTRY_SIZE: usize = 8 + 4 + MOVE_SIZE;
+
+#[derive(Error, Debug)]
+pub enum BookError {
+    #[error("Could not access {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Not an opening book")]
+    NotABook,
+    #[error("Corrupt opening book, {0}")]
+    Encoding(#[from] EncodingError),
+}
+
+pub type Result<T> = std::result::Result<T, BookError>;
+
+/// A move of a book entry with its weight, see Book::moves()
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BookMove {
+    pub mv: Move,
+    /// How often the move was played, a higher weight is a better move
+    pub weight: u32,
+}
+
+/// Compiles the best move of every position of *db* visited at least
+/// *min_visits* times into a book, weighted by the visits of the position
+pub fn build(db: &PositionDb, min_visits: u64) -> Vec<u8> {
+    let mut entries = db
+        .positions()
+        .filter(|(_, _, stats)| stats.visits >= min_visits)
+        .filter_map(|(position, color, stats)| {
+            let weight = stats.visits.min(u32::MAX as u64) as u32;
+            Some((PositionDb::key(position, color), weight, stats.best_move?))
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|(key, weight, _)| (*key, std::cmp::Reverse(*weight)));
+
+    let mut output = MAGIC.to_vec();
+    output.extend((entries.len() as u64).to_le_bytes());
+    for (key, weight, mv) in entries {
+        output.extend(key.to_le_bytes());
+        output.extend(weight.to_le_bytes());
+        let mut encoded = Vec::with_capacity(MOVE_SIZE);
+        encoding::write_move(&mv, &mut encoded);
+        encoded.resize(MOVE_SIZE, 0);
+        output.extend(encoded);
+    }
+    output
+}
+
+/// Compiles *db* into the book file at *path*, see build(). Returns the
+/// number of entries written
+pub fn write(db: &PositionDb, min_visits: u64, path: &Path) -> Result<usize> {
+    let book = build(db, min_visits);
+    std::fs::write(path, &book).map_err(|e| BookError::Io(path.display().to_string(), e))?;
+    Ok((book.len() - HEADER_SIZE) / ENTRY_SIZE)
+}
+
+/// An opening book file mapped into memory, see the layout above
+#[derive(Debug)]
+pub struct Book {
+    map: Mmap,
+    entries: usize,
+}
+
+impl Book {
+    /// Maps the book file at *path* without reading it
+    pub fn open(path: &Path) -> Result<Book> {
+        let io_error = |e| BookError::Io(path.display().to_string(), e);
+        let file = File::open(path).map_err(io_error)?;
+        // Safety: books are only ever written whole by write(), the mapping
+        // is invalid if the file is changed while it is open
+        let map = unsafe { Mmap::map(&file) }.map_err(io_error)?;
+
+        let Some(count) = map
+            .get(..HEADER_SIZE)
+            .and_then(|header| header.strip_prefix(MAGIC))
+        else {
+            return Err(BookError::NotABook);
+        };
+        let entries = u64::from_le_bytes(count.try_into().unwrap()) as usize;
+        match (map.len() - HEADER_SIZE).checked_sub(entries.saturating_mul(ENTRY_SIZE)) {
+            Some(0) => Ok(Book { map, entries }),
+            Some(left_over) => Err(EncodingError::TrailingBytes(left_over).into()),
+            None => Err(EncodingError::Truncated.into()),
+        }
+    }
+
+    /// The number of moves stored
+    pub fn len(&self) -> usize {
+        self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+
+    /// Returns the moves of *grid* with *color* to move in the book relative
+    /// to *grid*, highest weight first
+    pub fn moves(&self, grid: &HexGrid, color: PieceColor) -> Result<Vec<BookMove>> {
+        let transform = grid.canonical_transform();
+        let key = PositionDb::key(&grid.apply_transform(&transform), color);
+
+        let first = partition_point(self.entries, |index| self.key(index) < key);
+        let mut moves = vec![];
+        for index in (first..self.entries).take_while(|index| self.key(*index) == key) {
+            let entry = self.entry(index);
+            let weight = u32::from_le_bytes(entry[8..12].try_into().unwrap());
+            let (mv, _) = encoding::read_move(&entry[12..])?;
+            moves.push(BookMove {
+                mv: mv.map_locations(|location| transform.invert(location)),
+                weight,
+            });
+        }
+        Ok(moves)
+    }
+
+    /// The move of *grid* with *color* to move with the highest weight, if
+    /// the book has any
+    pub fn best_move(&self, grid: &HexGrid, color: PieceColor) -> Result<Option<Move>> {
+        Ok(self
+            .moves(grid, color)?
+            .first()
+            .map(|book_move| book_move.mv))
+    }
+
+    fn entry(&self, index: usize) -> &[u8] {
+        let start = HEADER_SIZE + index * ENTRY_SIZE;
+        &self.map[start..start + ENTRY_SIZE]
+    }
+
+    fn key(&self, index: usize) -> u64 {
+        u64::from_le_bytes(self.entry(index)[..8].try_into().unwrap())
+    }
+}
+
+/// The first index below *len* for which *before* is false, *before* being
+/// true for a prefix of the indices
+fn partition_point(len: usize, before: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        match before(middle) {
+            true => low = middle + 1,
+            false => high = middle,
+        }
+    }
+    low
+}
+
+/// Compiles the position database at *database* into the book at *output*
+pub fn print_build(database: &Path, output: &Path, min_visits: u64) {
+    let db = match PositionDb::load(database) {
+        Ok(db) => db,
+        Err(e) => return println!("{}", e),
+    };
+    match write(&db, min_visits, output) {
+        Ok(entries) => println!(
+            "Written {} moves of {} positions to {}",
+            entries,
+            db.len(),
+            output.display()
+        ),
+        Err(e) => println!("{}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameDebugger;
+    use crate::location::HexLocation;
+    use crate::testing::temp_path;
+    use crate::uhp::GameType;
+    use PieceColor::*;
+
+    #[test]
+    pub fn test_book() {
+        let mut db = PositionDb::new();
+        let moves = ["wA1", "bG1 -wA1", "wQ wA1-", "bQ /bG1"];
+        let game = GameDebugger::from_move_list(&moves, GameType::Standard).unwrap();
+        db.import_game(&game);
+        let positions = game.positions().cloned().collect::<Vec<_>>();
+        let colors = [White, Black].into_iter().cycle();
+        for ((before, after), color) in positions.iter().zip(&positions[1..]).zip(colors) {
+            db.set_best_move(before, color, before.diff(after).unwrap());
+        }
+        // A position seen twice, and one seen once without a best move
+        db.import_game(&GameDebugger::from_move_list(&moves[..1], GameType::Standard).unwrap());
+
+        let path = temp_path("test.book");
+        assert_eq!(write(&db, 1, &path).unwrap(), 4);
+        let book = Book::open(&path).unwrap();
+        assert_eq!(book.len(), 4);
+        let colors = [White, Black].into_iter().cycle();
+        for ((before, after), color) in positions.iter().zip(&positions[1..]).zip(colors) {
+            let mv = book.best_move(before, color).unwrap().unwrap();
+            assert_eq!(mv.apply(before), *after);
+            assert_eq!(book.best_move(before, color.opposite()).unwrap(), None);
+        }
+        let weights = book
+            .moves(&positions[1], Black)
+            .unwrap()
+            .iter()
+            .map(|book_move| book_move.weight)
+            .collect::<Vec<_>>();
+        assert_eq!(weights, [2]);
+
+        // Symmetric positions share their moves
+        let moved = positions[2].translate(HexLocation::new(2, -1));
+        let mv = book.best_move(&moved, White).unwrap().unwrap();
+        assert!(mv.apply(&moved).eq_up_to_translation(&positions[3]));
+        assert!(book.best_move(&positions[4], White).unwrap().is_none());
+
+        assert_eq!(write(&db, 2, &path).unwrap(), 2);
+        std::fs::write(&path, b"APDB").unwrap();
+        assert!(matches!(Book::open(&path), Err(BookError::NotABook)));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
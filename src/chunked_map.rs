@@ -0,0 +1,176 @@
+//! Hash maps split into chunks that clones share until they are written to.
+//!
+//! Move generation clones a grid for every position it reaches and then
+//! changes a stack or two of the clone. Cloning a ChunkedMap only copies a
+//! pointer to each chunk, and a write copies the chunk it lands in if another
+//! clone still shares it, so a move costs a copy of the chunks around the
+//! locations it touches rather than of the whole board
+use crate::location::HexLocation;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+
+/// The number of chunks of a ChunkedMap
+pub const CHUNKS: usize = 16;
+
+/// The width and height, in locations, of the tiles the board is cut into,
+/// see ChunkKey
+const TILE: usize = 4;
+
+/// Keys of a ChunkedMap, sorted into chunks so that keys written together
+/// tend to share a chunk.
+///
+/// Locations are cut into square tiles repeating every 4 tiles across and
+/// down, so a location and most of its neighbors fall into the same chunk
+pub trait ChunkKey: Hash + Eq + Clone {
+    /// The chunk of the key, below CHUNKS
+    fn chunk(&self) -> usize;
+}
+
+/// The chunk of the tile holding the location at *x*, *y*
+fn tile_chunk(x: usize, y: usize) -> usize {
+    (x / TILE) % 4 + (y / TILE) % 4 * 4
+}
+
+impl ChunkKey for HexLocation {
+    fn chunk(&self) -> usize {
+        tile_chunk(self.x as u16 as usize, self.y as u16 as usize)
+    }
+}
+
+impl ChunkKey for (usize, usize) {
+    fn chunk(&self) -> usize {
+        tile_chunk(self.0, self.1)
+    }
+}
+
+/// A HashMap split into CHUNKS maps shared between clones until written to
+#[derive(Clone, Debug)]
+pub struct ChunkedMap<K, V> {
+    chunks: [Arc<HashMap<K, V>>; CHUNKS],
+    len: usize,
+}
+
+impl<K: ChunkKey, V: Clone> ChunkedMap<K, V> {
+    pub fn new() -> ChunkedMap<K, V> {
+        ChunkedMap {
+            chunks: std::array::from_fn(|_| Arc::new(HashMap::new())),
+            len: 0,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.chunks[key.chunk()].get(key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.chunks[key.chunk()].contains_key(key)
+    }
+
+    /// The value of *key* to change in place, copying its chunk first if a
+    /// clone still shares it
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let chunk = &mut self.chunks[key.chunk()];
+        if !chunk.contains_key(key) {
+            return None;
+        }
+        Arc::make_mut(chunk).get_mut(key)
+    }
+
+    /// The value of *key*, inserting the default value first if there is none
+    pub fn get_or_default(&mut self, key: K) -> &mut V
+    where
+        V: Default,
+    {
+        let chunk = Arc::make_mut(&mut self.chunks[key.chunk()]);
+        if !chunk.contains_key(&key) {
+            self.len += 1;
+        }
+        chunk.entry(key).or_default()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = Arc::make_mut(&mut self.chunks[key.chunk()]).insert(key, value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let chunk = &mut self.chunks[key.chunk()];
+        if !chunk.contains_key(key) {
+            return None;
+        }
+        self.len -= 1;
+        Arc::make_mut(chunk).remove(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the entries, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// The number of chunks *self* still shares with *other*, a clone of it
+    pub fn shared_chunks(&self, other: &ChunkedMap<K, V>) -> usize {
+        self.chunks
+            .iter()
+            .zip(&other.chunks)
+            .filter(|(ours, theirs)| Arc::ptr_eq(ours, theirs))
+            .count()
+    }
+}
+
+impl<K: ChunkKey, V: Clone> Default for ChunkedMap<K, V> {
+    fn default() -> ChunkedMap<K, V> {
+        ChunkedMap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_chunked_map() {
+        let mut map = ChunkedMap::new();
+        for x in 0..16 {
+            map.insert(HexLocation::new(x, -x), x);
+        }
+        assert_eq!(map.len(), 16);
+        *map.get_or_default(HexLocation::new(1, -1)) += 10;
+        *map.get_or_default(HexLocation::new(-5, 5)) += 1;
+        assert_eq!(map.len(), 17);
+
+        // A clone shares every chunk until one of them is written to
+        let original = map.clone();
+        assert_eq!(map.shared_chunks(&original), CHUNKS);
+        *map.get_mut(&HexLocation::new(2, -2)).unwrap() = 0;
+        assert_eq!(map.remove(&HexLocation::new(3, -3)), Some(3));
+        assert_eq!(map.remove(&HexLocation::new(3, -3)), None);
+        assert_eq!(map.shared_chunks(&original), CHUNKS - 1);
+
+        assert_eq!(map.get(&HexLocation::new(2, -2)), Some(&0));
+        assert_eq!(original.get(&HexLocation::new(2, -2)), Some(&2));
+        assert!(original.contains_key(&HexLocation::new(3, -3)));
+        assert_eq!((map.len(), original.len()), (16, 17));
+        assert_eq!(map.iter().count(), 16);
+        assert_eq!(original.values().sum::<i16>(), (0..16).sum::<i16>() + 11);
+    }
+}
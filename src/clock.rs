@@ -0,0 +1,256 @@
+//! Time controls and the clocks of the players under them.
+//!
+//! Besides the usual main time with an increment (Fischer), a time control
+//! may add Japanese byo-yomi: once the main time is spent, every move has to
+//! be made within a period, and each period overrun is lost, the player
+//! running out of time when no period is left
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ClockError {
+    #[error("Invalid time control {0}, expected a preset (bullet, blitz, rapid, classical), minutes+increment or minutes+periods x seconds")]
+    InvalidTimeControl(String),
+}
+
+pub type Result<T> = std::result::Result<T, ClockError>;
+
+/// The share of the main time an engine thinks on a single move,
+/// see Clock::move_time()
+const MOVES_TO_GO: u32 = 30;
+
+/// The time limits of both players, the same for each
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimeControl {
+    /// The thinking time of each player for the whole game
+    pub main: Duration,
+    /// Thinking time added to a player's clock after each of their moves
+    pub increment: Duration,
+    /// The number of byo-yomi periods once the main time is spent, none
+    /// for a plain time control
+    pub periods: u32,
+    /// The time each move may take once the main time is spent
+    pub period: Duration,
+}
+
+impl TimeControl {
+    /// *main* time for the game, with *increment* added after each move
+    pub fn fischer(main: Duration, increment: Duration) -> TimeControl {
+        TimeControl {
+            main,
+            increment,
+            periods: 0,
+            period: Duration::ZERO,
+        }
+    }
+
+    /// *main* time for the game, followed by *periods* byo-yomi periods of
+    /// *period* each
+    pub fn byo_yomi(main: Duration, periods: u32, period: Duration) -> TimeControl {
+        TimeControl {
+            main,
+            increment: Duration::ZERO,
+            periods,
+            period,
+        }
+    }
+
+    /// 1 minute and 2 seconds a move
+    pub fn bullet() -> TimeControl {
+        TimeControl::fischer(Duration::from_secs(60), Duration::from_secs(2))
+    }
+
+    /// 5 minutes and 3 seconds a move
+    pub fn blitz() -> TimeControl {
+        TimeControl::fischer(Duration::from_secs(5 * 60), Duration::from_secs(3))
+    }
+
+    /// 15 minutes and 10 seconds a move
+    pub fn rapid() -> TimeControl {
+        TimeControl::fischer(Duration::from_secs(15 * 60), Duration::from_secs(10))
+    }
+
+    /// 30 minutes and 20 seconds a move
+    pub fn classical() -> TimeControl {
+        TimeControl::fischer(Duration::from_secs(30 * 60), Duration::from_secs(20))
+    }
+
+    /// Whether the time control has byo-yomi periods
+    pub fn is_byo_yomi(&self) -> bool {
+        self.periods > 0
+    }
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        TimeControl::fischer(Duration::from_secs(300), Duration::ZERO)
+    }
+}
+
+impl TryFrom<&str> for TimeControl {
+    type Error = ClockError;
+
+    /// Parses a preset name, or the main time in minutes followed by either
+    /// the increment in seconds ("10+5") or the number and length in seconds
+    /// of byo-yomi periods ("10+3x30")
+    fn try_from(input: &str) -> Result<TimeControl> {
+        let invalid = || ClockError::InvalidTimeControl(input.to_string());
+        match input.trim() {
+            "bullet" => return Ok(TimeControl::bullet()),
+            "blitz" => return Ok(TimeControl::blitz()),
+            "rapid" => return Ok(TimeControl::rapid()),
+            "classical" => return Ok(TimeControl::classical()),
+            _ => {}
+        }
+
+        let (minutes, extra) = input.trim().split_once('+').ok_or_else(invalid)?;
+        let main = Duration::from_secs(minutes.parse::<u64>().map_err(|_| invalid())? * 60);
+        let seconds = |seconds: &str| seconds.parse::<u64>().map(Duration::from_secs);
+        match extra.split_once('x') {
+            Some((periods, period)) => {
+                let periods = periods.parse::<u32>().map_err(|_| invalid())?;
+                let period = seconds(period).map_err(|_| invalid())?;
+                if periods == 0 || period.is_zero() {
+                    return Err(invalid());
+                }
+                Ok(TimeControl::byo_yomi(main, periods, period))
+            }
+            None => Ok(TimeControl::fischer(
+                main,
+                seconds(extra).map_err(|_| invalid())?,
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for TimeControl {
+    /// Writes the time control the way TimeControl::try_from() reads it,
+    /// with the main time rounded down to whole minutes
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let minutes = self.main.as_secs() / 60;
+        match self.is_byo_yomi() {
+            true => write!(f, "{}+{}x{}", minutes, self.periods, self.period.as_secs()),
+            false => write!(f, "{}+{}", minutes, self.increment.as_secs()),
+        }
+    }
+}
+
+/// The time left to one player under a TimeControl
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Clock {
+    control: TimeControl,
+    /// The main time left
+    remaining: Duration,
+    periods: u32,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Clock {
+            control,
+            remaining: control.main,
+            periods: control.periods,
+        }
+    }
+
+    pub fn time_control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// The main time left
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    /// The byo-yomi periods left
+    pub fn periods(&self) -> u32 {
+        self.periods
+    }
+
+    /// The longest the player may think on the next move without running
+    /// out of time, the main time left and every period left
+    pub fn available(&self) -> Duration {
+        self.remaining + self.control.period * self.periods
+    }
+
+    /// How long an engine should think on the next move: a share of the main
+    /// time left and most of the increment, or most of a period once only
+    /// byo-yomi is left, so that a move never costs a period
+    pub fn move_time(&self) -> Duration {
+        let spare = |time: Duration| time * 9 / 10;
+        let time = match (self.remaining.is_zero(), self.periods > 0) {
+            (true, true) => spare(self.control.period),
+            (false, true) => self.remaining / MOVES_TO_GO + spare(self.control.period),
+            _ => self.remaining / MOVES_TO_GO + spare(self.control.increment),
+        };
+        time.min(self.available())
+    }
+
+    /// Charges *elapsed* thinking time for a move, returning false if the
+    /// player ran out of time on it.
+    ///
+    /// The main time is spent first, then a period is lost for every full
+    /// period the move took beyond it. The increment is only added to moves
+    /// made within the main time
+    pub fn spend(&mut self, elapsed: Duration) -> bool {
+        if elapsed <= self.remaining {
+            self.remaining = self.remaining - elapsed + self.control.increment;
+            return true;
+        }
+        let overrun = elapsed - self.remaining;
+        self.remaining = Duration::ZERO;
+        if self.periods == 0 {
+            return false;
+        }
+        let lost = overrun.as_nanos() / self.control.period.as_nanos();
+        if lost >= self.periods as u128 {
+            self.periods = 0;
+            return false;
+        }
+        self.periods -= lost as u32;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_time_control() {
+        let seconds = Duration::from_secs;
+        assert_eq!(TimeControl::try_from("blitz"), Ok(TimeControl::blitz()));
+        assert_eq!(
+            TimeControl::try_from("10+5"),
+            Ok(TimeControl::fischer(seconds(600), seconds(5)))
+        );
+        let byo_yomi = TimeControl::try_from("1+3x30").unwrap();
+        assert_eq!(byo_yomi, TimeControl::byo_yomi(seconds(60), 3, seconds(30)));
+        assert_eq!(byo_yomi.to_string(), "1+3x30");
+        assert_eq!(TimeControl::rapid().to_string(), "15+10");
+        for invalid in ["", "10", "10+", "a+5", "10+0x30", "10+3x", "10+3x30x2"] {
+            assert!(TimeControl::try_from(invalid).is_err(), "{}", invalid);
+        }
+
+        let mut clock = Clock::new(TimeControl::blitz());
+        assert!(clock.spend(seconds(10)));
+        assert_eq!(clock.remaining(), seconds(293));
+        assert!(clock.move_time() < clock.available());
+        assert!(!clock.spend(seconds(294)));
+
+        let mut clock = Clock::new(byo_yomi);
+        assert_eq!(clock.available(), seconds(150));
+        assert!(clock.spend(seconds(50)));
+        // Within the main time and a period, no period is lost
+        assert!(clock.spend(seconds(35)));
+        assert_eq!((clock.remaining(), clock.periods()), (Duration::ZERO, 3));
+        assert_eq!(clock.move_time(), seconds(27));
+        assert!(clock.spend(seconds(29)));
+        assert_eq!(clock.periods(), 3);
+        // Two periods overrun
+        assert!(clock.spend(seconds(61)));
+        assert_eq!(clock.periods(), 1);
+        assert!(!clock.spend(seconds(31)));
+        assert_eq!(clock.available(), Duration::ZERO);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod puzzles;
 pub mod statistics;
 
 pub use statistics::*;
@@ -0,0 +1,129 @@
+use crate::data_analysis::uhp_strings;
+use crate::game::{board_result, parse_game_string};
+use crate::hex_grid::*;
+use crate::replay::Replay;
+use crate::solver::SurroundSolver;
+use std::fmt::Display;
+
+/// A position where the player to move can force a surround of the
+/// opposing queen in exactly *moves* of their own moves, with a single
+/// first move that achieves it.
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    pub position: HexGrid,
+    pub to_move: PieceColor,
+    pub moves: usize,
+    pub solution: HexGrid,
+}
+
+impl Display for Puzzle {
+    /// Writes the puzzle and its solution as DSL strings, separated by a
+    /// short description of the task
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} to move, surround in {}\n",
+            self.to_move.to_str(),
+            self.moves
+        )?;
+        writeln!(f, "{}", self.position.to_dsl())?;
+        writeln!(f, "Solution:\n")?;
+        write!(f, "{}", self.solution.to_dsl())
+    }
+}
+
+/// Looks for a puzzle in the given position, trying increasingly long
+/// forced surrounds up to *max_moves*.
+///
+/// Positions with a forced surround that can be achieved by more than one
+/// first move are rejected, as are positions where a shorter surround exists.
+pub fn find_puzzle(
+    solver: &SurroundSolver,
+    position: &HexGrid,
+    last_move: Option<HexLocation>,
+    to_move: PieceColor,
+    max_moves: usize,
+) -> Option<Puzzle> {
+    if board_result(position).is_some() {
+        return None;
+    }
+
+    for moves in 1..=max_moves {
+        let mut wins = solver.winning_moves(position, last_move, to_move, moves);
+        if wins.is_empty() {
+            continue;
+        }
+        if wins.len() > 1 {
+            return None;
+        }
+
+        return Some(Puzzle {
+            position: position.clone(),
+            to_move,
+            moves,
+            solution: wins.pop().unwrap(),
+        });
+    }
+
+    None
+}
+
+/// Mines every position of the given UHP GameStrings for puzzles of
+/// at most *max_moves* moves.
+pub fn mine_puzzles(game_strings: &[&str], max_moves: usize) -> Vec<Puzzle> {
+    let mut puzzles = vec![];
+
+    for game_string in game_strings {
+        let Some((game_type, moves)) = parse_game_string(game_string) else {
+            continue;
+        };
+
+        let solver = SurroundSolver::new(game_type);
+        let mut replay = Replay::new(&moves);
+        let mut previous = replay.current_position().clone();
+
+        while let Some(position) = replay.next() {
+            let last_move = previous.diff(&position).and_then(|mv| mv.destination());
+            let to_move = match replay.ply() % 2 {
+                0 => PieceColor::White,
+                _ => PieceColor::Black,
+            };
+
+            if let Some(puzzle) = find_puzzle(&solver, &position, last_move, to_move, max_moves) {
+                puzzles.push(puzzle);
+            }
+            previous = position;
+        }
+    }
+
+    puzzles
+}
+
+/// Prints the puzzles found in the first *games* recorded games
+pub fn print_puzzles(max_moves: usize, games: usize) {
+    let games = uhp_strings().into_iter().take(games).collect::<Vec<_>>();
+    for puzzle in mine_puzzles(&games, max_moves) {
+        println!("{}\n", puzzle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_mine_puzzles() {
+        // The last move of this game surrounds the black queen
+        let white_wins = r"Base+PL;WhiteWins;Black[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/;wB1 \bL";
+        let puzzles = mine_puzzles(&[white_wins], 1);
+        assert!(!puzzles.is_empty());
+
+        let puzzle = puzzles.last().unwrap();
+        assert_eq!(puzzle.to_move, PieceColor::White);
+        assert_eq!(puzzle.moves, 1);
+        assert_eq!(
+            board_result(&puzzle.solution).unwrap().winner(),
+            Some(PieceColor::White)
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::bitgrid::basic::BasicBitGrid;
 use crate::game::GameDebugger;
-use crate::uhp::UHPInterface;
+#[cfg(feature = "uhp")]
+use crate::uhp_interface::UHPInterface;
 use regex::Regex;
 use std::collections::HashMap;
 
@@ -48,6 +49,7 @@ pub fn get_size(game: &GameDebugger) -> usize {
     bounds.width().max(bounds.height())
 }
 
+#[cfg(feature = "uhp")]
 pub fn check_positions() {
     let mut stats = Stats::new();
     let games = uhp_strings();
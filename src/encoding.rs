@@ -0,0 +1,492 @@
+use crate::game::GameResult;
+use crate::hex_grid::HexGrid;
+use crate::location::{Coordinate, HexLocation};
+use crate::moves::Move;
+use crate::piece::{IntoPieces, Piece, PieceColor};
+use crate::uhp::GameType;
+#[cfg(feature = "seal")]
+use sha2::{Digest as _, Sha256};
+use thiserror::Error;
+
+/// The version written at the start of every record, bumped whenever
+/// the layout changes so that old databases are rejected instead of misread
+pub const VERSION: u8 = 1;
+
+const POSITION_TAG: u8 = b'P';
+const GAME_TAG: u8 = b'G';
+const SEAL_TAG: u8 = b'S';
+
+const PASS_TAG: u8 = 0;
+const PLACE_TAG: u8 = 1;
+const MOVEMENT_TAG: u8 = 2;
+
+/// How a game ended, for the results decided by the players
+const NO_ENDING: u8 = 0;
+const WHITE_RESIGNED: u8 = 1;
+const BLACK_RESIGNED: u8 = 2;
+const DRAW_AGREED: u8 = 3;
+
+/// The game types in the order of their encoded index
+const GAME_TYPES: [GameType; 8] = [
+    GameType::Standard,
+    GameType::M,
+    GameType::L,
+    GameType::P,
+    GameType::ML,
+    GameType::MP,
+    GameType::LP,
+    GameType::MLP,
+];
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingError {
+    #[error("Unsupported encoding version {0}, expected {VERSION}")]
+    UnsupportedVersion(u8),
+    #[error("Expected a {0} record")]
+    WrongRecord(&'static str),
+    #[error("The record ends unexpectedly")]
+    Truncated,
+    #[error("{0:#04x} is not a valid piece")]
+    InvalidPiece(u8),
+    #[error("{0} is not a valid game type")]
+    InvalidGameType(u8),
+    #[error("{0} is not a valid move")]
+    InvalidMove(u8),
+    #[error("{0} is not a valid game ending")]
+    InvalidEnding(u8),
+    #[error("A stack cannot be empty")]
+    EmptyStack,
+    #[error("{0} bytes are left over after the record")]
+    TrailingBytes(usize),
+    #[error("The record does not match its digest, it was corrupted or changed")]
+    DigestMismatch,
+    #[error("The signature of the record is missing or invalid")]
+    InvalidSignature,
+}
+
+pub type Result<T> = std::result::Result<T, EncodingError>;
+
+/// Appends the binary encoding of a position to *output*, see encode_position()
+pub fn write_position(position: &impl IntoPieces, output: &mut Vec<u8>) {
+    let stacks = position.pieces();
+    output.extend([VERSION, POSITION_TAG]);
+    output.extend((stacks.len() as u16).to_le_bytes());
+    for (stack, location) in stacks {
+        write_location(location, output);
+        output.push(stack.len() as u8);
+        output.extend(stack.iter().map(|piece| piece.to_byte()));
+    }
+}
+
+/// Encodes a position as:
+///
+/// ```text
+/// version (u8) 'P' (u8) number of stacks (u16, little endian)
+/// for each stack in board order:
+///     x (i16) y (i16) height (u8) pieces from bottom to top (u8 each, see Piece::to_byte())
+/// ```
+///
+/// Equal positions always have the same encoding
+pub fn encode_position(position: &impl IntoPieces) -> Vec<u8> {
+    let mut output = vec![];
+    write_position(position, &mut output);
+    output
+}
+
+/// Decodes a position from the start of *bytes*, returning it with the
+/// number of bytes it took up, so that records stored back to back can
+/// be read one after another
+pub fn read_position(bytes: &[u8]) -> Result<(HexGrid, usize)> {
+    let mut reader = Reader::new(bytes);
+    reader.header(POSITION_TAG, "position")?;
+    let num_stacks = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+    let mut grid = HexGrid::new();
+    for _ in 0..num_stacks {
+        let location = reader.location()?;
+        let height = reader.byte()?;
+        if height == 0 {
+            return Err(EncodingError::EmptyStack);
+        }
+        for _ in 0..height {
+            grid.add(reader.piece()?, location);
+        }
+    }
+    Ok((grid, reader.offset))
+}
+
+/// Decodes a position made by encode_position(), which must span all of *bytes*
+pub fn decode_position(bytes: &[u8]) -> Result<HexGrid> {
+    let (grid, length) = read_position(bytes)?;
+    match bytes.len() - length {
+        0 => Ok(grid),
+        left_over => Err(EncodingError::TrailingBytes(left_over)),
+    }
+}
+
+/// Encodes a game as the moves played from the empty board, followed by
+/// how it ended if the players decided it (see GameDebugger::act()), the
+/// other results following from the moves:
+///
+/// ```text
+/// version (u8) 'G' (u8) game type (u8) number of moves (u32, little endian)
+/// for each move, one of:
+///     0 (pass)
+///     1 piece (u8) x (i16) y (i16) (placement)
+///     2 piece (u8) from x (i16) from y (i16) to x (i16) to y (i16) (movement)
+/// ending (u8): 0 (none), 1 (white resigned), 2 (black resigned), 3 (draw agreed)
+/// ```
+///
+/// Coordinates are little endian
+pub fn encode_game(game_type: GameType, moves: &[Move], ending: Option<&GameResult>) -> Vec<u8> {
+    let game_type = GAME_TYPES.iter().position(|&t| t == game_type).unwrap();
+    let mut output = vec![VERSION, GAME_TAG, game_type as u8];
+    output.extend((moves.len() as u32).to_le_bytes());
+    for mv in moves {
+        write_move(mv, &mut output);
+    }
+    output.push(match ending {
+        Some(GameResult::Resigned(PieceColor::White)) => WHITE_RESIGNED,
+        Some(GameResult::Resigned(PieceColor::Black)) => BLACK_RESIGNED,
+        Some(GameResult::DrawAgreed) => DRAW_AGREED,
+        _ => NO_ENDING,
+    });
+    output
+}
+
+/// Decodes a game made by encode_game(), which must span all of *bytes*,
+/// returning its game type, its moves and how the players ended it
+pub fn decode_game(bytes: &[u8]) -> Result<(GameType, Vec<Move>, Option<GameResult>)> {
+    let mut reader = Reader::new(bytes);
+    reader.header(GAME_TAG, "game")?;
+    let game_type = reader.byte()?;
+    let game_type = *GAME_TYPES
+        .get(game_type as usize)
+        .ok_or(EncodingError::InvalidGameType(game_type))?;
+    let mut length = [0; 4];
+    for byte in length.iter_mut() {
+        *byte = reader.byte()?;
+    }
+
+    let mut moves = vec![];
+    for _ in 0..u32::from_le_bytes(length) {
+        moves.push(reader.mv()?);
+    }
+    let ending = match reader.byte()? {
+        NO_ENDING => None,
+        WHITE_RESIGNED => Some(GameResult::Resigned(PieceColor::White)),
+        BLACK_RESIGNED => Some(GameResult::Resigned(PieceColor::Black)),
+        DRAW_AGREED => Some(GameResult::DrawAgreed),
+        ending => return Err(EncodingError::InvalidEnding(ending)),
+    };
+
+    match bytes.len() - reader.offset {
+        0 => Ok((game_type, moves, ending)),
+        left_over => Err(EncodingError::TrailingBytes(left_over)),
+    }
+}
+
+/// The SHA-256 digest of a record, see seal()
+#[cfg(feature = "seal")]
+pub type Digest = [u8; 32];
+
+/// Signs the digests of records written, see seal().
+///
+/// The crate does no public key cryptography itself: a Signer and a
+/// Verifier wrap whatever signature scheme the owner of the records uses,
+/// such as Ed25519, and sign the digest of a record rather than the record
+#[cfg(feature = "seal")]
+pub trait Signer {
+    fn sign(&self, digest: &Digest) -> Vec<u8>;
+}
+
+/// Checks the signatures of records read, see unseal()
+#[cfg(feature = "seal")]
+pub trait Verifier {
+    /// Whether *signature* was made by a trusted Signer for *digest*
+    fn verify(&self, digest: &Digest, signature: &[u8]) -> bool;
+}
+
+/// The SHA-256 digest of *bytes*
+#[cfg(feature = "seal")]
+pub fn digest(bytes: &[u8]) -> Digest {
+    Sha256::digest(bytes).into()
+}
+
+/// Wraps any record with its SHA-256 digest and, if a *signer* is given,
+/// the signature of the digest, so that unseal() detects any change to it:
+///
+/// ```text
+/// version (u8) 'S' (u8) digest of the record (32 bytes)
+/// length of the signature (u16, little endian) signature, empty if unsigned
+/// the record
+/// ```
+#[cfg(feature = "seal")]
+pub fn seal(record: &[u8], signer: Option<&dyn Signer>) -> Vec<u8> {
+    let digest = digest(record);
+    let signature = signer.map_or(vec![], |signer| signer.sign(&digest));
+    let mut output = vec![VERSION, SEAL_TAG];
+    output.extend(digest);
+    output.extend((signature.len() as u16).to_le_bytes());
+    output.extend(signature);
+    output.extend(record);
+    output
+}
+
+/// Returns the record sealed by seal() in *bytes* once it matches its
+/// digest and, if a *verifier* is given, the digest is signed by a signer
+/// it trusts
+#[cfg(feature = "seal")]
+pub fn unseal<'a>(bytes: &'a [u8], verifier: Option<&dyn Verifier>) -> Result<&'a [u8]> {
+    let mut reader = Reader::new(bytes);
+    reader.header(SEAL_TAG, "sealed")?;
+    let mut digest = [0; 32];
+    for byte in digest.iter_mut() {
+        *byte = reader.byte()?;
+    }
+    let length = u16::from_le_bytes([reader.byte()?, reader.byte()?]) as usize;
+    let signature = bytes
+        .get(reader.offset..reader.offset + length)
+        .ok_or(EncodingError::Truncated)?;
+    let record = &bytes[reader.offset + length..];
+
+    if self::digest(record) != digest {
+        return Err(EncodingError::DigestMismatch);
+    }
+    match verifier {
+        Some(verifier) if signature.is_empty() || !verifier.verify(&digest, signature) => {
+            Err(EncodingError::InvalidSignature)
+        }
+        _ => Ok(record),
+    }
+}
+
+/// Appends the encoding of a single move to *output*, as laid out in encode_game()
+pub fn write_move(mv: &Move, output: &mut Vec<u8>) {
+    match *mv {
+        Move::Pass => output.push(PASS_TAG),
+        Move::Place { piece, to } => {
+            output.extend([PLACE_TAG, piece.to_byte()]);
+            write_location(to, output);
+        }
+        Move::Movement { piece, from, to } => {
+            output.extend([MOVEMENT_TAG, piece.to_byte()]);
+            write_location(from, output);
+            write_location(to, output);
+        }
+    }
+}
+
+/// Decodes a move made by write_move() from the start of *bytes*, returning
+/// it with the number of bytes it took up
+pub fn read_move(bytes: &[u8]) -> Result<(Move, usize)> {
+    let mut reader = Reader::new(bytes);
+    let mv = reader.mv()?;
+    Ok((mv, reader.offset))
+}
+
+fn write_location(location: HexLocation, output: &mut Vec<u8>) {
+    output.extend(location.x.to_le_bytes());
+    output.extend(location.y.to_le_bytes());
+}
+
+/// Reads the fields of a record in order
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, offset: 0 }
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let byte = *self
+            .bytes
+            .get(self.offset)
+            .ok_or(EncodingError::Truncated)?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn header(&mut self, tag: u8, record: &'static str) -> Result<()> {
+        let version = self.byte()?;
+        if version != VERSION {
+            return Err(EncodingError::UnsupportedVersion(version));
+        }
+        if self.byte()? != tag {
+            return Err(EncodingError::WrongRecord(record));
+        }
+        Ok(())
+    }
+
+    fn location(&mut self) -> Result<HexLocation> {
+        let x = Coordinate::from_le_bytes([self.byte()?, self.byte()?]);
+        let y = Coordinate::from_le_bytes([self.byte()?, self.byte()?]);
+        Ok(HexLocation::new(x, y))
+    }
+
+    fn mv(&mut self) -> Result<Move> {
+        let mv = match self.byte()? {
+            PASS_TAG => Move::Pass,
+            PLACE_TAG => Move::Place {
+                piece: self.piece()?,
+                to: self.location()?,
+            },
+            MOVEMENT_TAG => Move::Movement {
+                piece: self.piece()?,
+                from: self.location()?,
+                to: self.location()?,
+            },
+            tag => return Err(EncodingError::InvalidMove(tag)),
+        };
+        Ok(mv)
+    }
+
+    fn piece(&mut self) -> Result<Piece> {
+        let byte = self.byte()?;
+        match Piece::from_byte(byte) {
+            Ok(Some(piece)) => Ok(piece),
+            _ => Err(EncodingError::InvalidPiece(byte)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::{PieceColor, PieceType};
+
+    #[test]
+    pub fn test_encode_position() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . Q 2 a .\n",
+            ". . g . .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ b B ]\n",
+        ));
+        let bytes = encode_position(&grid);
+        // header, 4 stacks of 5 bytes, 5 pieces
+        assert_eq!(bytes.len(), 4 + 4 * 5 + 5);
+        assert_eq!(decode_position(&bytes), Ok(grid.clone()));
+        assert_eq!(
+            decode_position(&encode_position(&HexGrid::new())),
+            Ok(HexGrid::new())
+        );
+
+        // Records stored back to back
+        let mut stream = encode_position(&HexGrid::new());
+        write_position(&grid, &mut stream);
+        let (first, length) = read_position(&stream).unwrap();
+        assert_eq!(first, HexGrid::new());
+        assert_eq!(read_position(&stream[length..]), Ok((grid, bytes.len())));
+
+        assert_eq!(
+            decode_position(&bytes[..bytes.len() - 1]),
+            Err(EncodingError::Truncated)
+        );
+        assert_eq!(
+            decode_position(&stream),
+            Err(EncodingError::TrailingBytes(bytes.len()))
+        );
+        let mut future = bytes.clone();
+        future[0] = VERSION + 1;
+        assert_eq!(
+            decode_position(&future),
+            Err(EncodingError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+
+    #[test]
+    pub fn test_encode_game() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        let moves = vec![
+            Move::Place {
+                piece: Piece::new(Queen, White),
+                to: HexLocation::new(0, 0),
+            },
+            Move::Place {
+                piece: Piece::new(Ant, Black),
+                to: HexLocation::new(1, 0),
+            },
+            Move::Movement {
+                piece: Piece::new(Queen, White),
+                from: HexLocation::new(0, 0),
+                to: HexLocation::new(1, -1),
+            },
+            Move::Pass,
+        ];
+        let bytes = encode_game(GameType::MP, &moves, None);
+        assert_eq!(decode_game(&bytes), Ok((GameType::MP, moves.clone(), None)));
+
+        // Only the results decided by the players are stored
+        let resigned = GameResult::Resigned(Black);
+        let bytes = encode_game(GameType::MP, &moves, Some(&resigned));
+        assert_eq!(
+            decode_game(&bytes),
+            Ok((GameType::MP, moves.clone(), Some(resigned)))
+        );
+        let bytes = encode_game(GameType::MP, &moves, Some(&GameResult::WhiteWins));
+        assert_eq!(decode_game(&bytes), Ok((GameType::MP, moves, None)));
+        let mut invalid = bytes.clone();
+        *invalid.last_mut().unwrap() = 9;
+        assert_eq!(decode_game(&invalid), Err(EncodingError::InvalidEnding(9)));
+
+        // Far beyond the range of a byte
+        let far = vec![Move::Place {
+            piece: Piece::new(Queen, White),
+            to: HexLocation::new(300, -1000),
+        }];
+        let bytes = encode_game(GameType::MP, &far, Some(&GameResult::DrawAgreed));
+        assert_eq!(
+            decode_game(&bytes),
+            Ok((GameType::MP, far, Some(GameResult::DrawAgreed)))
+        );
+
+        assert_eq!(
+            decode_position(&bytes),
+            Err(EncodingError::WrongRecord("position"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "seal")]
+    pub fn test_seal() {
+        use crate::testing::KeyedSigner;
+
+        let game = encode_game(GameType::MLP, &[Move::Pass], None);
+        let sealed = seal(&game, None);
+        assert_eq!(unseal(&sealed, None), Ok(&game[..]));
+        let key = KeyedSigner(*b"key1");
+        assert_eq!(
+            unseal(&sealed, Some(&key)),
+            Err(EncodingError::InvalidSignature)
+        );
+
+        let signed = seal(&game, Some(&key));
+        assert_eq!(unseal(&signed, Some(&key)), Ok(&game[..]));
+        let other = KeyedSigner(*b"key2");
+        assert_eq!(
+            unseal(&signed, Some(&other)),
+            Err(EncodingError::InvalidSignature)
+        );
+        // A single bit flipped in the record
+        let mut corrupted = signed.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(unseal(&corrupted, None), Err(EncodingError::DigestMismatch));
+        let truncated = &signed[..signed.len() - 1];
+        assert_eq!(unseal(truncated, None), Err(EncodingError::DigestMismatch));
+        assert_eq!(unseal(&signed[..50], None), Err(EncodingError::Truncated));
+
+        let mut future = sealed.clone();
+        future[0] = VERSION + 1;
+        assert_eq!(
+            unseal(&future, None),
+            Err(EncodingError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use crate::engine::evaluation::*;
+use crate::engine::profile::Profile;
+use crate::engine::search::Searcher;
+use crate::engine::transposition::{TranspositionTable, DEFAULT_CAPACITY};
+use crate::game::GameDebugger;
+use crate::hex_grid::HexGrid;
+use std::path::Path;
+
+/// Formats a score for display, forced surrounds are shown with the
+/// number of plies until the queen is surrounded
+pub fn format_score(score: Score) -> String {
+    match forced_surround(score) {
+        Some(ForcedSurround::Surrounds(plies)) => format!("surround in {}", plies),
+        Some(ForcedSurround::Surrounded(plies)) => format!("surrounded in {}", plies),
+        None => format!("{:+}", score),
+    }
+}
+
+/// Names the moves of a line of positions played from the current position
+/// of *game*, stopping at the first position that does not follow
+pub fn line_strings(game: &GameDebugger, line: &[HexGrid]) -> Vec<String> {
+    let mut game = game.clone();
+    let mut moves = vec![];
+    for position in line.iter() {
+        let Ok(move_string) = game.move_string(position) else {
+            break;
+        };
+        if game.append_position(position).is_err() {
+            break;
+        }
+        moves.push(move_string);
+    }
+    moves
+}
+
+/// Searches the position given by a UHP GameString (or a file containing one)
+/// to *depth* plies and prints its evaluation, best line and the scores of
+/// the *top* best moves, see Searcher::set_deterministic() for *deterministic*.
+///
+/// Positions are evaluated with the weights of *profile*, the name of a
+/// built-in profile or a profile file, see Profile::find()
+///
+/// If *tree* is given as a file and a limit, the first positions visited by
+/// the search up to the limit are written to the file, in the DOT language if
+/// it ends in .dot and as JSON otherwise, see SearchTree.
+///
+/// If *cache* is given, the search starts from the transposition table saved
+/// in it (if any) and saves the table back to it afterwards, so that analysis
+/// of similar positions is not repeated between runs
+pub fn print_analysis(
+    position: &str,
+    depth: usize,
+    top: usize,
+    deterministic: bool,
+    profile: &str,
+    tree: Option<(&Path, usize)>,
+    cache: Option<&Path>,
+) {
+    let profile = match Profile::find(profile) {
+        Ok(profile) => profile,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let game_string = match Path::new(position).is_file() {
+        true => match std::fs::read_to_string(position) {
+            Ok(contents) => contents,
+            Err(e) => {
+                println!("Could not read {}: {}", position, e);
+                return;
+            }
+        },
+        false => position.to_string(),
+    };
+    let game = match GameDebugger::from_game_string(&game_string) {
+        Ok(game) => game,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    if let Some(result) = game.game_result() {
+        println!("The game is over: {:?}", result);
+        return;
+    }
+
+    let color = game.player_to_move();
+    let mut searcher = Searcher::with_weights(game.game_type(), profile.weights);
+    searcher.set_deterministic(deterministic);
+    searcher.set_tree_limit(tree.map(|(_, limit)| limit));
+    if let Some(path) = cache {
+        let table = match path.exists() {
+            true => match TranspositionTable::load(path, DEFAULT_CAPACITY) {
+                Ok(table) => table,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            },
+            false => TranspositionTable::default(),
+        };
+        searcher.set_transposition_table(Some(table));
+    }
+    let analysis = searcher.analyze(game.position(), game.last_move(), color, depth);
+
+    println!(
+        "Evaluation: {} for {} at depth {}",
+        format_score(analysis.score),
+        color.to_str(),
+        depth,
+    );
+    println!("Search: {}", analysis.stats);
+
+    let moves = line_strings(&game, &analysis.best_line);
+    println!("Best line: {}", moves.join("; "));
+
+    println!("Candidates:");
+    for (position, score) in analysis.candidates.iter().take(top) {
+        let move_string = game
+            .move_string(position)
+            .unwrap_or_else(|_| "unknown".to_string());
+        println!("  {:<16} {}", move_string, format_score(*score));
+    }
+
+    if let (Some((path, _)), Some(search_tree)) = (tree, searcher.tree()) {
+        let contents = match path.extension().is_some_and(|extension| extension == "dot") {
+            true => search_tree.to_dot(),
+            false => search_tree.to_json(),
+        };
+        match std::fs::write(path, contents) {
+            Ok(()) => println!(
+                "Wrote {} positions of the search tree to {}{}",
+                search_tree.nodes().len(),
+                path.display(),
+                match search_tree.is_truncated() {
+                    true => " (truncated)",
+                    false => "",
+                }
+            ),
+            Err(e) => println!("Could not write {}: {}", path.display(), e),
+        }
+    }
+
+    if let (Some(path), Some(table)) = (cache, searcher.transposition_table()) {
+        match table.save(path) {
+            Ok(()) => println!("Saved {} positions to {}", table.len(), path.display()),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
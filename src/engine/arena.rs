@@ -0,0 +1,123 @@
+use std::ops::{Index, IndexMut, Range};
+
+/// A bump arena for the items the search keeps for each position on the
+/// path from the root, such as the moves left to search.
+///
+/// A position pushes its items on top of the arena and releases them when
+/// the search leaves it, so positions deeper in the search stack their items
+/// above those of the positions they were reached from. The memory stays
+/// with the arena once released, so after the first few positions a search
+/// no longer allocates for them. Each Searcher has its own arena, so threads
+/// searching in parallel do not share one
+#[derive(Clone, Debug)]
+pub struct StackArena<T> {
+    items: Vec<T>,
+    /// The most items held at once since the last reset()
+    peak: usize,
+}
+
+impl<T> StackArena<T> {
+    pub fn new() -> StackArena<T> {
+        StackArena {
+            items: vec![],
+            peak: 0,
+        }
+    }
+
+    /// The top of the arena, to release() back to once the items pushed
+    /// from now on are no longer needed
+    pub fn mark(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Pushes *items* on top of the arena, returning the indices they are at
+    pub fn extend(&mut self, items: impl IntoIterator<Item = T>) -> Range<usize> {
+        let start = self.items.len();
+        self.items.extend(items);
+        self.peak = self.peak.max(self.items.len());
+        start..self.items.len()
+    }
+
+    /// The items at *range*, as returned by extend()
+    pub fn slice_mut(&mut self, range: Range<usize>) -> &mut [T] {
+        &mut self.items[range]
+    }
+
+    /// Drops every item pushed since *mark* was taken, see mark()
+    pub fn release(&mut self, mark: usize) {
+        self.items.truncate(mark);
+    }
+
+    /// Drops every item and the peak, keeping the memory for the next search
+    pub fn reset(&mut self) {
+        self.items.clear();
+        self.peak = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The most items held at once since the last reset()
+    pub fn peak(&self) -> usize {
+        self.peak
+    }
+
+    /// The number of items the arena holds without allocating
+    pub fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+}
+
+impl<T> Default for StackArena<T> {
+    fn default() -> StackArena<T> {
+        StackArena::new()
+    }
+}
+
+impl<T> Index<usize> for StackArena<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.items[index]
+    }
+}
+
+impl<T> IndexMut<usize> for StackArena<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        &mut self.items[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_stack_arena() {
+        let mut arena = StackArena::new();
+        let root = arena.extend([1, 2, 3]);
+        assert_eq!(root, 0..3);
+
+        // A deeper position stacks its items above and releases them
+        let mark = arena.mark();
+        let child = arena.extend([4, 5]);
+        arena.slice_mut(child).reverse();
+        assert_eq!((arena[3], arena[4]), (5, 4));
+        arena.release(mark);
+        assert_eq!(arena.len(), 3);
+        arena[0] = 7;
+        assert_eq!(arena.slice_mut(root), [7, 2, 3]);
+
+        // The memory is kept for the next search
+        let capacity = arena.capacity();
+        assert_eq!(arena.peak(), 5);
+        arena.reset();
+        assert!(arena.is_empty() && arena.peak() == 0);
+        assert_eq!(arena.capacity(), capacity);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::engine::evaluation::Score;
+use crate::engine::search::Searcher;
+use crate::engine::transposition::TranspositionTable;
+use crate::game::GameDebugger;
+use std::num::NonZeroUsize;
+
+/// The number of workers evaluate_batch() uses unless told otherwise, one
+/// per available core
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Searches the current position of every game of *games* *depth* plies
+/// deep, returning the scores for the player to move in the order of the
+/// games, for labeling datasets and scoring the ends of playouts. A depth
+/// of zero gives the static evaluation. Moves going back to an earlier state
+/// of a game are scored as draws, see Searcher::set_history().
+///
+/// The games are split into *threads* runs of consecutive games, each
+/// searched by a clone of *searcher* on its own thread. A worker keeps its
+/// transposition table from one game to the next (starting with an empty
+/// one if *searcher* has none), so batches listing related positions next
+/// to each other, such as the plies of a game, are searched faster
+pub fn evaluate_batch(
+    games: &[GameDebugger],
+    searcher: &Searcher,
+    depth: usize,
+    threads: usize,
+) -> Vec<Score> {
+    if games.is_empty() {
+        return vec![];
+    }
+    let chunk_size = games.len().div_ceil(threads.max(1));
+    std::thread::scope(|scope| {
+        let workers = games
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let mut searcher = searcher.clone();
+                if searcher.transposition_table().is_none() {
+                    searcher.set_transposition_table(Some(TranspositionTable::default()));
+                }
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|game| {
+                            searcher.set_history(game.state_keys());
+                            searcher
+                                .analyze(
+                                    game.position(),
+                                    game.last_move(),
+                                    game.player_to_move(),
+                                    depth,
+                                )
+                                .score
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("A batch worker panicked"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::evaluation::{evaluate, Weights};
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_evaluate_batch() {
+        let moves = ["wA1", "bG1 -wA1", "wQ wA1-", "bQ /bG1", "wG1 wQ-"];
+        let games = (1..=moves.len())
+            .map(|plies| GameDebugger::from_move_list(&moves[..plies], GameType::Standard).unwrap())
+            .collect::<Vec<_>>();
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_deterministic(true);
+
+        let sequential = games
+            .iter()
+            .map(|game| {
+                searcher
+                    .clone()
+                    .analyze(game.position(), game.last_move(), game.player_to_move(), 2)
+                    .score
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(evaluate_batch(&games, &searcher, 2, 1), sequential);
+        assert_eq!(evaluate_batch(&games, &searcher, 2, 3), sequential);
+        assert_eq!(evaluate_batch(&games, &searcher, 2, 64), sequential);
+
+        let statics = evaluate_batch(&games, &searcher, 0, default_threads());
+        for (game, score) in games.iter().zip(statics) {
+            let expected = evaluate(game.position(), game.player_to_move(), &Weights::default());
+            assert_eq!(score, expected);
+        }
+        assert!(evaluate_batch(&[], &searcher, 2, 4).is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+use crate::game::board_result;
+use crate::generator::debug::{PositionGenerator, ReferenceGenerator};
+use crate::hex_grid::HexGrid;
+use crate::location::HexLocation;
+use crate::moves::Move;
+use crate::piece::PieceColor;
+use crate::uhp::GameType;
+use crate::zobrist;
+use smallvec::SmallVec;
+use std::collections::{HashMap, VecDeque};
+
+/// The index of a position in a PositionDag
+pub type PositionId = usize;
+
+/// A position of a PositionDag together with the moves out of it
+#[derive(Clone, Debug)]
+pub struct DagNode {
+    pub grid: HexGrid,
+    /// The destination of the piece moved in the previous turn
+    pub last_move: Option<HexLocation>,
+    /// The player to move
+    pub color: PieceColor,
+    /// The fewest plies from a root to the position
+    pub ply: usize,
+    /// The moves out of the position and the positions they lead to, None
+    /// until the position is expanded and empty once the game is over
+    pub children: Option<Vec<(Move, PositionId)>>,
+    /// The number of moves into the position, more than one when it is
+    /// reached through several orders of moves
+    pub parents: usize,
+}
+
+/// Counts kept by a PositionDag as positions are interned
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DagStats {
+    /// Positions reached by a move, through every order of moves
+    pub reached: usize,
+    /// Positions reached by a move that were already in the DAG, and share
+    /// the node stored for them instead of being stored again
+    pub shared: usize,
+    /// Positions with the same key as a different position, told apart by
+    /// comparing the positions themselves
+    pub collisions: usize,
+}
+
+/// The positions met by a wide search (breadth first or by playouts), each
+/// stored once however many orders of moves reach it.
+///
+/// Positions are interned: a position reached again is looked up by its
+/// zobrist::state_key() and compared with the positions stored under that
+/// key, and the moves into it point to the node already stored. The moves
+/// of the search then form a directed acyclic graph rather than a tree, so
+/// a transposition is expanded and scored once
+#[derive(Clone, Debug)]
+pub struct PositionDag {
+    game_type: GameType,
+    nodes: Vec<DagNode>,
+    /// The positions stored under each key, more than one only for
+    /// positions whose keys collide
+    index: HashMap<u64, SmallVec<[PositionId; 1]>>,
+    stats: DagStats,
+}
+
+impl PositionDag {
+    pub fn new(game_type: GameType) -> PositionDag {
+        PositionDag {
+            game_type,
+            nodes: vec![],
+            index: HashMap::new(),
+            stats: DagStats::default(),
+        }
+    }
+
+    /// The id of *grid* with *color* to move, where *last_move* is the
+    /// destination of the piece moved in the previous turn, if it is in the
+    /// DAG
+    pub fn find(
+        &self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        color: PieceColor,
+    ) -> Option<PositionId> {
+        let key = zobrist::state_key(grid, last_move, color);
+        self.index.get(&key)?.iter().copied().find(|&id| {
+            let node = &self.nodes[id];
+            node.last_move == last_move && node.color == color && node.grid == *grid
+        })
+    }
+
+    /// Adds a position *ply* plies from a root, see find(), returning its id
+    /// and whether it was new. A position already in the DAG keeps its node,
+    /// with the ply lowered if it is now reached sooner
+    pub fn intern(
+        &mut self,
+        grid: HexGrid,
+        last_move: Option<HexLocation>,
+        color: PieceColor,
+        ply: usize,
+    ) -> (PositionId, bool) {
+        if let Some(id) = self.find(&grid, last_move, color) {
+            let node = &mut self.nodes[id];
+            node.ply = node.ply.min(ply);
+            return (id, false);
+        }
+
+        let id = self.nodes.len();
+        let ids = self
+            .index
+            .entry(zobrist::state_key(&grid, last_move, color))
+            .or_default();
+        if !ids.is_empty() {
+            self.stats.collisions += 1;
+        }
+        ids.push(id);
+        self.nodes.push(DagNode {
+            grid,
+            last_move,
+            color,
+            ply,
+            children: None,
+            parents: 0,
+        });
+        (id, true)
+    }
+
+    /// Generates the moves out of the position *id* the first time it is
+    /// called, interning the positions they lead to, and returns them
+    pub fn expand(&mut self, id: PositionId) -> &[(Move, PositionId)] {
+        if self.nodes[id].children.is_none() {
+            let children = self.generate(id);
+            self.nodes[id].children = Some(children);
+        }
+        self.nodes[id].children.as_deref().unwrap_or_default()
+    }
+
+    fn generate(&mut self, id: PositionId) -> Vec<(Move, PositionId)> {
+        let node = &self.nodes[id];
+        if board_result(&node.grid).is_some() {
+            return vec![];
+        }
+        let (grid, color, ply) = (node.grid.clone(), node.color, node.ply + 1);
+        let mut generator =
+            ReferenceGenerator::from_hex_grid(&grid, self.game_type, node.last_move);
+
+        let mut children = vec![];
+        for position in generator.generate_positions_for(color) {
+            let mv = grid.diff(&position).unwrap_or(Move::Pass);
+            let (child, new) = self.intern(position, mv.destination(), color.opposite(), ply);
+            self.stats.reached += 1;
+            if !new {
+                self.stats.shared += 1;
+            }
+            self.nodes[child].parents += 1;
+            children.push((mv, child));
+        }
+        children
+    }
+
+    /// Expands every position up to *plies* moves below *root* breadth
+    /// first, returning the number of distinct positions first reached at
+    /// each ply, starting with the root
+    pub fn explore(&mut self, root: PositionId, plies: usize) -> Vec<usize> {
+        let mut widths = vec![1];
+        let mut queue = VecDeque::from([(root, 0)]);
+        let mut queued = vec![false; self.nodes.len()];
+        queued[root] = true;
+
+        while let Some((id, ply)) = queue.pop_front() {
+            if ply == plies {
+                continue;
+            }
+            let children = self.expand(id).to_vec();
+            queued.resize(self.nodes.len(), false);
+            for (_, child) in children {
+                if queued[child] {
+                    continue;
+                }
+                queued[child] = true;
+                if widths.len() <= ply + 1 {
+                    widths.push(0);
+                }
+                widths[ply + 1] += 1;
+                queue.push_back((child, ply + 1));
+            }
+        }
+        widths
+    }
+
+    /// The number of orders of *plies* moves below *id*, as counted by
+    /// perft::perft(), counting the paths through each position once
+    pub fn paths(&mut self, id: PositionId, plies: usize) -> usize {
+        let mut counted = HashMap::new();
+        self.count_paths(id, plies, &mut counted)
+    }
+
+    fn count_paths(
+        &mut self,
+        id: PositionId,
+        plies: usize,
+        counted: &mut HashMap<(PositionId, usize), usize>,
+    ) -> usize {
+        if plies == 0 {
+            return 1;
+        }
+        if let Some(&count) = counted.get(&(id, plies)) {
+            return count;
+        }
+        let children = self.expand(id).to_vec();
+        let count = children
+            .into_iter()
+            .map(|(_, child)| self.count_paths(child, plies - 1, counted))
+            .sum();
+        counted.insert((id, plies), count);
+        count
+    }
+
+    pub fn node(&self, id: PositionId) -> &DagNode {
+        &self.nodes[id]
+    }
+
+    /// The number of distinct positions stored
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn stats(&self) -> DagStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameDebugger;
+    use crate::perft::perft;
+
+    #[test]
+    pub fn test_position_dag() {
+        use PieceColor::*;
+
+        let mut dag = PositionDag::new(GameType::Standard);
+        let (root, new) = dag.intern(HexGrid::new(), None, White, 0);
+        assert!(new);
+        assert_eq!(dag.intern(HexGrid::new(), None, White, 0), (root, false));
+        assert_eq!(dag.find(&HexGrid::new(), None, Black), None);
+
+        let widths = dag.explore(root, 2);
+        assert_eq!(widths, [1, 4, 4 * 4 * 6]);
+        assert_eq!(dag.len(), widths.iter().sum::<usize>());
+        assert_eq!(dag.stats().shared, 0);
+        let (mv, child) = dag.expand(root)[0];
+        let child = dag.node(child);
+        assert_eq!(mv.apply(&HexGrid::new()), child.grid);
+        assert_eq!((child.color, child.last_move), (Black, mv.destination()));
+
+        // Black placing two pieces in either order reaches the same position
+        let moves = [r"wA1", r"bA1 wA1-", r"wQ -wA1"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        let game = GameDebugger::from_moves_custom(&moves, GameType::Standard).unwrap();
+        let (grid, last_move) = (game.position(), game.last_move());
+        let mut dag = PositionDag::new(GameType::Standard);
+        let (root, _) = dag.intern(grid.clone(), last_move, Black, 0);
+        dag.explore(root, 3);
+        let stats = dag.stats();
+        assert!(stats.shared > 0);
+        assert_eq!(stats.reached - stats.shared, dag.len() - 1);
+        let transposed = (0..dag.len()).find(|&id| dag.node(id).parents > 1);
+        assert_eq!(dag.node(transposed.unwrap()).ply, 3);
+        assert_eq!(
+            dag.paths(root, 3),
+            perft(grid, GameType::Standard, Black, last_move, 3)
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use crate::engine::batch;
+use crate::engine::evaluation::{Score, Weights};
+use crate::engine::rng::Rng;
+use crate::engine::search::Searcher;
+use crate::engine::seen::SeenPositions;
+use crate::engine::strength::Strength;
+use crate::game::GameDebugger;
+use crate::hex_grid::HexGrid;
+
+/// A player that picks a move in any position, so that different engines
+/// can be played against each other
+pub trait Engine {
+    /// A short name to report the engine by
+    fn name(&self) -> String;
+
+    /// Returns the position after the move chosen in the current position
+    /// of *game*, None if the game is over
+    fn best_move(&mut self, game: &mut GameDebugger) -> Option<HexGrid>;
+
+    /// Returns the score of the current position of every game of *games*
+    /// for its player to move, in order, see batch::evaluate_batch(). The
+    /// games must all be of the same game type. Defaults to the static
+    /// evaluation with the default weights
+    fn evaluate_batch(&mut self, games: &[GameDebugger]) -> Vec<Score> {
+        let Some(first) = games.first() else {
+            return vec![];
+        };
+        batch::evaluate_batch(games, &Searcher::new(first.game_type()), 0, 1)
+    }
+}
+
+/// Plays a uniformly random legal move, the weakest possible opponent
+#[derive(Clone, Debug)]
+pub struct RandomEngine {
+    rng: Rng,
+    /// The positions of the game being played, see set_avoid_repeats()
+    seen: Option<SeenPositions>,
+    /// The number of positions of the game at the last move
+    plies: usize,
+}
+
+impl RandomEngine {
+    /// An engine that plays the same moves in the same games for equal *seed*s
+    pub fn new(seed: u64) -> RandomEngine {
+        RandomEngine {
+            rng: Rng::new(seed),
+            seen: None,
+            plies: 0,
+        }
+    }
+
+    /// Whether to choose among the moves leading to a position not yet
+    /// seen in the game when there are any, so that random games and
+    /// playouts do not cycle. Seen positions are kept in a SeenPositions
+    /// filter, forgotten whenever a game no longer than the last one is
+    /// given, so a few unseen positions may be taken as seen
+    pub fn set_avoid_repeats(&mut self, avoid: bool) {
+        self.seen = avoid.then(SeenPositions::default);
+        self.plies = 0;
+    }
+
+    /// Marks the positions of *game* played since the last move as seen
+    fn update_seen(&mut self, game: &GameDebugger) {
+        let Some(seen) = self.seen.as_mut() else {
+            return;
+        };
+        let plies = game.positions().count();
+        if plies <= self.plies {
+            seen.clear();
+            self.plies = 0;
+        }
+        for position in game.positions().skip(self.plies) {
+            seen.insert(position);
+        }
+        self.plies = plies;
+    }
+}
+
+impl Engine for RandomEngine {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn best_move(&mut self, game: &mut GameDebugger) -> Option<HexGrid> {
+        let current = game.position().clone();
+        let mut positions = game.legal_positions();
+        self.update_seen(game);
+        if let Some(seen) = self.seen.as_ref() {
+            let unseen = positions
+                .iter()
+                .filter(|position| !seen.contains(*position))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !unseen.is_empty() {
+                positions = unseen;
+            }
+        }
+        if positions.is_empty() {
+            return None;
+        }
+        // By move rather than in the order of the generator, so that a seed
+        // keeps choosing the same moves as the generator changes
+        positions.sort_by_cached_key(|position| current.diff(position));
+        let index = self.rng.below(positions.len() as u64) as usize;
+        Some(positions.swap_remove(index))
+    }
+}
+
+/// Plays the move with the best static evaluation, looking no further
+/// than one ply ahead
+#[derive(Clone, Debug, Default)]
+pub struct GreedyEngine {
+    weights: Weights,
+}
+
+impl GreedyEngine {
+    pub fn new(weights: Weights) -> GreedyEngine {
+        GreedyEngine { weights }
+    }
+}
+
+impl Engine for GreedyEngine {
+    fn name(&self) -> String {
+        "greedy".to_string()
+    }
+
+    fn best_move(&mut self, game: &mut GameDebugger) -> Option<HexGrid> {
+        if game.game_result().is_some() {
+            return None;
+        }
+        let mut searcher = Searcher::with_weights(game.game_type(), self.weights.clone());
+        searcher.set_deterministic(true);
+        let analysis =
+            searcher.analyze(game.position(), game.last_move(), game.player_to_move(), 1);
+        analysis.best_line.into_iter().next()
+    }
+
+    /// Searches one ply with the engine's weights
+    fn evaluate_batch(&mut self, games: &[GameDebugger]) -> Vec<Score> {
+        let Some(first) = games.first() else {
+            return vec![];
+        };
+        let mut searcher = Searcher::with_weights(first.game_type(), self.weights.clone());
+        searcher.set_deterministic(true);
+        batch::evaluate_batch(games, &searcher, 1, batch::default_threads())
+    }
+}
+
+/// Plays with the alpha-beta Searcher at a given Strength
+#[derive(Clone, Debug)]
+pub struct SearchEngine {
+    weights: Weights,
+    strength: Strength,
+    rng: Rng,
+    contempt: Score,
+}
+
+impl SearchEngine {
+    /// An engine searching with *weights* at *strength*, where *seed*
+    /// decides the mistakes made by weaker levels
+    pub fn new(weights: Weights, strength: Strength, seed: u64) -> SearchEngine {
+        SearchEngine {
+            weights,
+            strength,
+            rng: Rng::new(seed),
+            contempt: 0,
+        }
+    }
+
+    /// How hard the engine avoids (positive) or seeks (negative) draws,
+    /// see Searcher::set_contempt()
+    pub fn set_contempt(&mut self, contempt: Score) {
+        self.contempt = contempt;
+    }
+}
+
+impl Engine for SearchEngine {
+    fn name(&self) -> String {
+        format!("search level {}", self.strength.level)
+    }
+
+    fn best_move(&mut self, game: &mut GameDebugger) -> Option<HexGrid> {
+        let mut searcher = Searcher::with_weights(game.game_type(), self.weights.clone());
+        searcher.set_contempt(self.contempt);
+        self.strength.best_move(game, searcher, &mut self.rng)
+    }
+
+    /// Searches as deep as the strength does, without its node limit or
+    /// noise
+    fn evaluate_batch(&mut self, games: &[GameDebugger]) -> Vec<Score> {
+        let Some(first) = games.first() else {
+            return vec![];
+        };
+        let searcher = Searcher::with_weights(first.game_type(), self.weights.clone());
+        let threads = batch::default_threads();
+        batch::evaluate_batch(games, &searcher, self.strength.depth, threads)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameResult;
+    use crate::uhp::GameType;
+
+    /// Plays *white* against *black* for at most *turns* turns
+    fn play(white: &mut dyn Engine, black: &mut dyn Engine, turns: usize) -> GameDebugger {
+        let mut game = GameDebugger::from_move_list(&[], GameType::Standard).unwrap();
+        for turn in 0..turns {
+            let engine: &mut dyn Engine = match turn % 2 {
+                0 => &mut *white,
+                _ => &mut *black,
+            };
+            let Some(position) = engine.best_move(&mut game) else {
+                break;
+            };
+            game.append_position(&position).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    pub fn test_random_engine() {
+        let first = play(&mut RandomEngine::new(3), &mut RandomEngine::new(4), 12);
+        let replay = play(&mut RandomEngine::new(3), &mut RandomEngine::new(4), 12);
+        assert!(first.positions().eq(replay.positions()));
+        assert_eq!(first.positions().count(), 13);
+
+        let other = play(&mut RandomEngine::new(5), &mut RandomEngine::new(4), 12);
+        assert!(!first.positions().eq(other.positions()));
+    }
+
+    #[test]
+    pub fn test_random_engine_avoid_repeats() {
+        let mut white = RandomEngine::new(3);
+        let mut black = RandomEngine::new(4);
+        white.set_avoid_repeats(true);
+        black.set_avoid_repeats(true);
+        for _ in 0..2 {
+            let game = play(&mut white, &mut black, 60);
+            let mut positions = game.positions().cloned().collect::<Vec<_>>();
+            let plies = positions.len();
+            positions.sort_by_key(|position| position.to_dsl());
+            positions.dedup();
+            assert_eq!(positions.len(), plies);
+        }
+    }
+
+    #[test]
+    pub fn test_greedy_engine() {
+        // Looking one move ahead is enough to beat random play
+        let mut greedy = GreedyEngine::default();
+        let mut game = play(&mut greedy, &mut RandomEngine::new(1), 200);
+        assert_eq!(game.game_result(), Some(GameResult::WhiteWins));
+        assert!(greedy.best_move(&mut game).is_none());
+
+        let mut search = SearchEngine::new(Weights::default(), Strength::level(1), 0);
+        let game = play(&mut search, &mut RandomEngine::new(0), 4);
+        assert_eq!(game.positions().count(), 5);
+        assert_eq!(search.name(), "search level 1");
+    }
+
+    #[test]
+    pub fn test_engine_evaluate_batch() {
+        let game = play(&mut RandomEngine::new(2), &mut RandomEngine::new(3), 6);
+        let games = (0..game.positions().count())
+            .map(|plies| {
+                let mut replay = game.clone();
+                for _ in plies..game.positions().count() - 1 {
+                    replay.undo_move().unwrap();
+                }
+                replay
+            })
+            .collect::<Vec<_>>();
+
+        let statics = RandomEngine::new(0).evaluate_batch(&games);
+        assert_eq!(statics.len(), games.len());
+        assert_eq!(statics[0], 0);
+        let greedy = GreedyEngine::default().evaluate_batch(&games);
+        let mut search = SearchEngine::new(Weights::default(), Strength::level(1), 0);
+        assert_eq!(search.evaluate_batch(&games), greedy);
+        assert!(search.evaluate_batch(&[]).is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+use crate::hex_grid::*;
+use crate::moves::Move;
+
+/// Evaluation of a position in centipiece-like units, positive when
+/// the position favors the player it is evaluated for
+pub type Score = i32;
+
+/// The score of a won position, greater than any evaluation.
+///
+/// Surrounds found by the search score less the further away they are,
+/// see win_in() and forced_surround()
+pub const WIN: Score = 1_000_000;
+
+/// The most plies to a surround that forced_surround() recognizes
+pub const MAX_PLY: Score = 1_000;
+
+/// A surround forced by the player a score is for, or against them,
+/// in a number of plies, see forced_surround()
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ForcedSurround {
+    Surrounds(Score),
+    Surrounded(Score),
+}
+
+/// The score of surrounding the opposing queen *plies* plies from the
+/// searched position, so that faster wins are preferred and, as the
+/// negation is used for being surrounded, slower losses
+pub fn win_in(plies: Score) -> Score {
+    WIN - plies
+}
+
+/// Reads the distance to a surround back from a score, None if the score
+/// is an evaluation of an undecided position
+pub fn forced_surround(score: Score) -> Option<ForcedSurround> {
+    match score {
+        score if score > WIN - MAX_PLY => Some(ForcedSurround::Surrounds(WIN - score)),
+        score if score < MAX_PLY - WIN => Some(ForcedSurround::Surrounded(WIN + score)),
+        _ => None,
+    }
+}
+
+/// Weights of the terms used by evaluate()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Weights {
+    /// Per piece surrounding the opposing queen, minus those surrounding our own
+    pub queen_pressure: Score,
+    /// Per opposing piece that cannot move as it is pinned or covered,
+    /// minus our own
+    pub immobile: Score,
+}
+
+/// The number of terms of evaluate(), see features()
+pub const NUM_FEATURES: usize = 2;
+
+impl Weights {
+    /// The names of the weights, in the order of to_array()
+    pub const NAMES: [&'static str; NUM_FEATURES] = ["queen_pressure", "immobile"];
+
+    /// The weights in the order of the terms returned by features()
+    pub fn to_array(&self) -> [Score; NUM_FEATURES] {
+        [self.queen_pressure, self.immobile]
+    }
+
+    pub fn from_array(weights: [Score; NUM_FEATURES]) -> Weights {
+        let [queen_pressure, immobile] = weights;
+        Weights {
+            queen_pressure,
+            immobile,
+        }
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            queen_pressure: 100,
+            immobile: 20,
+        }
+    }
+}
+
+/// Statically evaluates a position that is not yet decided,
+/// from the point of view of *color*
+pub fn evaluate(grid: &HexGrid, color: PieceColor, weights: &Weights) -> Score {
+    weigh(features(grid, color), weights)
+}
+
+/// The terms of evaluate() weighted and added up
+fn weigh(features: [Score; NUM_FEATURES], weights: &Weights) -> Score {
+    weights
+        .to_array()
+        .iter()
+        .zip(features)
+        .map(|(weight, feature)| weight * feature)
+        .sum()
+}
+
+/// The unweighted terms of evaluate() from the point of view of *color*,
+/// in the order of Weights::to_array()
+pub fn features(grid: &HexGrid, color: PieceColor) -> [Score; NUM_FEATURES] {
+    let pressure = queen_pressure(grid, color.opposite()) - queen_pressure(grid, color);
+    let immobile = immobile_pieces(grid, color.opposite()) - immobile_pieces(grid, color);
+    [pressure, immobile]
+}
+
+/// The number of pieces around the queen of the given *color*
+fn queen_pressure(grid: &HexGrid, color: PieceColor) -> Score {
+    match grid.find(Piece::new(PieceType::Queen, color)) {
+        Some((location, _)) => grid.get_neighbors(location).len() as Score,
+        None => 0,
+    }
+}
+
+/// The number of pieces of the given *color* that are pinned or covered
+fn immobile_pieces(grid: &HexGrid, color: PieceColor) -> Score {
+    let pinned = grid.pinned();
+    grid.pieces_of(color)
+        .into_iter()
+        .filter(|(_, location, height)| {
+            *height + 1 < grid.height(*location)
+                || (grid.height(*location) == 1 && pinned.contains(location))
+        })
+        .count() as Score
+}
+
+/// The index of *color* in the per color arrays of IncrementalTerms
+fn side(color: PieceColor) -> usize {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => 1,
+    }
+}
+
+/// The terms of evaluate() that only change around the pieces a move
+/// touches, updated move by move (see after()) so that the search does not
+/// recompute them for every position it evaluates.
+///
+/// Whether a piece is pinned depends on the whole hive, so that part of the
+/// immobile term is still found from the position evaluated
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IncrementalTerms {
+    /// Where the queen of each color is, if placed
+    queens: [Option<HexLocation>; 2],
+    /// The number of pieces around the queen of each color
+    pressure: [Score; 2],
+    /// The number of pieces of each color covered by another piece
+    covered: [Score; 2],
+}
+
+impl IncrementalTerms {
+    /// The terms of *grid* computed from scratch
+    pub fn new(grid: &HexGrid) -> IncrementalTerms {
+        let mut terms = IncrementalTerms {
+            queens: [None; 2],
+            pressure: [0; 2],
+            covered: [0; 2],
+        };
+        for color in [PieceColor::White, PieceColor::Black] {
+            let queen = grid.find(Piece::new(PieceType::Queen, color));
+            terms.queens[side(color)] = queen.map(|(location, _)| location);
+            terms.pressure[side(color)] = queen_pressure(grid, color);
+        }
+        for (_, stack) in grid.occupied() {
+            for piece in &stack[..stack.len() - 1] {
+                terms.covered[side(piece.color)] += 1;
+            }
+        }
+        terms
+    }
+
+    /// The terms after *mv* is played, where *grid* is the position the
+    /// move leads to
+    pub fn after(&self, grid: &HexGrid, mv: &Move) -> IncrementalTerms {
+        let mut terms = *self;
+        let (piece, from, to) = match *mv {
+            Move::Place { piece, to } => (piece, None, to),
+            Move::Movement { piece, from, to } => (piece, Some(from), to),
+            Move::Pass => return terms,
+        };
+        if piece.piece_type == PieceType::Queen {
+            terms.queens[side(piece.color)] = Some(to);
+        }
+
+        // The piece moved covers the top of the stack it lands on, and
+        // uncovers the piece it leaves on top of its old stack
+        if let [.., below, _] = grid.stack(to) {
+            terms.covered[side(below.color)] += 1;
+        }
+        if let Some(uncovered) = from.and_then(|from| grid.top(from)) {
+            terms.covered[side(uncovered.color)] -= 1;
+        }
+
+        let touches = |queen: HexLocation, location: HexLocation| {
+            queen == location || queen.direction_to(location).is_some()
+        };
+        for color in [PieceColor::White, PieceColor::Black] {
+            let Some(queen) = terms.queens[side(color)] else {
+                continue;
+            };
+            if touches(queen, to) || from.is_some_and(|from| touches(queen, from)) {
+                terms.pressure[side(color)] = grid.get_neighbors(queen).len() as Score;
+            }
+        }
+        terms
+    }
+
+    /// features() of *grid*, the position the terms are up to date with
+    pub fn features(&self, grid: &HexGrid, color: PieceColor) -> [Score; NUM_FEATURES] {
+        let mut immobile = self.covered;
+        for location in grid.pinned() {
+            if let [piece] = grid.stack(location) {
+                immobile[side(piece.color)] += 1;
+            }
+        }
+        let (us, them) = (side(color), side(color.opposite()));
+        [
+            self.pressure[them] - self.pressure[us],
+            immobile[them] - immobile[us],
+        ]
+    }
+
+    /// evaluate() of *grid*, the position the terms are up to date with
+    pub fn evaluate(&self, grid: &HexGrid, color: PieceColor, weights: &Weights) -> Score {
+        weigh(self.features(grid, color), weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_evaluate() {
+        use PieceColor::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a Q A .\n",
+            " . . q . .\n",
+            ". . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let weights = Weights::default();
+
+        // The white queen touches three pieces and is pinned,
+        // the black queen touches two
+        let score = evaluate(&grid, White, &weights);
+        assert_eq!(score, -weights.queen_pressure - weights.immobile);
+        assert_eq!(evaluate(&grid, Black, &weights), -score);
+    }
+
+    #[test]
+    pub fn test_incremental_terms() {
+        use crate::generator::debug::{PositionGenerator, ReferenceGenerator};
+        use crate::uhp::GameType;
+
+        let moves = [
+            "wS1", "bG1 -wS1", "wB1 wS1-", "bQ -bG1", r"wQ wS1\", "bA1 -bQ", "wB1 wS1",
+        ];
+        let grid = HexGrid::from_move_list(&moves).unwrap();
+        let children = |grid: &HexGrid, color: PieceColor| {
+            ReferenceGenerator::from_hex_grid(grid, GameType::MLP, None)
+                .generate_positions_for(color)
+                .into_iter()
+                .filter_map(|child| Some((grid.diff(&child)?, child)))
+                .collect::<Vec<_>>()
+        };
+
+        // Two plies of climbing, uncovering and moving next to the queens,
+        // updated move by move and from scratch
+        let terms = IncrementalTerms::new(&grid);
+        let weights = Weights::default();
+        for (mv, child) in children(&grid, PieceColor::Black) {
+            let after = terms.after(&child, &mv);
+            assert_eq!(after, IncrementalTerms::new(&child), "{:?}", mv);
+            for (reply, grandchild) in children(&child, PieceColor::White) {
+                let updated = after.after(&grandchild, &reply);
+                assert_eq!(updated, IncrementalTerms::new(&grandchild), "{:?}", reply);
+                assert_eq!(
+                    updated.evaluate(&grandchild, PieceColor::White, &weights),
+                    evaluate(&grandchild, PieceColor::White, &weights)
+                );
+            }
+        }
+        assert_eq!(terms.after(&grid, &Move::Pass), terms);
+    }
+
+    #[test]
+    pub fn test_forced_surround() {
+        use ForcedSurround::*;
+
+        assert_eq!(forced_surround(win_in(3)), Some(Surrounds(3)));
+        assert_eq!(forced_surround(-win_in(2)), Some(Surrounded(2)));
+        assert_eq!(forced_surround(-WIN), Some(Surrounded(0)));
+        assert_eq!(forced_surround(500), None);
+        assert!(win_in(1) > win_in(3) && -win_in(4) > -win_in(2));
+    }
+}
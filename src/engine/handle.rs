@@ -0,0 +1,313 @@
+use crate::engine::search::{Analysis, Searcher};
+use crate::game::GameDebugger;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// The depth of searches bounded only by time or nodes, see SearchLimits
+pub const MAX_DEPTH: usize = 64;
+
+/// Bounds on a search started with start_search()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// Deepest iteration searched, in plies
+    pub depth: usize,
+    /// Time after which the search is stopped, if any
+    pub time: Option<Duration>,
+    /// Positions the search may visit over all of its iterations before it
+    /// is stopped, if any
+    pub nodes: Option<usize>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            depth: 3,
+            time: None,
+            nodes: None,
+        }
+    }
+}
+
+impl SearchLimits {
+    /// Searches *depth* plies deep, however long that takes
+    pub fn depth(depth: usize) -> SearchLimits {
+        SearchLimits {
+            depth,
+            ..SearchLimits::default()
+        }
+    }
+
+    /// Searches as deep as possible within *time*
+    pub fn time(time: Duration) -> SearchLimits {
+        SearchLimits {
+            depth: MAX_DEPTH,
+            time: Some(time),
+            nodes: None,
+        }
+    }
+
+    /// Searches as deep as possible while visiting at most *nodes* positions
+    /// in total
+    pub fn nodes(nodes: usize) -> SearchLimits {
+        SearchLimits {
+            depth: MAX_DEPTH,
+            time: None,
+            nodes: Some(nodes),
+        }
+    }
+}
+
+/// A search running on its own thread, see start_search()
+///
+/// Dropping the handle stops the search
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+    result: Receiver<Option<Analysis>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SearchHandle {
+    /// Asks the search to stop as soon as possible, the result of the
+    /// deepest completed iteration is still delivered
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_none_or(|thread| thread.is_finished())
+    }
+
+    /// Returns the result if the search has finished, without blocking.
+    ///
+    /// The result is None if no iteration completed or the game is already over
+    pub fn try_result(&mut self) -> Option<Option<Analysis>> {
+        match self.result.try_recv() {
+            Ok(result) => {
+                self.join();
+                Some(result)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(None),
+        }
+    }
+
+    /// Blocks until the search finishes and returns its result, see try_result()
+    pub fn wait(mut self) -> Option<Analysis> {
+        let result = self.result.recv().unwrap_or(None);
+        self.join();
+        result
+    }
+
+    fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            // A panicking search has already dropped its sender
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for SearchHandle {
+    fn drop(&mut self) {
+        self.stop();
+        self.join();
+    }
+}
+
+/// Searches the current position of *game* with *searcher* by iterative
+/// deepening on another thread, calling *progress* on that thread with the
+/// result of every completed depth. Each iteration expects the score of the
+/// one before, see Searcher::analyze_near(). Moves going back to an earlier
+/// state of *game* are scored as draws, see Searcher::set_history().
+///
+/// An iteration interrupted by SearchHandle::stop() or the time limit is
+/// discarded, the final result is the deepest completed iteration
+pub fn start_search<F>(
+    game: &GameDebugger,
+    mut searcher: Searcher,
+    limits: SearchLimits,
+    mut progress: F,
+) -> SearchHandle
+where
+    F: FnMut(usize, &Analysis) + Send + 'static,
+{
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, result) = channel();
+
+    let grid = game.position().clone();
+    let last_move = game.last_move();
+    let color = game.player_to_move();
+    let finished = game.game_result().is_some();
+    searcher.set_history(game.state_keys());
+    searcher.set_stop_flag(stop.clone());
+    searcher.set_deadline(limits.time.map(|time| Instant::now() + time));
+
+    let thread = std::thread::spawn(move || {
+        let mut best = None;
+        // The positions visited by the iterations so far, which count
+        // towards the node limit of the whole search
+        let mut visited = 0;
+        if !finished {
+            for depth in 1..=limits.depth {
+                let left = limits.nodes.map(|nodes| nodes.saturating_sub(visited));
+                if left == Some(0) {
+                    break;
+                }
+                searcher.set_node_limit(left);
+                #[cfg(feature = "tracing")]
+                let _span = tracing::info_span!("iteration", depth).entered();
+                let analysis = match &best {
+                    Some(Analysis { score, .. }) => {
+                        searcher.analyze_near(&grid, last_move, color, depth, *score)
+                    }
+                    None => searcher.analyze(&grid, last_move, color, depth),
+                };
+                visited += analysis.stats.nodes;
+                if searcher.stopped() {
+                    #[cfg(feature = "tracing")]
+                    tracing::info!("stopped, discarding the iteration");
+                    break;
+                }
+                progress(depth, &analysis);
+                best = Some(analysis);
+            }
+        }
+        // The handle may have been dropped already
+        let _ = sender.send(best);
+    });
+
+    SearchHandle {
+        stop,
+        result,
+        thread: Some(thread),
+    }
+}
+
+/// Searches the current position of *game* within *limits* and waits for
+/// the result, see start_search().
+///
+/// Unlike start_search(), a search stopped before its first iteration
+/// completed still returns a one ply search, so that any limit gives a
+/// move. None if the game is over
+pub fn search(game: &GameDebugger, searcher: Searcher, limits: SearchLimits) -> Option<Analysis> {
+    let mut shallow = searcher.clone();
+    if let Some(analysis) = start_search(game, searcher, limits, |_, _| {}).wait() {
+        return Some(analysis);
+    }
+    if game.game_result().is_some() {
+        return None;
+    }
+    shallow.set_history(game.state_keys());
+    Some(shallow.analyze(game.position(), game.last_move(), game.player_to_move(), 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uhp::GameType;
+    use std::sync::Mutex;
+
+    #[test]
+    pub fn test_search_handle() {
+        let moves = [r"wA1", r"bA1 wA1-"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        let game = GameDebugger::from_moves(&moves).unwrap();
+
+        let depths = Arc::new(Mutex::new(vec![]));
+        let reported = depths.clone();
+        let limits = SearchLimits {
+            depth: 2,
+            time: None,
+            nodes: None,
+        };
+        let handle = start_search(
+            &game,
+            Searcher::new(GameType::Standard),
+            limits,
+            move |depth, _| reported.lock().unwrap().push(depth),
+        );
+        let analysis = handle.wait().unwrap();
+        assert_eq!(*depths.lock().unwrap(), vec![1, 2]);
+        assert!(!analysis.best_line.is_empty());
+
+        // Stopping keeps the iterations completed so far
+        let limits = SearchLimits {
+            depth: 50,
+            time: None,
+            nodes: None,
+        };
+        let (sender, receiver) = channel();
+        let mut handle = start_search(
+            &game,
+            Searcher::new(GameType::Standard),
+            limits,
+            move |depth, _| {
+                let _ = sender.send(depth);
+            },
+        );
+        assert_eq!(receiver.recv().unwrap(), 1);
+        handle.stop();
+        while handle.try_result().is_none() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(handle.is_finished());
+
+        // Time limits interrupt the search in the same way
+        let limits = SearchLimits {
+            depth: 50,
+            time: Some(Duration::from_millis(200)),
+            nodes: None,
+        };
+        let handle = start_search(&game, Searcher::new(GameType::Standard), limits, |_, _| {});
+        assert!(handle
+            .wait()
+            .is_some_and(|analysis| analysis.stats.nodes > 0));
+
+        // And so do node limits, which count the nodes of every iteration
+        let limits = SearchLimits {
+            depth: 50,
+            time: None,
+            nodes: Some(500),
+        };
+        let visited = Arc::new(Mutex::new(vec![]));
+        let reported = visited.clone();
+        let handle = start_search(
+            &game,
+            Searcher::new(GameType::Standard),
+            limits,
+            move |_, analysis| reported.lock().unwrap().push(analysis.stats.nodes),
+        );
+        assert!(handle.wait().is_some());
+        let visited = visited.lock().unwrap();
+        assert!(visited.len() > 1 && visited.len() < 50);
+        assert!(visited.iter().sum::<usize>() <= 500);
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_node_limit(Some(500));
+        let color = game.player_to_move();
+        let analysis = searcher.analyze(game.position(), game.last_move(), color, 50);
+        assert!(searcher.stopped());
+        assert!(analysis.stats.nodes <= 500);
+        // Limits too tight for a single iteration still give a move
+        let analysis = search(
+            &game,
+            Searcher::new(GameType::Standard),
+            SearchLimits::nodes(1),
+        );
+        assert!(analysis.is_some_and(|analysis| !analysis.best_line.is_empty()));
+        let limits = SearchLimits::time(Duration::ZERO);
+        assert_eq!(limits.depth, MAX_DEPTH);
+        assert!(search(&game, Searcher::new(GameType::Standard), limits).is_some());
+        let analysis = search(
+            &game,
+            Searcher::new(GameType::Standard),
+            SearchLimits::depth(1),
+        );
+        assert!(analysis.is_some_and(|analysis| analysis.stats.nodes > 1));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::engine::analysis::format_score;
+use crate::engine::evaluation::*;
+use crate::engine::search::Searcher;
+use crate::game::GameDebugger;
+use crate::hex_grid::*;
+
+/// Why a move is good for the player making it, read from the changes it
+/// makes to the terms of evaluate(), see explain()
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// The search found a surround of the opposing queen in this many plies
+    Surrounds { queen: PieceColor, plies: Score },
+    /// Adds a piece around the opposing queen, leaving this many around it
+    Surrounding { queen: PieceColor, pieces: Score },
+    /// Takes pieces away from around the queen of the player, leaving this
+    /// many around it
+    Relieves { pieces: Score },
+    /// A piece of the player that was pinned or covered can move again
+    Frees {
+        piece_type: PieceType,
+        covered: bool,
+    },
+    /// An opposing piece can no longer move as it is pinned or covered
+    Immobilizes { piece: Piece, covered: bool },
+}
+
+impl std::fmt::Display for Reason {
+    /// Writes the reason as a short phrase addressed to the player making
+    /// the move, e.g. "frees your pinned beetle"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reason::Surrounds { queen, plies } => write!(
+                f,
+                "surrounds {}'s queen in {} plies",
+                queen.to_str().to_lowercase(),
+                plies
+            ),
+            Reason::Surrounding { queen, pieces } => write!(
+                f,
+                "adds a {} piece around {}'s queen",
+                ordinal(*pieces),
+                queen.to_str().to_lowercase()
+            ),
+            Reason::Relieves { pieces } => {
+                write!(f, "leaves only {} pieces around your queen", pieces)
+            }
+            Reason::Frees {
+                piece_type,
+                covered: false,
+            } => write!(f, "frees your pinned {}", piece_type.name()),
+            Reason::Frees {
+                piece_type,
+                covered: true,
+            } => write!(f, "uncovers your {}", piece_type.name()),
+            Reason::Immobilizes { piece, covered } => write!(
+                f,
+                "{} {}'s {}",
+                if *covered { "covers" } else { "pins" },
+                piece.color.to_str().to_lowercase(),
+                piece.piece_type.name()
+            ),
+        }
+    }
+}
+
+/// "first" to "sixth", the most pieces a queen can be surrounded by
+fn ordinal(n: Score) -> String {
+    match n {
+        1 => "first".to_string(),
+        2 => "second".to_string(),
+        3 => "third".to_string(),
+        4 => "fourth".to_string(),
+        5 => "fifth".to_string(),
+        6 => "sixth".to_string(),
+        n => format!("{}th", n),
+    }
+}
+
+/// The best move of a position with the reasons it is good, see hint()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hint {
+    pub move_string: String,
+    /// The position after the move
+    pub position: HexGrid,
+    /// The score of the move for the player making it
+    pub score: Score,
+    /// Most important first, empty when the move only pays off beyond the
+    /// next position
+    pub reasons: Vec<Reason>,
+}
+
+impl std::fmt::Display for Hint {
+    /// Writes the move with its score followed by its reasons,
+    /// e.g. "wB1 bQ- (+120): adds a fourth piece around black's queen"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.move_string, format_score(self.score))?;
+        if self.reasons.is_empty() {
+            return write!(f, ": best found by looking further ahead");
+        }
+        let reasons = self
+            .reasons
+            .iter()
+            .map(|reason| reason.to_string())
+            .collect::<Vec<_>>();
+        write!(f, ": {}", reasons.join(", "))
+    }
+}
+
+/// Searches the current position of *game* to *depth* plies and returns the
+/// best move explained, None if the game is over or there is no move
+pub fn hint(game: &GameDebugger, searcher: &mut Searcher, depth: usize) -> Option<Hint> {
+    if game.game_result().is_some() {
+        return None;
+    }
+    let color = game.player_to_move();
+    let analysis = searcher.analyze(game.position(), game.last_move(), color, depth);
+    let (position, score) = analysis.candidates.first()?;
+    let move_string = game.move_string(position).ok()?;
+    let mut reasons = vec![];
+    if let Some(ForcedSurround::Surrounds(plies)) = forced_surround(*score) {
+        reasons.push(Reason::Surrounds {
+            queen: color.opposite(),
+            plies,
+        });
+    }
+    reasons.extend(explain(game.position(), position, color));
+    Some(Hint {
+        move_string,
+        position: position.clone(),
+        score: *score,
+        reasons,
+    })
+}
+
+/// The reasons the move from *before* to *after* made by *color* is good,
+/// from the terms of features() that changed in its favor
+pub fn explain(before: &HexGrid, after: &HexGrid, color: PieceColor) -> Vec<Reason> {
+    let mut reasons = vec![];
+    let opponent = color.opposite();
+
+    let (pressure_before, pressure_after) = (
+        queen_neighbors(before, opponent),
+        queen_neighbors(after, opponent),
+    );
+    if pressure_after > pressure_before {
+        reasons.push(Reason::Surrounding {
+            queen: opponent,
+            pieces: pressure_after,
+        });
+    }
+
+    let (immobile_before, immobile_after) = (immobile(before), immobile(after));
+    for (piece, location, covered) in immobile_after.iter() {
+        if piece.color == opponent && !immobile_before.contains(&(*piece, *location, *covered)) {
+            reasons.push(Reason::Immobilizes {
+                piece: *piece,
+                covered: *covered,
+            });
+        }
+    }
+    for (piece, location, covered) in immobile_before.iter() {
+        let still = immobile_after
+            .iter()
+            .any(|(other, other_location, _)| other == piece && other_location == location);
+        // Pieces that moved away were not stuck after all
+        let moved = !after.peek(*location).iter().any(|other| other == piece);
+        if piece.color == color && !still && !moved {
+            reasons.push(Reason::Frees {
+                piece_type: piece.piece_type,
+                covered: *covered,
+            });
+        }
+    }
+
+    let (own_before, own_after) = (
+        queen_neighbors(before, color),
+        queen_neighbors(after, color),
+    );
+    if own_after < own_before {
+        reasons.push(Reason::Relieves { pieces: own_after });
+    }
+    reasons
+}
+
+/// The number of pieces around the queen of the given *color*, as counted
+/// by the queen_pressure term of evaluate()
+fn queen_neighbors(grid: &HexGrid, color: PieceColor) -> Score {
+    match grid.find(Piece::new(PieceType::Queen, color)) {
+        Some((location, _)) => grid.get_neighbors(location).len() as Score,
+        None => 0,
+    }
+}
+
+/// The pieces that cannot move as counted by the immobile term of evaluate(),
+/// with whether they are covered rather than pinned
+fn immobile(grid: &HexGrid) -> Vec<(Piece, HexLocation, bool)> {
+    let pinned = grid.pinned();
+    [PieceColor::White, PieceColor::Black]
+        .into_iter()
+        .flat_map(|color| grid.pieces_of(color))
+        .filter_map(|(piece, location, height)| {
+            let covered = height + 1 < grid.height(location);
+            let stuck = covered || (grid.height(location) == 1 && pinned.contains(&location));
+            stuck.then_some((piece, location, covered))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uhp::GameType;
+    use PieceColor::*;
+
+    #[test]
+    pub fn test_explain() {
+        // The white ant joins the black queen and pins it between the queens
+        let before = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". Q q . .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let after = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". Q q A .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let reasons = explain(&before, &after, White);
+        assert_eq!(
+            reasons,
+            vec![
+                Reason::Surrounding {
+                    queen: Black,
+                    pieces: 2
+                },
+                Reason::Immobilizes {
+                    piece: Piece::new(PieceType::Queen, Black),
+                    covered: false
+                },
+            ]
+        );
+        assert_eq!(
+            reasons[0].to_string(),
+            "adds a second piece around black's queen"
+        );
+        assert_eq!(reasons[1].to_string(), "pins black's queen");
+
+        // Closing the ring frees the white ant
+        let before = HexGrid::from_dsl(concat!(
+            ". q A Q .\n",
+            " . G . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let after = HexGrid::from_dsl(concat!(
+            ". q A Q .\n",
+            " . G G . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let reasons = explain(&before, &after, White);
+        assert_eq!(
+            reasons,
+            vec![Reason::Frees {
+                piece_type: PieceType::Ant,
+                covered: false
+            }]
+        );
+        assert_eq!(reasons[0].to_string(), "frees your pinned ant");
+    }
+
+    #[test]
+    pub fn test_hint() {
+        let game = GameDebugger::from_move_list(
+            &["wA1", "bG1 -wA1", "wQ wA1-", "bQ /bG1"],
+            GameType::Standard,
+        )
+        .unwrap();
+        let hint = game.hint(1).unwrap();
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_deterministic(true);
+        let analysis = searcher.analyze(game.position(), game.last_move(), White, 1);
+        assert_eq!(hint.score, analysis.score);
+        assert_eq!(hint.move_string, game.move_string(&hint.position).unwrap());
+        assert_eq!(
+            hint.reasons,
+            explain(game.position(), &hint.position, White)
+        );
+        assert!(hint.to_string().starts_with(&hint.move_string));
+    }
+}
@@ -0,0 +1,23 @@
+pub mod analysis;
+pub mod arena;
+pub mod batch;
+pub mod dag;
+pub mod engines;
+pub mod evaluation;
+pub mod handle;
+pub mod hint;
+pub mod profile;
+pub mod review;
+pub mod rng;
+pub mod search;
+pub mod seen;
+pub mod series;
+pub mod session;
+pub mod strength;
+pub mod transposition;
+pub mod tree;
+pub mod tuner;
+
+pub use evaluation::*;
+pub use handle::*;
+pub use search::*;
@@ -0,0 +1,384 @@
+use crate::engine::evaluation::*;
+use std::path::Path;
+use thiserror::Error;
+
+/// The newest profile format, written by Profile::to_toml()
+pub const PROFILE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum ProfileError {
+    #[error("Line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("Unsupported profile version {0}, expected at most {PROFILE_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("Unknown weight \"{0}\"")]
+    UnknownWeight(String),
+    #[error("Invalid JSON profile: {0}")]
+    Json(String),
+    #[error("Could not access {0}: {1}")]
+    Io(String, std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, ProfileError>;
+
+/// A named set of evaluation weights that can be saved and loaded at
+/// runtime, so that playing styles can be tried without recompiling.
+///
+/// Profiles are written as TOML, weights that are not listed keep their
+/// default value:
+///
+/// ```text
+/// version = 1
+/// name = "aggressive"
+///
+/// [weights]
+/// queen_pressure = 160
+/// immobile = 10
+/// ```
+///
+/// With the "json" feature, profiles can also be read and written as the
+/// equivalent JSON object
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    pub version: u32,
+    pub name: String,
+    pub weights: Weights,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::new("default", Weights::default())
+    }
+}
+
+impl Profile {
+    pub fn new(name: &str, weights: Weights) -> Profile {
+        Profile {
+            version: PROFILE_VERSION,
+            name: name.to_string(),
+            weights,
+        }
+    }
+
+    /// The profiles that come with the engine: "default", "aggressive",
+    /// which goes after the opposing queen, and "positional", which
+    /// prefers restricting the opponent's pieces
+    pub fn builtin(name: &str) -> Option<Profile> {
+        let weights = match name {
+            "default" => Weights::default(),
+            "aggressive" => Weights {
+                queen_pressure: 160,
+                immobile: 10,
+            },
+            "positional" => Weights {
+                queen_pressure: 70,
+                immobile: 45,
+            },
+            _ => return None,
+        };
+        Some(Profile::new(name, weights))
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut toml = format!(
+            "version = {}\nname = \"{}\"\n\n[weights]\n",
+            self.version,
+            escape(&self.name)
+        );
+        for (name, weight) in Weights::NAMES.iter().zip(self.weights.to_array()) {
+            toml += &format!("{} = {}\n", name, weight);
+        }
+        toml
+    }
+
+    /// Reads a profile written by to_toml(), a profile without a version
+    /// is assumed to be of the newest version
+    pub fn from_toml(input: &str) -> Result<Profile> {
+        let mut profile = Profile::new("", Weights::default());
+        let mut weights = profile.weights.to_array();
+        let mut in_weights = false;
+
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            let syntax = |message: String| ProfileError::Syntax {
+                line: index + 1,
+                message,
+            };
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_weights = match line {
+                    "[weights]" => true,
+                    _ => return Err(syntax(format!("Unknown table {}", line))),
+                };
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(syntax(format!(
+                    "Expected \"key = value\", found \"{}\"",
+                    line
+                )));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let integer = |value: &str| {
+                value
+                    .parse::<i64>()
+                    .map_err(|_| syntax(format!("Expected a number for {}, found {}", key, value)))
+            };
+            let out_of_range = || syntax(format!("{} is out of range for {}", value, key));
+
+            match (in_weights, key) {
+                (true, _) => {
+                    let index = Weights::NAMES
+                        .iter()
+                        .position(|name| *name == key)
+                        .ok_or_else(|| ProfileError::UnknownWeight(key.to_string()))?;
+                    weights[index] =
+                        Score::try_from(integer(value)?).map_err(|_| out_of_range())?;
+                }
+                (false, "version") => {
+                    profile.version = u32::try_from(integer(value)?).map_err(|_| out_of_range())?
+                }
+                (false, "name") => {
+                    let name = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'));
+                    profile.name = name.and_then(unescape).ok_or_else(|| {
+                        syntax(format!("Expected a quoted name, found {}", value))
+                    })?;
+                }
+                (false, _) => return Err(syntax(format!("Unknown key {}", key))),
+            }
+        }
+
+        if profile.version > PROFILE_VERSION {
+            return Err(ProfileError::UnsupportedVersion(profile.version));
+        }
+        profile.weights = Weights::from_array(weights);
+        Ok(profile)
+    }
+
+    /// Writes the profile as a JSON object with the same keys as to_toml()
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        let weights = Weights::NAMES
+            .iter()
+            .zip(self.weights.to_array())
+            .map(|(name, weight)| (name.to_string(), serde_json::Value::from(weight)))
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::json!({
+            "version": self.version,
+            "name": self.name,
+            "weights": weights,
+        })
+        .to_string()
+    }
+
+    /// Reads a profile written by to_json()
+    #[cfg(feature = "json")]
+    pub fn from_json(input: &str) -> Result<Profile> {
+        let json_error = |message: &str| ProfileError::Json(message.to_string());
+        let value: serde_json::Value =
+            serde_json::from_str(input).map_err(|e| ProfileError::Json(e.to_string()))?;
+
+        let version = match value.get("version") {
+            Some(version) => version
+                .as_u64()
+                .ok_or_else(|| json_error("version must be a number"))?,
+            None => PROFILE_VERSION as u64,
+        };
+        let version = u32::try_from(version)
+            .ok()
+            .filter(|version| *version <= PROFILE_VERSION)
+            .ok_or(ProfileError::UnsupportedVersion(
+                u32::try_from(version).unwrap_or(u32::MAX),
+            ))?;
+        let name = match value.get("name") {
+            Some(name) => name
+                .as_str()
+                .ok_or_else(|| json_error("name must be a string"))?,
+            None => "",
+        };
+
+        let mut weights = Weights::default().to_array();
+        if let Some(object) = value.get("weights") {
+            let object = object
+                .as_object()
+                .ok_or_else(|| json_error("weights must be an object"))?;
+            for (key, weight) in object {
+                let index = Weights::NAMES
+                    .iter()
+                    .position(|name| name == key)
+                    .ok_or_else(|| ProfileError::UnknownWeight(key.to_string()))?;
+                let weight = weight
+                    .as_i64()
+                    .ok_or_else(|| json_error("weights must be numbers"))?;
+                weights[index] = Score::try_from(weight)
+                    .map_err(|_| json_error("weights must fit in 32 bits"))?;
+            }
+        }
+
+        Ok(Profile {
+            version,
+            name: name.to_string(),
+            weights: Weights::from_array(weights),
+        })
+    }
+
+    /// Reads a profile from *path*, as JSON if its extension is .json
+    /// (with the "json" feature) and as TOML otherwise
+    pub fn load(path: &Path) -> Result<Profile> {
+        let input = std::fs::read_to_string(path)
+            .map_err(|e| ProfileError::Io(path.display().to_string(), e))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            #[cfg(feature = "json")]
+            Some("json") => Profile::from_json(&input),
+            _ => Profile::from_toml(&input),
+        }
+    }
+
+    /// The built-in profile called *name*, or else the profile in the file
+    /// at *name*
+    pub fn find(name: &str) -> Result<Profile> {
+        match Profile::builtin(name) {
+            Some(profile) => Ok(profile),
+            None => Profile::load(Path::new(name)),
+        }
+    }
+
+    /// Writes the profile to *path* as TOML, see load()
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_toml())
+            .map_err(|e| ProfileError::Io(path.display().to_string(), e))
+    }
+}
+
+/// *name* as the inside of a TOML basic string
+fn escape(name: &str) -> String {
+    let mut escaped = String::new();
+    for c in name.chars() {
+        match c {
+            '"' => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            '\t' => escaped += "\\t",
+            '\r' => escaped += "\\r",
+            c if c.is_control() => escaped += &format!("\\u{:04X}", c as u32),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reads the inside of a TOML basic string written by escape(), None if it
+/// has an unknown escape or an unescaped quote
+fn unescape(escaped: &str) -> Option<String> {
+    let mut name = String::new();
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '"' => return None,
+            '\\' => match chars.next()? {
+                '"' => '"',
+                '\\' => '\\',
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'u' => {
+                    let code = chars.by_ref().take(4).collect::<String>();
+                    char::from_u32(u32::from_str_radix(&code, 16).ok()?)?
+                }
+                _ => return None,
+            },
+            c => c,
+        };
+        name.push(c);
+    }
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_toml_profile() {
+        let profile = Profile::builtin("aggressive").unwrap();
+        let toml = profile.to_toml();
+        assert_eq!(
+            toml,
+            "version = 1\nname = \"aggressive\"\n\n[weights]\nqueen_pressure = 160\nimmobile = 10\n"
+        );
+        assert_eq!(Profile::from_toml(&toml).unwrap(), profile);
+        assert!(Profile::builtin("reckless").is_none());
+        assert_eq!(Profile::find("positional").unwrap().name, "positional");
+        assert!(matches!(
+            Profile::find("reckless"),
+            Err(ProfileError::Io(..))
+        ));
+
+        let partial = Profile::from_toml("# tuned\n[weights]\nimmobile = -7\n").unwrap();
+        assert_eq!(partial.version, PROFILE_VERSION);
+        assert_eq!(partial.weights.immobile, -7);
+        assert_eq!(
+            partial.weights.queen_pressure,
+            Weights::default().queen_pressure
+        );
+
+        assert!(matches!(
+            Profile::from_toml("version = 2"),
+            Err(ProfileError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            Profile::from_toml("[weights]\nmobility = 1"),
+            Err(ProfileError::UnknownWeight(_))
+        ));
+        assert!(matches!(
+            Profile::from_toml("name = \"x\"\nimmobile = 3"),
+            Err(ProfileError::Syntax { line: 2, .. })
+        ));
+        for input in ["version = -1", "[weights]\nimmobile = 3000000000"] {
+            assert!(
+                matches!(
+                    Profile::from_toml(input),
+                    Err(ProfileError::Syntax { ref message, .. }) if message.contains("out of range")
+                ),
+                "{}",
+                input
+            );
+        }
+
+        // Names are escaped, so that any name reads back the same
+        let quoted = Profile::new("the \"best\" C:\\ \n\u{1}", Weights::default());
+        let toml = quoted.to_toml();
+        assert!(toml.starts_with("version = 1\nname = \"the \\\"best\\\" C:\\\\ \\n\\u0001\"\n"));
+        assert_eq!(Profile::from_toml(&toml).unwrap(), quoted);
+        assert!(matches!(
+            Profile::from_toml("name = \"a\"b\""),
+            Err(ProfileError::Syntax { line: 1, .. })
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    pub fn test_json_profile() {
+        let profile = Profile::builtin("positional").unwrap();
+        assert_eq!(Profile::from_json(&profile.to_json()).unwrap(), profile);
+
+        let partial = Profile::from_json(r#"{"weights": {"queen_pressure": 5}}"#).unwrap();
+        assert_eq!(partial.weights.queen_pressure, 5);
+        assert_eq!(partial.weights.immobile, Weights::default().immobile);
+        assert!(matches!(
+            Profile::from_json(r#"{"weights": {"immobile": "high"}}"#),
+            Err(ProfileError::Json(_))
+        ));
+        assert!(matches!(
+            Profile::from_json(r#"{"weights": {"immobile": 3000000000}}"#),
+            Err(ProfileError::Json(_))
+        ));
+        assert!(matches!(
+            Profile::from_json(r#"{"version": 5000000000}"#),
+            Err(ProfileError::UnsupportedVersion(u32::MAX))
+        ));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::engine::analysis::format_score;
+use crate::engine::evaluation::*;
+use crate::engine::profile::Profile;
+use crate::engine::search::Searcher;
+use crate::game::GameDebugger;
+use std::path::Path;
+
+/// How a move of a reviewed game compares to the best move found,
+/// see review_game()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoveReview {
+    /// The number of moves played before this one
+    pub ply: usize,
+    pub move_string: String,
+    /// The score of the position after the move, for the player who made it
+    pub score: Score,
+    /// The best move found and its score for the same player
+    pub best_move: String,
+    pub best_score: Score,
+    /// How much worse the move is than the best move, never negative
+    pub loss: Score,
+    /// Whether the loss is above the threshold of the review
+    pub blunder: bool,
+}
+
+impl std::fmt::Display for MoveReview {
+    /// Writes the move with its score, and for blunders "??" followed by
+    /// the loss and the best move
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>3}. {:<16} {}",
+            self.ply + 1,
+            self.move_string,
+            format_score(self.score)
+        )?;
+        if self.blunder {
+            write!(
+                f,
+                " ?? loses {}, best was {} {}",
+                self.loss,
+                self.best_move,
+                format_score(self.best_score)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Replays *game* and searches every position before a move to *depth*
+/// plies, comparing the move played with the best move found. Moves scoring
+/// more than *threshold* below the best move are flagged as blunders
+pub fn review_game(
+    game: &GameDebugger,
+    searcher: &mut Searcher,
+    depth: usize,
+    threshold: Score,
+) -> Vec<MoveReview> {
+    let mut replay = GameDebugger::from_move_list(&[], game.game_type())
+        .expect("The empty game should be valid");
+    let mut reviews = vec![];
+    for (ply, position) in game.positions().skip(1).enumerate() {
+        let analysis = searcher.analyze(
+            replay.position(),
+            replay.last_move(),
+            replay.player_to_move(),
+            depth,
+        );
+        let played = analysis
+            .candidates
+            .iter()
+            .find(|(candidate, _)| candidate == position);
+        let (Some((best, best_score)), Some((_, score))) = (analysis.candidates.first(), played)
+        else {
+            break;
+        };
+        let (Ok(move_string), Ok(best_move)) =
+            (replay.move_string(position), replay.move_string(best))
+        else {
+            break;
+        };
+        let loss = (best_score - score).max(0);
+        reviews.push(MoveReview {
+            ply,
+            move_string,
+            score: *score,
+            best_move,
+            best_score: *best_score,
+            loss,
+            blunder: loss > threshold,
+        });
+        if replay.append_position(position).is_err() {
+            break;
+        }
+    }
+    reviews
+}
+
+/// Reviews the game given by a UHP GameString (or a file containing one) at
+/// *depth* plies with the weights of *profile*, and prints every move with
+/// its score, annotating the moves that lose more than *threshold*
+pub fn print_review(game: &str, depth: usize, threshold: Score, profile: &str) {
+    let profile = match Profile::find(profile) {
+        Ok(profile) => profile,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let game_string = match Path::new(game).is_file() {
+        true => match std::fs::read_to_string(game) {
+            Ok(contents) => contents,
+            Err(e) => {
+                println!("Could not read {}: {}", game, e);
+                return;
+            }
+        },
+        false => game.to_string(),
+    };
+    let game = match GameDebugger::from_game_string(&game_string) {
+        Ok(game) => game,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    let mut searcher = Searcher::with_weights(game.game_type(), profile.weights);
+    searcher.set_deterministic(true);
+    let reviews = review_game(&game, &mut searcher, depth, threshold);
+    for review in reviews.iter() {
+        println!("{}", review);
+    }
+    let blunders = reviews.iter().filter(|review| review.blunder).count();
+    println!(
+        "{} moves reviewed at depth {}, {} lose more than {}",
+        reviews.len(),
+        depth,
+        blunders,
+        threshold
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::engines::{Engine, GreedyEngine, RandomEngine};
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_review_game() {
+        // The greedy engine plays the best move at depth 1, the random one
+        // rarely does
+        let mut game = GameDebugger::from_move_list(&[], GameType::Standard).unwrap();
+        let mut greedy = GreedyEngine::default();
+        let mut random = RandomEngine::new(7);
+        for turn in 0..8 {
+            let engine: &mut dyn Engine = match turn % 2 {
+                0 => &mut greedy,
+                _ => &mut random,
+            };
+            let position = engine.best_move(&mut game).unwrap();
+            game.append_position(&position).unwrap();
+        }
+
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_deterministic(true);
+        let reviews = review_game(&game, &mut searcher, 1, 0);
+        assert_eq!(reviews.len(), 8);
+        for review in reviews.iter() {
+            assert_eq!(review.loss, review.best_score - review.score);
+            assert_eq!(review.blunder, review.loss > 0);
+        }
+        assert!(reviews.iter().step_by(2).all(|review| review.loss == 0));
+        assert!(reviews
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .any(|review| review.blunder));
+
+        let blunder = reviews.iter().find(|review| review.blunder).unwrap();
+        assert!(blunder.to_string().contains(" ?? loses "));
+        let reviews = review_game(&game, &mut searcher, 1, Score::MAX);
+        assert!(reviews.iter().all(|review| !review.blunder));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::zobrist::splitmix64;
+
+/// A small seeded random number generator (SplitMix64), so that engines
+/// that play randomly can be replayed exactly from their seed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// Seeds the generator from the clock, for play that differs from run to run
+    pub fn from_time() -> Rng {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Rng::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let value = splitmix64(self.state);
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        value
+    }
+
+    /// A number in 0..*bound*, which must not be 0
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_rng() {
+        let mut rng = Rng::new(7);
+        let values = (0..100).map(|_| rng.below(10)).collect::<Vec<_>>();
+        assert!(values.iter().all(|value| *value < 10));
+        assert!((0..10).all(|digit| values.contains(&digit)));
+
+        let mut replay = Rng::new(7);
+        assert!(values.iter().all(|value| *value == replay.below(10)));
+        assert_ne!(Rng::new(8).next_u64(), Rng::new(7).next_u64());
+    }
+}
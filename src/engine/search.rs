@@ -0,0 +1,1118 @@
+use crate::engine::arena::StackArena;
+use crate::engine::evaluation::*;
+use crate::engine::transposition::{Bound, Entry, TranspositionTable};
+use crate::engine::tree::{Pruning, SearchTree};
+use crate::game::board_result;
+use crate::generator::debug::{PositionGenerator, ReferenceGenerator};
+use crate::hex_grid::*;
+use crate::moves::Move;
+use crate::uhp::GameType;
+use crate::zobrist;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The outcome of searching a position, see Searcher::analyze()
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    /// The score of the position for the player to move
+    pub score: Score,
+    /// The positions after each move of the principal variation
+    pub best_line: Vec<HexGrid>,
+    /// The position after every legal move with its score for the player
+    /// to move, best first
+    pub candidates: Vec<(HexGrid, Score)>,
+    /// What the search did to get there
+    pub stats: SearchStats,
+}
+
+/// Counters collected during a search, to measure the effect of changes to it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// The number of positions visited
+    pub nodes: usize,
+    /// Positions whose remaining moves were skipped as the opponent would
+    /// avoid them anyway
+    pub cutoffs: usize,
+    /// Positions pruned by letting the opponent move twice,
+    /// see Searcher::set_null_move()
+    pub null_cutoffs: usize,
+    /// Moves first searched a ply shallower, see Searcher::set_late_move_reductions()
+    pub reductions: usize,
+    /// Reduced moves and aspiration windows (see Searcher::analyze_near())
+    /// that had to be searched again
+    pub re_searches: usize,
+    /// The number of times moves were generated
+    pub movegen_calls: usize,
+    /// Positions settled by an earlier search,
+    /// see Searcher::set_transposition_table()
+    pub tt_hits: usize,
+    /// Positions looked up in the table by their canonical form,
+    /// see Searcher::set_symmetry_plies()
+    pub symmetric_probes: usize,
+    /// Of those, positions settled by an earlier search
+    pub symmetric_hits: usize,
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    /// Nodes searched per second
+    pub fn nps(&self) -> u64 {
+        match self.elapsed.as_micros() {
+            0 => 0,
+            micros => (self.nodes as u128 * 1_000_000 / micros) as u64,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchStats {
+    /// Writes the counters as "name value" pairs, the way UHP engines
+    /// report progress in info lines
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "nodes {} nps {} time {} cutoffs {} nullcutoffs {} reductions {} researches {} movegen {} tthits {} symprobes {} symhits {}",
+            self.nodes,
+            self.nps(),
+            self.elapsed.as_millis(),
+            self.cutoffs,
+            self.null_cutoffs,
+            self.reductions,
+            self.re_searches,
+            self.movegen_calls,
+            self.tt_hits,
+            self.symmetric_probes,
+            self.symmetric_hits,
+        )
+    }
+}
+
+/// How many plies shallower the opponent's extra move is searched,
+/// see Searcher::set_null_move()
+const NULL_MOVE_REDUCTION: usize = 2;
+
+/// How many moves of a position are searched at full depth before the
+/// rest are reduced, see Searcher::set_late_move_reductions()
+const LATE_MOVE_INDEX: usize = 3;
+
+/// The shallowest remaining depth at which late moves are reduced
+const LATE_MOVE_DEPTH: usize = 3;
+
+/// The default distance from the expected score of the aspiration window,
+/// see Searcher::set_aspiration_window()
+const ASPIRATION_WINDOW: Score = 50;
+
+/// Fixed depth alpha-beta search over the ReferenceGenerator
+#[derive(Clone, Debug)]
+pub struct Searcher {
+    game_type: GameType,
+    weights: Weights,
+    stats: SearchStats,
+    /// How many plies below the root of the running analyze() the searched
+    /// position is
+    ply: Score,
+    /// Whether the next position searched may not be skipped, see set_null_move()
+    no_skip: bool,
+    stop: Option<Arc<AtomicBool>>,
+    deadline: Option<Instant>,
+    max_nodes: Option<usize>,
+    null_move: bool,
+    late_move_reductions: bool,
+    aspiration_window: Option<Score>,
+    deterministic: bool,
+    tree: Option<SearchTree>,
+    table: Option<TranspositionTable>,
+    /// How many plies below the root positions are looked up in the table
+    /// by their canonical form, see set_symmetry_plies()
+    symmetry_plies: usize,
+    /// How much a draw is worth less than an even position to the player
+    /// to move at the root, see set_contempt()
+    contempt: Score,
+    /// The player to move at the root of the running analyze()
+    root: PieceColor,
+    /// The state keys of the game before the root, see set_history()
+    history: HashSet<u64>,
+    /// The state keys of the positions from the root to the one searched
+    path: Vec<u64>,
+    /// The evaluation terms of the positions from the root to the one
+    /// searched, updated as moves are made and dropped as they are unmade
+    terms: Vec<IncrementalTerms>,
+    check_evaluation: bool,
+    /// The moves of the positions from the root to the one searched, with
+    /// the positions they lead to, see children()
+    moves: StackArena<(HexGrid, Move)>,
+}
+
+impl Searcher {
+    pub fn new(game_type: GameType) -> Searcher {
+        Searcher {
+            game_type,
+            weights: Weights::default(),
+            stats: SearchStats::default(),
+            ply: 0,
+            no_skip: false,
+            stop: None,
+            deadline: None,
+            max_nodes: None,
+            null_move: true,
+            late_move_reductions: true,
+            aspiration_window: Some(ASPIRATION_WINDOW),
+            deterministic: false,
+            tree: None,
+            table: None,
+            symmetry_plies: 0,
+            contempt: 0,
+            root: PieceColor::White,
+            history: HashSet::new(),
+            path: vec![],
+            terms: vec![],
+            check_evaluation: false,
+            moves: StackArena::new(),
+        }
+    }
+
+    pub fn with_weights(game_type: GameType, weights: Weights) -> Searcher {
+        Searcher {
+            weights,
+            ..Searcher::new(game_type)
+        }
+    }
+
+    /// Searches moves in a fixed order, so that moves of equal score are
+    /// chosen the same way on every run and identical inputs give identical
+    /// results (as long as no time limit is set). Off by default as ordering
+    /// the moves slows the search down
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Evaluates every position from scratch as well and panics if that
+    /// differs from the evaluation updated move by move, to test the updates.
+    /// Off by default as it undoes what the updates save
+    pub fn set_check_evaluation(&mut self, check_evaluation: bool) {
+        self.check_evaluation = check_evaluation;
+    }
+
+    /// Scores draws as *contempt* worse than an even position for the player
+    /// to move at the root of the search, and as that much better for the
+    /// opponent. A positive contempt avoids draws, for playing weaker
+    /// opponents, a negative one seeks them against stronger ones. Draws are
+    /// scored 0 by default
+    pub fn set_contempt(&mut self, contempt: Score) {
+        self.contempt = contempt;
+    }
+
+    /// Sets the state keys of the positions played in the game so far (see
+    /// GameDebugger::state_keys()), so that moves going back to one of them
+    /// are scored as draws by repetition.
+    ///
+    /// Going back to an earlier state of the game or of the line searched is
+    /// scored as a draw the first time, as either player can keep repeating it
+    pub fn set_history(&mut self, keys: impl IntoIterator<Item = u64>) {
+        self.history = keys.into_iter().collect();
+    }
+
+    /// Aborts searches once *stop* is set, see stopped()
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = Some(stop);
+    }
+
+    /// Aborts searches once *deadline* has passed, see stopped()
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Prunes positions in which even letting the opponent move twice in a
+    /// row leaves the player to move too well off for the opponent to allow,
+    /// see negamax(). On by default, turning it off gives the exact alpha-beta
+    /// result to test the pruning against
+    pub fn set_null_move(&mut self, null_move: bool) {
+        self.null_move = null_move;
+    }
+
+    /// Searches moves after the first LATE_MOVE_INDEX of a position a ply
+    /// shallower, unless that shows them to be better than the moves before,
+    /// see negamax(). On by default
+    pub fn set_late_move_reductions(&mut self, late_move_reductions: bool) {
+        self.late_move_reductions = late_move_reductions;
+    }
+
+    /// Sets how far from the expected score analyze_near() first searches,
+    /// None to always search with a full window
+    pub fn set_aspiration_window(&mut self, window: Option<Score>) {
+        self.aspiration_window = window;
+    }
+
+    /// Records the positions visited by each analyze(), up to *limit* of
+    /// them, or stops recording them if None, see tree()
+    pub fn set_tree_limit(&mut self, limit: Option<usize>) {
+        self.tree = limit.map(SearchTree::new);
+    }
+
+    /// The positions visited by the last analyze(), if recorded,
+    /// see set_tree_limit()
+    pub fn tree(&self) -> Option<&SearchTree> {
+        self.tree.as_ref()
+    }
+
+    fn clear_tree(&mut self) {
+        if let Some(tree) = self.tree.as_mut() {
+            *tree = SearchTree::new(tree.limit());
+        }
+    }
+
+    /// Stores the result of every searched position in *table* and looks
+    /// positions up in it before searching them, None to search without one.
+    ///
+    /// The table is kept across calls to analyze(), and can be saved to reuse
+    /// it in a later session, see take_transposition_table()
+    pub fn set_transposition_table(&mut self, table: Option<TranspositionTable>) {
+        self.table = table;
+    }
+
+    pub fn transposition_table(&self) -> Option<&TranspositionTable> {
+        self.table.as_ref()
+    }
+
+    /// Removes the table set by set_transposition_table() with everything the
+    /// searches stored in it
+    pub fn take_transposition_table(&mut self) -> Option<TranspositionTable> {
+        self.table.take()
+    }
+
+    /// Looks the positions up to *plies* below the root up in the
+    /// transposition table by their canonical form (see HexGrid::canonical()),
+    /// so that a rotation, reflection or translation of a position already
+    /// searched is settled by it. Such transpositions are common in the
+    /// opening and close to the root, and rare deeper in the search, where
+    /// finding the canonical form would cost more than it saves. It only
+    /// pays off when the positions settled have deep searches below them
+    /// (see the symmetric_search benchmark), so 0 (the default) turns it off,
+    /// see SearchStats::symmetric_hits
+    pub fn set_symmetry_plies(&mut self, plies: usize) {
+        self.symmetry_plies = plies;
+    }
+
+    /// Aborts searches once they visited *max_nodes* positions, see stopped()
+    pub fn set_node_limit(&mut self, max_nodes: Option<usize>) {
+        self.max_nodes = max_nodes;
+    }
+
+    /// Whether the search was asked to stop or ran out of time or nodes, in which case
+    /// the result of the last analyze() is incomplete and should be discarded
+    pub fn stopped(&self) -> bool {
+        let stopped = self
+            .stop
+            .as_ref()
+            .is_some_and(|stop| stop.load(Ordering::Relaxed));
+        stopped
+            || self
+                .max_nodes
+                .is_some_and(|max_nodes| self.stats.nodes >= max_nodes)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Searches *depth* plies below *grid* with *color* to move, where
+    /// *last_move* is the destination of the piece moved in the previous turn.
+    ///
+    /// Every legal move is searched with a full window so that each
+    /// candidate gets an exact score.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, grid, last_move))
+    )]
+    pub fn analyze(
+        &mut self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        color: PieceColor,
+        depth: usize,
+    ) -> Analysis {
+        let start = Instant::now();
+        self.stats = SearchStats::default();
+        self.clear_tree();
+        let mut analysis = self.search_root(grid, last_move, color, depth, -WIN, WIN);
+        analysis.stats.elapsed = start.elapsed();
+        #[cfg(feature = "tracing")]
+        trace_analysis(&analysis);
+        analysis
+    }
+
+    /// Like analyze(), but expects the score to be close to *guess*, usually
+    /// the score of the previous iteration of iterative deepening.
+    ///
+    /// With an aspiration window set (see set_aspiration_window()) the moves
+    /// are first searched with a window around *guess*, and only searched
+    /// again with a full window if the score turns out to be outside of it.
+    /// The scores of the candidates other than the best move are then only
+    /// upper bounds when they fall below the window
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, grid, last_move))
+    )]
+    pub fn analyze_near(
+        &mut self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        color: PieceColor,
+        depth: usize,
+        guess: Score,
+    ) -> Analysis {
+        let Some(window) = self.aspiration_window else {
+            return self.analyze(grid, last_move, color, depth);
+        };
+        let start = Instant::now();
+        self.stats = SearchStats::default();
+        self.clear_tree();
+
+        let low = guess.saturating_sub(window).max(-WIN);
+        let high = guess.saturating_add(window).min(WIN);
+        let mut analysis = self.search_root(grid, last_move, color, depth, low, high);
+        if (analysis.score <= low && low > -WIN) || (analysis.score >= high && high < WIN) {
+            self.stats.re_searches += 1;
+            self.prune_last(Pruning::ReSearched);
+            analysis = self.search_root(grid, last_move, color, depth, -WIN, WIN);
+        }
+        analysis.stats.elapsed = start.elapsed();
+        #[cfg(feature = "tracing")]
+        trace_analysis(&analysis);
+        analysis
+    }
+
+    /// Searches every move of the root with the window *low* to *high*,
+    /// adding to the nodes visited so far
+    fn search_root(
+        &mut self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        color: PieceColor,
+        depth: usize,
+        low: Score,
+        high: Score,
+    ) -> Analysis {
+        self.stats.nodes += 1;
+        self.ply = 0;
+        self.no_skip = false;
+        self.root = color;
+        // The memory of the previous search is reused
+        self.path.clear();
+        self.path.push(zobrist::state_key(grid, last_move, color));
+        self.terms.clear();
+        self.terms.push(IncrementalTerms::new(grid));
+        self.moves.reset();
+        if let Some(tree) = self.tree.as_mut() {
+            tree.enter(None, depth, low, high);
+        }
+
+        if board_result(grid).is_some() || depth == 0 {
+            let score = self.leaf(grid, color);
+            self.terms.clear();
+            if let Some(tree) = self.tree.as_mut() {
+                tree.exit(score);
+            }
+            return Analysis {
+                score,
+                best_line: vec![],
+                candidates: vec![],
+                stats: self.stats,
+            };
+        }
+
+        let children = self.children(grid, last_move, color);
+        let mut lines = Vec::with_capacity(children.len());
+        for index in children.clone() {
+            let (child, mv) = self.take_child(index);
+            let (score, mut line) =
+                self.descend(&child, Some(mv), color.opposite(), depth - 1, -high, -low);
+            line.insert(0, child);
+            lines.push((-score, line));
+        }
+        self.moves.release(children.start);
+        lines.sort_by_key(|(score, _)| -score);
+        self.terms.clear();
+
+        let (score, best_line) = lines.first().cloned().unwrap_or((0, vec![]));
+        let candidates = lines
+            .into_iter()
+            .map(|(score, mut line)| (line.swap_remove(0), score))
+            .collect();
+        if let Some(tree) = self.tree.as_mut() {
+            tree.exit(score);
+        }
+
+        Analysis {
+            score,
+            best_line,
+            candidates,
+            stats: self.stats,
+        }
+    }
+
+    /// Returns the score of *grid* for *color* and the positions of the
+    /// best line found below it
+    fn negamax(
+        &mut self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        color: PieceColor,
+        depth: usize,
+        mut alpha: Score,
+        beta: Score,
+    ) -> (Score, Vec<HexGrid>) {
+        // Checked before the position is counted so that a search never
+        // visits more positions than its limit
+        if self
+            .max_nodes
+            .is_some_and(|max_nodes| self.stats.nodes >= max_nodes)
+        {
+            self.prune(Pruning::Stopped);
+            return (0, vec![]);
+        }
+        self.stats.nodes += 1;
+        let no_skip = std::mem::take(&mut self.no_skip);
+        if depth == 0 || board_result(grid).is_some() {
+            return (self.leaf(grid, color), vec![]);
+        }
+        if self.stopped() {
+            self.prune(Pruning::Stopped);
+            return (self.leaf(grid, color), vec![]);
+        }
+
+        let (key, symmetry) = match self.table.is_some() {
+            true => {
+                let (key, symmetry) = self.table_key(grid, last_move, color);
+                (Some(key), symmetry)
+            }
+            false => (None, None),
+        };
+        let mut entry = key.and_then(|key| self.table.as_ref()?.get(key).copied());
+        if let Some(transform) = symmetry {
+            self.stats.symmetric_probes += 1;
+            // The table holds the moves of the canonical form, the move is
+            // checked against the grid once it is mapped back below
+            if let Some(entry) = entry.as_mut() {
+                entry.best_move = entry
+                    .best_move
+                    .map(|mv| mv.map_locations(|location| transform.invert(location)));
+            }
+        }
+        // Positions with the same key overwrite each other, an entry whose
+        // move does not fit the grid was stored for a different position
+        let entry = entry.filter(|entry| match entry.best_move {
+            Some(mv) => mv.try_apply(grid).is_some(),
+            None => true,
+        });
+        if let Some(entry) = entry.filter(|entry| entry.cuts(depth, self.ply, alpha, beta)) {
+            self.stats.tt_hits += 1;
+            if symmetry.is_some() {
+                self.stats.symmetric_hits += 1;
+            }
+            self.prune(Pruning::Transposition);
+            let line = entry
+                .best_move
+                .and_then(|mv| mv.try_apply(grid))
+                .into_iter()
+                .collect();
+            return (entry.score_at(self.ply), line);
+        }
+        let original_alpha = alpha;
+
+        // Hive has no null move, a pass is only legal without any other move.
+        // Letting the opponent move twice instead still shows positions so
+        // good that any real move holds beta, which is then verified by a
+        // shallower search as having to move can hurt in Hive
+        let undecided = beta < WIN - MAX_PLY;
+        if self.null_move && !no_skip && undecided && depth > NULL_MOVE_REDUCTION {
+            self.no_skip = true;
+            let reduced = depth - 1 - NULL_MOVE_REDUCTION;
+            let (score, _) = self.descend(grid, None, color.opposite(), reduced, -beta, 1 - beta);
+            if -score >= beta {
+                self.no_skip = true;
+                let depth = depth - NULL_MOVE_REDUCTION;
+                let verified = self.negamax(grid, last_move, color, depth, alpha, beta);
+                if verified.0 >= beta {
+                    self.stats.null_cutoffs += 1;
+                    self.prune(Pruning::NullMove);
+                    return verified;
+                }
+            }
+        }
+
+        let mut best = (-WIN, vec![]);
+        let mut best_move = None;
+        let children = self.children(grid, last_move, color);
+        // The best move of an earlier search is likely still good
+        let hash_move = entry.and_then(|entry| entry.best_move);
+        let moves = self.moves.slice_mut(children.clone());
+        if let Some(index) = moves.iter().position(|(_, mv)| Some(*mv) == hash_move) {
+            moves[..=index].rotate_right(1);
+        }
+        for (index, slot) in children.clone().enumerate() {
+            let (child, mv) = self.take_child(slot);
+            let opponent = color.opposite();
+            // Moves late in the order are less likely to be best, so they are
+            // first searched a ply shallower with a null window, and only
+            // searched fully if they turn out to raise alpha
+            let reduce =
+                self.late_move_reductions && index >= LATE_MOVE_INDEX && depth >= LATE_MOVE_DEPTH;
+            let mut reduced = None;
+            if reduce {
+                self.stats.reductions += 1;
+                let (score, line) =
+                    self.descend(&child, Some(mv), opponent, depth - 2, -alpha - 1, -alpha);
+                if -score <= alpha {
+                    self.prune_last(Pruning::Reduced);
+                    reduced = Some((score, line));
+                } else {
+                    self.stats.re_searches += 1;
+                    self.prune_last(Pruning::ReSearched);
+                }
+            }
+            let (score, mut line) = match reduced {
+                Some(result) => result,
+                None => self.descend(&child, Some(mv), opponent, depth - 1, -beta, -alpha),
+            };
+            let score = -score;
+            if score > best.0 || best.1.is_empty() {
+                line.insert(0, child);
+                best = (score, line);
+                best_move = Some(mv);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                self.stats.cutoffs += 1;
+                self.prune(Pruning::Cutoff);
+                break;
+            }
+        }
+        self.moves.release(children.start);
+
+        let stopped = self.stopped();
+        if let (Some(table), Some(key)) = (self.table.as_mut(), key.filter(|_| !stopped)) {
+            let bound = match best.0 {
+                score if score <= original_alpha => Bound::Upper,
+                score if score >= beta => Bound::Lower,
+                _ => Bound::Exact,
+            };
+            let best_move = match symmetry {
+                Some(transform) => {
+                    best_move.map(|mv| mv.map_locations(|location| transform.apply(location)))
+                }
+                None => best_move,
+            };
+            table.store(key, Entry::new(depth, best.0, self.ply, bound, best_move));
+        }
+        best
+    }
+
+    /// The key of *grid* in the transposition table, with the transform to
+    /// the canonical form the key was made from if it is looked up by its
+    /// symmetry, see set_symmetry_plies()
+    fn table_key(
+        &self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        color: PieceColor,
+    ) -> (u64, Option<Transform>) {
+        if self.ply >= self.symmetry_plies as Score {
+            return (TranspositionTable::key(grid, last_move, color), None);
+        }
+        let transform = grid.canonical_transform();
+        let canonical = grid.apply_transform(&transform);
+        let last_move = last_move.map(|location| transform.apply(location));
+        let key = TranspositionTable::key(&canonical, last_move, color);
+        (key, Some(transform))
+    }
+
+    /// Searches the position *grid* reached by *mv*, one ply further from the
+    /// root, see negamax(). The move is None when the opponent moves twice
+    fn descend(
+        &mut self,
+        grid: &HexGrid,
+        mv: Option<Move>,
+        color: PieceColor,
+        depth: usize,
+        alpha: Score,
+        beta: Score,
+    ) -> (Score, Vec<HexGrid>) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.enter(mv, depth, alpha, beta);
+        }
+        self.ply += 1;
+        let last_move = mv.and_then(|mv| mv.destination());
+        let key = mv.map(|_| zobrist::state_key(grid, last_move, color));
+        let result = match key {
+            Some(key) if self.history.contains(&key) || self.path.contains(&key) => {
+                (self.draw_score(color), vec![])
+            }
+            _ => {
+                self.path.extend(key);
+                let terms = match (self.terms.last(), mv) {
+                    (Some(terms), Some(mv)) => terms.after(grid, &mv),
+                    (Some(terms), None) => *terms,
+                    (None, _) => IncrementalTerms::new(grid),
+                };
+                self.terms.push(terms);
+                let result = self.negamax(grid, last_move, color, depth, alpha, beta);
+                self.terms.pop();
+                if key.is_some() {
+                    self.path.pop();
+                }
+                result
+            }
+        };
+        self.ply -= 1;
+        if let Some(tree) = self.tree.as_mut() {
+            tree.exit(result.0);
+        }
+        result
+    }
+
+    fn prune(&mut self, pruning: Pruning) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.prune(pruning);
+        }
+    }
+
+    fn prune_last(&mut self, pruning: Pruning) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.prune_last(pruning);
+        }
+    }
+
+    /// Scores a position without searching further
+    fn leaf(&self, grid: &HexGrid, color: PieceColor) -> Score {
+        match board_result(grid) {
+            Some(result) if result.is_draw() => self.draw_score(color),
+            Some(result) if result.winner() == Some(color) => win_in(self.ply),
+            Some(_) => -win_in(self.ply),
+            None => self.evaluate(grid, color),
+        }
+    }
+
+    /// Statically evaluates *grid* from the terms updated along the path
+    /// to it, see set_check_evaluation()
+    fn evaluate(&self, grid: &HexGrid, color: PieceColor) -> Score {
+        let terms = match self.terms.last() {
+            Some(terms) => *terms,
+            None => IncrementalTerms::new(grid),
+        };
+        if self.check_evaluation {
+            assert_eq!(
+                terms.features(grid, color),
+                features(grid, color),
+                "Evaluation updated move by move differs for\n{}",
+                grid.to_dsl()
+            );
+        }
+        terms.evaluate(grid, color, &self.weights)
+    }
+
+    /// The score of a draw for *color*, see set_contempt()
+    fn draw_score(&self, color: PieceColor) -> Score {
+        match color == self.root {
+            true => -self.contempt,
+            false => self.contempt,
+        }
+    }
+
+    /// Pushes the positions reachable by *color* paired with the move that
+    /// reaches them on top of the move arena, returning where they are.
+    ///
+    /// The caller takes them out with take_child() and releases them once
+    /// searched
+    fn children(
+        &mut self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        color: PieceColor,
+    ) -> Range<usize> {
+        self.stats.movegen_calls += 1;
+        let mut generator = ReferenceGenerator::from_hex_grid(grid, self.game_type, last_move);
+        let children =
+            self.moves.extend(
+                generator
+                    .generate_positions_for(color)
+                    .into_iter()
+                    .map(|child| {
+                        let mv = grid.diff(&child).unwrap_or(Move::Pass);
+                        (child, mv)
+                    }),
+            );
+        if self.deterministic {
+            // By move rather than in the order of the generator, which may
+            // change as the generator does
+            self.moves
+                .slice_mut(children.clone())
+                .sort_by_key(|(_, mv)| *mv);
+        }
+        children
+    }
+
+    /// Moves the position at *index* of the move arena out of it, leaving
+    /// an empty grid that costs nothing to release
+    fn take_child(&mut self, index: usize) -> (HexGrid, Move) {
+        let (child, mv) = &mut self.moves[index];
+        (std::mem::replace(child, HexGrid::new()), *mv)
+    }
+}
+
+/// Records the result of a search as an event of the span of analyze()
+#[cfg(feature = "tracing")]
+fn trace_analysis(analysis: &Analysis) {
+    tracing::debug!(
+        score = analysis.score,
+        nodes = analysis.stats.nodes,
+        cutoffs = analysis.stats.cutoffs,
+        tt_hits = analysis.stats.tt_hits,
+        symmetric_hits = analysis.stats.symmetric_hits,
+        elapsed = ?analysis.stats.elapsed,
+        "searched"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameResult;
+
+    #[test]
+    pub fn test_deterministic() {
+        use PieceColor::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . a q . .\n",
+            ". . A Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let analyses = (0..3)
+            .map(|_| {
+                let mut searcher = Searcher::new(GameType::Standard);
+                searcher.set_deterministic(true);
+                searcher.analyze(&grid, None, White, 2)
+            })
+            .collect::<Vec<_>>();
+
+        for analysis in analyses.iter().skip(1) {
+            assert_eq!(analysis.score, analyses[0].score);
+            assert_eq!(analysis.best_line, analyses[0].best_line);
+            assert_eq!(analysis.candidates, analyses[0].candidates);
+        }
+    }
+
+    #[test]
+    pub fn test_contempt() {
+        use crate::game::GameDebugger;
+
+        // The queens shuffle back and forth, so that black can repeat the
+        // state after the fourth move
+        let moves = [
+            "wA1", "bA1 wA1-", "wQ -wA1", "bQ bA1-", "wQ \\wA1", "bQ bA1/", "wQ -wA1",
+        ];
+        let game = GameDebugger::from_move_list(&moves, GameType::Standard).unwrap();
+        let mut repeated = game.clone();
+        repeated.make_move("bQ bA1-").unwrap();
+        let repetition = repeated.position().clone();
+        let score = |searcher: &mut Searcher| {
+            let analysis =
+                searcher.analyze(game.position(), game.last_move(), game.player_to_move(), 1);
+            let (_, score) = analysis
+                .candidates
+                .into_iter()
+                .find(|(position, _)| *position == repetition)
+                .unwrap();
+            score
+        };
+
+        // Without the history the repetition is evaluated like any position
+        let mut searcher = Searcher::new(GameType::Standard);
+        let evaluated = score(&mut searcher);
+        searcher.set_contempt(100);
+        assert_eq!(score(&mut searcher), evaluated);
+
+        searcher.set_history(game.state_keys());
+        assert_eq!(score(&mut searcher), -100);
+        searcher.set_contempt(-100);
+        assert_eq!(score(&mut searcher), 100);
+        searcher.set_contempt(0);
+        assert_eq!(score(&mut searcher), 0);
+    }
+
+    #[test]
+    pub fn test_finds_surround() {
+        use PieceColor::*;
+
+        // The white ant can complete the surround of the black queen
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . . .\n",
+            " . . g a . .\n",
+            ". . . q b .\n",
+            " . A G Q . .\n",
+            ". . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let mut searcher = Searcher::new(GameType::Standard);
+        let analysis = searcher.analyze(&grid, None, White, 2);
+
+        assert_eq!(analysis.score, win_in(1));
+        let finish = analysis.best_line.first().unwrap();
+        assert_eq!(board_result(finish), Some(GameResult::WhiteWins));
+        assert_eq!(analysis.candidates.first().unwrap().1, win_in(1));
+        assert!(analysis.candidates.len() > 1);
+        assert!(analysis.stats.nodes > analysis.candidates.len());
+        assert!(analysis.stats.movegen_calls > 1);
+        let info = analysis.stats.to_string();
+        assert!(info.starts_with(&format!("nodes {} nps ", analysis.stats.nodes)));
+        assert!(!searcher.stopped());
+
+        // Deeper searches also find slower surrounds, but prefer the fastest
+        let deeper = searcher.analyze(&grid, None, White, 3);
+        assert_eq!(deeper.score, win_in(1));
+        assert_eq!(deeper.best_line.len(), 1);
+
+        searcher.set_node_limit(Some(analysis.stats.nodes / 2));
+        searcher.analyze(&grid, None, White, 2);
+        assert!(searcher.stopped());
+    }
+
+    #[test]
+    pub fn test_null_move() {
+        use PieceColor::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . . g q .\n",
+            ". . G Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let mut exact = Searcher::new(GameType::Standard);
+        exact.set_null_move(false);
+        exact.set_late_move_reductions(false);
+        exact.set_deterministic(true);
+        let mut pruned = exact.clone();
+        pruned.set_null_move(true);
+
+        // White is ahead of beta, so giving black an extra move is
+        // enough to prune the position
+        let beta = -300;
+        let (exact_score, _) = exact.negamax(&grid, None, White, 3, beta - 1, beta);
+        let (pruned_score, _) = pruned.negamax(&grid, None, White, 3, beta - 1, beta);
+        assert!(exact_score >= beta && pruned_score >= beta);
+        assert!(pruned.stats.nodes < exact.stats.nodes);
+        assert_eq!(
+            (exact.stats.null_cutoffs, pruned.stats.null_cutoffs),
+            (0, 1)
+        );
+    }
+
+    #[test]
+    pub fn test_check_evaluation() {
+        use PieceColor::*;
+
+        let moves = [
+            "wS1", "bG1 -wS1", "wB1 wS1-", "bQ -bG1", r"wQ wS1\", "bA1 -bQ", "wB1 wS1",
+        ];
+        let grid = HexGrid::from_move_list(&moves).unwrap();
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_deterministic(true);
+        searcher.set_transposition_table(Some(TranspositionTable::new(1 << 12)));
+        let mut checked = searcher.clone();
+        checked.set_check_evaluation(true);
+
+        // Searched again, positions are settled by the table
+        for _ in 0..2 {
+            let analysis = checked.analyze(&grid, None, Black, 2);
+            assert_eq!(
+                analysis.score,
+                searcher.analyze(&grid, None, Black, 2).score
+            );
+        }
+        assert!(checked.stats.tt_hits > 0);
+        assert!(checked.terms.is_empty());
+    }
+
+    #[test]
+    pub fn test_symmetric_transpositions() {
+        use PieceColor::*;
+
+        let mut plain = Searcher::new(GameType::Standard);
+        plain.set_deterministic(true);
+        plain.set_transposition_table(Some(TranspositionTable::new(1 << 16)));
+        let mut symmetric = plain.clone();
+        symmetric.set_symmetry_plies(3);
+
+        // Black's first piece on any side of white's gives the same position
+        // up to rotation, so only one of them has to be searched
+        let grid = HexGrid::new();
+        let expected = plain.analyze(&grid, None, White, 3);
+        let analysis = symmetric.analyze(&grid, None, White, 3);
+        assert_eq!(analysis.score, expected.score);
+        assert_eq!(analysis.candidates.len(), expected.candidates.len());
+        assert!(analysis.stats.nodes < expected.stats.nodes);
+        assert!(analysis.stats.symmetric_hits > 0);
+        assert!(analysis.stats.symmetric_probes >= analysis.stats.symmetric_hits);
+        assert_eq!(expected.stats.symmetric_probes, 0);
+
+        // Moves taken from the table are moves of the position searched
+        let mut line = vec![grid];
+        line.extend(analysis.best_line);
+        for positions in line.windows(2) {
+            assert!(positions[0].diff(&positions[1]).is_some());
+        }
+    }
+
+    #[test]
+    pub fn test_colliding_symmetric_entries() {
+        use PieceColor::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . . g q .\n",
+            ". . G Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_deterministic(true);
+        searcher.set_symmetry_plies(2);
+        let expected = searcher.analyze(&grid, None, White, 2);
+
+        // The canonical form of every position after white's move shares its
+        // key with a position whose best move takes a piece from an empty hex
+        let mut table = TranspositionTable::new(1 << 10);
+        let empty = HexLocation::new(-20, -20);
+        for (child, _) in &expected.candidates {
+            let last_move = grid.diff(child).unwrap().destination();
+            let transform = child.canonical_transform();
+            let canonical = child.apply_transform(&transform);
+            let last_move = last_move.map(|location| transform.apply(location));
+            let piece = Piece::new(PieceType::Ant, Black);
+            let stale = Move::Movement {
+                piece,
+                from: empty,
+                to: empty,
+            };
+            let entry = Entry::new(10, WIN / 2, 1, Bound::Exact, Some(stale));
+            table.store(TranspositionTable::key(&canonical, last_move, Black), entry);
+        }
+        searcher.set_transposition_table(Some(table));
+        let analysis = searcher.analyze(&grid, None, White, 2);
+        assert_eq!(analysis.score, expected.score);
+        assert!(analysis.stats.symmetric_probes > 0);
+        assert_eq!(analysis.stats.symmetric_hits, 0);
+    }
+
+    #[test]
+    pub fn test_colliding_entries() {
+        use PieceColor::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . . g q .\n",
+            ". . G Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_deterministic(true);
+        let expected = searcher.analyze(&grid, None, White, 2);
+
+        // Every position after white's move shares its key with a position
+        // whose best move takes a piece from an empty hex
+        let mut table = TranspositionTable::new(1 << 10);
+        let empty = HexLocation::new(-20, -20);
+        for (child, _) in &expected.candidates {
+            let mv = grid.diff(child).unwrap();
+            let piece = Piece::new(PieceType::Ant, Black);
+            let stale = Move::Movement {
+                piece,
+                from: empty,
+                to: empty,
+            };
+            let entry = Entry::new(10, WIN / 2, 1, Bound::Exact, Some(stale));
+            table.store(
+                TranspositionTable::key(child, mv.destination(), Black),
+                entry,
+            );
+        }
+        searcher.set_transposition_table(Some(table));
+        let analysis = searcher.analyze(&grid, None, White, 2);
+        assert_eq!(analysis.score, expected.score);
+        assert_eq!(analysis.best_line, expected.best_line);
+        assert_eq!(analysis.stats.tt_hits, 0);
+    }
+
+    #[test]
+    pub fn test_move_arena() {
+        use PieceColor::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . . g q .\n",
+            ". . G Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_deterministic(true);
+        let analysis = searcher.analyze(&grid, None, White, 2);
+
+        // Every position released its moves, the deepest line holding the
+        // moves of a position at each ply
+        assert!(searcher.moves.is_empty());
+        assert!(searcher.moves.peak() > analysis.candidates.len());
+        let capacity = searcher.moves.capacity();
+        let again = searcher.analyze(&grid, None, White, 2);
+        assert_eq!(again.score, analysis.score);
+        assert_eq!(searcher.moves.capacity(), capacity);
+    }
+
+    #[test]
+    pub fn test_late_moves_and_aspiration() {
+        use PieceColor::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . . g q .\n",
+            ". . G Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let mut exact = Searcher::new(GameType::Standard);
+        exact.set_null_move(false);
+        exact.set_late_move_reductions(false);
+        exact.set_deterministic(true);
+        let mut reduced = exact.clone();
+        reduced.set_late_move_reductions(true);
+
+        // No move reaches alpha, so every move is searched, late ones shallower
+        let alpha = 300;
+        let (exact_score, _) = exact.negamax(&grid, None, White, 3, alpha, alpha + 1);
+        let (reduced_score, _) = reduced.negamax(&grid, None, White, 3, alpha, alpha + 1);
+        assert!(exact_score <= alpha && reduced_score <= alpha);
+        assert!(reduced.stats.nodes < exact.stats.nodes);
+        assert!(reduced.stats.reductions > 0 && exact.stats.reductions == 0);
+
+        // A good guess gives the same best score with fewer nodes, a bad
+        // guess needs to be searched again
+        let full = exact.analyze(&grid, None, White, 2);
+        let near = exact.analyze_near(&grid, None, White, 2, full.score);
+        assert_eq!(near.score, full.score);
+        assert!(near.stats.nodes <= full.stats.nodes);
+        let far = exact.analyze_near(&grid, None, White, 2, full.score + 1000);
+        assert_eq!(far.score, full.score);
+        assert!(far.stats.nodes > full.stats.nodes);
+        assert_eq!((near.stats.re_searches, far.stats.re_searches), (0, 1));
+
+        exact.set_aspiration_window(None);
+        let far = exact.analyze_near(&grid, None, White, 2, full.score + 1000);
+        assert_eq!(far.stats.nodes, full.stats.nodes);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::piece::IntoPieces;
+use crate::zobrist::{self, splitmix64};
+
+/// The share of positions wrongly reported as seen unless told otherwise,
+/// see SeenPositions::new()
+pub const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// A Bloom filter of the positions seen so far, for playouts and random
+/// games to steer away from positions they already went through without
+/// the memory and hashing cost of storing every position.
+///
+/// A position never inserted may be reported as seen (with about the false
+/// positive rate the filter was made for, as long as no more positions than
+/// its capacity are inserted), but an inserted one is always reported as seen
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeenPositions {
+    bits: Vec<u64>,
+    hashes: u32,
+    len: usize,
+}
+
+impl SeenPositions {
+    /// A filter for *capacity* positions reporting *false_positive_rate* of
+    /// the positions never inserted as seen once full
+    pub fn new(capacity: usize, false_positive_rate: f64) -> SeenPositions {
+        let capacity = capacity.max(1) as f64;
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-capacity * rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let words = bits.div_ceil(64).max(1);
+        let hashes = ((words * 64) as f64 / capacity * ln2).round().max(1.0) as u32;
+        SeenPositions {
+            bits: vec![0; words],
+            hashes,
+            len: 0,
+        }
+    }
+
+    /// A filter for *capacity* positions with the DEFAULT_FALSE_POSITIVE_RATE
+    pub fn with_capacity(capacity: usize) -> SeenPositions {
+        SeenPositions::new(capacity, DEFAULT_FALSE_POSITIVE_RATE)
+    }
+
+    /// Marks *position* as seen, returning whether it may have been seen
+    /// before
+    pub fn insert(&mut self, position: &impl IntoPieces) -> bool {
+        self.insert_hash(zobrist::hash(position))
+    }
+
+    /// Whether *position* may have been seen, false if it certainly was not
+    pub fn contains(&self, position: &impl IntoPieces) -> bool {
+        self.contains_hash(zobrist::hash(position))
+    }
+
+    /// insert() for the Zobrist hash of a position (or any other key), for
+    /// callers that keep the hash up to date as they play
+    pub fn insert_hash(&mut self, hash: u64) -> bool {
+        let mut seen = true;
+        for bit in self.bit_indices(hash) {
+            let word = &mut self.bits[bit / 64];
+            seen &= *word & (1 << (bit % 64)) != 0;
+            *word |= 1 << (bit % 64);
+        }
+        if !seen {
+            self.len += 1;
+        }
+        seen
+    }
+
+    /// contains() for the Zobrist hash of a position
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        self.bit_indices(hash)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// The number of positions inserted that were not reported as seen
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Forgets every position, keeping the memory for the next playout
+    pub fn clear(&mut self) {
+        self.bits.fill(0);
+        self.len = 0;
+    }
+
+    /// The expected share of the positions never inserted that are reported
+    /// as seen, given how full the filter is
+    pub fn false_positive_rate(&self) -> f64 {
+        let set = self.bits.iter().map(|word| word.count_ones()).sum::<u32>();
+        (set as f64 / (self.bits.len() * 64) as f64).powi(self.hashes as i32)
+    }
+
+    /// The bits of *hash*, derived from two hashes by double hashing
+    fn bit_indices(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let size = (self.bits.len() * 64) as u64;
+        let step = splitmix64(hash) | 1;
+        (0..self.hashes as u64)
+            .map(move |i| (hash.wrapping_add(i.wrapping_mul(step)) % size) as usize)
+    }
+}
+
+impl Default for SeenPositions {
+    fn default() -> SeenPositions {
+        SeenPositions::with_capacity(1 << 16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameDebugger;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_seen_positions() {
+        let moves = ["wA1", "bG1 -wA1", "wQ wA1-", "bQ /bG1"];
+        let game = GameDebugger::from_move_list(&moves, GameType::Standard).unwrap();
+        let mut seen = SeenPositions::with_capacity(100);
+        for position in game.positions() {
+            assert!(!seen.contains(position));
+            assert!(!seen.insert(position));
+            assert!(seen.insert(position));
+        }
+        assert_eq!(seen.len(), 5);
+        assert!(game.positions().all(|position| seen.contains(position)));
+
+        // Filled to capacity, the rate stays close to the one asked for
+        let mut seen = SeenPositions::new(10_000, 0.01);
+        for hash in 0..10_000u64 {
+            seen.insert_hash(splitmix64(hash));
+        }
+        let false_positives = (10_000..110_000u64)
+            .filter(|hash| seen.contains_hash(splitmix64(*hash)))
+            .count();
+        assert!(
+            false_positives < 2_000,
+            "{} false positives",
+            false_positives
+        );
+        assert!(seen.false_positive_rate() < 0.02);
+
+        seen.clear();
+        assert!(seen.is_empty());
+        assert!(!seen.contains_hash(splitmix64(0)));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::engine::evaluation::*;
+use crate::engine::search::Searcher;
+use crate::engine::transposition::TranspositionTable;
+use crate::game::{self, GameDebugger};
+use crate::piece::PieceColor;
+use crate::uhp::GameType;
+use std::collections::HashMap;
+
+/// The evaluation of every position of a game, for drawing an evaluation
+/// graph next to it.
+///
+/// Scores are cached by position, so evaluating a game again after a move
+/// was added only searches the new position. Searches share a transposition
+/// table and start from the score of the previous ply, see
+/// Searcher::analyze_near()
+#[derive(Clone, Debug)]
+pub struct EvalSeries {
+    game_type: GameType,
+    depth: usize,
+    searcher: Searcher,
+    /// Scores for white by TranspositionTable::key()
+    cache: HashMap<u64, Score>,
+}
+
+impl EvalSeries {
+    /// Evaluates games of *game_type* by searching *depth* plies
+    pub fn new(game_type: GameType, depth: usize) -> EvalSeries {
+        let mut searcher = Searcher::new(game_type);
+        searcher.set_deterministic(true);
+        searcher.set_transposition_table(Some(TranspositionTable::default()));
+        EvalSeries::with_searcher(game_type, searcher, depth)
+    }
+
+    /// Evaluates games with the settings and weights of *searcher*
+    pub fn with_searcher(game_type: GameType, searcher: Searcher, depth: usize) -> EvalSeries {
+        EvalSeries {
+            game_type,
+            depth,
+            searcher,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the score for white after every ply of the game given by the
+    /// UHP MoveStrings *moves*
+    pub fn evaluate(&mut self, moves: &[&str]) -> game::Result<Vec<Score>> {
+        let game = GameDebugger::from_move_list(moves, self.game_type)?;
+        Ok(self.evaluate_game(&game))
+    }
+
+    /// Returns the score for white after every ply of *game*
+    pub fn evaluate_game(&mut self, game: &GameDebugger) -> Vec<Score> {
+        let mut replay = GameDebugger::from_move_list(&[], game.game_type())
+            .expect("The empty game should be valid");
+        let mut scores = vec![];
+        let mut guess = 0;
+        for position in game.positions().skip(1) {
+            if replay.append_position(position).is_err() {
+                break;
+            }
+            let color = replay.player_to_move();
+            let last_move = replay.last_move();
+            let key = TranspositionTable::key(position, last_move, color);
+            let score = match self.cache.get(&key) {
+                Some(score) => *score,
+                None => {
+                    let analysis = self
+                        .searcher
+                        .analyze_near(position, last_move, color, self.depth, guess);
+                    let score = match color {
+                        PieceColor::White => analysis.score,
+                        PieceColor::Black => -analysis.score,
+                    };
+                    self.cache.insert(key, score);
+                    score
+                }
+            };
+            // The next search is from the other side
+            guess = match color {
+                PieceColor::White => -score,
+                PieceColor::Black => score,
+            };
+            scores.push(score);
+        }
+        scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_eval_series() {
+        let moves = ["wA1", "bG1 -wA1", "wQ wA1-", "bQ /bG1"];
+        let mut series = EvalSeries::new(GameType::Standard, 2);
+        let scores = series.evaluate(&moves).unwrap();
+        assert_eq!(scores.len(), 4);
+
+        // Scores are for white, whoever is to move
+        let mut searcher = Searcher::new(GameType::Standard);
+        let game = GameDebugger::from_move_list(&moves[..3], GameType::Standard).unwrap();
+        let analysis = searcher.analyze(game.position(), game.last_move(), PieceColor::Black, 2);
+        assert_eq!(scores[2], -analysis.score);
+
+        // Only the new position is searched when the game grows
+        assert_eq!(series.cache.len(), 4);
+        let longer = [&moves[..], &["wG1 wQ-"]].concat();
+        let extended = series.evaluate(&longer).unwrap();
+        assert_eq!(extended[..4], scores[..]);
+        assert_eq!(series.cache.len(), 5);
+
+        assert!(series.evaluate(&["wA1", "wA1"]).is_err());
+    }
+}
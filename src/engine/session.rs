@@ -0,0 +1,216 @@
+use crate::engine::handle::{start_search, SearchHandle, SearchLimits};
+use crate::engine::search::{Analysis, Searcher};
+use crate::game::{self, GameDebugger};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// A consistent view of an AnalysisSession at one moment, see
+/// AnalysisSession::snapshot()
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub game: Arc<GameDebugger>,
+    /// Counts the changes made to the game of the session, so that readers
+    /// can tell whether their snapshot is still current
+    pub generation: u64,
+    /// The deepest completed iteration of the search of this game and its
+    /// depth, None until the first iteration completes
+    pub analysis: Option<(usize, Analysis)>,
+}
+
+/// The state readers see, behind a single lock so that a game is never
+/// paired with the analysis of another
+#[derive(Debug)]
+struct State {
+    game: Arc<GameDebugger>,
+    generation: u64,
+    analysis: Option<(usize, Analysis)>,
+}
+
+/// A game shared between threads while an engine analyzes it, so that a GUI
+/// can render the board and the evaluation while the search runs.
+///
+/// Cloning the session gives another handle to the same game. Readers take
+/// snapshots, which stay valid however the game changes afterwards: changes
+/// replace the game instead of modifying it. Changing the game stops the
+/// search, as its results would no longer apply
+#[derive(Clone)]
+pub struct AnalysisSession {
+    state: Arc<RwLock<State>>,
+    searcher: Arc<Mutex<Searcher>>,
+    search: Arc<Mutex<Option<SearchHandle>>>,
+}
+
+impl AnalysisSession {
+    /// Shares *game*, searching it with the default weights
+    pub fn new(game: GameDebugger) -> AnalysisSession {
+        let searcher = Searcher::new(game.game_type());
+        AnalysisSession::with_searcher(game, searcher)
+    }
+
+    /// Shares *game*, searching it with copies of *searcher*
+    pub fn with_searcher(game: GameDebugger, searcher: Searcher) -> AnalysisSession {
+        AnalysisSession {
+            state: Arc::new(RwLock::new(State {
+                game: Arc::new(game),
+                generation: 0,
+                analysis: None,
+            })),
+            searcher: Arc::new(Mutex::new(searcher)),
+            search: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns the current game with its analysis so far
+    pub fn snapshot(&self) -> Snapshot {
+        let state = self.state.read().unwrap();
+        Snapshot {
+            game: state.game.clone(),
+            generation: state.generation,
+            analysis: state.analysis.clone(),
+        }
+    }
+
+    /// Returns the current game, which is not affected by later changes
+    pub fn game(&self) -> Arc<GameDebugger> {
+        self.state.read().unwrap().game.clone()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.state.read().unwrap().generation
+    }
+
+    /// Plays the UHP MoveString *move_string*, see GameDebugger::make_move()
+    pub fn play(&self, move_string: &str) -> game::Result<()> {
+        self.update(|game| game.make_move(move_string))
+    }
+
+    /// Takes back the last move, see GameDebugger::undo_move()
+    pub fn undo(&self) -> game::Result<()> {
+        self.update(|game| game.undo_move())
+    }
+
+    /// Replaces the game with *game*
+    pub fn set_game(&self, game: GameDebugger) {
+        let _ = self.update(|current| {
+            *current = game;
+            Ok(())
+        });
+    }
+
+    /// Stops the search and applies *change* to a copy of the game, which
+    /// replaces it if the change succeeds
+    fn update<F>(&self, change: F) -> game::Result<()>
+    where
+        F: FnOnce(&mut GameDebugger) -> game::Result<()>,
+    {
+        // The search reports to the state, so it must be stopped before
+        // the state is locked for writing
+        self.stop();
+        let mut state = self.state.write().unwrap();
+        let mut game = (*state.game).clone();
+        change(&mut game)?;
+        state.game = Arc::new(game);
+        state.generation += 1;
+        state.analysis = None;
+        Ok(())
+    }
+
+    /// Starts searching the current game on another thread, stopping the
+    /// search running before. Every completed iteration becomes visible to
+    /// snapshot() as soon as it finishes, see start_search()
+    pub fn start(&self, limits: SearchLimits) {
+        let mut search = self.search.lock().unwrap();
+        drop(search.take());
+        let (game, generation) = {
+            let state = self.state.read().unwrap();
+            (state.game.clone(), state.generation)
+        };
+        let searcher = self.searcher.lock().unwrap().clone();
+        let state = self.state.clone();
+        *search = Some(start_search(
+            &game,
+            searcher,
+            limits,
+            move |depth, analysis| {
+                let mut state = state.write().unwrap();
+                if state.generation == generation {
+                    state.analysis = Some((depth, analysis.clone()));
+                }
+            },
+        ));
+    }
+
+    /// Stops the search, keeping the iterations completed so far
+    pub fn stop(&self) {
+        // Dropping the handle stops the search and waits for its thread
+        let handle = self.search.lock().unwrap().take();
+        drop(handle);
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Blocks until the search finishes and returns the deepest completed
+    /// iteration of the current game, if any
+    pub fn wait(&self) -> Option<(usize, Analysis)> {
+        let handle = self.search.lock().unwrap().take();
+        if let Some(handle) = handle {
+            handle.wait();
+        }
+        self.state.read().unwrap().analysis.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_analysis_session() {
+        let game = GameDebugger::from_move_list(&["wA1", "bG1 -wA1"], GameType::Standard).unwrap();
+        let session = AnalysisSession::new(game);
+        let before = session.snapshot();
+        assert!(before.analysis.is_none());
+
+        let limits = SearchLimits {
+            depth: 2,
+            ..SearchLimits::default()
+        };
+        session.start(limits.clone());
+        // Readers on other threads see the game while the engine searches
+        let readers = (0..4)
+            .map(|_| {
+                let session = session.clone();
+                std::thread::spawn(move || session.snapshot().game.position().num_pieces())
+            })
+            .collect::<Vec<_>>();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 2);
+        }
+        let (depth, analysis) = session.wait().unwrap();
+        assert_eq!(depth, 2);
+        assert!(!session.is_searching());
+        assert_eq!(session.snapshot().analysis.unwrap().1.score, analysis.score);
+
+        // Changes leave earlier snapshots alone and discard the analysis
+        session.start(limits);
+        session.play("wQ wA1-").unwrap();
+        let after = session.snapshot();
+        assert_eq!(after.generation, before.generation + 1);
+        assert!(after.analysis.is_none());
+        assert_eq!(after.game.position().num_pieces(), 3);
+        assert_eq!(before.game.position().num_pieces(), 2);
+        assert!(session.wait().is_none());
+
+        // Failed changes keep the game
+        assert!(session.play("wQ wA1-").is_err());
+        assert_eq!(session.generation(), after.generation);
+        session.undo().unwrap();
+        assert_eq!(session.game().position(), before.game.position());
+    }
+}
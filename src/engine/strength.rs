@@ -0,0 +1,145 @@
+use crate::engine::evaluation::*;
+use crate::engine::handle::{start_search, SearchLimits};
+use crate::engine::rng::Rng;
+use crate::engine::search::{Analysis, Searcher};
+use crate::game::GameDebugger;
+use crate::hex_grid::HexGrid;
+
+/// The strongest skill level, see Strength::level()
+pub const MAX_LEVEL: u8 = 10;
+
+/// Depth, nodes and noise of each skill level, weakest first.
+///
+/// The noise is compared to the default weights, a noise of 300 lets the
+/// weakest level ignore up to three pieces of queen pressure, while the
+/// strongest levels only break near ties
+const LEVELS: [(usize, Option<usize>, Score); MAX_LEVEL as usize] = [
+    (1, None, 300),
+    (1, None, 150),
+    (2, Some(2_000), 120),
+    (2, Some(5_000), 80),
+    (2, None, 50),
+    (3, Some(20_000), 30),
+    (3, Some(50_000), 20),
+    (3, None, 10),
+    (4, Some(200_000), 5),
+    (4, None, 0),
+];
+
+/// How well the engine plays, for offering weaker opponents.
+///
+/// Weaker levels search shallower and fewer positions, and choose among
+/// the candidate moves after adding random noise to their scores, so they
+/// make mistakes of about the size of the noise
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Strength {
+    pub level: u8,
+    /// Deepest iteration searched, in plies
+    pub depth: usize,
+    /// Positions the whole search may visit, if limited
+    pub nodes: Option<usize>,
+    /// The largest random bonus added to the score of a candidate move
+    pub noise: Score,
+}
+
+impl Default for Strength {
+    fn default() -> Self {
+        Strength::level(MAX_LEVEL)
+    }
+}
+
+impl Strength {
+    /// The skill level *level* from 1 (beginner) to MAX_LEVEL (full
+    /// strength), levels outside of that range are clamped into it
+    pub fn level(level: u8) -> Strength {
+        let level = level.clamp(1, MAX_LEVEL);
+        let (depth, nodes, noise) = LEVELS[level as usize - 1];
+        Strength {
+            level,
+            depth,
+            nodes,
+            noise,
+        }
+    }
+
+    pub fn limits(&self) -> SearchLimits {
+        SearchLimits {
+            depth: self.depth,
+            time: None,
+            nodes: self.nodes,
+        }
+    }
+
+    /// Picks the candidate of *analysis* with the best score after adding a
+    /// random bonus of at most *noise* to each. Forced wins are always taken
+    /// as no bonus is larger than WIN
+    pub fn choose<'a>(&self, analysis: &'a Analysis, rng: &mut Rng) -> Option<&'a HexGrid> {
+        analysis
+            .candidates
+            .iter()
+            .max_by_key(|(_, score)| score + rng.below(self.noise as u64 + 1) as Score)
+            .map(|(position, _)| position)
+    }
+
+    /// Searches the current position of *game* within the limits of the
+    /// level and returns the position after the chosen move, None if the
+    /// game is over
+    pub fn best_move(
+        &self,
+        game: &GameDebugger,
+        searcher: Searcher,
+        rng: &mut Rng,
+    ) -> Option<HexGrid> {
+        let analysis = start_search(game, searcher, self.limits(), |_, _| {}).wait()?;
+        self.choose(&analysis, rng).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::search::SearchStats;
+    use crate::hex_grid::*;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_strength() {
+        assert_eq!(Strength::level(0), Strength::level(1));
+        assert_eq!(Strength::level(200), Strength::default());
+        assert_eq!(Strength::default().noise, 0);
+        assert!(Strength::level(1).noise > Strength::level(5).noise);
+
+        let mut analysis = Analysis {
+            score: 40,
+            best_line: vec![],
+            candidates: (0..4)
+                .map(|i| {
+                    let mut grid = HexGrid::new();
+                    grid.add(
+                        Piece::new(PieceType::Ant, PieceColor::White),
+                        HexLocation::new(i, 0),
+                    );
+                    (grid, 40 - 10 * i as Score)
+                })
+                .collect(),
+            stats: SearchStats::default(),
+        };
+        let best = &analysis.candidates[0].0;
+        let mut rng = Rng::new(0);
+
+        // Full strength always plays the best move, the weakest level does not
+        let strongest = Strength::default();
+        assert!((0..20).all(|_| strongest.choose(&analysis, &mut rng) == Some(best)));
+        let weakest = Strength::level(1);
+        assert!((0..20).any(|_| weakest.choose(&analysis, &mut rng) != Some(best)));
+
+        analysis.candidates[3].1 = WIN;
+        let win = analysis.candidates[3].0.clone();
+        assert!((0..20).all(|_| weakest.choose(&analysis, &mut rng) == Some(&win)));
+
+        let game = GameDebugger::from_move_list(&["wA1"], GameType::Standard).unwrap();
+        let searcher = Searcher::new(GameType::Standard);
+        let position = weakest.best_move(&game, searcher, &mut rng).unwrap();
+        assert_eq!(position.pieces().len(), 2);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::encoding::{self, EncodingError};
+use crate::engine::evaluation::*;
+use crate::hex_grid::HexGrid;
+use crate::location::HexLocation;
+use crate::moves::Move;
+use crate::piece::PieceColor;
+use crate::zobrist;
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// The first bytes of a saved TranspositionTable
+const MAGIC: &[u8; 4] = b"ATTB";
+
+/// The number of positions a table holds unless told otherwise,
+/// see TranspositionTable::new()
+pub const DEFAULT_CAPACITY: usize = 1 << 20;
+
+#[derive(Error, Debug)]
+pub enum TranspositionError {
+    #[error("Could not access {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Not a transposition table")]
+    NotATable,
+    #[error("{0} is not a valid bound")]
+    InvalidBound(u8),
+    #[error("Corrupt transposition table, {0}")]
+    Encoding(#[from] EncodingError),
+}
+
+pub type Result<T> = std::result::Result<T, TranspositionError>;
+
+/// How the stored score relates to the true score of a position
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is the true score at the stored depth
+    Exact,
+    /// A move reached beta, so the true score is at least the stored one
+    Lower,
+    /// No move reached alpha, so the true score is at most the stored one
+    Upper,
+}
+
+/// What a search found out about a position, see TranspositionTable
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The remaining depth the position was searched to
+    pub depth: usize,
+    /// The score for the player to move, with forced surrounds counted
+    /// in plies from this position rather than from the root of the search
+    pub score: Score,
+    pub bound: Bound,
+    /// The best move found, tried first when the position is searched again
+    pub best_move: Option<Move>,
+}
+
+impl Entry {
+    /// Stores *score*, found *ply* plies below the root of the search
+    pub fn new(
+        depth: usize,
+        score: Score,
+        ply: Score,
+        bound: Bound,
+        best_move: Option<Move>,
+    ) -> Entry {
+        let score = match forced_surround(score) {
+            Some(ForcedSurround::Surrounds(_)) => score + ply,
+            Some(ForcedSurround::Surrounded(_)) => score - ply,
+            None => score,
+        };
+        Entry {
+            depth,
+            score,
+            bound,
+            best_move,
+        }
+    }
+
+    /// The stored score as seen from *ply* plies below the root of the search
+    pub fn score_at(&self, ply: Score) -> Score {
+        match forced_surround(self.score) {
+            Some(ForcedSurround::Surrounds(_)) => self.score - ply,
+            Some(ForcedSurround::Surrounded(_)) => self.score + ply,
+            None => self.score,
+        }
+    }
+
+    /// Whether the stored score settles a search of *depth* plies with the
+    /// window *alpha* to *beta* without searching any further
+    pub fn cuts(&self, depth: usize, ply: Score, alpha: Score, beta: Score) -> bool {
+        let score = self.score_at(ply);
+        self.depth >= depth
+            && match self.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            }
+    }
+}
+
+/// The results of searches of positions, so that a position reached again
+/// (through a different order of moves, by a deeper iteration or in a later
+/// session, see save()) does not have to be searched again.
+///
+/// Positions are keyed by their Zobrist hash together with the player to
+/// move and the last move (which decides what the pillbug may throw), and
+/// different positions with the same key overwrite each other
+#[derive(Clone, Debug)]
+pub struct TranspositionTable {
+    entries: HashMap<u64, Entry>,
+    capacity: usize,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl TranspositionTable {
+    /// An empty table that stores at most *capacity* positions, after which
+    /// only positions already stored are updated
+    pub fn new(capacity: usize) -> TranspositionTable {
+        TranspositionTable {
+            entries: HashMap::new(),
+            capacity,
+        }
+    }
+
+    /// The key of *grid* with *color* to move, where *last_move* is the
+    /// destination of the piece moved in the previous turn, see
+    /// zobrist::state_key()
+    pub fn key(grid: &HexGrid, last_move: Option<HexLocation>, color: PieceColor) -> u64 {
+        zobrist::state_key(grid, last_move, color)
+    }
+
+    /// The number of positions stored
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn get(&self, key: u64) -> Option<&Entry> {
+        self.entries.get(&key)
+    }
+
+    /// Stores *entry* under *key*, unless a deeper search of the position
+    /// is already stored or the table is full
+    pub fn store(&mut self, key: u64, entry: Entry) {
+        let full = self.entries.len() >= self.capacity;
+        match self.entries.get_mut(&key) {
+            Some(stored) if stored.depth > entry.depth => {}
+            Some(stored) => *stored = entry,
+            None if full => {}
+            None => {
+                self.entries.insert(key, entry);
+            }
+        }
+    }
+
+    /// Serializes the table as the magic bytes and the number of entries
+    /// (u64, little endian), then for each entry the key (u64), depth (u32)
+    /// and score (i32, all little endian), the bound (0 exact, 1 lower,
+    /// 2 upper) and the best move (0 if there is none, otherwise 1
+    /// followed by the move, see encoding::write_move())
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = MAGIC.to_vec();
+        output.extend((self.entries.len() as u64).to_le_bytes());
+        for (key, entry) in self.entries.iter() {
+            output.extend(key.to_le_bytes());
+            output.extend((entry.depth as u32).to_le_bytes());
+            output.extend(entry.score.to_le_bytes());
+            output.push(match entry.bound {
+                Bound::Exact => 0,
+                Bound::Lower => 1,
+                Bound::Upper => 2,
+            });
+            match entry.best_move {
+                Some(mv) => {
+                    output.push(1);
+                    encoding::write_move(&mv, &mut output);
+                }
+                None => output.push(0),
+            }
+        }
+        output
+    }
+
+    /// Reads a table made by to_bytes(), which holds at most *capacity*
+    /// positions from then on
+    pub fn from_bytes(bytes: &[u8], capacity: usize) -> Result<TranspositionTable> {
+        let Some(bytes) = bytes.strip_prefix(MAGIC.as_slice()) else {
+            return Err(TranspositionError::NotATable);
+        };
+        let mut offset = 0;
+        let mut table = TranspositionTable::new(capacity);
+        let length = u64::from_le_bytes(read(bytes, &mut offset)?);
+        for _ in 0..length {
+            let key = u64::from_le_bytes(read(bytes, &mut offset)?);
+            let depth = u32::from_le_bytes(read(bytes, &mut offset)?) as usize;
+            let score = Score::from_le_bytes(read(bytes, &mut offset)?);
+            let bound = match read(bytes, &mut offset)? {
+                [0] => Bound::Exact,
+                [1] => Bound::Lower,
+                [2] => Bound::Upper,
+                [other] => return Err(TranspositionError::InvalidBound(other)),
+            };
+            let best_move = match read(bytes, &mut offset)? {
+                [0] => None,
+                _ => {
+                    let (mv, length) = encoding::read_move(&bytes[offset..])?;
+                    offset += length;
+                    Some(mv)
+                }
+            };
+            let entry = Entry {
+                depth,
+                score,
+                bound,
+                best_move,
+            };
+            table.store(key, entry);
+        }
+
+        match bytes.len() - offset {
+            0 => Ok(table),
+            left_over => Err(EncodingError::TrailingBytes(left_over).into()),
+        }
+    }
+
+    /// Writes the table to *path*, see to_bytes()
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| TranspositionError::Io(path.display().to_string(), e))
+    }
+
+    /// Reads a table written by save(), see from_bytes()
+    pub fn load(path: &Path, capacity: usize) -> Result<TranspositionTable> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| TranspositionError::Io(path.display().to_string(), e))?;
+        TranspositionTable::from_bytes(&bytes, capacity)
+    }
+}
+
+/// Reads the next N bytes at *offset*, moving the offset past them
+fn read<const N: usize>(bytes: &[u8], offset: &mut usize) -> Result<[u8; N]> {
+    let end = *offset + N;
+    let value = bytes.get(*offset..end).ok_or(EncodingError::Truncated)?;
+    *offset = end;
+    Ok(value.try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::search::Searcher;
+    use crate::hex_grid::*;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_transposition_table() {
+        use PieceColor::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . . g q .\n",
+            ". . G Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let key = TranspositionTable::key(&grid, None, White);
+        assert_ne!(key, TranspositionTable::key(&grid, None, Black));
+        assert_ne!(
+            key,
+            TranspositionTable::key(&grid, Some(HexLocation::new(0, 0)), White)
+        );
+
+        // Forced surrounds are stored relative to the position
+        let entry = Entry::new(2, win_in(5), 3, Bound::Exact, None);
+        assert_eq!((entry.score, entry.score_at(1)), (win_in(2), win_in(3)));
+        assert!(entry.cuts(2, 3, 0, 1) && !entry.cuts(3, 3, 0, 1));
+        let lower = Entry::new(2, 10, 0, Bound::Lower, None);
+        assert!(lower.cuts(1, 0, 0, 10) && !lower.cuts(1, 0, 0, 11));
+
+        let mut table = TranspositionTable::new(1);
+        table.store(1, entry);
+        table.store(1, lower);
+        assert_eq!(table.get(1), Some(&lower));
+        table.store(1, Entry::new(1, 0, 0, Bound::Exact, None));
+        assert_eq!(table.get(1), Some(&lower));
+        table.store(2, entry);
+        assert_eq!((table.len(), table.get(2)), (1, None));
+
+        // A search with a table filled by an earlier search visits fewer
+        // positions for the same result
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_deterministic(true);
+        let plain = searcher.analyze(&grid, None, White, 3);
+        searcher.set_transposition_table(Some(TranspositionTable::default()));
+        let first = searcher.analyze(&grid, None, White, 3);
+        assert_eq!(first.score, plain.score);
+        assert!(first.stats.nodes <= plain.stats.nodes);
+
+        let table = searcher.take_transposition_table().unwrap();
+        assert!(!table.is_empty());
+        let loaded = TranspositionTable::from_bytes(&table.to_bytes(), DEFAULT_CAPACITY).unwrap();
+        assert_eq!(loaded.entries, table.entries);
+        searcher.set_transposition_table(Some(loaded));
+        let second = searcher.analyze(&grid, None, White, 3);
+        assert_eq!(second.score, plain.score);
+        assert!(second.stats.nodes < first.stats.nodes);
+        assert!(second.stats.tt_hits > first.stats.tt_hits);
+
+        let bytes = table.to_bytes();
+        assert!(matches!(
+            TranspositionTable::from_bytes(&bytes[..bytes.len() - 1], 10),
+            Err(TranspositionError::Encoding(EncodingError::Truncated))
+        ));
+        assert!(matches!(
+            TranspositionTable::from_bytes(b"nope", 10),
+            Err(TranspositionError::NotATable)
+        ));
+    }
+}
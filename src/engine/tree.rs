@@ -0,0 +1,272 @@
+use crate::engine::evaluation::Score;
+use crate::moves::Move;
+
+/// Why a position was not searched in full, see TreeNode
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pruning {
+    /// The remaining moves were skipped once one of them reached beta
+    Cutoff,
+    /// Letting the opponent move twice still reached beta,
+    /// see Searcher::set_null_move()
+    NullMove,
+    /// Searched a ply shallower and not searched again,
+    /// see Searcher::set_late_move_reductions()
+    Reduced,
+    /// Searched again with a full depth or window after the reduced
+    /// depth or aspiration window turned out to be too narrow
+    ReSearched,
+    /// The search was asked to stop or ran out of time or nodes
+    Stopped,
+    /// An earlier search of the same position settled its score,
+    /// see Searcher::set_transposition_table()
+    Transposition,
+}
+
+impl Pruning {
+    pub fn name(&self) -> &str {
+        match self {
+            Pruning::Cutoff => "cutoff",
+            Pruning::NullMove => "null move",
+            Pruning::Reduced => "reduced",
+            Pruning::ReSearched => "re-searched",
+            Pruning::Stopped => "stopped",
+            Pruning::Transposition => "transposition",
+        }
+    }
+}
+
+/// A position visited by the search, see SearchTree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeNode {
+    /// The index of the position searched before, None for the root
+    pub parent: Option<usize>,
+    /// The move played from the parent, None for the root and for the
+    /// extra opponent move of null-move pruning
+    pub mv: Option<Move>,
+    /// Remaining depth in plies
+    pub depth: usize,
+    pub alpha: Score,
+    pub beta: Score,
+    /// The score for the player to move, None if the search of this
+    /// position never returned
+    pub score: Option<Score>,
+    pub pruning: Option<Pruning>,
+}
+
+impl TreeNode {
+    /// A short description of the move leading to this position
+    pub fn label(&self) -> String {
+        match (self.parent, self.mv) {
+            (None, _) => "root".to_string(),
+            (Some(_), None) => "null move".to_string(),
+            (Some(_), Some(Move::Pass)) => "pass".to_string(),
+            (Some(_), Some(Move::Place { piece, to })) => {
+                format!("{} @ {},{}", piece.to_str(), to.x, to.y)
+            }
+            (Some(_), Some(Move::Movement { piece, from, to })) => {
+                format!(
+                    "{} {},{} -> {},{}",
+                    piece.to_str(),
+                    from.x,
+                    from.y,
+                    to.x,
+                    to.y
+                )
+            }
+        }
+    }
+}
+
+/// The positions visited by a search in the order they were visited, up to
+/// a limit, for inspecting why a move was chosen.
+///
+/// See Searcher::set_tree_limit() to record one, and to_dot() and to_json()
+/// to export it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchTree {
+    nodes: Vec<TreeNode>,
+    limit: usize,
+    truncated: bool,
+    /// The positions from the root to the one being searched, None for
+    /// those past the limit
+    path: Vec<Option<usize>>,
+    /// The position whose search returned most recently
+    last: Option<usize>,
+}
+
+impl SearchTree {
+    /// An empty tree that records at most *limit* positions
+    pub fn new(limit: usize) -> SearchTree {
+        SearchTree {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    pub fn nodes(&self) -> &[TreeNode] {
+        &self.nodes
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Whether the search visited more positions than the limit
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Records the start of the search of a position reached by *mv*
+    /// from the position being searched
+    pub(crate) fn enter(&mut self, mv: Option<Move>, depth: usize, alpha: Score, beta: Score) {
+        let parent = self.path.last().copied();
+        // Positions below one past the limit are dropped with it
+        if self.nodes.len() >= self.limit || parent == Some(None) {
+            self.truncated = true;
+            self.path.push(None);
+            return;
+        }
+        self.nodes.push(TreeNode {
+            parent: parent.flatten(),
+            mv,
+            depth,
+            alpha,
+            beta,
+            score: None,
+            pruning: None,
+        });
+        self.path.push(Some(self.nodes.len() - 1));
+    }
+
+    /// Records the score of the position entered last
+    pub(crate) fn exit(&mut self, score: Score) {
+        self.last = self.path.pop().flatten();
+        if let Some(index) = self.last {
+            self.nodes[index].score = Some(score);
+        }
+    }
+
+    /// Records why the position being searched was not searched in full
+    pub(crate) fn prune(&mut self, pruning: Pruning) {
+        if let Some(Some(index)) = self.path.last() {
+            self.nodes[*index].pruning = Some(pruning);
+        }
+    }
+
+    /// Records why the position whose search returned last was not
+    /// searched in full
+    pub(crate) fn prune_last(&mut self, pruning: Pruning) {
+        if let Some(index) = self.last {
+            self.nodes[index].pruning = Some(pruning);
+        }
+    }
+
+    /// Writes the tree in the Graphviz DOT language, with the move, score,
+    /// window and pruning of every position
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph search {\n    node [shape=box];\n".to_string();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let score = node
+                .score
+                .map_or("?".to_string(), |score| score.to_string());
+            let mut label = format!(
+                "{}\\nscore {} depth {}\\n[{}, {}]",
+                node.label(),
+                score,
+                node.depth,
+                node.alpha,
+                node.beta
+            );
+            if let Some(pruning) = node.pruning {
+                label += &format!("\\n{}", pruning.name());
+            }
+            dot += &format!("    n{} [label=\"{}\"];\n", index, label);
+            if let Some(parent) = node.parent {
+                dot += &format!("    n{} -> n{};\n", parent, index);
+            }
+        }
+        dot + "}\n"
+    }
+
+    /// Writes the tree as a JSON object with a list of "nodes", each with the
+    /// index of its "parent", see TreeNode
+    pub fn to_json(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or("null".to_string());
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "{{\"parent\": {}, \"move\": \"{}\", \"depth\": {}, \"alpha\": {}, \"beta\": {}, \"score\": {}, \"pruning\": {}}}",
+                    optional(node.parent.map(|parent| parent.to_string())),
+                    node.label(),
+                    node.depth,
+                    node.alpha,
+                    node.beta,
+                    optional(node.score.map(|score| score.to_string())),
+                    optional(node.pruning.map(|pruning| format!("\"{}\"", pruning.name()))),
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"truncated\": {}, \"nodes\": [\n{}\n]}}\n",
+            self.truncated,
+            nodes.join(",\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::search::Searcher;
+    use crate::hex_grid::*;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_search_tree() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . . g q .\n",
+            ". . G Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let mut searcher = Searcher::new(GameType::Standard);
+        searcher.set_deterministic(true);
+        searcher.analyze(&grid, None, PieceColor::White, 2);
+        assert!(searcher.tree().is_none());
+
+        searcher.set_tree_limit(Some(usize::MAX));
+        let analysis = searcher.analyze(&grid, None, PieceColor::White, 2);
+        let tree = searcher.tree().unwrap();
+        assert_eq!(tree.nodes().len(), analysis.stats.nodes);
+        assert!(!tree.is_truncated());
+        let root = &tree.nodes()[0];
+        assert_eq!((root.parent, root.score), (None, Some(analysis.score)));
+        assert!(tree.nodes().iter().all(|node| node.score.is_some()));
+        let cutoffs = tree
+            .nodes()
+            .iter()
+            .filter(|node| node.pruning == Some(Pruning::Cutoff));
+        assert_eq!(cutoffs.count(), analysis.stats.cutoffs);
+
+        // Every position is listed after the position it was reached from
+        searcher.set_tree_limit(Some(10));
+        searcher.analyze(&grid, None, PieceColor::White, 2);
+        let tree = searcher.tree().unwrap();
+        assert_eq!(tree.nodes().len(), 10);
+        assert!(tree.is_truncated());
+        assert!(tree.nodes()[1..]
+            .iter()
+            .enumerate()
+            .all(|(index, node)| node.parent.is_some_and(|parent| parent <= index)));
+
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph search {"));
+        assert_eq!(dot.matches(" -> n").count(), 9);
+        let json = tree.to_json();
+        assert!(json.starts_with("{\"truncated\": true, \"nodes\": ["));
+        assert_eq!(json.matches("\"parent\": null").count(), 1);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::data_analysis::uhp_strings;
+use crate::engine::evaluation::*;
+use crate::engine::profile::Profile;
+use crate::game::board_result;
+use crate::game::parse_game_string;
+use crate::hex_grid::*;
+use crate::replay::Replay;
+use std::path::Path;
+
+/// The score at which a position is predicted to be won about 90% of the
+/// time, see win_probability()
+const SCALE: f64 = 400.0;
+
+/// A position labeled with the outcome of the game it was played in
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub position: HexGrid,
+    /// The player the outcome is given for
+    pub color: PieceColor,
+    /// 1 if *color* won, 0.5 for a draw and 0 if *color* lost
+    pub outcome: f64,
+}
+
+impl Sample {
+    /// Labels every undecided position of a finished UHP GameString with
+    /// the result of the game, from the point of view of white.
+    ///
+    /// Unfinished or unreadable games give no samples
+    pub fn from_game_string(game_string: &str) -> Vec<Sample> {
+        let outcome = match game_string.split(';').nth(1) {
+            Some("WhiteWins") => 1.0,
+            Some("BlackWins") => 0.0,
+            Some("Draw") => 0.5,
+            _ => return vec![],
+        };
+        let Some((_, moves)) = parse_game_string(game_string) else {
+            return vec![];
+        };
+
+        let mut samples = vec![];
+        for position in Replay::new(&moves) {
+            if board_result(&position).is_some() {
+                continue;
+            }
+            samples.push(Sample {
+                position,
+                color: PieceColor::White,
+                outcome,
+            });
+        }
+        samples
+    }
+}
+
+/// Fits the evaluation weights to the outcomes of labeled positions
+/// (Texel's tuning method), by minimizing the squared difference between
+/// each outcome and the win probability predicted from the evaluation.
+///
+/// The features of every position are computed once up front, as
+/// evaluate() is linear in the weights
+#[derive(Clone, Debug)]
+pub struct Tuner {
+    samples: Vec<([Score; NUM_FEATURES], f64)>,
+}
+
+impl Tuner {
+    pub fn new(samples: &[Sample]) -> Tuner {
+        let samples = samples
+            .iter()
+            .map(|sample| (features(&sample.position, sample.color), sample.outcome))
+            .collect();
+        Tuner { samples }
+    }
+
+    /// The number of positions tuned against
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The mean squared error of the predictions made with *weights*
+    pub fn error(&self, weights: &Weights) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let weights = weights.to_array();
+        let total: f64 = self
+            .samples
+            .iter()
+            .map(|(features, outcome)| {
+                let score = weights.iter().zip(features).map(|(w, f)| w * f).sum();
+                (outcome - win_probability(score)).powi(2)
+            })
+            .sum();
+        total / self.samples.len() as f64
+    }
+
+    /// Improves *weights* by nudging one weight at a time for as long as that
+    /// lowers the error, starting with large steps and refining them, for at
+    /// most *max_rounds* passes over the weights
+    pub fn tune(&self, weights: &Weights, max_rounds: usize) -> Weights {
+        let mut best = weights.to_array();
+        let mut best_error = self.error(weights);
+        let mut step = 16;
+
+        for _ in 0..max_rounds {
+            let mut improved = false;
+            for index in 0..NUM_FEATURES {
+                for delta in [step, -step] {
+                    let mut candidate = best;
+                    candidate[index] += delta;
+                    let error = self.error(&Weights::from_array(candidate));
+                    if error < best_error {
+                        (best, best_error) = (candidate, error);
+                        improved = true;
+                        break;
+                    }
+                }
+            }
+
+            if !improved {
+                if step == 1 {
+                    break;
+                }
+                step /= 2;
+            }
+        }
+
+        Weights::from_array(best)
+    }
+}
+
+/// The expected outcome of a position with the given *score*
+fn win_probability(score: Score) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-score as f64 / SCALE))
+}
+
+/// Tunes the default weights against the first *games* recorded games and
+/// writes the result to *output* as a profile named "tuned", see
+/// Profile::load()
+pub fn print_tuning(games: usize, max_rounds: usize, output: &Path) {
+    let samples = uhp_strings()
+        .into_iter()
+        .take(games)
+        .flat_map(Sample::from_game_string)
+        .collect::<Vec<_>>();
+    let tuner = Tuner::new(&samples);
+    let start = Weights::default();
+    let tuned = tuner.tune(&start, max_rounds);
+
+    println!("Positions: {}", tuner.len());
+    println!(
+        "Error: {:.6} -> {:.6}",
+        tuner.error(&start),
+        tuner.error(&tuned)
+    );
+    let profile = Profile::new("tuned", tuned);
+    print!("{}", profile.to_toml());
+    match profile.save(output) {
+        Ok(()) => println!("Written to {}", output.display()),
+        Err(e) => println!("{}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_tune() {
+        // White won after pressuring the black queen
+        let white_wins = r"Base+PL;WhiteWins;Black[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/;wB1 \bL";
+        let samples = Sample::from_game_string(white_wins);
+        assert!(!samples.is_empty());
+        assert!(samples.iter().all(|sample| sample.outcome == 1.0));
+        assert!(Sample::from_game_string(r"Base;InProgress;Black[1];wS1").is_empty());
+
+        let tuner = Tuner::new(&samples);
+        let start = Weights {
+            queen_pressure: 0,
+            immobile: 0,
+        };
+        let tuned = tuner.tune(&start, 20);
+        assert!(tuner.error(&tuned) < tuner.error(&start));
+        assert!(tuned.queen_pressure > 0);
+        assert_eq!(Tuner::new(&[]).error(&start), 0.0);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::game::GameDebugger;
+use crate::hex_grid::HexGrid;
+use crate::moves::Move;
+use crate::position_db::{PositionDb, PositionStats};
+
+/// A legal move from the explored position that leads to a position
+/// found in the database, see explore()
+#[derive(Clone, Debug, PartialEq)]
+pub struct Continuation {
+    pub mv: Move,
+    /// The UHP MoveString of the move
+    pub move_string: String,
+    /// The position after the move
+    pub position: HexGrid,
+    /// The statistics of the position after the move, which include every
+    /// game that reached it, also through other move orders
+    pub stats: PositionStats,
+}
+
+/// Returns the continuations of the current position of *game* seen in the
+/// games of *db* (see PositionDb::import_game()), most visited first and
+/// with ties broken by the MoveString
+pub fn explore(db: &PositionDb, game: &mut GameDebugger) -> Vec<Continuation> {
+    let current = game.position().clone();
+    let opponent = game.player_to_move().opposite();
+    let mut continuations = game
+        .legal_positions()
+        .into_iter()
+        .filter_map(|position| {
+            let stats = db.get(&position, opponent)?;
+            let mv = current.diff(&position)?;
+            let move_string = game.move_string(&position).ok()?;
+            Some(Continuation {
+                mv,
+                move_string,
+                position,
+                stats,
+            })
+        })
+        .collect::<Vec<_>>();
+    continuations.sort_by(|a, b| {
+        b.stats
+            .visits
+            .cmp(&a.stats.visits)
+            .then_with(|| a.move_string.cmp(&b.move_string))
+    });
+    continuations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameResult;
+    use crate::piece::PieceColor;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_explore() {
+        let games = [
+            vec!["wG1", "bG1 wG1-", "wQ -wG1"],
+            vec!["wG1", "bG1 wG1-", "wQ \\wG1"],
+            vec!["wS1", "bS1 wS1-"],
+            vec!["wG1", "bA1 wG1-"],
+        ];
+        let mut db = PositionDb::new();
+        for moves in games.iter() {
+            let game = GameDebugger::from_move_list(moves, GameType::MLP).unwrap();
+            db.import_game(&game);
+        }
+
+        let mut game = GameDebugger::from_move_list(&[], GameType::MLP).unwrap();
+        let first = explore(&db, &mut game);
+        let first_moves = first
+            .iter()
+            .map(|continuation| continuation.move_string.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(first_moves, vec!["wG1", "wS1"]);
+        assert_eq!(first[0].stats.visits, 3);
+        assert_eq!(first[0].mv.apply(game.position()), first[0].position);
+
+        // Placing the queen on either diagonal gives the same position up
+        // to reflection, so both are reported from the single game
+        game.make_move("wG1").unwrap();
+        game.make_move("bG1 wG1-").unwrap();
+        let second = explore(&db, &mut game);
+        let second_moves = second
+            .iter()
+            .map(|continuation| continuation.move_string.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(second_moves, vec!["wQ -wG1", "wQ /wG1", r"wQ \wG1"]);
+        for continuation in second.iter() {
+            assert_eq!(continuation.stats.visits, 1);
+            assert_eq!(continuation.stats.score(PieceColor::White), None);
+        }
+
+        let mut finished = db.clone();
+        let mut won = GameDebugger::from_move_list(&["wS1", "bS1 wS1-"], GameType::MLP).unwrap();
+        finished.record(
+            won.position(),
+            PieceColor::White,
+            Some(GameResult::WhiteWins),
+        );
+        won.undo_move().unwrap();
+        let stats = explore(&finished, &mut won)[0].stats;
+        assert_eq!((stats.visits, stats.white_wins), (2, 1));
+    }
+}
@@ -1,14 +1,31 @@
+use crate::board_view::RenderOptions;
+#[cfg(feature = "engine")]
+use crate::engine::hint::{self, Hint};
+#[cfg(feature = "engine")]
+use crate::engine::search::Searcher;
 use crate::generator::debug::*;
 use crate::hex_grid::*;
+use crate::inventory::Inventory;
+use crate::moves::Move;
 use crate::piece::*;
+use crate::rules::{self, IllegalMoveReason};
 use crate::uhp::*;
+use crate::zobrist;
 use std::collections::HashSet;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum GameDebuggerError {
     #[error("Positions could not convert to legal annotations, {0}")]
     AnnotationError(UHPError),
+    #[error("Could not parse GameString: {0}")]
+    GameStringError(String),
+    #[error("Illegal move: {0}")]
+    IllegalMove(IllegalMoveReason),
+    #[error("{0:?} is not possible: {1}")]
+    InvalidAction(GameAction, &'static str),
 }
 
 pub type Result<T> = std::result::Result<T, GameDebuggerError>;
@@ -29,13 +46,104 @@ pub struct GameDebugger {
     annotations: Vec<Annotator>,
     generator: ReferenceGenerator,
     game_type: GameType,
+    tournament_opening: bool,
+    observers: Observers,
+    /// The result decided by the players rather than the board, see act()
+    ending: Option<GameResult>,
+    /// The player whose draw offer stands, see act()
+    draw_offer: Option<PieceColor>,
+}
+
+/// What a player may do besides moving, see GameDebugger::act()
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GameAction {
+    /// Gives up the game, which the opponent wins
+    Resign,
+    /// Proposes a draw, which stands until the opponent accepts it or moves
+    OfferDraw,
+    /// Accepts the draw offered by the opponent, ending the game
+    AcceptDraw,
+}
+
+/// A change to a GameDebugger reported to its observers, see
+/// GameDebugger::subscribe()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GameEvent {
+    /// A legal move was played, leading to *position*
+    MoveApplied {
+        move_string: String,
+        position: HexGrid,
+    },
+    /// The last move was taken back, returning to *position*
+    Undone { position: HexGrid },
+    /// The player of *color* took *action*, see GameDebugger::act()
+    Action {
+        color: PieceColor,
+        action: GameAction,
+    },
+    /// The last move or action ended the game, sent after its MoveApplied
+    /// or Action
+    GameOver(GameResult),
+    /// The time left to the player of *color*, see GameDebugger::update_clock()
+    Clock {
+        color: PieceColor,
+        remaining: Duration,
+    },
+}
+
+type Observer = Arc<dyn Fn(&GameEvent) + Send + Sync>;
+
+/// The callbacks subscribed to a GameDebugger, shared by its clones
+#[derive(Clone, Default)]
+struct Observers(Vec<Observer>);
+
+impl std::fmt::Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum GameResult {
     WhiteWins,
     BlackWins,
+    /// The same position occurred for the third time
     Draw,
+    /// Both queens were surrounded by the same move
+    BothSurrounded,
+    /// Both players passed in succession, so neither can ever move again
+    Stalemate,
+    /// The player of the color gave up
+    Resigned(PieceColor),
+    /// One player offered a draw and the other accepted it
+    DrawAgreed,
+}
+
+impl GameResult {
+    /// Returns the color of the winning player, if any
+    pub fn winner(&self) -> Option<PieceColor> {
+        match self {
+            GameResult::WhiteWins => Some(PieceColor::White),
+            GameResult::BlackWins => Some(PieceColor::Black),
+            GameResult::Resigned(color) => Some(color.opposite()),
+            _ => None,
+        }
+    }
+
+    /// Returns true for every way a game can end without a winner
+    pub fn is_draw(&self) -> bool {
+        self.winner().is_none()
+    }
+
+    /// The UHP GameStateString of a game ended with this result, which has
+    /// a single state for every kind of draw
+    pub fn game_state(&self) -> &'static str {
+        match self.winner() {
+            Some(PieceColor::White) => "WhiteWins",
+            Some(PieceColor::Black) => "BlackWins",
+            None => "Draw",
+        }
+    }
 }
 
 impl GameDebugger {
@@ -58,6 +166,10 @@ impl GameDebugger {
             annotations,
             generator: ReferenceGenerator::new(game_type),
             game_type,
+            tournament_opening: true,
+            observers: Observers::default(),
+            ending: None,
+            draw_offer: None,
         };
 
         for mv in moves.iter() {
@@ -66,6 +178,32 @@ impl GameDebugger {
         Ok(game)
     }
 
+    /// Like from_moves_custom() but for borrowed MoveStrings, which may also be
+    /// written in standard notation (e.g. wQ1). Each move is checked to be
+    /// legal and the first that is not is reported as an error
+    pub fn from_move_list(moves: &[&str], game_type: GameType) -> Result<Self> {
+        let mut game = GameDebugger::from_moves_custom(&[], game_type)?;
+        for mv in moves.iter() {
+            game.make_move(mv.trim())?;
+        }
+        Ok(game)
+    }
+
+    /// Creates a GameDebugger from a UHP GameString, or from a GameTypeString
+    /// for a game that has not started yet
+    pub fn from_game_string(input: &str) -> Result<Self> {
+        let input = input.trim();
+        if let Ok(game_type) = GameType::try_from(input) {
+            return GameDebugger::from_moves_custom(&[], game_type);
+        }
+        let (game_type, moves) = parse_game_string(input)
+            .ok_or_else(|| GameDebuggerError::GameStringError(input.to_string()))?;
+        let mut game = GameDebugger::from_moves_custom(&moves, game_type)?;
+        let state = input.split(';').nth(1).unwrap_or_default().trim();
+        game.apply_game_state(state)?;
+        Ok(game)
+    }
+
     /// Given all positions arrived at within the game create
     /// and return a GameDebugger with the positions accounted for.
     /// assumes Base+MLP
@@ -83,6 +221,10 @@ impl GameDebugger {
             annotations,
             generator: ReferenceGenerator::new(game_type),
             game_type,
+            tournament_opening: true,
+            observers: Observers::default(),
+            ending: None,
+            draw_offer: None,
         };
 
         // Must begin with the empty board
@@ -96,29 +238,170 @@ impl GameDebugger {
         Ok(game)
     }
 
-    /// Undoes the last move made in the game if possible
+    /// Undoes the last move made in the game if possible. A resignation or
+    /// an agreed draw is taken back before the move that preceded it, and
+    /// draw offers are withdrawn
     pub fn undo_move(&mut self) -> Result<()> {
+        self.draw_offer = None;
+        if self.ending.take().is_some() {
+            self.notify(|| GameEvent::Undone {
+                position: self.position().clone(),
+            });
+            return Ok(());
+        }
         if self.annotations.len() == 1 {
             return Err(GameDebuggerError::AnnotationError(UHPError::UndoError));
         }
         self.annotations.pop();
-        let last_move = self.annotations.last().unwrap().last_move();
+        self.update_generator();
+        self.notify(|| GameEvent::Undone {
+            position: self.position().clone(),
+        });
+        Ok(())
+    }
+
+    /// Calls *observer* with every later change to the game: moves, undos,
+    /// the end of the game and clock updates. Observers are kept by clones
+    /// of the game, and are called on the thread making the change
+    pub fn subscribe<F>(&mut self, observer: F)
+    where
+        F: Fn(&GameEvent) + Send + Sync + 'static,
+    {
+        self.observers.0.push(Arc::new(observer));
+    }
+
+    /// Like subscribe(), but sends the changes to the returned channel,
+    /// which stops receiving them once it is dropped
+    pub fn events(&mut self) -> mpsc::Receiver<GameEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribe(move |event| {
+            let _ = sender.send(event.clone());
+        });
+        receiver
+    }
+
+    /// Removes every observer of the game
+    pub fn unsubscribe_all(&mut self) {
+        self.observers.0.clear();
+    }
+
+    /// Makes the player of *color* take *action*, at any time before the
+    /// game is over.
+    ///
+    /// A draw offer stands until the opponent accepts it or moves, and only
+    /// the opponent of the player who offered it may accept it
+    pub fn act(&mut self, color: PieceColor, action: GameAction) -> Result<()> {
+        let invalid = |reason| Err(GameDebuggerError::InvalidAction(action, reason));
+        if self.game_result().is_some() {
+            return invalid("the game is over");
+        }
+        match action {
+            GameAction::Resign => self.ending = Some(GameResult::Resigned(color)),
+            GameAction::OfferDraw => self.draw_offer = Some(color),
+            GameAction::AcceptDraw => match self.draw_offer {
+                Some(offered) if offered != color => self.ending = Some(GameResult::DrawAgreed),
+                Some(_) => return invalid("a player cannot accept their own draw offer"),
+                None => return invalid("no draw was offered"),
+            },
+        }
+        self.notify(|| GameEvent::Action { color, action });
+        if let Some(result) = self.ending.clone() {
+            self.draw_offer = None;
+            self.notify(|| GameEvent::GameOver(result));
+        }
+        Ok(())
+    }
+
+    /// The player whose draw offer stands, if any, see act()
+    pub fn draw_offer(&self) -> Option<PieceColor> {
+        self.draw_offer
+    }
+
+    /// Ends the game as stated by the GameStateString *state* of a GameString
+    /// when the board does not decide it: a win by the resignation of the
+    /// loser, or an agreed draw. Other states are left to the board
+    pub(crate) fn apply_game_state(&mut self, state: &str) -> Result<()> {
+        if self.game_result().is_some() {
+            return Ok(());
+        }
+        let player = self.player_to_move();
+        match state {
+            "WhiteWins" => self.act(PieceColor::Black, GameAction::Resign),
+            "BlackWins" => self.act(PieceColor::White, GameAction::Resign),
+            "Draw" => {
+                self.act(player, GameAction::OfferDraw)?;
+                self.act(player.opposite(), GameAction::AcceptDraw)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports that the player of *color* has *remaining* time left. The
+    /// game has no clock of its own, this is for whoever runs one to keep
+    /// the observers of the game informed
+    pub fn update_clock(&self, color: PieceColor, remaining: Duration) {
+        self.notify(|| GameEvent::Clock { color, remaining });
+    }
+
+    /// Sends the event made by *event* to every observer, making it only if
+    /// there are any
+    fn notify<F: FnOnce() -> GameEvent>(&self, event: F) {
+        if self.observers.0.is_empty() {
+            return;
+        }
+        let event = event();
+        for observer in self.observers.0.iter() {
+            observer(&event);
+        }
+    }
+
+    /// Rebuilds the generator for the latest position in the game
+    fn update_generator(&mut self) {
+        let annotator = self.annotations.last().unwrap();
         self.generator = ReferenceGenerator::from_hex_grid(
-            self.annotations.last().unwrap().position(),
+            annotator.position(),
             self.game_type,
-            last_move,
+            annotator.last_move(),
         );
-        Ok(())
+        self.generator
+            .set_tournament_opening(self.tournament_opening);
+    }
+
+    /// Enables or disables the tournament opening rule, which forbids
+    /// placing the queen on a player's first turn. Enabled by default.
+    pub fn set_tournament_opening(&mut self, enabled: bool) {
+        self.tournament_opening = enabled;
+        self.generator.set_tournament_opening(enabled);
+    }
+
+    pub fn tournament_opening(&self) -> bool {
+        self.tournament_opening
+    }
+
+    pub fn game_type(&self) -> GameType {
+        self.game_type
     }
 
-    /// Makes a legal UHP move from the UHP-compatible string passed in
+    /// Makes a legal UHP move from the UHP-compatible string passed in,
+    /// failing with the rule it breaks if the move is not legal
     pub fn make_move(&mut self, move_string: &str) -> Result<()> {
         let mut annotator = self.annotations.last().unwrap().clone();
         annotator = annotator
             .next_uhp_move(move_string)
             .map_err(GameDebuggerError::AnnotationError)?;
 
-        self.append_position(annotator.position())
+        match self.append_position(annotator.position()) {
+            Err(GameDebuggerError::AnnotationError(UHPError::IllegalMove { info })) => {
+                let mv = self.position().diff(annotator.position());
+                match mv.map(|mv| self.check_move(&mv)) {
+                    Some(Err(reason)) => Err(GameDebuggerError::IllegalMove(reason)),
+                    _ => Err(GameDebuggerError::AnnotationError(UHPError::IllegalMove {
+                        info,
+                    })),
+                }
+            }
+            result => result,
+        }
     }
 
     pub fn player_to_move(&self) -> PieceColor {
@@ -148,22 +431,34 @@ impl GameDebugger {
             .next_state(&grid)
             .map_err(GameDebuggerError::AnnotationError)?;
 
-        self.generator = ReferenceGenerator::from_hex_grid(
-            annotator.position(),
-            self.game_type,
-            annotator.last_move(),
-        );
+        let move_string = match self.observers.0.is_empty() {
+            true => String::new(),
+            false => self.move_string(&grid)?,
+        };
+        // Moving declines the opponent's draw offer
+        if self.draw_offer != Some(self.player_to_move()) {
+            self.draw_offer = None;
+        }
         self.annotations.push(annotator);
+        self.update_generator();
+
+        self.notify(|| GameEvent::MoveApplied {
+            move_string,
+            position: grid,
+        });
+        if let Some(result) = self.game_result() {
+            self.notify(|| GameEvent::GameOver(result));
+        }
 
         Ok(())
     }
 
-    /// Returns a set of legal positions that can be arrived at
-    /// from the current position
-    pub fn legal_positions(&mut self) -> HashSet<HexGrid> {
+    /// Returns the legal positions that can be arrived at from the current
+    /// position, in the order of PositionGenerator::generate_positions_for()
+    pub fn legal_positions(&mut self) -> Vec<HexGrid> {
         // If the game is over, no legal moves
         match self.game_result() {
-            Some(_) => HashSet::new(),
+            Some(_) => vec![],
             _ => self.generator.generate_positions_for(self.player_to_move()),
         }
     }
@@ -171,46 +466,242 @@ impl GameDebugger {
     /// If the game is over, returns the result of the game.
     /// Otherwise, returns None.
     pub fn game_result(&self) -> Option<GameResult> {
-        use PieceColor::*;
-        use PieceType::*;
+        if let Some(ending) = &self.ending {
+            return Some(ending.clone());
+        }
         let annotator = self.annotations.last().unwrap();
         let grid = annotator.position();
 
-        let white_queen = grid.find(Piece::new(Queen, White));
-        let black_queen = grid.find(Piece::new(Queen, Black));
-
-        let queen_surrounded = |(queen_loc, _): (HexLocation, _)| -> bool {
-            let neighbors = grid.get_neighbors(queen_loc);
-            neighbors.len() == 6
-        };
-
-        let white_queen_surrounded = white_queen.map(queen_surrounded).unwrap_or(false);
-        let black_queen_surrounded = black_queen.map(queen_surrounded).unwrap_or(false);
+        if let Some(result) = board_result(grid) {
+            return Some(result);
+        }
 
-        match (white_queen_surrounded, black_queen_surrounded) {
-            (true, false) => return Some(GameResult::BlackWins),
-            (false, true) => return Some(GameResult::WhiteWins),
-            (true, true) => return Some(GameResult::Draw),
-            _ => {}
+        // A pass is only legal without any other move, and two in a row
+        // leave both players facing the same position again
+        let mut previous = self.annotations.iter().rev().skip(1).take(2);
+        if previous.len() == 2 && previous.all(|annotator| annotator.position() == grid) {
+            return Some(GameResult::Stalemate);
         }
 
-        let mut position_count = 0;
-        for annotator in self.annotations.iter() {
-            if annotator.position() == grid {
-                position_count += 1;
-            }
-            if position_count > 2 {
-                return Some(GameResult::Draw);
-            }
+        // Repeated with the same player to move and the same piece the
+        // pillbug may not throw, see state_key()
+        let key = self.state_key();
+        let repetitions = (0..self.annotations.len())
+            .filter(|turn| {
+                self.state_key_at(*turn) == key && self.annotations[*turn].position() == grid
+            })
+            .count();
+        if repetitions > 2 {
+            return Some(GameResult::Draw);
         }
 
         None
     }
 
+    /// The key of the current state of the game: the position together with
+    /// the player to move and the last move, see zobrist::state_key().
+    /// Unlike the hash of the board, states with the same key are the same
+    /// for the rules, so it identifies repetitions and transpositions
+    pub fn state_key(&self) -> u64 {
+        self.state_key_at(self.annotations.len() - 1)
+    }
+
+    /// The state_key() after every turn played so far, the current state
+    /// last, for searches to recognize repetitions of earlier states
+    pub fn state_keys(&self) -> Vec<u64> {
+        (0..self.annotations.len())
+            .map(|turn| self.state_key_at(turn))
+            .collect()
+    }
+
+    /// The state_key() after *turn* turns
+    fn state_key_at(&self, turn: usize) -> u64 {
+        let annotator = &self.annotations[turn];
+        let color = match turn % 2 {
+            0 => PieceColor::White,
+            _ => PieceColor::Black,
+        };
+        zobrist::state_key(annotator.position(), annotator.last_move(), color)
+    }
+
     /// Get the latest position in the game
     pub fn position(&self) -> &HexGrid {
         self.annotations.last().unwrap().position()
     }
+
+    /// Iterates over every position of the game in order, from the
+    /// empty board up to and including position()
+    pub fn positions(&self) -> impl Iterator<Item = &HexGrid> {
+        self.annotations
+            .iter()
+            .map(|annotator| annotator.position())
+    }
+
+    /// Returns the pieces the player of the given *color* has yet to place
+    pub fn inventory(&self, color: PieceColor) -> Inventory {
+        Inventory::from_grid(self.position(), color, self.game_type)
+            .expect("Legal positions should respect the piece counts")
+    }
+
+    /// Returns which physical piece is on top of the stack at *location*
+    /// (e.g. bS2), or None if the hex is empty
+    pub fn piece_id(&self, location: HexLocation) -> Option<PieceId> {
+        let height = self.position().height(location).checked_sub(1)?;
+        self.annotations.last().unwrap().piece_id(location, height)
+    }
+
+    /// Returns the UHP MoveString of the move leading from the current
+    /// position to *position*, which must be one of the legal_positions()
+    pub fn move_string(&self, position: &HexGrid) -> Result<String> {
+        self.annotations
+            .last()
+            .unwrap()
+            .annotate(position)
+            .map_err(GameDebuggerError::AnnotationError)
+    }
+
+    /// Returns the UHP MoveString of *mv* played from the current position,
+    /// see Annotator::move_to_uhp()
+    pub fn move_to_uhp(&self, mv: &Move) -> Result<String> {
+        self.annotations
+            .last()
+            .unwrap()
+            .move_to_uhp(mv)
+            .map_err(GameDebuggerError::AnnotationError)
+    }
+
+    /// Parses a UHP MoveString played from the current position,
+    /// see Annotator::uhp_to_move()
+    pub fn uhp_to_move(&self, move_string: &str) -> Result<Move> {
+        self.annotations
+            .last()
+            .unwrap()
+            .uhp_to_move(move_string)
+            .map_err(GameDebuggerError::AnnotationError)
+    }
+
+    /// Returns the destination of the piece moved in the last turn, if any
+    pub fn last_move(&self) -> Option<HexLocation> {
+        self.annotations.last().unwrap().last_move()
+    }
+
+    /// Returns the Move played in the last turn, None at the start of the game
+    pub fn last_move_played(&self) -> Option<Move> {
+        let previous = self.annotations.iter().rev().nth(1)?;
+        previous.position().diff(self.position())
+    }
+
+    /// Returns rendering options highlighting the last move played,
+    /// see BoardView::ansi_string() and BoardView::svg_string()
+    pub fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            last_move: self.last_move_played(),
+        }
+    }
+
+    /// Returns every hex the piece at *location* can be moved to by the player
+    /// to move, either by itself or by a pillbug.
+    ///
+    /// Cheaper than filtering legal_positions() as no boards are built for the moves
+    pub fn legal_destinations(&self, location: HexLocation) -> HashSet<HexLocation> {
+        if self.game_result().is_some() {
+            return HashSet::new();
+        }
+        self.generator
+            .legal_destinations(self.player_to_move(), location)
+    }
+
+    /// Returns true if the move can be made by the player to move
+    pub fn is_legal(&mut self, mv: &Move) -> bool {
+        self.check_move(mv).is_ok()
+    }
+
+    /// Returns the reason the move cannot be made by the player to move,
+    /// or None if the move is legal
+    pub fn illegal_reason(&mut self, mv: &Move) -> Option<IllegalMoveReason> {
+        self.check_move(mv).err()
+    }
+
+    /// Checks that the move can be made by the player to move, failing with
+    /// the first rule it breaks, see rules::illegal_reason().
+    ///
+    /// Every check of a single move (is_legal(), make_move(), UHP play
+    /// commands and explain_illegal()) goes through here, so they all agree
+    pub fn check_move(&mut self, mv: &Move) -> std::result::Result<(), IllegalMoveReason> {
+        if self.game_result().is_some() {
+            return Err(IllegalMoveReason::GameOver);
+        }
+        let moves_top = match mv {
+            Move::Movement { piece, from, .. } => self.position().top(*from) == Some(*piece),
+            _ => true,
+        };
+        if moves_top && self.legal_positions().contains(&mv.apply(self.position())) {
+            return Ok(());
+        }
+        Err(rules::illegal_reason(
+            self.position(),
+            self.game_type,
+            self.tournament_opening,
+            self.player_to_move(),
+            self.last_move(),
+            mv,
+        ))
+    }
+
+    /// Explains in plain words why the move cannot be made by the player to move
+    pub fn explain_illegal(&mut self, mv: &Move) -> String {
+        match self.illegal_reason(mv) {
+            Some(reason) => reason.to_string(),
+            None => "this move is legal".to_string(),
+        }
+    }
+
+    /// Searches the current position to *depth* plies and returns the best
+    /// move with the reasons it is good, None if the game is over
+    #[cfg(feature = "engine")]
+    pub fn hint(&self, depth: usize) -> Option<Hint> {
+        let mut searcher = Searcher::new(self.game_type);
+        searcher.set_deterministic(true);
+        hint::hint(self, &mut searcher, depth)
+    }
+}
+
+/// Splits a UHP GameString into its game type and move strings
+pub fn parse_game_string(game_string: &str) -> Option<(GameType, Vec<String>)> {
+    let mut parts = game_string.trim().split(';');
+    let game_type = GameType::try_from(parts.next()?).ok()?;
+    // Skip the GameStateString and TurnString
+    parts.next()?;
+    parts.next()?;
+    let moves = parts.map(|s| s.to_string()).collect();
+    Some((game_type, moves))
+}
+
+/// Returns the result of the game if it can be decided from the board alone,
+/// that is, if one or both of the queens are surrounded.
+///
+/// Draws by repetition depend on the history of the game and are not detected.
+pub fn board_result(grid: &HexGrid) -> Option<GameResult> {
+    use PieceColor::*;
+    use PieceType::*;
+
+    let white_queen = grid.find(Piece::new(Queen, White));
+    let black_queen = grid.find(Piece::new(Queen, Black));
+
+    let queen_surrounded = |(queen_loc, _): (HexLocation, _)| -> bool {
+        let neighbors = grid.get_neighbors(queen_loc);
+        neighbors.len() == 6
+    };
+
+    let white_queen_surrounded = white_queen.map(queen_surrounded).unwrap_or(false);
+    let black_queen_surrounded = black_queen.map(queen_surrounded).unwrap_or(false);
+
+    match (white_queen_surrounded, black_queen_surrounded) {
+        (true, false) => Some(GameResult::BlackWins),
+        (false, true) => Some(GameResult::WhiteWins),
+        (true, true) => Some(GameResult::BothSurrounded),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -221,6 +712,15 @@ mod tests {
     #[test]
     pub fn pinned_pieces() {}
 
+    #[test]
+    pub fn test_parse_game_string() {
+        let (game_type, moves) =
+            parse_game_string(r"Base+PL;InProgress;White[2];wP;bL wP-").unwrap();
+        assert_eq!(game_type, GameType::LP);
+        assert_eq!(moves, vec![String::from("wP"), String::from("bL wP-")]);
+        assert!(parse_game_string("Base+X;InProgress;White[1]").is_none());
+    }
+
     #[test]
     pub fn test_win() {
         let white_wins = [
@@ -272,6 +772,359 @@ mod tests {
         assert_eq!(game.game_result(), Some(GameResult::BlackWins));
     }
 
+    #[test]
+    pub fn test_explain_illegal() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        let mut game = GameDebugger::from_moves(&[]).unwrap();
+        let queen_first = Move::Place {
+            piece: Piece::new(Queen, White),
+            to: HexLocation::new(0, 0),
+        };
+        assert_eq!(
+            game.explain_illegal(&queen_first),
+            "the queen may not be placed on the first turn"
+        );
+
+        let moves = [
+            String::from(r"wA1"),
+            String::from(r"bA1 wA1-"),
+            String::from(r"wQ -wA1"),
+            String::from(r"bQ bA1-"),
+        ];
+        let mut game = GameDebugger::from_moves(&moves).unwrap();
+        let grid = game.position().clone();
+        let (ant, _) = grid.find(Piece::new(Ant, White)).unwrap();
+        let (black_queen, _) = grid.find(Piece::new(Queen, Black)).unwrap();
+
+        let split_hive = Move::Movement {
+            piece: Piece::new(Ant, White),
+            from: ant,
+            to: ant.apply(Direction::NW),
+        };
+        assert_eq!(
+            game.illegal_reason(&split_hive),
+            Some(IllegalMoveReason::BreaksHive(Ant))
+        );
+        assert_eq!(
+            game.explain_illegal(&split_hive),
+            "moving this ant would split the hive"
+        );
+
+        let touches_opponent = Move::Place {
+            piece: Piece::new(Grasshopper, White),
+            to: black_queen.apply(Direction::E),
+        };
+        assert_eq!(
+            game.illegal_reason(&touches_opponent),
+            Some(IllegalMoveReason::PlacementTouchesOpponent)
+        );
+
+        let wrong_color = Move::Place {
+            piece: Piece::new(Grasshopper, Black),
+            to: black_queen.apply(Direction::E),
+        };
+        assert_eq!(
+            game.illegal_reason(&wrong_color),
+            Some(IllegalMoveReason::WrongColor)
+        );
+
+        let legal = Move::Place {
+            piece: Piece::new(Grasshopper, White),
+            to: ant.apply(Direction::NW).apply(Direction::W),
+        };
+        assert_eq!(game.illegal_reason(&legal), None);
+        assert_eq!(game.explain_illegal(&legal), "this move is legal");
+        assert!(game.is_legal(&legal));
+        assert!(!game.is_legal(&touches_opponent));
+
+        assert!(matches!(
+            game.make_move(r"wG1 bQ-"),
+            Err(GameDebuggerError::IllegalMove(
+                IllegalMoveReason::PlacementTouchesOpponent
+            ))
+        ));
+    }
+
+    #[test]
+    pub fn test_tournament_opening() {
+        let mut game = GameDebugger::from_moves(&[]).unwrap();
+        assert!(game.tournament_opening());
+        assert!(game.make_move("wQ").is_err());
+
+        game.set_tournament_opening(false);
+        game.make_move("wQ").unwrap();
+        game.make_move(r"bQ wQ-").unwrap();
+        game.undo_move().unwrap();
+        assert!(!game.tournament_opening());
+        game.make_move(r"bQ wQ-").unwrap();
+
+        let mut game = GameDebugger::from_moves(&[String::from("wL")]).unwrap();
+        let queen_placements = |game: &mut GameDebugger| {
+            game.legal_positions()
+                .iter()
+                .filter(|grid| {
+                    grid.find(Piece::new(PieceType::Queen, PieceColor::Black))
+                        .is_some()
+                })
+                .count()
+        };
+        assert_eq!(queen_placements(&mut game), 0);
+        game.set_tournament_opening(false);
+        assert_eq!(queen_placements(&mut game), 6);
+    }
+
+    #[test]
+    pub fn test_from_move_list() {
+        let moves = [r"wS1", r"bG1 -wS1", r"wQ wS1/", r"bQ \bG1"];
+        let game = GameDebugger::from_move_list(&moves, GameType::Standard).unwrap();
+        let owned = moves.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let expected = GameDebugger::from_moves_custom(&owned, GameType::Standard).unwrap();
+        assert_eq!(game.position(), expected.position());
+        assert_eq!(game.player_to_move(), PieceColor::White);
+
+        // Standard notation gives the same game
+        let standard = [r"wS1", r"bG1 -wS1", r"wQ1 wS1/", r"bQ1 \bG1"];
+        let game = GameDebugger::from_move_list(&standard, GameType::Standard).unwrap();
+        assert_eq!(game.position(), expected.position());
+
+        let invalid = [
+            vec![r"wQ"],
+            vec![r"wS1", r"bS1"],
+            vec![r"wS1", r"bS2 wS1-"],
+            vec![r"wS1", r"bS1 wA1-"],
+            vec![r"wS1", r"bS1 wS1- wS1"],
+            vec![r"wX1"],
+            vec![r""],
+        ];
+        for moves in invalid {
+            assert!(
+                GameDebugger::from_move_list(&moves, GameType::Standard).is_err(),
+                "{:?} should be rejected",
+                moves
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_last_move_played() {
+        let mut game = GameDebugger::from_move_list(&[], GameType::Standard).unwrap();
+        assert_eq!(game.last_move_played(), None);
+
+        game.make_move("wS1").unwrap();
+        let placed = Move::Place {
+            piece: Piece::new(PieceType::Spider, PieceColor::White),
+            to: game.last_move().unwrap(),
+        };
+        assert_eq!(game.last_move_played(), Some(placed));
+        assert_eq!(game.render_options().last_move, Some(placed));
+
+        game.make_move("bS1 wS1-").unwrap();
+        game.undo_move().unwrap();
+        assert_eq!(game.last_move_played(), Some(placed));
+    }
+
+    #[test]
+    pub fn test_observers() {
+        let moves = [
+            r"wP",
+            r"bL wP-",
+            r"wB1 \wP",
+            r"bQ bL/",
+            r"wA1 /wB1",
+            r"bA1 \bQ",
+            r"wQ wA1\",
+            r"bB1 bQ/",
+            r"wB1 wP",
+            r"bG1 bB1\",
+            r"wA1 bQ\",
+            r"bG2 bG1/",
+        ];
+        let mut game = GameDebugger::from_move_list(&moves, GameType::MLP).unwrap();
+        let events = game.events();
+        let count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = count.clone();
+        game.subscribe(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+
+        let before = game.position().clone();
+        game.make_move(r"wB1 bL").unwrap();
+        let climbed = game.position().clone();
+        game.update_clock(PieceColor::Black, Duration::from_secs(5));
+        game.undo_move().unwrap();
+        assert!(game.make_move("wQ").is_err());
+
+        // Clones keep the observers, and moves are reported in the notation
+        // of move_string()
+        let mut clone = game.clone();
+        clone.make_move(r"wB1 \bL").unwrap();
+        let won = clone.position().clone();
+        clone.unsubscribe_all();
+        clone.undo_move().unwrap();
+
+        let received = events.try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            received,
+            vec![
+                GameEvent::MoveApplied {
+                    move_string: "wB1 bL".to_string(),
+                    position: climbed,
+                },
+                GameEvent::Clock {
+                    color: PieceColor::Black,
+                    remaining: Duration::from_secs(5)
+                },
+                GameEvent::Undone { position: before },
+                GameEvent::MoveApplied {
+                    move_string: game.move_string(&won).unwrap(),
+                    position: won,
+                },
+                GameEvent::GameOver(GameResult::WhiteWins),
+            ]
+        );
+        assert_eq!(
+            count.load(std::sync::atomic::Ordering::SeqCst),
+            received.len()
+        );
+    }
+
+    #[test]
+    pub fn test_resign_and_draw_offers() {
+        use PieceColor::*;
+        let moves = ["wA1", "bG1 -wA1", "wQ wA1-", "bQ /bG1"];
+        let mut game = GameDebugger::from_move_list(&moves, GameType::Standard).unwrap();
+        let events = game.events();
+
+        // Accepting needs an offer by the other player
+        let accept = game.act(White, GameAction::AcceptDraw);
+        assert!(matches!(accept, Err(GameDebuggerError::InvalidAction(..))));
+        game.act(White, GameAction::OfferDraw).unwrap();
+        assert_eq!(game.draw_offer(), Some(White));
+        assert!(game.act(White, GameAction::AcceptDraw).is_err());
+        // The offer stands while its player moves, and lapses once the
+        // opponent moves instead of accepting
+        game.make_move(r"wS1 wQ-").unwrap();
+        assert_eq!(game.draw_offer(), Some(White));
+        game.make_move(r"bS1 -bG1").unwrap();
+        assert_eq!(game.draw_offer(), None);
+
+        game.act(Black, GameAction::OfferDraw).unwrap();
+        game.act(White, GameAction::AcceptDraw).unwrap();
+        assert_eq!(game.game_result(), Some(GameResult::DrawAgreed));
+        assert!(game.legal_positions().is_empty());
+        assert!(game.act(Black, GameAction::Resign).is_err());
+
+        // Undo takes back the agreement before any move
+        let position = game.position().clone();
+        game.undo_move().unwrap();
+        assert_eq!((game.game_result(), game.position()), (None, &position));
+        game.act(Black, GameAction::Resign).unwrap();
+        assert_eq!(game.game_result(), Some(GameResult::Resigned(Black)));
+        assert_eq!(game.game_result().unwrap().winner(), Some(White));
+
+        let received = events.try_iter().collect::<Vec<_>>();
+        let actions = received
+            .iter()
+            .filter(|event| !matches!(event, GameEvent::MoveApplied { .. }))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                GameEvent::Action {
+                    color: White,
+                    action: GameAction::OfferDraw
+                },
+                GameEvent::Action {
+                    color: Black,
+                    action: GameAction::OfferDraw
+                },
+                GameEvent::Action {
+                    color: White,
+                    action: GameAction::AcceptDraw
+                },
+                GameEvent::GameOver(GameResult::DrawAgreed),
+                GameEvent::Undone { position },
+                GameEvent::Action {
+                    color: Black,
+                    action: GameAction::Resign
+                },
+                GameEvent::GameOver(GameResult::Resigned(Black)),
+            ]
+        );
+
+        // GameStrings stating a result the board does not show
+        let resigned = GameDebugger::from_game_string("Base;BlackWins;Black[1];wA1").unwrap();
+        assert_eq!(resigned.game_result(), Some(GameResult::Resigned(White)));
+        let drawn = GameDebugger::from_game_string("Base;Draw;Black[1];wA1").unwrap();
+        assert_eq!(drawn.game_result(), Some(GameResult::DrawAgreed));
+        let playing = GameDebugger::from_game_string("Base;InProgress;Black[1];wA1").unwrap();
+        assert_eq!(playing.game_result(), None);
+
+        // A GameString of a game decided on the board infers no ending, even
+        // one stating a different result
+        let white_wins = r"Base+PL;WhiteWins;Black[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/;wB1 \bL";
+        for game_string in [
+            white_wins.to_string(),
+            white_wins.replace("WhiteWins", "BlackWins"),
+        ] {
+            let mut decided = GameDebugger::from_game_string(&game_string).unwrap();
+            assert_eq!(decided.game_result(), Some(GameResult::WhiteWins));
+            assert_eq!(decided.ending, None);
+            let surrounded = decided.position().clone();
+            decided.undo_move().unwrap();
+            assert_ne!(*decided.position(), surrounded);
+            assert_eq!(decided.game_result(), None);
+        }
+    }
+
+    #[test]
+    pub fn test_legal_destinations() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        let moves = [
+            String::from(r"wP"),
+            String::from(r"bA1 wP-"),
+            String::from(r"wQ -wP"),
+            String::from(r"bQ bA1-"),
+        ];
+        let mut game = GameDebugger::from_moves(&moves).unwrap();
+        let grid = game.position().clone();
+
+        let legal_positions = game.legal_positions();
+        let expected_destinations = |from: HexLocation| -> HashSet<HexLocation> {
+            legal_positions
+                .iter()
+                .filter_map(|position| match grid.diff(position) {
+                    Some(Move::Movement {
+                        from: origin, to, ..
+                    }) if origin == from => Some(to),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // The queen can both walk and be thrown by the pillbug
+        let (queen, _) = grid.find(Piece::new(Queen, White)).unwrap();
+        let (pillbug, _) = grid.find(Piece::new(Pillbug, White)).unwrap();
+        let destinations = game.legal_destinations(queen);
+        assert!(destinations.contains(&pillbug.apply(Direction::NE)));
+        assert_eq!(destinations, expected_destinations(queen));
+
+        for (_, location) in grid.pieces() {
+            assert_eq!(
+                game.legal_destinations(location),
+                expected_destinations(location)
+            );
+        }
+
+        let empty = HexLocation::new(0, 0).apply(Direction::NW);
+        assert!(game.legal_destinations(empty).is_empty());
+    }
+
     #[test]
     pub fn test_draw() {
         // Test three fold repetition
@@ -293,6 +1146,15 @@ mod tests {
         let game = GameDebugger::from_moves(&draw).unwrap();
         println!("game\n:{}", game.position().to_dsl());
         assert_eq!(game.game_result(), Some(GameResult::Draw));
+
+        // The repeated state is the same board with the same player to move
+        let second = GameDebugger::from_moves(&draw[..8]).unwrap();
+        assert_eq!(second.game_result(), None);
+        assert_eq!(second.state_key(), game.state_key());
+        let mut undone = second.clone();
+        undone.undo_move().unwrap();
+        assert_ne!(undone.state_key(), second.state_key());
+        assert_ne!(second.state_key(), zobrist::hash(second.position()));
     }
 
     #[test]
@@ -323,6 +1185,71 @@ mod tests {
 
         let game = GameDebugger::from_moves(&draw).unwrap();
         println!("game\n:{}", game.position().to_dsl());
-        assert_eq!(game.game_result(), Some(GameResult::Draw));
+        assert_eq!(game.game_result(), Some(GameResult::BothSurrounded));
+        assert!(game.game_result().unwrap().is_draw());
+    }
+
+    #[test]
+    pub fn test_forced_pass() {
+        // White's only pieces in play are pinned and every empty cell next to
+        // them touches black, so white has no move but to pass
+        let moves = [
+            r"wG1",
+            r"bS1 \wG1",
+            r"wQ wG1-",
+            r"bS2 bS1/",
+            r"wQ bS2\",
+            r"bB1 \bS2",
+            r"wG1 /bB1",
+            r"bQ bB1/",
+            r"wQ bS2-",
+            r"bS1 wQ-",
+            r"wG1 bQ/",
+            r"bB2 \bB1",
+            r"wG2 wG1/",
+            r"bB2 \bQ",
+            r"wG2 /bB1",
+            r"bB2 \wG1",
+            r"wG3 /wG2",
+            r"bB2 wG1/",
+            r"wG3 bB2/",
+            r"bG1 bS1/",
+            r"wG2 bG1\",
+            r"bG2 \bG1",
+            r"wS1 wG3-",
+            r"bG2 wG2\",
+            r"wS1 wG1\",
+            r"bG3 /bB1",
+            r"wS1 \bG1",
+            r"bG3 wG3/",
+            r"wS1 wG1\",
+            r"bG3 /bB1",
+            r"wS1 \bG1",
+            r"bG3 wG3/",
+            r"wS1 bG2/",
+            r"bG2 wS1/",
+            r"wS2 wG2\",
+            r"bG1 wS2\",
+        ];
+        let mut game = GameDebugger::from_move_list(&moves, GameType::Standard).unwrap();
+        assert_eq!(game.legal_positions(), vec![game.position().clone()]);
+        assert!(game.is_legal(&Move::Pass));
+
+        game.make_move("pass").unwrap();
+        assert_eq!(game.last_move_played(), Some(Move::Pass));
+        assert_eq!(game.game_result(), None);
+
+        // A single pass leaves the game going, only a second one in a row
+        // would end it in a stalemate
+        let reply = game.legal_positions()[0].clone();
+        assert_ne!(&reply, game.position());
+        let reply = game.move_string(&reply).unwrap();
+        game.make_move(&reply).unwrap();
+        assert_eq!(game.game_result(), None);
+
+        game.undo_move().unwrap();
+        game.undo_move().unwrap();
+        assert_eq!(game.game_result(), None);
+        assert_eq!(game.legal_positions().len(), 1);
     }
 }
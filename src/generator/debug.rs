@@ -1,7 +1,11 @@
+use crate::board::Board;
 use crate::hex_grid::{HexGrid, HexLocation, Shiftable};
+use crate::inventory::Inventory;
 use crate::location::Direction;
-use crate::piece::{IntoPieces, Piece, PieceColor, PieceType, PIECE_COUNTS};
+use crate::piece::{IntoPieces, Piece, PieceColor, PieceType};
 use crate::uhp::GameType;
+use crate::zobrist;
+use smallvec::SmallVec;
 use std::collections::HashSet;
 
 /// Represents a HexGrid wrapper that can generate new positions.
@@ -12,107 +16,159 @@ use std::collections::HashSet;
 ///
 /// The move generator is only guaranteed to generate moves correctly
 /// for positions that follow the One Hive Rule
+///
+/// Runs on any Board, a HexGrid unless specified otherwise
 #[derive(Clone, Debug)]
-pub struct ReferenceGenerator {
-    grid: HexGrid,
+pub struct ReferenceGenerator<B: Board = HexGrid> {
+    grid: B,
     pinned: Vec<HexLocation>,
-    outside: HashSet<HexLocation>,
     game_type: GameType,
     immobilized: Option<HexLocation>,
+    /// Forbids placing the queen on a player's first turn
+    tournament_opening: bool,
+    /// Lists a position reached by several moves once
+    deduplicate: bool,
 }
 
 impl ReferenceGenerator {
     pub fn new(game_type: GameType) -> ReferenceGenerator {
+        ReferenceGenerator::with_board(game_type)
+    }
+
+    /// FromHexGrid::from_hex_grid() for the default board, which keeps
+    /// callers from having to name it
+    pub fn from_hex_grid(
+        grid: &HexGrid,
+        game_type: GameType,
+        previous_change: Option<HexLocation>,
+    ) -> ReferenceGenerator {
+        <ReferenceGenerator as FromHexGrid>::from_hex_grid(grid, game_type, previous_change)
+    }
+
+    /// FromHexGrid::from_default() for the default board
+    pub fn from_default(grid: &HexGrid) -> ReferenceGenerator {
+        <ReferenceGenerator as FromHexGrid>::from_default(grid)
+    }
+}
+
+impl<B: Board> ReferenceGenerator<B> {
+    /// Same as new(), for a generator running on another Board
+    pub fn with_board(game_type: GameType) -> ReferenceGenerator<B> {
         ReferenceGenerator {
-            grid: HexGrid::new(),
+            grid: <B as Position>::new(),
             pinned: Vec::new(),
-            outside: HashSet::new(),
             game_type,
             immobilized: None,
+            tournament_opening: true,
+            deduplicate: true,
         }
     }
 
-    fn spider_dfs(
-        &self,
-        location: HexLocation,
-        mut visited: Vec<HexLocation>,
-        depth: usize,
-        spider_removed: &HexGrid,
-    ) -> Vec<HexLocation> {
-        if visited.contains(&location) {
-            return vec![];
-        }
-        visited.push(location);
-
-        if depth == 3 {
-            return vec![location];
-        }
+    /// Enables or disables the tournament opening rule, which forbids
+    /// placing the queen on a player's first turn. Enabled by default.
+    pub fn set_tournament_opening(&mut self, enabled: bool) {
+        self.tournament_opening = enabled;
+    }
 
-        let mut result = vec![];
+    /// Enables or disables listing a position reached by several moves (such
+    /// as a piece moving by itself or thrown by a pillbug to the same hex)
+    /// once, so that a search does not go through it again. Positions are
+    /// told apart by their Zobrist hash as they are generated. Enabled by
+    /// default, disable to list a position for every move reaching it.
+    pub fn set_deduplicate(&mut self, enabled: bool) {
+        self.deduplicate = enabled;
+    }
 
-        for slidable_location in spider_removed.slidable_locations_2d(location).iter() {
-            let found = self.spider_dfs(
-                *slidable_location,
-                visited.clone(),
-                depth + 1,
-                spider_removed,
-            );
-            result.extend(found);
+    /// The empty hexes a piece lifted from *removed* can slide to from the
+    /// empty hex *current*, which it can when exactly one of the two hexes
+    /// they share is occupied: both would form a gate and neither would
+    /// leave the hive
+    fn slides(&self, removed: HexLocation, current: HexLocation) -> SmallVec<[HexLocation; 6]> {
+        let directions = Direction::all();
+        let mut occupied = [false; 6];
+        for (index, direction) in directions.iter().enumerate() {
+            let neighbor = current.apply(*direction);
+            occupied[index] = neighbor != removed && self.grid.height(neighbor) > 0;
         }
-
-        result
+        directions
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                let (left, right) = (occupied[(index + 5) % 6], occupied[(index + 1) % 6]);
+                !occupied[*index] && left != right
+            })
+            .map(|(_, direction)| current.apply(*direction))
+            .collect()
     }
 
-    fn pieces_in_hand(&self, color: PieceColor) -> Vec<Piece> {
-        let all_pieces = self.grid.pieces();
-        let friendly_pieces = all_pieces
-            .iter()
-            .flat_map(|(stack, _)| stack)
-            .filter(|piece| piece.color == color)
-            .collect::<Vec<_>>();
-        let mut result = Vec::new();
-
-        for piece in PieceType::all(self.game_type) {
-            let num_placed = friendly_pieces
-                .iter()
-                .filter(|p| p.piece_type == piece)
-                .count();
-            let (_, total) = PIECE_COUNTS
-                .iter()
-                .find(|(piece_type, _)| *piece_type == piece)
-                .unwrap();
-            if num_placed < *total {
-                result.push(Piece::new(piece, color));
+    /// Returns every way the spider at *location* can walk three hexes
+    /// without visiting a hex twice, as the hexes from *location* to the
+    /// destination. Different paths may lead to the same destination
+    pub fn spider_paths(&self, location: HexLocation) -> Vec<[HexLocation; 4]> {
+        if self.pinned.contains(&location) {
+            return vec![];
+        }
+
+        let mut paths = vec![];
+        let mut path = [location; 4];
+        // The slides still to try after each step of the path
+        let mut options: [SmallVec<[HexLocation; 6]>; 3] = Default::default();
+        options[0] = self.slides(location, location);
+        let mut step = 0;
+        loop {
+            let Some(next) = options[step].pop() else {
+                if step == 0 {
+                    break;
+                }
+                step -= 1;
+                continue;
+            };
+            if path[..=step].contains(&next) {
+                continue;
+            }
+            path[step + 1] = next;
+            if step == 2 {
+                paths.push(path);
+            } else {
+                step += 1;
+                options[step] = self.slides(location, next);
             }
         }
+        paths
+    }
 
-        result
+    fn pieces_in_hand(&self, color: PieceColor) -> Vec<Piece> {
+        // Positions written by hand or fuzzed may hold more pieces than the
+        // set contains, which then simply leaves none of them in hand
+        Inventory::saturating_from_grid(&self.grid, color, self.game_type).pieces()
     }
 }
 
-impl FromHexGrid for ReferenceGenerator {
+impl<B: Board> FromHexGrid for ReferenceGenerator<B> {
     fn from_hex_grid(
         grid: &HexGrid,
         game_type: GameType,
         previous_change: Option<HexLocation>,
-    ) -> ReferenceGenerator {
+    ) -> ReferenceGenerator<B> {
         ReferenceGenerator {
-            grid: grid.clone(),
+            grid: <B as Position>::from_hex_grid(grid)
+                .expect("Every HexGrid should be representable by the board"),
             pinned: grid.pinned(),
-            outside: grid.outside(),
             game_type,
             immobilized: previous_change,
+            tournament_opening: true,
+            deduplicate: true,
         }
     }
 }
 
-impl PlacementGenerator for ReferenceGenerator {
+impl<B: Board> PlacementGenerator for ReferenceGenerator<B> {
     fn placements(&mut self, placing_color: PieceColor) -> Vec<HexLocation> {
-        let mut placements = self.outside.clone();
+        let mut placements = self.grid.outside();
 
         if self.grid.num_pieces() == 1 {
             let piece_loc = self.grid.pieces().first().unwrap().1;
-            return self.grid.get_empty_neighbors(piece_loc);
+            return in_board_order(self.grid.get_empty_neighbors(piece_loc));
         }
 
         for (_, loc) in self.grid.pieces() {
@@ -131,150 +187,118 @@ impl PlacementGenerator for ReferenceGenerator {
             placements.insert(HexLocation::center());
         }
 
-        placements.into_iter().collect()
+        in_board_order(placements)
     }
 }
 
-impl MoveGenerator<HexGrid> for ReferenceGenerator {
-    fn spider_moves(&mut self, location: HexLocation) -> Vec<HexGrid> {
-        let stack = self.grid.peek(location);
+impl<B: Board> ReferenceGenerator<B> {
+    fn spider_destinations(&self, location: HexLocation) -> Vec<HexLocation> {
+        let stack = self.grid.stack(location);
         debug_assert!(stack.len() == 1_usize);
         debug_assert!(
             stack[0].piece_type == PieceType::Spider || stack[0].piece_type == PieceType::Mosquito
         );
 
-        if self.pinned.contains(&location) {
-            return vec![];
-        }
-
-        let mut spider_removed = self.grid.clone();
-        spider_removed.remove(location);
-
-        let new_locations = self.spider_dfs(location, vec![], 0, &spider_removed);
-        let deduplicated = new_locations
-            .iter()
-            .cloned()
+        let destinations = self
+            .spider_paths(location)
+            .into_iter()
+            .map(|path| path[3])
             .collect::<HashSet<HexLocation>>();
-
-        let mut result = vec![];
-
-        for new_location in deduplicated.iter() {
-            let mut new_grid = self.grid.clone();
-            new_grid.remove(location);
-            new_grid.add(stack[0], *new_location);
-            result.push(new_grid);
-        }
-
-        result
+        destinations.into_iter().collect()
     }
 
-    fn grasshopper_moves(&mut self, location: HexLocation) -> Vec<HexGrid> {
-        debug_assert!(self.grid.peek(location).len() == 1);
+    fn grasshopper_destinations(&self, location: HexLocation) -> Vec<HexLocation> {
+        debug_assert!(self.grid.height(location) == 1);
         debug_assert!(
-            self.grid.peek(location)[0].piece_type == PieceType::Grasshopper
-                || self.grid.peek(location)[0].piece_type == PieceType::Mosquito
+            self.grid.top(location).unwrap().piece_type == PieceType::Grasshopper
+                || self.grid.top(location).unwrap().piece_type == PieceType::Mosquito
         );
 
         if self.pinned.contains(&location) {
             return vec![];
         }
-        let grasshopper = self.grid.peek(location)[0];
 
+        // A line of pieces is at most as long as the hive, and a landing hex
+        // past the edge of the coordinates cannot be jumped to
+        let max_steps = self.grid.pieces().len();
         let mut result = vec![];
         for direction in Direction::all().iter() {
-            let mut search_location = location.apply(*direction);
+            let mut search_location = location.checked_apply(*direction);
 
             // No piece to jump over, don't bother searching
-            if self.outside.contains(&search_location) {
+            if search_location.is_none_or(|hex| self.grid.is_outside(hex)) {
                 continue;
             }
-            while !self.outside.contains(&search_location) {
-                search_location = search_location.apply(*direction);
+            for _ in 0..max_steps {
+                match search_location {
+                    Some(hex) if !self.grid.is_outside(hex) => {
+                        search_location = hex.checked_apply(*direction);
+                    }
+                    _ => break,
+                }
             }
 
-            let mut new_grid = self.grid.clone();
-            new_grid.remove(location);
-            new_grid.add(grasshopper, search_location);
-            result.push(new_grid);
+            if let Some(hex) = search_location.filter(|hex| self.grid.is_outside(*hex)) {
+                result.push(hex);
+            }
         }
 
         result
     }
 
-    fn queen_moves(&mut self, location: HexLocation) -> Vec<HexGrid> {
-        debug_assert!(self.grid.peek(location).len() == 1);
+    fn queen_destinations(&self, location: HexLocation) -> Vec<HexLocation> {
+        debug_assert!(self.grid.height(location) == 1);
         debug_assert!(
-            self.grid.peek(location)[0].piece_type == PieceType::Queen
-                || self.grid.peek(location)[0].piece_type == PieceType::Mosquito
+            self.grid.top(location).unwrap().piece_type == PieceType::Queen
+                || self.grid.top(location).unwrap().piece_type == PieceType::Mosquito
         );
 
         if self.pinned.contains(&location) {
             return vec![];
         }
-        let queen = self.grid.peek(location)[0];
-        let mut result = vec![];
 
         let mut queen_removed = self.grid.clone();
         queen_removed.remove(location);
-        let outside = queen_removed.outside();
-
-        for slidable_location in self.grid.slidable_locations_2d(location).iter() {
-            if outside.contains(slidable_location) {
-                let mut new_grid = self.grid.clone();
-                new_grid.remove(location);
-                new_grid.add(queen, *slidable_location);
-                result.push(new_grid);
-            }
-        }
 
-        result
+        self.grid
+            .slidable_locations_2d(location)
+            .into_iter()
+            .filter(|slidable_location| queen_removed.is_outside(*slidable_location))
+            .collect()
     }
 
-    fn ant_moves(&mut self, location: HexLocation) -> Vec<HexGrid> {
-        debug_assert!(self.grid.peek(location).len() == 1);
+    fn ant_destinations(&self, location: HexLocation) -> Vec<HexLocation> {
+        debug_assert!(self.grid.height(location) == 1);
         debug_assert!(
-            self.grid.peek(location)[0].piece_type == PieceType::Ant
-                || self.grid.peek(location)[0].piece_type == PieceType::Mosquito
+            self.grid.top(location).unwrap().piece_type == PieceType::Ant
+                || self.grid.top(location).unwrap().piece_type == PieceType::Mosquito
         );
 
         if self.pinned.contains(&location) {
             return vec![];
         }
 
-        fn dfs(location: HexLocation, visited: &mut HashSet<HexLocation>, grid: &HexGrid) {
-            if visited.contains(&location) {
-                return;
-            }
-            visited.insert(location);
-
-            for slidable_location in grid.slidable_locations_2d(location).iter() {
-                // In contact with the hive
-                if !grid.get_neighbors(*slidable_location).is_empty() {
-                    dfs(*slidable_location, visited, grid);
+        // Walks the empty hexes along the boundary of the hive
+        let mut visited = vec![location];
+        let mut frontier = vec![location];
+        while let Some(current) = frontier.pop() {
+            for destination in self.slides(location, current) {
+                if !visited.contains(&destination) {
+                    visited.push(destination);
+                    frontier.push(destination);
                 }
             }
         }
 
-        let mut ant_removed = self.grid.clone();
-        let ant = ant_removed.remove(location).unwrap();
-        let mut visited = HashSet::new();
-        dfs(location, &mut visited, &ant_removed);
-
-        visited.remove(&location);
-
-        let mut result = vec![];
+        visited.swap_remove(0);
         for location in visited.iter() {
-            debug_assert!(self.outside.contains(location));
-            let mut new_grid = ant_removed.clone();
-            new_grid.add(ant, *location);
-            result.push(new_grid);
+            debug_assert!(self.grid.is_outside(*location));
         }
-
-        result
+        visited
     }
 
-    fn beetle_moves(&mut self, location: HexLocation) -> Vec<HexGrid> {
-        let height = self.grid.peek(location).len();
+    fn beetle_destinations(&self, location: HexLocation) -> Vec<HexLocation> {
+        let height = self.grid.height(location);
         debug_assert!(height >= 1);
         debug_assert!(
             self.grid.top(location).unwrap().piece_type == PieceType::Beetle
@@ -292,27 +316,20 @@ impl MoveGenerator<HexGrid> for ReferenceGenerator {
             return vec![];
         }
 
-        let beetle = self.grid.top(location).unwrap();
-        let mut result = vec![];
-
         let mut beetle_removed = self.grid.clone();
         beetle_removed.remove(location);
-        let outside = beetle_removed.outside();
 
-        for slidable_location in self.grid.slidable_locations_3d(location).iter() {
-            if outside.contains(slidable_location) || hive.contains(slidable_location) {
-                let mut new_grid = self.grid.clone();
-                new_grid.remove(location);
-                new_grid.add(beetle, *slidable_location);
-                result.push(new_grid);
-            }
-        }
-
-        result
+        self.grid
+            .slidable_locations_3d(location)
+            .into_iter()
+            .filter(|slidable_location| {
+                beetle_removed.is_outside(*slidable_location) || hive.contains(slidable_location)
+            })
+            .collect()
     }
 
-    fn ladybug_moves(&mut self, location: HexLocation) -> Vec<HexGrid> {
-        let height = self.grid.peek(location).len();
+    fn ladybug_destinations(&self, location: HexLocation) -> Vec<HexLocation> {
+        let height = self.grid.height(location);
         debug_assert!(height == 1);
 
         let piece_type = self.grid.top(location).unwrap().piece_type;
@@ -324,10 +341,7 @@ impl MoveGenerator<HexGrid> for ReferenceGenerator {
 
         // The grid without a "ladybug" on it
         let mut ladybug_removed = self.grid.clone();
-        let ladybug = ladybug_removed.remove(location).unwrap();
-
-        let mut outside = ladybug_removed.outside();
-        outside.remove(&location);
+        ladybug_removed.remove(location);
 
         let hive = ladybug_removed
             .pieces()
@@ -335,8 +349,6 @@ impl MoveGenerator<HexGrid> for ReferenceGenerator {
             .map(|(_, loc)| loc)
             .collect::<HashSet<HexLocation>>();
 
-        let mut result = vec![];
-
         // First move unto the hive
         let height = 1;
         let slidable_locs = ladybug_removed.slidable_locations_3d_height(location, height);
@@ -346,31 +358,26 @@ impl MoveGenerator<HexGrid> for ReferenceGenerator {
         let climb_atop = neighbors.flat_map(|loc| {
             // The height must account for an imaginary ladybug now being on top of
             // the existing board
-            let effective_height = ladybug_removed.peek(*loc).len() + 1;
+            let effective_height = ladybug_removed.height(*loc) + 1;
             ladybug_removed.slidable_locations_3d_height(*loc, effective_height)
         });
         let climb_atop = climb_atop.filter(|loc| hive.contains(loc));
 
         // Then climb off the hive
         let climb_down = climb_atop.flat_map(|loc| {
-            let height = ladybug_removed.peek(loc).len() + 1;
+            let height = ladybug_removed.height(loc) + 1;
             ladybug_removed.slidable_locations_3d_height(loc, height)
         });
 
-        let climb_down = climb_down.filter(|loc| outside.contains(loc));
+        let climb_down =
+            climb_down.filter(|loc| *loc != location && ladybug_removed.is_outside(*loc));
         let unique_final_moves = climb_down.collect::<HashSet<HexLocation>>();
 
-        for final_move in unique_final_moves {
-            let mut new_grid = ladybug_removed.clone();
-            new_grid.add(ladybug, final_move);
-            result.push(new_grid);
-        }
-
-        result
+        unique_final_moves.into_iter().collect()
     }
 
-    fn pillbug_moves(&mut self, location: HexLocation) -> Vec<HexGrid> {
-        let height = self.grid.peek(location).len();
+    fn pillbug_destinations(&self, location: HexLocation) -> Vec<HexLocation> {
+        let height = self.grid.height(location);
         debug_assert!(height == 1);
         debug_assert!(
             self.grid.top(location).unwrap().piece_type == PieceType::Pillbug
@@ -382,22 +389,14 @@ impl MoveGenerator<HexGrid> for ReferenceGenerator {
         }
 
         let mut pillbug_removed = self.grid.clone();
-        let pillbug = pillbug_removed.remove(location).unwrap();
-
-        let mut result = vec![];
-        for slidable_location in pillbug_removed.slidable_locations_2d(location).iter() {
-            let mut new_grid = self.grid.clone();
-            new_grid.remove(location);
-            new_grid.add(pillbug, *slidable_location);
-            result.push(new_grid);
-        }
+        pillbug_removed.remove(location);
 
-        result
+        pillbug_removed.slidable_locations_2d(location).into_vec()
     }
 
-    fn mosquito_moves(&mut self, location: HexLocation) -> Vec<HexGrid> {
+    fn mosquito_destinations(&self, location: HexLocation) -> Vec<HexLocation> {
         use PieceType::*;
-        let height = self.grid.peek(location).len();
+        let height = self.grid.height(location);
         debug_assert!(height >= 1);
         debug_assert!(self.grid.top(location).unwrap().piece_type == PieceType::Mosquito);
 
@@ -405,8 +404,8 @@ impl MoveGenerator<HexGrid> for ReferenceGenerator {
             return vec![];
         }
 
-        if height > 1 {
-            return self.beetle_moves(location);
+        if self.grid.is_covered(location) {
+            return self.beetle_destinations(location);
         }
 
         let mut adjacent_pieces = Vec::new();
@@ -415,45 +414,31 @@ impl MoveGenerator<HexGrid> for ReferenceGenerator {
             adjacent_pieces.push(piece);
         }
 
-        let mut grids = HashSet::new();
+        let mut destinations = HashSet::new();
         for piece in adjacent_pieces {
             match piece {
                 Mosquito => {}
-                Spider => {
-                    grids.extend(self.spider_moves(location).into_iter());
-                }
-                Grasshopper => {
-                    grids.extend(self.grasshopper_moves(location).into_iter());
-                }
-                Queen => {
-                    grids.extend(self.queen_moves(location).into_iter());
-                }
-                Ant => {
-                    grids.extend(self.ant_moves(location).into_iter());
-                }
-                Beetle => {
-                    grids.extend(self.beetle_moves(location).into_iter());
-                }
-                Ladybug => {
-                    grids.extend(self.ladybug_moves(location).into_iter());
-                }
-                Pillbug => {
-                    grids.extend(self.pillbug_moves(location).into_iter());
-                }
+                Spider => destinations.extend(self.spider_destinations(location)),
+                Grasshopper => destinations.extend(self.grasshopper_destinations(location)),
+                Queen => destinations.extend(self.queen_destinations(location)),
+                Ant => destinations.extend(self.ant_destinations(location)),
+                Beetle => destinations.extend(self.beetle_destinations(location)),
+                Ladybug => destinations.extend(self.ladybug_destinations(location)),
+                Pillbug => destinations.extend(self.pillbug_destinations(location)),
             }
         }
 
-        grids.into_iter().collect()
+        destinations.into_iter().collect()
     }
-}
 
-impl SwapGenerator<HexGrid> for ReferenceGenerator {
-    fn pillbug_swaps(
-        &mut self,
+    /// Returns the (source, destination) pairs of every piece the top-facing
+    /// pillbug at *pillbug_location* can move, see SwapGenerator::pillbug_swaps()
+    fn pillbug_throws(
+        &self,
         pillbug_location: HexLocation,
         immobilized: Option<HexLocation>,
-    ) -> Vec<HexGrid> {
-        let height = self.grid.peek(pillbug_location).len();
+    ) -> Vec<(HexLocation, HexLocation)> {
+        let height = self.grid.height(pillbug_location);
         debug_assert!(height == 1, "The stack must only contain the pillbug");
         debug_assert!(
             self.grid.top(pillbug_location).unwrap().piece_type == PieceType::Pillbug
@@ -473,8 +458,7 @@ impl SwapGenerator<HexGrid> for ReferenceGenerator {
                 }
             }
 
-            let candidate_stack = self.grid.peek(candidate_loc);
-            if candidate_stack.len() > 1 {
+            if self.grid.is_covered(candidate_loc) {
                 continue;
             }
 
@@ -495,13 +479,145 @@ impl SwapGenerator<HexGrid> for ReferenceGenerator {
         let mut empty_neighbors = Vec::new();
         let slidable = self.grid.slidable_locations_3d_height(pillbug_location, 2);
         for &candidate_loc in slidable.iter() {
-            if self.grid.peek(candidate_loc).is_empty() {
+            if self.grid.height(candidate_loc) == 0 {
                 empty_neighbors.push(candidate_loc);
             }
         }
 
-        itertools::iproduct!(empty_neighbors, swappable)
-            .map(|(destination, source)| {
+        let mut throws = itertools::iproduct!(swappable, empty_neighbors).collect::<Vec<_>>();
+        throws.sort_by_key(|(source, destination)| {
+            (
+                HexGrid::board_order(*source),
+                HexGrid::board_order(*destination),
+            )
+        });
+        throws
+    }
+
+    /// Returns the positions resulting from moving the top piece at *location*
+    /// to each of the given *destinations*, in board order of the destinations
+    fn moved_to(&self, location: HexLocation, destinations: Vec<HexLocation>) -> Vec<B> {
+        let mut piece_removed = self.grid.clone();
+        let piece = piece_removed.remove(location).unwrap();
+
+        in_board_order(destinations)
+            .into_iter()
+            .map(|destination| {
+                let mut new_grid = piece_removed.clone();
+                new_grid.add(piece, destination);
+                new_grid
+            })
+            .collect()
+    }
+
+    /// Returns the locations the top piece at *location* can move to
+    /// by itself, according to its piece type (ignores pillbug swaps),
+    /// in board order
+    pub fn destinations(&self, location: HexLocation) -> Vec<HexLocation> {
+        let Some(top) = self.grid.top(location) else {
+            return vec![];
+        };
+        let destinations = match top.piece_type {
+            PieceType::Queen => self.queen_destinations(location),
+            PieceType::Grasshopper => self.grasshopper_destinations(location),
+            PieceType::Spider => self.spider_destinations(location),
+            PieceType::Ant => self.ant_destinations(location),
+            PieceType::Beetle => self.beetle_destinations(location),
+            PieceType::Ladybug => self.ladybug_destinations(location),
+            PieceType::Mosquito => self.mosquito_destinations(location),
+            PieceType::Pillbug => self.pillbug_destinations(location),
+        };
+        in_board_order(destinations)
+    }
+
+    /// Returns every hex the top piece at *location* can legally reach this turn
+    /// if it is the turn of the specified color, including being moved by a
+    /// friendly pillbug.
+    ///
+    /// Follows the same rules as generate_positions_for(), but only reports destinations
+    pub fn legal_destinations(
+        &self,
+        color: PieceColor,
+        location: HexLocation,
+    ) -> HashSet<HexLocation> {
+        let mut destinations = HashSet::new();
+        let Some(piece) = self.grid.top(location) else {
+            return destinations;
+        };
+
+        let queen = self.grid.find(Piece::new(PieceType::Queen, color));
+
+        // Pieces cannot move until the queen is placed
+        if queen.is_none() {
+            return destinations;
+        }
+
+        if piece.color == color {
+            destinations.extend(self.destinations(location));
+        }
+
+        for neighbor in self.grid.get_neighbors(location) {
+            let is_pillbug =
+                self.grid.stack(neighbor) == [Piece::new(PieceType::Pillbug, color)];
+            if !is_pillbug {
+                continue;
+            }
+            let throws = self.pillbug_throws(neighbor, self.immobilized);
+            destinations.extend(
+                throws
+                    .into_iter()
+                    .filter(|(source, _)| *source == location)
+                    .map(|(_, destination)| destination),
+            );
+        }
+
+        destinations
+    }
+}
+
+impl<B: Board> MoveGenerator<B> for ReferenceGenerator<B> {
+    fn spider_moves(&mut self, location: HexLocation) -> Vec<B> {
+        self.moved_to(location, self.spider_destinations(location))
+    }
+
+    fn grasshopper_moves(&mut self, location: HexLocation) -> Vec<B> {
+        self.moved_to(location, self.grasshopper_destinations(location))
+    }
+
+    fn queen_moves(&mut self, location: HexLocation) -> Vec<B> {
+        self.moved_to(location, self.queen_destinations(location))
+    }
+
+    fn ant_moves(&mut self, location: HexLocation) -> Vec<B> {
+        self.moved_to(location, self.ant_destinations(location))
+    }
+
+    fn beetle_moves(&mut self, location: HexLocation) -> Vec<B> {
+        self.moved_to(location, self.beetle_destinations(location))
+    }
+
+    fn ladybug_moves(&mut self, location: HexLocation) -> Vec<B> {
+        self.moved_to(location, self.ladybug_destinations(location))
+    }
+
+    fn pillbug_moves(&mut self, location: HexLocation) -> Vec<B> {
+        self.moved_to(location, self.pillbug_destinations(location))
+    }
+
+    fn mosquito_moves(&mut self, location: HexLocation) -> Vec<B> {
+        self.moved_to(location, self.mosquito_destinations(location))
+    }
+}
+
+impl<B: Board> SwapGenerator<B> for ReferenceGenerator<B> {
+    fn pillbug_swaps(
+        &mut self,
+        pillbug_location: HexLocation,
+        immobilized: Option<HexLocation>,
+    ) -> Vec<B> {
+        self.pillbug_throws(pillbug_location, immobilized)
+            .into_iter()
+            .map(|(source, destination)| {
                 let mut new_grid = self.grid.clone();
                 let piece = new_grid.remove(source).unwrap();
                 new_grid.add(piece, destination);
@@ -511,9 +627,10 @@ impl SwapGenerator<HexGrid> for ReferenceGenerator {
     }
 }
 
-impl PositionGenerator<HexGrid> for ReferenceGenerator {
-    fn generate_positions_for(&mut self, color: PieceColor) -> HashSet<HexGrid> {
-        let mut positions = HashSet::new();
+impl<B: Board> PositionGenerator<B> for ReferenceGenerator<B> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
+    fn generate_positions_for(&mut self, color: PieceColor) -> Vec<B> {
+        let mut positions = Positions::new(self.deduplicate);
         let queen = self.grid.find(Piece::new(PieceType::Queen, color));
         let all_pieces = self.grid.pieces();
         let friendly_pieces = all_pieces
@@ -532,15 +649,16 @@ impl PositionGenerator<HexGrid> for ReferenceGenerator {
                     new_grid.add(Piece::new(PieceType::Queen, color), placement);
                     positions.insert(new_grid);
                 }
-                return positions;
+                return positions.ordered;
             }
         }
 
         // 1. Calculate placements
         itertools::iproduct!(self.pieces_in_hand(color), self.placements(color)).for_each(
             |(piece, placement)| {
-                let placement_disallowed =
-                    piece.piece_type == PieceType::Queen && num_friendly_pieces == 0;
+                let placement_disallowed = self.tournament_opening
+                    && piece.piece_type == PieceType::Queen
+                    && num_friendly_pieces == 0;
 
                 if !placement_disallowed {
                     let mut new_grid = self.grid.clone();
@@ -550,8 +668,10 @@ impl PositionGenerator<HexGrid> for ReferenceGenerator {
             },
         );
 
-        // Then 2. Calculate moves
-        for (stack, location) in all_pieces {
+        // Then 2. Calculate moves, pieces cannot move until the queen is placed
+        let mut movable = if queen.is_some() { all_pieces } else { vec![] };
+        movable.sort_by_key(|(_, location)| HexGrid::board_order(*location));
+        for (stack, location) in movable {
             let top = stack.last().unwrap();
             if top.color != color {
                 continue;
@@ -572,19 +692,55 @@ impl PositionGenerator<HexGrid> for ReferenceGenerator {
                 _ => Vec::new(),
             };
 
-            positions.extend(moves.into_iter());
-            positions.extend(swaps.into_iter());
+            for position in moves.into_iter().chain(swaps) {
+                positions.insert(position);
+            }
         }
 
         // If there are no possible moves, return this board to represent the
         // "pass" move
-        if positions.is_empty() {
+        if positions.ordered.is_empty() {
             positions.insert(self.grid.clone());
         }
-        positions
+        positions.ordered
     }
 }
 
+/// Positions in the order they were inserted, without duplicates when
+/// deduplicating, see ReferenceGenerator::set_deduplicate()
+struct Positions<B> {
+    ordered: Vec<B>,
+    /// The Zobrist hashes of the positions inserted, None when not
+    /// deduplicating
+    seen: Option<HashSet<u64>>,
+}
+
+impl<B: Board> Positions<B> {
+    fn new(deduplicate: bool) -> Self {
+        Positions {
+            ordered: vec![],
+            seen: deduplicate.then(HashSet::new),
+        }
+    }
+
+    fn insert(&mut self, position: B) {
+        let is_new = match self.seen.as_mut() {
+            Some(seen) => seen.insert(zobrist::hash(&position)),
+            None => true,
+        };
+        if is_new {
+            self.ordered.push(position);
+        }
+    }
+}
+
+/// Sorts *locations* in board order, see HexGrid::board_order()
+fn in_board_order(locations: impl IntoIterator<Item = HexLocation>) -> Vec<HexLocation> {
+    let mut locations = locations.into_iter().collect::<Vec<_>>();
+    locations.sort_by_key(|location| HexGrid::board_order(*location));
+    locations
+}
+
 pub trait FromHexGrid {
     /// Initializes this type from a HexGrid,
     /// with the previous change being the destination of the piece
@@ -699,8 +855,15 @@ pub trait PositionGenerator<Position: IntoPieces>:
     MoveGenerator<Position> + PlacementGenerator + SwapGenerator<Position>
 {
     /// Returns the legal positions reachable from the current board state
-    /// as if it is the turn of the specified color.
-    fn generate_positions_for(&mut self, color: PieceColor) -> HashSet<Position>;
+    /// as if it is the turn of the specified color, each once.
+    ///
+    /// The order is the same on every run: placements first, by piece type
+    /// in the order of PieceType::all() and then by location, followed by
+    /// the moves of each piece by location, a pillbug's throws coming after
+    /// its own moves. Locations are in board order (see HexGrid::board_order()),
+    /// throws by the piece thrown and then by destination. A position
+    /// reached by several moves is listed at the first of them
+    fn generate_positions_for(&mut self, color: PieceColor) -> Vec<Position>;
 }
 
 pub trait Position: Sized {
@@ -713,7 +876,8 @@ pub trait Position: Sized {
 mod tests {
     use super::ReferenceGenerator;
     use super::*;
-    use crate::testing_utils::compare_moves;
+    use crate::location::Coordinate;
+    use crate::testing::compare_moves;
 
     #[test]
     pub fn test_spider_gate() {
@@ -905,6 +1069,32 @@ mod tests {
         compare_moves(spider, selector, &grid, &spider_moves);
     }
 
+    #[test]
+    pub fn test_spider_paths() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        // Around a single piece the spider reaches the opposite hex both ways
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . . g S .\n",
+            ". . . . .\n\n",
+            "start - [0 0]\n\n"
+        ));
+        let generator = ReferenceGenerator::from_default(&grid);
+        let (spider, _) = grid.find(Piece::new(Spider, White)).unwrap();
+        let (grasshopper, _) = grid.find(Piece::new(Grasshopper, Black)).unwrap();
+        let paths = generator.spider_paths(spider);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0][3], paths[1][3]);
+        assert_eq!(paths[0][3], grasshopper.apply(Direction::W));
+        for path in paths.iter() {
+            assert_eq!(path[0], spider);
+            assert!(path.windows(2).all(|step| step[0].direction_to(step[1]).is_some()));
+            assert!(path[1..].iter().all(|hex| grid.is_outside(*hex)));
+        }
+    }
+
     #[test]
     pub fn test_grasshopper() {
         use PieceColor::*;
@@ -963,6 +1153,27 @@ mod tests {
         assert!(grasshopper_moves.is_empty());
     }
 
+    #[test]
+    pub fn test_grasshopper_edge() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        // Jumping east would land past the largest representable coordinate
+        let mut grid = HexGrid::new();
+        let grasshopper = HexLocation::new(Coordinate::MAX - 2, 0);
+        grid.add(Piece::new(Grasshopper, White), grasshopper);
+        grid.add(Piece::new(Ant, Black), HexLocation::new(Coordinate::MAX - 1, 0));
+        grid.add(Piece::new(Ant, Black), HexLocation::new(Coordinate::MAX, 0));
+        let generator = ReferenceGenerator::from_default(&grid);
+        assert!(generator.grasshopper_destinations(grasshopper).is_empty());
+
+        // Landing on the last representable hex is still allowed
+        grid.remove(HexLocation::new(Coordinate::MAX, 0));
+        let generator = ReferenceGenerator::from_default(&grid);
+        let destinations = generator.grasshopper_destinations(grasshopper);
+        assert_eq!(destinations, vec![HexLocation::new(Coordinate::MAX, 0)]);
+    }
+
     #[test]
     pub fn test_queen_pinned() {
         use PieceColor::*;
@@ -1178,7 +1389,7 @@ mod tests {
         let selector = concat!(
             ". . . . . . .\n",
             " a . * * a . .\n",
-            ". a . B * a .\n",
+            ". a . B *1 a .\n",
             " a . * * a . .\n",
             ". a a a . . .\n\n",
             "start - [0 0]\n\n"
@@ -1237,9 +1448,9 @@ mod tests {
         ));
         let selector = concat!(
             ". . . . . . .\n",
-            " . . . * * . .\n",
-            ". . . a 2 * .\n",
-            " . . . * * . .\n",
+            " . . . *3 *1 . .\n",
+            ". . . a 2 *1 .\n",
+            " . . . *3 *0 . .\n",
             ". . . . . . .\n\n",
             "start - [0 0]\n\n",
         );
@@ -1944,4 +2155,132 @@ mod tests {
         let mosquito_moves = generator.mosquito_moves(mosquito);
         assert!(mosquito_moves.is_empty());
     }
+
+    #[test]
+    pub fn test_generation_order() {
+        use crate::moves::Move;
+        use PieceColor::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . . .\n",
+            " . . g a . .\n",
+            ". . S q b .\n",
+            " . A G Q . .\n",
+            ". . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let generate = || {
+            let mut generator = ReferenceGenerator::from_default(&grid);
+            generator.generate_positions_for(White)
+        };
+        let positions = generate();
+        assert_eq!(positions, generate());
+        let unique = positions.iter().collect::<HashSet<_>>();
+        assert_eq!(unique.len(), positions.len());
+
+        // Placements by piece type then location, then moves by the location
+        // of the piece moved (there is no pillbug to throw pieces)
+        let types = PieceType::all(GameType::default());
+        let keys = positions
+            .iter()
+            .map(|position| match grid.diff(position).unwrap() {
+                Move::Place { piece, to } => (
+                    0,
+                    types.iter().position(|t| *t == piece.piece_type).unwrap(),
+                    HexGrid::board_order(to),
+                ),
+                Move::Movement { from, .. } => (1, 0, HexGrid::board_order(from)),
+                Move::Pass => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(keys.iter().any(|(kind, _, _)| *kind == 1));
+
+        let mut generator = ReferenceGenerator::from_default(&grid);
+        let (queen, _) = grid.find(Piece::new(PieceType::Queen, White)).unwrap();
+        let destinations = generator.destinations(queen);
+        let mut sorted = destinations.clone();
+        sorted.sort_by_key(|location| HexGrid::board_order(*location));
+        assert_eq!(destinations, sorted);
+        let placements = generator.placements(White);
+        let mut sorted = placements.clone();
+        sorted.sort_by_key(|location| HexGrid::board_order(*location));
+        assert_eq!(placements, sorted);
+    }
+
+    #[test]
+    pub fn test_no_moves_before_queen() {
+        use crate::moves::Move;
+        use PieceColor::*;
+
+        // White has two pieces on the board but not its queen
+        let grid = HexGrid::from_move_list(&["wA1", "bG1 wA1-", "wG1 -wA1", "bQ bG1-"]).unwrap();
+        let mut generator =
+            ReferenceGenerator::from_hex_grid(&grid, GameType::Standard, None);
+        let positions = generator.generate_positions_for(White);
+        assert!(!positions.is_empty());
+        for position in positions.iter() {
+            assert!(matches!(grid.diff(position), Some(Move::Place { .. })));
+        }
+        let (ant, _) = grid.find(Piece::new(PieceType::Ant, White)).unwrap();
+        assert!(generator.legal_destinations(White, ant).is_empty());
+
+        // Black has its queen, so its pieces move
+        let black = generator.generate_positions_for(Black);
+        assert!(black
+            .iter()
+            .any(|position| matches!(grid.diff(position), Some(Move::Movement { .. }))));
+    }
+
+    #[test]
+    pub fn test_too_many_pieces() {
+        use crate::moves::Move;
+        use PieceColor::*;
+
+        // Four white ants, one more than the set contains
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . . . .\n",
+            ". A A A A Q .\n",
+            " . . . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let mut generator = ReferenceGenerator::from_default(&grid);
+        let positions = generator.generate_positions_for(White);
+        let ant = Piece::new(PieceType::Ant, White);
+        assert!(positions.iter().all(|position| !matches!(
+            grid.diff(position),
+            Some(Move::Place { piece, .. }) if piece == ant
+        )));
+    }
+
+    #[test]
+    pub fn test_deduplicate() {
+        use PieceColor::*;
+
+        // The queen slides to the hexes the pillbug can throw it to
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . . .\n",
+            " . . q b . .\n",
+            ". . P Q . .\n",
+            " . . . . . .\n",
+            ". . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let mut generator =
+            ReferenceGenerator::from_hex_grid(&grid, GameType::P, None);
+        let unique = generator.generate_positions_for(White);
+        generator.set_deduplicate(false);
+        let all = generator.generate_positions_for(White);
+        assert!(unique.len() < all.len());
+        assert_eq!(
+            unique.iter().collect::<HashSet<_>>(),
+            all.iter().collect::<HashSet<_>>()
+        );
+        assert_eq!(unique.iter().collect::<HashSet<_>>().len(), unique.len());
+        // Each listed at the first move reaching it
+        let mut first = all.clone();
+        let mut seen = HashSet::new();
+        first.retain(|position| seen.insert(position.clone()));
+        assert_eq!(unique, first);
+    }
 }
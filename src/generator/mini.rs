@@ -58,7 +58,7 @@ impl MoveGenerator<MiniBitGrid> for MiniGenerator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::testing_utils::positions::test_suite::*;
+    use crate::testing::positions::test_suite::*;
 
     #[test] 
     fn test_spider_suite() {
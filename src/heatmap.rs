@@ -0,0 +1,94 @@
+use crate::game::GameDebugger;
+use crate::hex_grid::HexLocation;
+use std::collections::HashMap;
+
+/// How many legal moves start and end at each hex of a position, for
+/// overlays showing the mobile pieces and the contested hexes, see heatmap()
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Heatmap {
+    /// The number of destinations of the piece on top of every occupied hex,
+    /// zero for pieces that cannot move
+    pub mobility: HashMap<HexLocation, usize>,
+    /// The number of pieces that can move to each hex reached by any. Hexes
+    /// are empty, except those reached by climbing
+    pub reach: HashMap<HexLocation, usize>,
+}
+
+impl Heatmap {
+    /// The number of destinations of the piece at *location*
+    pub fn mobility(&self, location: HexLocation) -> usize {
+        self.mobility.get(&location).copied().unwrap_or(0)
+    }
+
+    /// The number of pieces that can move to *location*
+    pub fn reach(&self, location: HexLocation) -> usize {
+        self.reach.get(&location).copied().unwrap_or(0)
+    }
+
+    /// The number of moves of pieces on the board, placements excluded
+    pub fn moves(&self) -> usize {
+        self.mobility.values().sum()
+    }
+}
+
+/// Counts the legal moves of the player to move in the current position of
+/// *game* by the hex they start and end at. Pieces of the opponent move
+/// when a pillbug of the player can throw them, see
+/// GameDebugger::legal_destinations()
+pub fn heatmap(game: &GameDebugger) -> Heatmap {
+    let mut heatmap = Heatmap::default();
+    for (location, _) in game.position().occupied() {
+        let destinations = game.legal_destinations(location);
+        heatmap.mobility.insert(location, destinations.len());
+        for destination in destinations {
+            *heatmap.reach.entry(destination).or_default() += 1;
+        }
+    }
+    heatmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+    use crate::piece::{PieceColor, PieceType};
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_heatmap() {
+        let mut game = GameDebugger::from_move_list(
+            &[
+                "wA1", "bG1 -wA1", "wQ wA1-", "bQ /bG1", "wP wQ-", "bA1 \\bQ",
+            ],
+            GameType::MLP,
+        )
+        .unwrap();
+        let map = heatmap(&game);
+
+        let current = game.position().clone();
+        let movements = game
+            .legal_positions()
+            .iter()
+            .filter(|position| matches!(current.diff(position), Some(Move::Movement { .. })))
+            .count();
+        assert_eq!(map.moves(), movements);
+        assert_eq!(map.reach.values().sum::<usize>(), movements);
+        assert_eq!(map.mobility.len(), current.occupied().count());
+
+        // Only the white pillbug at the end of the line can move, the queen
+        // next to it is pinned
+        let (_, pillbug, _) = current
+            .pieces_of(PieceColor::White)
+            .into_iter()
+            .find(|(piece, _, _)| piece.piece_type == PieceType::Pillbug)
+            .unwrap();
+        assert_eq!(map.mobility(pillbug), 2);
+        assert_eq!(map.moves(), 2);
+        assert_eq!(map.reach.len(), 2);
+        assert!(map
+            .reach
+            .keys()
+            .all(|location| map.reach(*location) == 1 && current.height(*location) == 0));
+        assert_eq!(map.reach(pillbug), 0);
+    }
+}
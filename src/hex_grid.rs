@@ -1,7 +1,14 @@
+use crate::board::{Board, Neighbors, Stack};
+use crate::board_view::{BoardView, RowView, StackView};
+use crate::chunked_map::ChunkedMap;
+use crate::game::GameDebugger;
 use crate::generator::debug::Position;
 use crate::hex_grid_dsl::Parser;
+use crate::inventory::{Inventory, InventoryError};
 pub use crate::location::*;
+use crate::moves::Move;
 pub use crate::piece::*;
+use crate::uhp::GameType;
 pub use std::collections::HashMap;
 use std::collections::HashSet;
 use thiserror::Error;
@@ -20,18 +27,62 @@ impl GridBounds {
     pub fn height(&self) -> usize {
         self.bottom_right.0 - self.top_left.0 + 1
     }
+
+    fn around(cell: (usize, usize)) -> GridBounds {
+        GridBounds {
+            top_left: cell,
+            bottom_right: cell,
+        }
+    }
+
+    /// Grows the bounds to include the given (row, column) cell
+    fn include(&mut self, (row, col): (usize, usize)) {
+        self.top_left = (self.top_left.0.min(row), self.top_left.1.min(col));
+        self.bottom_right = (self.bottom_right.0.max(row), self.bottom_right.1.max(col));
+    }
+
+    /// Returns true if the (row, column) cell lies on the edge of the bounds
+    fn on_edge(&self, (row, col): (usize, usize)) -> bool {
+        row == self.top_left.0
+            || row == self.bottom_right.0
+            || col == self.top_left.1
+            || col == self.bottom_right.1
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum HexGridError {
     #[error("String input cannot be converted to piece")]
     PieceError,
+    #[error("Unknown direction {0}")]
+    DirectionError(String),
     #[error("Board is illegal, too many pieces on the top of the hive")]
     TooManyPiecesOnHive,
+    #[error("Move {index} ({move_string}) cannot be played: {reason}")]
+    InvalidMove {
+        index: usize,
+        move_string: String,
+        reason: String,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, HexGridError>;
 
+/// A way in which a position breaks the rules of Hive, see HexGrid::validate()
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    #[error("The hive is split into {0} disconnected groups")]
+    Disconnected(usize),
+    #[error("The {} at ({}, {}) is on top of another piece but cannot climb", .piece.piece_type.name(), .location.x, .location.y)]
+    IllegalStack { piece: Piece, location: HexLocation },
+    #[error("{} has more pieces of type {} than the set contains", .0.to_str(), .1.name())]
+    TooManyPieces(PieceColor, PieceType),
+    #[error("{} has a {} which is not part of this game type", .0.to_str(), .1.name())]
+    NotInGame(PieceColor, PieceType),
+    #[error("{} placed four pieces without placing the queen", .0.to_str())]
+    QueenMissing(PieceColor),
+}
+
 pub type Height = usize;
 pub const HEX_GRID_SIZE: usize = 60;
 pub const HEX_GRID_CENTER: (usize, usize) = (HEX_GRID_SIZE / 2, HEX_GRID_SIZE / 2);
@@ -46,9 +97,18 @@ pub const HEX_GRID_CENTER: (usize, usize) = (HEX_GRID_SIZE / 2, HEX_GRID_SIZE /
 ///
 /// HexLocation 0,0 is in the center of the grid to make
 /// the grid easier to reason about as Hive is a boardless "floating" game
+///
+/// Clones share the stacks and the frontier with the grid they were cloned
+/// from until either changes them, see ChunkedMap
 #[derive(Debug, Clone)]
 pub struct HexGrid {
-    fast_grid: HashMap<(usize, usize), Vec<Piece>>,
+    fast_grid: ChunkedMap<(usize, usize), Stack>,
+    /// Bounding box of the occupied locations in odd-r coordinates,
+    /// kept up to date by add() and remove()
+    bounds: Option<GridBounds>,
+    /// The number of occupied neighbors of every empty location touching
+    /// the hive, kept up to date by add() and remove(), see outside()
+    frontier: ChunkedMap<HexLocation, u8>,
 }
 
 impl HexGrid {
@@ -58,12 +118,37 @@ impl HexGrid {
         Parser::parse_selector(input).expect("Failed to parse selector")
     }
 
+    /// Like selector() but also reports the landing height written after
+    /// each "*", if any, see Parser::parse_height_selector()
+    pub fn height_selector(input: &str) -> Vec<(HexLocation, Option<Height>)> {
+        Parser::parse_height_selector(input).expect("Failed to parse selector")
+    }
+
     /// Translates a typical DSL string into a HexGrid, ignoring the
     /// "*" characters
     pub fn from_dsl(input: &str) -> Self {
         Parser::parse_hex_grid(input).expect("Failed to parse input into HexGrid")
     }
 
+    /// Plays UHP or standard notation MoveStrings (e.g. "wS1", "bG1 -wS1",
+    /// "wB1 wQ") from the empty board and returns the final position.
+    ///
+    /// Every move must be legal in Base+MLP, the index of the first
+    /// move that is not is reported
+    pub fn from_move_list(moves: &[&str]) -> Result<Self> {
+        let mut game =
+            GameDebugger::from_moves(&[]).expect("The empty game should always be valid");
+        for (index, move_string) in moves.iter().enumerate() {
+            game.make_move(move_string.trim())
+                .map_err(|e| HexGridError::InvalidMove {
+                    index,
+                    move_string: move_string.to_string(),
+                    reason: e.to_string(),
+                })?;
+        }
+        Ok(game.position().clone())
+    }
+
     pub fn from_pieces(input: Vec<(Vec<Piece>, HexLocation)>) -> Self {
         let mut grid = HexGrid::new();
         for (stack, location) in input {
@@ -74,49 +159,156 @@ impl HexGrid {
         grid
     }
 
-    pub fn new() -> HexGrid {
-        HexGrid {
-            fast_grid: HashMap::new(),
+    /// Returns a copy of the grid rotated clockwise around the center by *n*
+    /// times 60 degrees, see HexLocation::rotate60()
+    pub fn rotate60(&self, n: usize) -> HexGrid {
+        self.transform(|location| location.rotate60(n))
+    }
+
+    /// Returns a copy of the grid mirrored in the given axis through
+    /// the center, see HexLocation::reflect()
+    pub fn reflect(&self, axis: Axis) -> HexGrid {
+        self.transform(|location| location.reflect(axis))
+    }
+
+    /// Returns a copy of the grid with every stack moved by *delta*
+    pub fn translate(&self, delta: HexLocation) -> HexGrid {
+        self.transform(|location| location + delta)
+    }
+
+    /// Returns a copy of the grid translated so that the first stack in board
+    /// order is at the center, grids that only differ in where the hive lies
+    /// have equal normalizations
+    pub fn normalized(&self) -> HexGrid {
+        match self.pieces().first() {
+            Some((_, origin)) => self.translate(-*origin),
+            None => self.clone(),
         }
     }
 
-    /// Depth first search on the stack with a location not allowed to be visited
-    /// to determine if the location contains a pinned piece
-    fn dfs(
-        &self,
-        visited: &mut HashSet<HexLocation>,
-        disallowed: HexLocation,
-        current_location: HexLocation,
-    ) {
-        if visited.contains(&current_location) {
-            return;
-        }
-        if current_location == disallowed {
-            return;
-        }
-        visited.insert(current_location);
-        for neighbor in self.get_neighbors(current_location) {
-            if !self.peek(neighbor).is_empty() {
-                self.dfs(visited, disallowed, neighbor);
+    /// Returns the transform that takes this grid to its canonical form, the
+    /// least (in the order of pieces()) of the normalizations of its rotations
+    /// and reflections. Grids that are the same up to symmetry have equal
+    /// canonical forms, see canonical()
+    pub fn canonical_transform(&self) -> Transform {
+        let candidates = [false, true].into_iter().flat_map(|reflected| {
+            (0..6).map(move |rotation| Transform {
+                reflected,
+                rotation,
+                offset: HexLocation::center(),
+            })
+        });
+
+        let Some((_, origin)) = self.pieces().first().cloned() else {
+            return Transform::default();
+        };
+
+        let (mut least, mut best) = (None, Transform::default());
+        for mut transform in candidates {
+            // Rotate around the first stack so that the hive stays in the window
+            let start = transform.apply(origin);
+            transform.offset = -start;
+            let moved = self.transform(|location| transform.apply(location));
+            let (_, first) = moved.pieces()[0];
+            transform.offset -= first;
+
+            let pieces = moved.translate(-first).pieces();
+            if least.as_ref().is_none_or(|least| pieces < *least) {
+                (least, best) = (Some(pieces), transform);
             }
         }
+        best
+    }
+
+    /// Returns a copy of the grid in its canonical form, see canonical_transform()
+    pub fn canonical(&self) -> HexGrid {
+        self.apply_transform(&self.canonical_transform())
+    }
+
+    /// Returns a copy of the grid with every stack moved by *transform*
+    pub fn apply_transform(&self, transform: &Transform) -> HexGrid {
+        self.transform(|location| transform.apply(location))
+    }
+
+    /// Returns true if the grids hold the same stacks in the same arrangement,
+    /// regardless of where the hive lies, see normalized()
+    pub fn eq_up_to_translation(&self, other: &HexGrid) -> bool {
+        let (ours, theirs) = (self.pieces(), other.pieces());
+        if ours.len() != theirs.len() {
+            return false;
+        }
+        let Some(((_, ours_first), (_, theirs_first))) = ours.first().zip(theirs.first()) else {
+            return true;
+        };
+        let delta = *theirs_first - *ours_first;
+        ours.iter()
+            .zip(theirs.iter())
+            .all(|((ours, ours_location), (theirs, theirs_location))| {
+                ours == theirs && ours_location.add(delta) == *theirs_location
+            })
+    }
+
+    /// Moves every stack of a copy of the grid to the location given by *f*,
+    /// which must map distinct locations to distinct locations
+    fn transform(&self, f: impl Fn(HexLocation) -> HexLocation) -> HexGrid {
+        let pieces = self.pieces().into_iter();
+        HexGrid::from_pieces(
+            pieces
+                .map(|(stack, location)| (stack, f(location)))
+                .collect(),
+        )
+    }
+
+    pub fn new() -> HexGrid {
+        HexGrid {
+            fast_grid: ChunkedMap::new(),
+            bounds: None,
+            frontier: ChunkedMap::new(),
+        }
     }
 
     /// Returns the locations that are neighbors of the current pieces of hive, but
     /// that location contains no pieces
     pub fn outside(&self) -> HashSet<HexLocation> {
-        let mut outside = HashSet::new();
-        for (_, location) in self.pieces() {
-            for direction in Direction::all().iter() {
-                let neighbor = location.apply(*direction);
-                let stack = self.peek(neighbor);
-                if stack.is_empty() {
-                    outside.insert(neighbor);
-                }
+        self.frontier.keys().cloned().collect()
+    }
+
+    /// Whether *location* is empty and next to a piece of the hive, that is,
+    /// whether it is part of outside(). Does not allocate
+    pub fn is_outside(&self, location: HexLocation) -> bool {
+        self.frontier.contains_key(&location)
+    }
+
+    /// Updates the frontier after the empty *location* received a piece
+    fn occupy(&mut self, location: HexLocation) {
+        self.frontier.remove(&location);
+        for direction in Direction::all() {
+            let neighbor = location.apply(direction);
+            if self.height(neighbor) == 0 {
+                *self.frontier.get_or_default(neighbor) += 1;
             }
         }
+    }
 
-        outside
+    /// Updates the frontier after the last piece of *location* was removed
+    fn vacate(&mut self, location: HexLocation) {
+        let mut occupied = 0;
+        for direction in Direction::all() {
+            let neighbor = location.apply(direction);
+            if self.height(neighbor) > 0 {
+                occupied += 1;
+                continue;
+            }
+            if let Some(count) = self.frontier.get_mut(&neighbor) {
+                *count -= 1;
+                if *count == 0 {
+                    self.frontier.remove(&neighbor);
+                }
+            }
+        }
+        if occupied > 0 {
+            self.frontier.insert(location, occupied);
+        }
     }
 
     /// Returns the locations in the hive that are "pinned",
@@ -125,49 +317,31 @@ impl HexGrid {
     /// returns in board order, that is, first top-to-bottom then left-to-right
     /// Assumes that the pieces on the board already form "One Hive"
     pub fn pinned(&self) -> Vec<HexLocation> {
-        let mut pinned = vec![];
-        let hive = self
-            .pieces()
-            .into_iter()
-            .map(|(_, location)| location)
-            .collect::<Vec<_>>();
-
-        for &candidate in hive.iter() {
-            let mut visited = HashSet::new();
-            let neighbors = self.get_neighbors(candidate);
-            if !neighbors.is_empty() {
-                self.dfs(&mut visited, candidate, neighbors[0])
-            }
+        Board::pinned(self)
+    }
 
-            visited.insert(candidate);
-            if visited.len() != hive.len() {
-                pinned.push(candidate);
-            }
-        }
-        pinned
+    /// Returns the six locations surrounding a given location, in the order
+    /// of Direction::all(), each with its pieces stacked from bottom to top.
+    ///
+    /// Empty locations are included with no pieces, the location itself
+    /// does not need to be occupied
+    pub fn neighbors(
+        &self,
+        location: HexLocation,
+    ) -> impl Iterator<Item = (HexLocation, &[Piece])> + '_ {
+        Board::neighbors(self, location)
     }
 
-    /// Returns the empty locations surrounding a given location
-    pub fn get_empty_neighbors(&self, location: HexLocation) -> Vec<HexLocation> {
-        let mut neighbors = vec![];
-        for direction in Direction::all().iter() {
-            let loc = location.apply(*direction);
-            if self.peek(loc).is_empty() {
-                neighbors.push(loc);
-            }
-        }
-        neighbors
+    /// Returns the empty locations surrounding a given location,
+    /// see neighbors()
+    pub fn get_empty_neighbors(&self, location: HexLocation) -> Neighbors {
+        Board::get_empty_neighbors(self, location)
     }
-    /// Returns the non-empty locations surrounding a given location
-    pub fn get_neighbors(&self, location: HexLocation) -> Vec<HexLocation> {
-        let mut neighbors = vec![];
-        for direction in Direction::all().iter() {
-            let loc = location.apply(*direction);
-            if !self.peek(loc).is_empty() {
-                neighbors.push(loc);
-            }
-        }
-        neighbors
+
+    /// Returns the non-empty locations surrounding a given location,
+    /// see neighbors()
+    pub fn get_neighbors(&self, location: HexLocation) -> Neighbors {
+        Board::get_neighbors(self, location)
     }
 
     /// Returns locations that are neighbors of an given location but are
@@ -179,44 +353,8 @@ impl HexGrid {
         &self,
         location: HexLocation,
         effective_height: usize,
-    ) -> Vec<HexLocation> {
-        let mut slidable = vec![];
-        let original_neighbors = self.get_neighbors(location);
-
-        for direction in Direction::all().iter() {
-            let destination = location.apply(*direction);
-            let destination_height = self.peek(destination).len();
-            let final_height = destination_height + 1;
-            let effective_height = final_height.max(effective_height);
-
-            let (left_dir, right_dir) = direction.adjacent();
-            let (left, right) = (location.apply(left_dir), location.apply(right_dir));
-            let (left_stack, right_stack) = (self.peek(left), self.peek(right));
-
-            // Must be high enough to step over and through gate
-            let gate_requirement = left_stack.len().min(right_stack.len());
-            if effective_height <= gate_requirement {
-                continue;
-            }
-
-            let destination_neighbors = self.get_neighbors(destination);
-            // maintains contact if the destination has a piece
-            // or if the location has a piece under it
-            let mut maintains_contact = !self.peek(destination).is_empty();
-            maintains_contact = maintains_contact || effective_height > 1;
-
-            for destination_neighbor in destination_neighbors.iter() {
-                if original_neighbors.contains(destination_neighbor) {
-                    maintains_contact = true;
-                    break;
-                }
-            }
-
-            if maintains_contact {
-                slidable.push(destination);
-            }
-        }
-        slidable
+    ) -> Neighbors {
+        Board::slidable_locations_3d_height(self, location, effective_height)
     }
 
     /// Returns locations that are neighbors of an given location but are
@@ -224,9 +362,8 @@ impl HexGrid {
     /// sliding pieces and maintains contact with at least one of its original neighbors
     ///
     /// "3D" because it allows climbing up the hive
-    pub fn slidable_locations_3d(&self, location: HexLocation) -> Vec<HexLocation> {
-        let effective_height = self.peek(location).len();
-        self.slidable_locations_3d_height(location, effective_height)
+    pub fn slidable_locations_3d(&self, location: HexLocation) -> Neighbors {
+        Board::slidable_locations_3d(self, location)
     }
 
     /// Returns locations that are neighbors of an given location but are
@@ -234,102 +371,177 @@ impl HexGrid {
     /// sliding pieces and maintains contact with at least one of its original neighbors
     ///
     /// "2D" because it ignores the height of the pieces, disallowing climbing up the hive
-    pub fn slidable_locations_2d(&self, location: HexLocation) -> Vec<HexLocation> {
-        debug_assert!(self.peek(location).len() <= 1); // Cannot climb up the hive
-        let all_locations = self.slidable_locations_3d_height(location, 1);
-        all_locations
-            .into_iter()
-            .filter(|&loc| self.peek(loc).is_empty())
-            .collect()
+    pub fn slidable_locations_2d(&self, location: HexLocation) -> Neighbors {
+        Board::slidable_locations_2d(self, location)
     }
 
     /// Returns the first occurrence of a specified piece in the grid.
     /// The search occurs in board order, that is, from top to bottom, then left to right.
     /// If the piece is not found, None is returned.
     pub fn find(&self, piece: Piece) -> Option<(HexLocation, Height)> {
-        for (stack, location) in self.pieces() {
-            for (height, &stack_piece) in stack.iter().enumerate() {
-                if piece == stack_piece {
-                    return Some((location, height));
-                }
-            }
-        }
-        None
+        Board::find(self, piece)
+    }
+
+    /// Returns every occurrence of a specified piece in the grid, in board order
+    pub fn find_all(&self, piece: Piece) -> Vec<(HexLocation, Height)> {
+        let mut found = self
+            .occupied()
+            .flat_map(|(location, stack)| {
+                stack
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, &stack_piece)| stack_piece == piece)
+                    .map(move |(height, _)| (location, height))
+            })
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(location, height)| (HexGrid::board_order(*location), *height));
+        found
+    }
+
+    /// Returns every piece of the specified color with its location and height,
+    /// in board order and with stacks from bottom to top
+    pub fn pieces_of(&self, color: PieceColor) -> Vec<(Piece, HexLocation, Height)> {
+        let mut found = self
+            .occupied()
+            .flat_map(|(location, stack)| {
+                stack
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, piece)| piece.color == color)
+                    .map(move |(height, &piece)| (piece, location, height))
+            })
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(_, location, height)| (HexGrid::board_order(*location), *height));
+        found
+    }
+
+    /// Iterates over every occupied location and the stack of pieces there,
+    /// stacked from bottom to top.
+    ///
+    /// The iteration order is unspecified, use pieces() if board order is needed
+    pub fn occupied(&self) -> impl Iterator<Item = (HexLocation, &[Piece])> {
+        self.fast_grid
+            .iter()
+            .map(|(&(x, y), stack)| (HexGrid::uncentralize(x, y), stack.as_slice()))
+    }
+
+    /// The (row, column) key by which locations are sorted in board order
+    pub(crate) fn board_order(location: HexLocation) -> (usize, usize) {
+        let (q, r) = HexGrid::centralize(location);
+        HexGrid::axial_to_oddr(q as Coordinate, r as Coordinate)
     }
 
     fn uncentralize(x: usize, y: usize) -> HexLocation {
         HexLocation::new(
-            x as i8 - HEX_GRID_CENTER.0 as i8,
-            y as i8 - HEX_GRID_CENTER.1 as i8,
+            (x as Coordinate).wrapping_sub(HEX_GRID_CENTER.0 as Coordinate),
+            (y as Coordinate).wrapping_sub(HEX_GRID_CENTER.1 as Coordinate),
         )
     }
 
+    /// The key of *location* in the grid, distinct for every location a
+    /// HexLocation can hold as the shift wraps around instead of overflowing
     fn centralize(location: HexLocation) -> (usize, usize) {
         let (x, y) = (location.x, location.y);
-        let (x, y) = (x + HEX_GRID_CENTER.0 as i8, y + HEX_GRID_CENTER.1 as i8);
+        let (x, y) = (
+            x.wrapping_add(HEX_GRID_CENTER.0 as Coordinate),
+            y.wrapping_add(HEX_GRID_CENTER.1 as Coordinate),
+        );
         (x as usize, y as usize)
     }
 
     /// Adds a piece to the top of the stack at the given location
     pub fn add(&mut self, piece: Piece, location: HexLocation) {
         let (x, y) = HexGrid::centralize(location);
-        self.fast_grid.entry((x, y)).or_default().push(piece);
+        let stack = self.fast_grid.get_or_default((x, y));
+        stack.push(piece);
+        if stack.len() == 1 {
+            self.occupy(location);
+        }
+
+        let cell = HexGrid::board_order(location);
+        match self.bounds.as_mut() {
+            Some(bounds) => bounds.include(cell),
+            None => self.bounds = Some(GridBounds::around(cell)),
+        }
     }
 
     /// Removes the top-most piece from the stack at the given location
     pub fn remove(&mut self, location: HexLocation) -> Option<Piece> {
         let (x, y) = HexGrid::centralize(location);
-        let piece = self
-            .fast_grid
-            .get_mut(&(x, y))
-            .and_then(|stack| stack.pop());
-        if self
-            .fast_grid
-            .get(&(x, y))
-            .map(|stack| stack.len())
-            .unwrap_or(0)
-            == 0
-        {
+        let stack = self.fast_grid.get_mut(&(x, y))?;
+        let piece = stack.pop();
+        if stack.is_empty() {
             self.fast_grid.remove(&(x, y));
+            self.vacate(location);
+            let cell = HexGrid::board_order(location);
+            // Only emptying a hex on the edge can shrink the bounds
+            if self.bounds.as_ref().is_some_and(|b| b.on_edge(cell)) {
+                self.bounds = self.compute_bounds();
+            }
         }
         piece
     }
 
     /// Access a copy of the pieces at a given location
     /// stacked from bottom to top
-    pub fn peek(&self, location: HexLocation) -> Vec<Piece> {
+    pub fn peek(&self, location: HexLocation) -> Stack {
+        Board::peek(self, location)
+    }
+
+    /// The pieces at a given location stacked from bottom to top,
+    /// like peek() but without copying them
+    pub fn stack(&self, location: HexLocation) -> &[Piece] {
         let (x, y) = HexGrid::centralize(location);
-        self.axial(x, y)
+        self.fast_grid
+            .get(&(x, y))
+            .map_or(&[], |stack| stack.as_slice())
     }
 
     /// Acces the top most piece at a given location
     pub fn top(&self, location: HexLocation) -> Option<Piece> {
-        self.peek(location).last().cloned()
+        let (x, y) = HexGrid::centralize(location);
+        self.fast_grid
+            .get(&(x, y))
+            .and_then(|stack| stack.last().cloned())
+    }
+
+    /// The number of pieces stacked at a given location, 0 if it is empty
+    pub fn height(&self, location: HexLocation) -> usize {
+        let (x, y) = HexGrid::centralize(location);
+        self.fast_grid.get(&(x, y)).map_or(0, |stack| stack.len())
+    }
+
+    /// Returns true if the stack at a given location has more than one piece,
+    /// that is, if the pieces below the top piece are covered
+    pub fn is_covered(&self, location: HexLocation) -> bool {
+        self.height(location) > 1
     }
 
     /// Access the grid using the axial coordinate system,
     /// with only the pieces that are present at the location
     /// https://www.redblobgames.com/grids/hexagons/#coordinates-cube
+    ///
+    /// The coordinates are those of centralize(), which holds every location
+    /// and not only those of the HEX_GRID_SIZE window
     fn axial(&self, x: usize, y: usize) -> Vec<Piece> {
-        if x >= HEX_GRID_SIZE || y >= HEX_GRID_SIZE {
-            return vec![];
-        }
-        self.fast_grid.get(&(x, y)).unwrap_or(&vec![]).clone()
+        self.fast_grid
+            .get(&(x, y))
+            .map_or(vec![], |stack| stack.to_vec())
     }
 
-    pub fn oddr_to_axial(row: usize, col: usize) -> (i8, i8) {
-        let q = col as i8 - (row as i8 - ((row as i8) & 1)) / 2;
-        let r = row as i8;
+    pub fn oddr_to_axial(row: usize, col: usize) -> (Coordinate, Coordinate) {
+        let q = col as Coordinate - (row as Coordinate - ((row as Coordinate) & 1)) / 2;
+        let r = row as Coordinate;
         (q, r)
     }
 
-    pub fn evenr_to_axial(row: usize, col: usize) -> (i8, i8) {
-        let q = col as i8 - (row as i8 + ((row as i8) & 1)) / 2;
-        let r = row as i8;
+    pub fn evenr_to_axial(row: usize, col: usize) -> (Coordinate, Coordinate) {
+        let q = col as Coordinate - (row as Coordinate + ((row as Coordinate) & 1)) / 2;
+        let r = row as Coordinate;
         (q, r)
     }
 
-    pub fn axial_to_oddr(q: i8, r: i8) -> (usize, usize) {
+    pub fn axial_to_oddr(q: Coordinate, r: Coordinate) -> (usize, usize) {
         let col = q + (r - (r & 1)) / 2;
         let row = r;
         (row as usize, col as usize)
@@ -339,9 +551,6 @@ impl HexGrid {
     /// https://www.redblobgames.com/grids/hexagons/#coordinates-offset
     fn oddr(&self, row: usize, col: usize) -> Vec<Piece> {
         let (q, r) = HexGrid::oddr_to_axial(row, col);
-        if q < 0 {
-            return vec![];
-        } // out of bounds
         self.axial(q as usize, r as usize)
     }
 
@@ -361,7 +570,12 @@ impl HexGrid {
     ///  3 - [G b B]
     ///  2 - [a M]
     pub fn to_dsl(&self) -> String {
-        self.board_string() + "\n" + &self.start_string() + "\n\n" + &self.stacks_string()
+        let view = self.view();
+        let board = match self.is_empty() {
+            true => ".".to_owned(),
+            false => view.board_string(),
+        };
+        board + "\n" + &view.start_string() + "\n\n" + &view.stacks_string()
     }
 
     /// Returns the coordinate of the top-most and left-most corner of the
@@ -371,21 +585,7 @@ impl HexGrid {
     /// Will have the format
     /// start - [ <x> <y> ]
     pub fn start_string(&self) -> String {
-        let ((top, left), _) = self.bounds();
-
-        let top_row = top - 1;
-        let left_col = left - 1;
-
-        let (left_q, top_r) = HexGrid::oddr_to_axial(top_row, left_col);
-        let (left, top) = (
-            left_q - HEX_GRID_CENTER.0 as i8,
-            top_r - HEX_GRID_CENTER.1 as i8,
-        );
-
-        let mut start = "start - [".to_owned();
-        start.push_str(&format!(" {} {} ", left, top));
-        start.push(']');
-        start
+        self.view().start_string()
     }
 
     pub fn num_pieces(&self) -> usize {
@@ -400,27 +600,7 @@ impl HexGrid {
     /// <number> - [ <piece> <piece> ... ]
     /// ...
     pub fn stacks_string(&self) -> String {
-        let (min, max) = self.bounds();
-        let (top, left) = min;
-        let (bottom, right) = max;
-
-        let mut stack_string = String::new();
-
-        for row in top..=bottom {
-            for col in left..=right {
-                let pieces = self.oddr(row, col);
-                if pieces.len() > 1 {
-                    stack_string.push_str(&format!("{} - [ ", pieces.len()));
-                    for piece in pieces {
-                        stack_string.push_str(&piece.to_str());
-                        stack_string.push(' ');
-                    }
-                    stack_string.push_str("]\n");
-                }
-            }
-        }
-
-        stack_string
+        self.view().stacks_string()
     }
 
     /// Outputs the board part of this current grid according to the DSL
@@ -438,81 +618,197 @@ impl HexGrid {
         if self.is_empty() {
             return ".".to_owned();
         }
+        self.view().board_string()
+    }
 
-        let (min, max) = self.bounds();
-        let (min_row, min_col) = min;
-        let (max_row, max_col) = max;
-
-        let left = min_col - 1;
-        let right = max_col + 1;
-        let bottom = max_row + 1;
-        let top = min_row - 1;
-
-        let mut board = String::new();
-
-        for row in top..=bottom {
-            if row % 2 == 1 {
-                board.push(' ');
-            }
-            for col in left..=right {
-                let pieces = self.oddr(row, col);
+    /// Returns a structured view of the grid from which the DSL is rendered,
+    /// see BoardView
+    pub fn view(&self) -> BoardView {
+        if self.is_empty() {
+            let center = StackView {
+                location: HexLocation::center(),
+                pieces: vec![],
+            };
+            return BoardView {
+                rows: vec![RowView {
+                    indented: false,
+                    cells: vec![center],
+                }],
+            };
+        }
 
-                match pieces.len() {
-                    0 => board.push('.'),
-                    1 => board.push_str(&pieces[0].to_str()),
-                    _ => board.push_str(&format!("{}", pieces.len())),
-                }
-                // Do not add a space after the last column
-                if col != right {
-                    board.push(' ');
+        let GridBounds {
+            top_left: (min_row, min_col),
+            bottom_right: (max_row, max_col),
+        } = self.bounds.clone().unwrap();
+        let rows = (min_row - 1..=max_row + 1)
+            .map(|row| {
+                let cells = (min_col - 1..=max_col + 1)
+                    .map(|col| {
+                        let (q, r) = HexGrid::oddr_to_axial(row, col);
+                        let location = HexLocation::new(
+                            q - HEX_GRID_CENTER.0 as Coordinate,
+                            r - HEX_GRID_CENTER.1 as Coordinate,
+                        );
+                        StackView {
+                            location,
+                            pieces: self.oddr(row, col),
+                        }
+                    })
+                    .collect();
+                RowView {
+                    indented: row % 2 == 1,
+                    cells,
                 }
-            }
-            board.push('\n');
-        }
+            })
+            .collect();
 
-        board
+        BoardView { rows }
     }
 
     /// Returns a bounding box around all present pieces
     /// in the grid according the odd_r format as described here:
     /// https://www.redblobgames.com/grids/hexagons/#coordinates-offset
+    pub fn bounding_box(&self) -> Option<GridBounds> {
+        self.bounds.clone()
+    }
+
+    /// Computes the bounding box from scratch, in time proportional
+    /// to the number of occupied locations
+    fn compute_bounds(&self) -> Option<GridBounds> {
+        let mut cells = self
+            .occupied()
+            .map(|(location, _)| HexGrid::board_order(location));
+        let mut bounds = GridBounds::around(cells.next()?);
+        cells.for_each(|cell| bounds.include(cell));
+        Some(bounds)
+    }
+
+    /// Checks that the position could occur in a game of the given type,
+    /// reporting every violation found:
+    ///
+    /// - all pieces are connected as One Hive
+    /// - only beetles and mosquitos are on top of other pieces
+    /// - no player has more pieces than their set contains
+    /// - every player has placed their queen by their fourth piece
     ///
-    /// Returns (top-left, bottom-right) corners
-    fn bounds(&self) -> ((usize, usize), (usize, usize)) {
-        let mut min_row = HEX_GRID_SIZE;
-        let mut min_col = HEX_GRID_SIZE;
-        let mut max_row = 0;
-        let mut max_col = 0;
+    /// Passing validation does not guarantee that the position is reachable
+    /// from the empty board
+    pub fn validate(&self, game_type: GameType) -> std::result::Result<(), Vec<Violation>> {
+        let mut violations = vec![];
+
+        let groups = self.groups();
+        if groups > 1 {
+            violations.push(Violation::Disconnected(groups));
+        }
 
-        for (_, location) in self.pieces() {
-            let (x, y) = HexGrid::centralize(location);
-            let (row, col) = HexGrid::axial_to_oddr(x as i8, y as i8);
-            min_row = min_row.min(row);
-            min_col = min_col.min(col);
-            max_row = max_row.max(row);
-            max_col = max_col.max(col);
+        for (stack, location) in self.pieces() {
+            for &piece in stack.iter().skip(1) {
+                if !matches!(piece.piece_type, PieceType::Beetle | PieceType::Mosquito) {
+                    violations.push(Violation::IllegalStack { piece, location });
+                }
+            }
         }
 
-        ((min_row, min_col), (max_row, max_col))
-    }
+        for color in [PieceColor::White, PieceColor::Black] {
+            let mut inventory = Inventory::full(color, game_type);
+            for (piece, _, _) in self.pieces_of(color) {
+                let violation = match inventory.take(piece.piece_type) {
+                    Err(InventoryError::NoneLeft(piece_type)) => {
+                        Violation::TooManyPieces(color, piece_type)
+                    }
+                    Err(InventoryError::NotInGame(piece_type)) => {
+                        Violation::NotInGame(color, piece_type)
+                    }
+                    _ => continue,
+                };
+                if !violations.contains(&violation) {
+                    violations.push(violation);
+                }
+            }
 
-    pub fn bounding_box(&self) -> Option<GridBounds> {
-        if self.is_empty() {
-            return None;
+            let queen = Piece::new(PieceType::Queen, color);
+            if self.find(queen).is_none() && self.pieces_of(color).len() >= 4 {
+                violations.push(Violation::QueenMissing(color));
+            }
         }
 
-        let ((top, left), (bottom, right)) = self.bounds();
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
 
-        Some(GridBounds {
-            top_left: (top, left),
-            bottom_right: (bottom, right),
-        })
+    /// Counts the number of connected groups of stacks on the board
+    pub(crate) fn groups(&self) -> usize {
+        let mut visited = HashSet::new();
+        let mut groups = 0;
+        for (start, _) in self.occupied() {
+            if visited.contains(&start) {
+                continue;
+            }
+            groups += 1;
+            let mut frontier = vec![start];
+            while let Some(location) = frontier.pop() {
+                if visited.insert(location) {
+                    frontier.extend(self.get_neighbors(location));
+                }
+            }
+        }
+        groups
     }
 
     /// Checks to see if the board contains no pieces
     pub fn is_empty(&self) -> bool {
         self.fast_grid.is_empty()
     }
+
+    /// Infers the single move that transforms this grid into the *other* grid.
+    ///
+    /// Stacks are compared from the bottom up, every piece above the first
+    /// difference in a stack is considered removed (from this grid) or added
+    /// (to the other grid).
+    ///
+    /// Returns None if the grids differ by something other than a single
+    /// placement, a single movement of a piece, or a pass.
+    pub fn diff(&self, other: &HexGrid) -> Option<Move> {
+        let mut removed = vec![];
+        let mut added = vec![];
+
+        let locations = self
+            .fast_grid
+            .keys()
+            .chain(other.fast_grid.keys())
+            .collect::<HashSet<_>>();
+
+        for &(x, y) in locations {
+            let old_stack = self.axial(x, y);
+            let new_stack = other.axial(x, y);
+            let common = old_stack
+                .iter()
+                .zip(new_stack.iter())
+                .take_while(|(old, new)| old == new)
+                .count();
+
+            let location = HexGrid::uncentralize(x, y);
+            removed.extend(old_stack[common..].iter().map(|&piece| (piece, location)));
+            added.extend(new_stack[common..].iter().map(|&piece| (piece, location)));
+        }
+
+        match (removed.as_slice(), added.as_slice()) {
+            ([], []) => Some(Move::Pass),
+            ([], [(piece, to)]) => Some(Move::Place {
+                piece: *piece,
+                to: *to,
+            }),
+            ([(old_piece, from)], [(piece, to)]) if old_piece == piece => Some(Move::Movement {
+                piece: *piece,
+                from: *from,
+                to: *to,
+            }),
+            _ => None,
+        }
+    }
 }
 
 impl IntoPieces for HexGrid {
@@ -520,8 +816,8 @@ impl IntoPieces for HexGrid {
         let mut pieces = vec![];
         for (&(q, r), stack) in self.fast_grid.iter() {
             let location = HexGrid::uncentralize(q, r);
-            let (row, col) = HexGrid::axial_to_oddr(q as i8, r as i8);
-            pieces.push(((row, col), stack.clone(), location));
+            let (row, col) = HexGrid::axial_to_oddr(q as Coordinate, r as Coordinate);
+            pieces.push(((row, col), stack.to_vec(), location));
         }
         pieces.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
 
@@ -556,6 +852,48 @@ impl<I: HexGridConvertible> From<I> for HexGrid {
 
 impl Eq for HexGrid {}
 
+impl Board for HexGrid {
+    fn stack(&self, location: HexLocation) -> &[Piece] {
+        HexGrid::stack(self, location)
+    }
+
+    fn add(&mut self, piece: Piece, location: HexLocation) {
+        HexGrid::add(self, piece, location)
+    }
+
+    fn remove(&mut self, location: HexLocation) -> Option<Piece> {
+        HexGrid::remove(self, location)
+    }
+
+    fn occupied(&self) -> impl Iterator<Item = (HexLocation, &[Piece])> {
+        HexGrid::occupied(self)
+    }
+
+    fn top(&self, location: HexLocation) -> Option<Piece> {
+        HexGrid::top(self, location)
+    }
+
+    fn height(&self, location: HexLocation) -> usize {
+        HexGrid::height(self, location)
+    }
+
+    fn is_empty(&self) -> bool {
+        HexGrid::is_empty(self)
+    }
+
+    fn num_pieces(&self) -> usize {
+        HexGrid::num_pieces(self)
+    }
+
+    fn outside(&self) -> HashSet<HexLocation> {
+        HexGrid::outside(self)
+    }
+
+    fn is_outside(&self, location: HexLocation) -> bool {
+        HexGrid::is_outside(self, location)
+    }
+}
+
 impl Position for HexGrid {
     fn new() -> Self {
         HexGrid::new()
@@ -999,4 +1337,532 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    pub fn test_diff() {
+        let before = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". . a Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let placed = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". . a Q .\n",
+            " . . G . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let moved = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". . a . .\n",
+            " . . Q . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let climbed = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". . 2 . .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [a Q]\n",
+        ));
+
+        let queen = Piece::new(PieceType::Queen, PieceColor::White);
+        let grasshopper = Piece::new(PieceType::Grasshopper, PieceColor::White);
+        let (queen_loc, _) = before.find(queen).unwrap();
+        let (ant_loc, _) = before
+            .find(Piece::new(PieceType::Ant, PieceColor::Black))
+            .unwrap();
+
+        assert_eq!(before.diff(&before), Some(Move::Pass));
+        assert_eq!(
+            before.diff(&placed),
+            Some(Move::Place {
+                piece: grasshopper,
+                to: placed.find(grasshopper).unwrap().0,
+            })
+        );
+        assert_eq!(
+            before.diff(&moved),
+            Some(Move::Movement {
+                piece: queen,
+                from: queen_loc,
+                to: moved.find(queen).unwrap().0,
+            })
+        );
+        assert_eq!(
+            before.diff(&climbed),
+            Some(Move::Movement {
+                piece: queen,
+                from: queen_loc,
+                to: ant_loc,
+            })
+        );
+
+        // Two pieces changed, no single move can explain the difference
+        assert_eq!(placed.diff(&moved), None);
+        assert_eq!(before.diff(&HexGrid::new()), None);
+    }
+
+    #[test]
+    pub fn test_find_all() {
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a 2 A .\n",
+            " . q A . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [A a]\n",
+        ));
+
+        let white_ant = Piece::new(PieceType::Ant, PieceColor::White);
+        let black_ant = Piece::new(PieceType::Ant, PieceColor::Black);
+        let all_white_ants = grid.find_all(white_ant);
+        assert_eq!(all_white_ants.len(), 3);
+        assert_eq!(all_white_ants.first(), grid.find(white_ant).as_ref());
+        assert_eq!(grid.find_all(black_ant).len(), 2);
+        assert!(grid
+            .find_all(Piece::new(PieceType::Queen, PieceColor::White))
+            .is_empty());
+
+        let (stack_loc, _) = grid.find_all(black_ant)[1];
+        assert_eq!(grid.find_all(black_ant)[1], (stack_loc, 1));
+
+        let black = grid.pieces_of(PieceColor::Black);
+        assert_eq!(black.len(), 3);
+        assert!(black
+            .iter()
+            .all(|(piece, _, _)| piece.color == PieceColor::Black));
+        let in_board_order = grid
+            .pieces()
+            .into_iter()
+            .flat_map(|(stack, location)| {
+                stack
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(height, piece)| (piece, location, height))
+            })
+            .filter(|(piece, _, _)| piece.color == PieceColor::Black)
+            .collect::<Vec<_>>();
+        assert_eq!(black, in_board_order);
+
+        let occupied = grid.occupied().collect::<Vec<_>>();
+        assert_eq!(occupied.len(), 5);
+        for (location, stack) in occupied {
+            assert_eq!(grid.peek(location).as_slice(), stack);
+        }
+    }
+
+    #[test]
+    pub fn test_height_queries() {
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a 3 Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "3 - [A b G]\n",
+        ));
+
+        let (ant, _) = grid
+            .find(Piece::new(PieceType::Ant, PieceColor::Black))
+            .unwrap();
+        let (stack, _) = grid
+            .find(Piece::new(PieceType::Ant, PieceColor::White))
+            .unwrap();
+        let empty = ant.apply(Direction::SE);
+
+        assert_eq!(grid.height(ant), 1);
+        assert_eq!(grid.height(stack), 3);
+        assert_eq!(grid.height(empty), 0);
+
+        assert!(!grid.is_covered(ant));
+        assert!(grid.is_covered(stack));
+        assert!(!grid.is_covered(empty));
+
+        assert_eq!(
+            grid.top(stack),
+            Some(Piece::new(PieceType::Grasshopper, PieceColor::White))
+        );
+        assert_eq!(grid.top(empty), None);
+    }
+
+    #[test]
+    pub fn test_cached_bounds() {
+        let mut grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a 2 Q .\n",
+            " . . . g .\n",
+            ". . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [A b]\n",
+        ));
+        assert_eq!(grid.bounding_box(), grid.compute_bounds());
+        let bounds = grid.bounding_box().unwrap();
+        assert_eq!((bounds.width(), bounds.height()), (4, 2));
+
+        // Removing the top of a stack leaves the bounds unchanged
+        let (stack, _) = grid
+            .find(Piece::new(PieceType::Beetle, PieceColor::Black))
+            .unwrap();
+        grid.remove(stack);
+        assert_eq!(grid.bounding_box(), Some(bounds));
+
+        let (grasshopper, _) = grid
+            .find(Piece::new(PieceType::Grasshopper, PieceColor::Black))
+            .unwrap();
+        grid.remove(grasshopper);
+        assert_eq!(grid.bounding_box(), grid.compute_bounds());
+        assert_eq!(grid.bounding_box().unwrap().height(), 1);
+
+        let far = grasshopper.apply(Direction::SE).apply(Direction::SE);
+        grid.add(Piece::new(PieceType::Spider, PieceColor::White), far);
+        assert_eq!(grid.bounding_box(), grid.compute_bounds());
+
+        for (_, location) in grid.pieces() {
+            while grid.remove(location).is_some() {}
+        }
+        assert_eq!(grid.bounding_box(), None);
+        assert_eq!(grid.to_dsl(), HexGrid::new().to_dsl());
+    }
+
+    #[test]
+    pub fn test_incremental_outside() {
+        // Recomputes outside() from scratch
+        fn scanned(grid: &HexGrid) -> HashSet<HexLocation> {
+            grid.pieces()
+                .into_iter()
+                .flat_map(|(_, location)| grid.get_empty_neighbors(location))
+                .collect()
+        }
+
+        let mut grid = HexGrid::from_dsl(concat!(
+            " . . . . . .\n",
+            ". . a . a .\n",
+            " . a 2 a . .\n",
+            ". . a . . .\n",
+            " . . a a . .\n",
+            ". . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [a B]\n",
+        ));
+        assert_eq!(grid.outside(), scanned(&grid));
+
+        let mut removed = vec![];
+        for (stack, location) in grid.pieces() {
+            for _ in stack {
+                removed.push((grid.remove(location).unwrap(), location));
+                assert_eq!(grid.outside(), scanned(&grid));
+                for &location in scanned(&grid).iter() {
+                    assert!(grid.is_outside(location));
+                }
+            }
+        }
+        assert!(grid.outside().is_empty());
+
+        for (piece, location) in removed.into_iter().rev() {
+            grid.add(piece, location);
+            assert_eq!(grid.outside(), scanned(&grid));
+        }
+        assert!(!grid.is_outside(HexLocation::new(0, 0)));
+    }
+
+    #[test]
+    pub fn test_neighbors() {
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . .\n",
+            ". a 2 .\n",
+            " . Q . .\n",
+            ". . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [g B]\n",
+        ));
+        let (queen, _) = grid
+            .find(Piece::new(PieceType::Queen, PieceColor::White))
+            .unwrap();
+
+        let neighbors = grid.neighbors(queen).collect::<Vec<_>>();
+        assert_eq!(neighbors.len(), 6);
+        for ((location, stack), direction) in neighbors.iter().zip(Direction::all()) {
+            assert_eq!(*location, queen.apply(direction));
+            assert_eq!(stack.to_vec(), grid.peek(*location).to_vec());
+        }
+        let heights = neighbors
+            .iter()
+            .map(|(_, stack)| stack.len())
+            .collect::<Vec<_>>();
+        assert_eq!(heights.iter().filter(|&&height| height > 0).count(), 2);
+        assert!(heights.contains(&2));
+
+        assert_eq!(grid.get_neighbors(queen).len(), 2);
+        assert_eq!(grid.get_empty_neighbors(queen).len(), 4);
+
+        // Empty locations have neighbors too
+        let empty = queen.apply(Direction::SE);
+        assert!(grid.stack(empty).is_empty());
+        assert_eq!(grid.get_neighbors(empty).to_vec(), vec![queen]);
+    }
+
+    #[test]
+    pub fn test_clones_share_stacks() {
+        let moves = ["wS1", "bG1 -wS1", "wQ wS1-", "bQ -bG1", "wA1 wQ-"];
+        let grid = HexGrid::from_move_list(&moves).unwrap();
+        let mut moved = grid.clone();
+        let (from, _) = grid
+            .find(Piece::new(PieceType::Ant, PieceColor::White))
+            .unwrap();
+        let to = grid.get_empty_neighbors(from)[0];
+        let ant = moved.remove(from).unwrap();
+        moved.add(ant, to);
+
+        assert_eq!(
+            grid.diff(&moved),
+            Some(Move::Movement {
+                piece: ant,
+                from,
+                to
+            })
+        );
+        assert_eq!(grid.height(from), 1);
+        assert!(moved.fast_grid.shared_chunks(&grid.fast_grid) > 0);
+        assert!(moved.frontier.shared_chunks(&grid.frontier) > 0);
+        assert_eq!(
+            moved.outside(),
+            HexGrid::from_pieces(moved.pieces()).outside()
+        );
+    }
+
+    #[test]
+    pub fn test_far_coordinates() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        // Hives that drifted out of the HEX_GRID_SIZE window either way
+        for origin in [HexLocation::new(40, 0), HexLocation::new(-40, -37)] {
+            let mut grid = HexGrid::new();
+            let (queen, ant) = (Piece::new(Queen, White), Piece::new(Ant, Black));
+            grid.add(queen, origin);
+            grid.add(ant, origin.apply(Direction::E));
+
+            let to = origin.apply(Direction::SW);
+            let mut moved = grid.clone();
+            moved.remove(origin.apply(Direction::E));
+            moved.add(ant, to);
+            assert_eq!(
+                grid.diff(&moved),
+                Some(Move::Movement {
+                    piece: ant,
+                    from: origin.apply(Direction::E),
+                    to
+                })
+            );
+            assert_eq!(
+                HexGrid::new().diff(&HexGrid::from_pieces(vec![(vec![queen], origin)])),
+                Some(Move::Place {
+                    piece: queen,
+                    to: origin
+                })
+            );
+
+            let board = moved.board_string();
+            assert_eq!(board.matches('Q').count(), 1);
+            assert_eq!(board.matches('a').count(), 1);
+            assert_eq!(HexGrid::from_dsl(&moved.to_dsl()), moved);
+        }
+    }
+
+    #[test]
+    pub fn test_from_move_list() {
+        use PieceColor::*;
+
+        let moves = [
+            "wS1", "bG1 -wS1", "wB1 wS1-", "bQ -bG1", r"wQ wS1\", "bA1 -bQ", "wB1 wS1",
+        ];
+        let grid = HexGrid::from_move_list(&moves).unwrap();
+        assert_eq!(grid.num_pieces(), 6);
+        let (location, _) = grid.find(Piece::new(PieceType::Beetle, White)).unwrap();
+        assert_eq!(
+            grid.stack(location),
+            [
+                Piece::new(PieceType::Spider, White),
+                Piece::new(PieceType::Beetle, White)
+            ]
+        );
+
+        let error = HexGrid::from_move_list(&["wS1", "bG1 -wS1", "wB1 wS1", "bQ -bG1"]);
+        assert!(matches!(
+            error,
+            Err(HexGridError::InvalidMove { index: 2, .. })
+        ));
+    }
+
+    #[test]
+    pub fn test_transforms() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . Q 2 a .\n",
+            ". . g . .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ b B ]\n",
+        ));
+        let queen = grid
+            .find(Piece::new(PieceType::Queen, PieceColor::White))
+            .unwrap()
+            .0;
+
+        let rotated = grid.rotate60(1);
+        let (rotated_queen, _) = rotated
+            .find(Piece::new(PieceType::Queen, PieceColor::White))
+            .unwrap();
+        assert_eq!(rotated_queen, queen.rotate60(1));
+        assert_eq!(HexLocation::new(1, 0).rotate60(1), HexLocation::new(0, 1));
+        assert_eq!(grid.rotate60(6), grid);
+        assert_eq!(rotated.rotate60(5), grid);
+        assert_eq!(rotated.num_pieces(), grid.num_pieces());
+        assert_eq!(rotated.stacks_string(), grid.stacks_string());
+
+        for axis in [Axis::EastWest, Axis::NorthEast, Axis::NorthWest] {
+            assert_eq!(grid.reflect(axis).reflect(axis), grid);
+            assert_ne!(grid.reflect(axis), grid);
+        }
+        let east = HexLocation::center().apply(Direction::E);
+        let north_east = HexLocation::center().apply(Direction::NE);
+        let north_west = HexLocation::center().apply(Direction::NW);
+        assert_eq!(east.reflect(Axis::EastWest), east);
+        assert_eq!(north_east.reflect(Axis::NorthEast), north_east);
+        assert_eq!(north_west.reflect(Axis::NorthWest), north_west);
+        assert_eq!(
+            north_east.reflect(Axis::EastWest),
+            HexLocation::center().apply(Direction::SE)
+        );
+
+        let delta = HexLocation::new(3, -2);
+        let translated = grid.translate(delta);
+        assert_eq!(translated.board_string(), grid.board_string());
+        assert_eq!(translated.translate(HexLocation::new(-3, 2)), grid);
+
+        // Transforms keep the neighbors of every piece
+        for transformed in [rotated, grid.reflect(Axis::NorthEast), translated] {
+            assert_eq!(transformed.groups(), 1);
+            assert_eq!(transformed.pinned().len(), grid.pinned().len());
+        }
+    }
+
+    #[test]
+    pub fn test_eq_up_to_translation() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . Q 2 a .\n",
+            ". . g . .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ b B ]\n",
+        ));
+        let delta = HexLocation::new(3, -2);
+        let translated = grid.translate(delta);
+        let rotated = grid.rotate60(1);
+        assert_ne!(translated, grid);
+        assert!(translated.eq_up_to_translation(&grid));
+        assert_eq!(translated.normalized(), grid.normalized());
+        assert!(!rotated.eq_up_to_translation(&grid));
+        assert!(HexGrid::new().eq_up_to_translation(&HexGrid::new()));
+        assert!(!HexGrid::new().eq_up_to_translation(&grid));
+
+        // The same stacks in a different arrangement
+        let mut moved = grid.clone();
+        let ant = moved
+            .find(Piece::new(PieceType::Ant, PieceColor::Black))
+            .unwrap()
+            .0;
+        let ant_piece = moved.remove(ant).unwrap();
+        moved.add(ant_piece, ant.apply(Direction::SE));
+        assert!(!moved.translate(delta).eq_up_to_translation(&grid));
+    }
+
+    #[test]
+    pub fn test_canonical() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . Q 2 a .\n",
+            ". . g . .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ b B ]\n",
+        ));
+        let canonical = grid.canonical();
+        for axis in [Axis::EastWest, Axis::NorthEast, Axis::NorthWest] {
+            for n in 0..6 {
+                let symmetric = grid
+                    .reflect(axis)
+                    .rotate60(n)
+                    .translate(HexLocation::new(2, -1));
+                assert_eq!(symmetric.canonical(), canonical);
+                assert_eq!(grid.rotate60(n).canonical(), canonical);
+            }
+        }
+        assert_eq!(HexGrid::new().canonical(), HexGrid::new());
+
+        let transform = grid.canonical_transform();
+        for (location, _) in grid.occupied() {
+            assert_eq!(transform.invert(transform.apply(location)), location);
+            assert_eq!(
+                canonical.stack(transform.apply(location)),
+                grid.stack(location)
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_validate() {
+        let valid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a 2 Q .\n",
+            " . . q . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [A b]\n",
+        ));
+        assert_eq!(valid.validate(GameType::Standard), Ok(()));
+        assert_eq!(HexGrid::new().validate(GameType::Standard), Ok(()));
+
+        let disconnected = HexGrid::from_dsl(concat!(
+            " . . . . . .\n",
+            ". a . Q . q\n",
+            " . . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert_eq!(
+            disconnected.validate(GameType::Standard),
+            Err(vec![Violation::Disconnected(3)])
+        );
+
+        let illegal = HexGrid::from_dsl(concat!(
+            " . . . . . .\n",
+            ". A A A A .\n",
+            " . 2 . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [G P]\n",
+        ));
+        let (stack, _) = illegal
+            .find(Piece::new(PieceType::Pillbug, PieceColor::White))
+            .unwrap();
+        let violations = illegal.validate(GameType::Standard).unwrap_err();
+        assert_eq!(
+            violations,
+            vec![
+                Violation::IllegalStack {
+                    piece: Piece::new(PieceType::Pillbug, PieceColor::White),
+                    location: stack,
+                },
+                Violation::TooManyPieces(PieceColor::White, PieceType::Ant),
+                Violation::NotInGame(PieceColor::White, PieceType::Pillbug),
+                Violation::QueenMissing(PieceColor::White),
+            ]
+        );
+        assert_eq!(
+            Violation::TooManyPieces(PieceColor::White, PieceType::Ant).to_string(),
+            "White has more pieces of type ant than the set contains"
+        );
+
+        // The pillbug is part of the game, but still cannot be on top of the grasshopper
+        let violations = illegal.validate(GameType::P).unwrap_err();
+        assert_eq!(violations.len(), 3);
+    }
 }
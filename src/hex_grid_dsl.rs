@@ -23,7 +23,7 @@ pub enum ParserError {
 /// The idea is to take a string of a format such as the following, and to
 /// interpret it deterministically as a HexGrid:
 ///
-/// ```
+/// ```text
 ///  . . . . .
 ///   . Q 3 g .
 ///  . . A b .
@@ -49,7 +49,7 @@ pub enum ParserError {
 ///
 /// More concretely, the syntax for a valid_dsl is as follows:
 ///
-/// ```
+/// ```text
 /// (All rules ignore whitespace unless specifically in quotes)
 /// (Rules are defined in the following format ===> <rulename>: REGEX)
 /// (Rules can also have one or more integers associated with it ===> <rulename>(n): REGEX{n}
@@ -58,7 +58,7 @@ pub enum ParserError {
 ///     whitespace: ' '
 ///     newline: '\n'
 ///     piece: ( [a-z] | [A-Z] )
-///     stack: ( [2-7] )
+///     stack: ( [2-9] | [1-9][0-9]+ )
 ///     integer: '-'?[0-9]+
 ///     empty: '.'
 ///
@@ -74,14 +74,19 @@ pub enum ParserError {
 ///     start_desc: "start" "-" "[" <integer> <whitespace> <integer> "]" <newline> <newline>
 ///     stack_desc(n): n "-" "[" (<piece> <whitespace>){n} "]" <newline>
 ///
-///     valid_dsl: <board> <start_desc> (<stack_desc>)*
+///     (a stack may be of any height of at least 2, and its stack_desc
+///      must list exactly that many pieces)
+///
+///     comment: "#" [^\n]* <newline>
+///
+///     valid_dsl: <board> <start_desc> (<stack_desc> | <comment>)*
 ///
 /// ```
 ///
 /// The above string can also be used not to create a HexGrid, but instead to
 /// produce locations at certain marked spots in the grid with the following format:
 ///
-/// ```
+/// ```text
 ///  . . . . .
 ///   . * . * .
 ///  . . * * .
@@ -96,17 +101,25 @@ pub enum ParserError {
 /// start location in the top left corner.
 ///
 /// In which case the syntax above is augmented to include the following:
+/// ```text
+/// height: [0-9]
+/// hex: <empty> | <stack> | <piece> | "*" <height>?
 /// ```
-/// hex: <empty> | <stack> | <piece> | "*"
-/// ```
+///
+/// A "*" may be followed by a height to mark a destination where a piece
+/// is expected to land on top of the hive, for example "*1" for a piece
+/// climbing onto a single piece and "*0" for a piece that must end up
+/// on the ground. See Parser::parse_height_selector()
 pub struct Parser {}
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum BoardInput {
     Piece(Piece),
     Stack(u8),
-    StackPieces([Option<Piece>; 7]),
-    Star,
+    /// The pieces of a stack from bottom to top
+    StackPieces(Vec<Piece>),
+    /// A selected location, with the expected landing height if given
+    Star(Option<u8>),
     Empty,
 }
 
@@ -123,12 +136,20 @@ impl Parser {
     /// and returns a vector of corresponding HexLocations in board order referring
     /// to the "*" characters found.
     pub fn parse_selector(input: &str) -> Result<Vec<HexLocation>> {
+        let selectors = Parser::parse_height_selector(input)?;
+        Ok(selectors.into_iter().map(|(loc, _)| loc).collect())
+    }
+
+    /// Like parse_selector() but also returns the height given after each "*",
+    /// that is, the number of pieces a piece moving to the location is
+    /// expected to land on top of
+    pub fn parse_height_selector(input: &str) -> Result<Vec<(HexLocation, Option<Height>)>> {
         let (board_inputs, _) = Parser::parse_head(input)?;
         let selector_locations = board_inputs
             .iter()
             .filter_map(|(input, loc)| {
-                if let BoardInput::Star = input {
-                    Some(*loc)
+                if let BoardInput::Star(height) = input {
+                    Some((*loc, height.map(|height| height as Height)))
                 } else {
                     None
                 }
@@ -149,9 +170,7 @@ impl Parser {
             match piece {
                 BoardInput::StackPieces(stack) => {
                     for piece in stack.iter() {
-                        if let Some(piece) = piece {
-                            grid.add(*piece, *loc);
-                        }
+                        grid.add(*piece, *loc);
                     }
                 }
                 BoardInput::Piece(piece) => grid.add(*piece, *loc),
@@ -208,19 +227,27 @@ impl Parser {
         let mut space_count = 0; // Consecutive # of spaces
         let mut piece_count = 0; // Consecutive # of pieces
 
-        for input in row.chars() {
+        let mut chars = row.chars().peekable();
+        while let Some(input) = chars.next() {
             match input {
                 '.' => {
                     board_inputs.push(BoardInput::Empty);
                     space_count = 0;
                 }
-                '2'..='7' => {
+                '1'..='9' => {
                     if piece_count > 0 {
                         return Err(RowError("Invalid stack placement".to_string()));
                     }
 
-                    let digit = input.to_digit(10).unwrap() as u8;
-                    board_inputs.push(BoardInput::Stack(digit));
+                    let mut height = input.to_string();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        height.push(digit);
+                    }
+                    let height = match height.parse::<u8>() {
+                        Ok(height) if height >= 2 => height,
+                        _ => return Err(RowError(format!("Invalid stack height: {}", height))),
+                    };
+                    board_inputs.push(BoardInput::Stack(height));
                     space_count = 0;
                     piece_count = 1;
                 }
@@ -258,7 +285,9 @@ impl Parser {
                     piece_count = 1;
                 }
                 '*' => {
-                    board_inputs.push(BoardInput::Star);
+                    let height = chars.next_if(char::is_ascii_digit);
+                    let height = height.map(|digit| digit.to_digit(10).unwrap() as u8);
+                    board_inputs.push(BoardInput::Star(height));
                     space_count = 0;
                 }
                 ' ' => {
@@ -346,7 +375,7 @@ impl Parser {
                     HexGrid::oddr_to_axial(y, x)
                 };
 
-                result.push((*input, HexLocation::new(q, r)))
+                result.push((input.clone(), HexLocation::new(q, r)))
             }
         }
 
@@ -368,15 +397,15 @@ impl Parser {
             return Err(ParserError::StartSyntaxError);
         };
 
-        let x = captures[1].parse::<i8>().unwrap();
-        let y = captures[2].parse::<i8>().unwrap();
+        let x = captures[1].parse::<Coordinate>().unwrap();
+        let y = captures[2].parse::<Coordinate>().unwrap();
         let start_location = HexLocation::new(x, y);
 
         let mut result = Vec::new();
         for loc in pieces {
             let (piece, piece_loc) = loc;
             let new_loc = piece_loc.add(start_location);
-            result.push((*piece, new_loc));
+            result.push((piece.clone(), new_loc));
         }
 
         Ok(result)
@@ -392,6 +421,9 @@ impl Parser {
         let mut result = Vec::new();
         let mut index = 0;
         for line in input.lines() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
             let re = Regex::new(r"(\d+)\s*-\s*\[\s*((\w\s*){2,})\s*\]").unwrap();
             let captures = re.captures(line);
             let Some(captures) = captures else {
@@ -402,11 +434,11 @@ impl Parser {
                 .parse::<u8>()
                 .expect("Couldn't parse stack number");
             while index < pieces.len() {
-                let (input, _) = pieces[index];
+                let (input, _) = &pieces[index];
                 if matches!(input, BoardInput::Stack(_)) {
                     break;
                 }
-                result.push(pieces[index]);
+                result.push(pieces[index].clone());
                 index += 1
             }
 
@@ -416,9 +448,9 @@ impl Parser {
                 ));
             }
 
-            let (stack, loc) = pieces[index];
+            let (stack, loc) = &pieces[index];
 
-            let num = match stack {
+            let num = match *stack {
                 BoardInput::Stack(num) => num,
                 _ => unreachable!(),
             };
@@ -427,8 +459,8 @@ impl Parser {
                 return Err(ParserError::StackParseError(format!("Stack number mismatch, check that stack string is in board order. Expected {} Got {} on line {}", num, stack_num, line)));
             }
 
-            let mut stack = [None; 7];
-            for (i, piece) in captures[2].split_whitespace().enumerate() {
+            let mut stack = vec![];
+            for piece in captures[2].split_whitespace() {
                 let piece_type = piece.chars().next().unwrap().try_into();
                 if piece_type.is_err() {
                     return Err(ParserError::StackParseError(format!(
@@ -444,16 +476,24 @@ impl Parser {
                     PieceColor::White
                 };
 
-                let piece = Piece::new(piece_type, color);
-                stack[i] = Some(piece)
+                stack.push(Piece::new(piece_type, color));
+            }
+
+            if stack.len() != num as usize {
+                return Err(ParserError::StackParseError(format!(
+                    "Stack of height {} lists {} pieces on line {}",
+                    num,
+                    stack.len(),
+                    line
+                )));
             }
 
-            result.push((BoardInput::StackPieces(stack), loc));
+            result.push((BoardInput::StackPieces(stack), *loc));
             index += 1;
         }
 
         while index < pieces.len() {
-            result.push(pieces[index]);
+            result.push(pieces[index].clone());
             index += 1;
         }
 
@@ -551,8 +591,8 @@ mod tests {
         );
         let board = result.unwrap();
         assert_eq!(board.len(), 1);
-        let (input, _) = board[0];
-        assert_eq!(input, BoardInput::Piece(Piece::new(Ant, White)));
+        let (input, _) = &board[0];
+        assert_eq!(*input, BoardInput::Piece(Piece::new(Ant, White)));
     }
 
     #[test]
@@ -753,44 +793,30 @@ mod tests {
 
         let pieces = Parser::parse_stacks(parse_string, &pieces).expect("Couldn't parse stacks");
 
-        let stack_2 = BoardInput::StackPieces([
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            None,
-            None,
-            None,
-            None,
-            None,
+        let stack_2 = BoardInput::StackPieces(vec![
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
         ]);
 
-        let stack_3 = BoardInput::StackPieces([
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            None,
-            None,
-            None,
-            None,
+        let stack_3 = BoardInput::StackPieces(vec![
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
         ]);
 
-        let stack_4 = BoardInput::StackPieces([
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            None,
-            None,
-            None,
+        let stack_4 = BoardInput::StackPieces(vec![
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
         ]);
 
-        let stack_5 = BoardInput::StackPieces([
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            Some(Piece::new(Mosquito, Black)),
-            None,
-            None,
+        let stack_5 = BoardInput::StackPieces(vec![
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
+            Piece::new(Mosquito, Black),
         ]);
 
         let white_ant = BoardInput::Piece(Piece::new(Ant, White));
@@ -807,6 +833,35 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_tall_stacks() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        let mut grid = HexGrid::new();
+        let center = HexLocation::center();
+        grid.add(Piece::new(Queen, White), center);
+        for _ in 0..11 {
+            grid.add(Piece::new(Beetle, Black), center);
+        }
+        grid.add(Piece::new(Ant, Black), center.apply(Direction::E));
+
+        let dsl = grid.to_dsl();
+        assert!(dsl.contains(" 12 a "));
+        assert_eq!(Parser::parse_hex_grid(&dsl).unwrap(), grid);
+
+        let short = concat!(". 3 .\n\n", "start - [ 0 0 ]\n\n", "3 - [ Q b ]\n");
+        assert!(matches!(
+            Parser::parse_hex_grid(short),
+            Err(ParserError::StackParseError(_))
+        ));
+        let low = concat!(". 1 .\n\n", "start - [ 0 0 ]\n\n", "1 - [ Q ]\n");
+        assert!(matches!(
+            Parser::parse_hex_grid(low),
+            Err(ParserError::RowError(_))
+        ));
+    }
+
     #[test]
     pub fn test_parse_stacks_empty() {
         use PieceColor::*;
@@ -884,11 +939,14 @@ mod tests {
 
         let mut location = HexLocation::new(-3, -2);
         location = location.apply(Direction::E);
-        assert_eq!(grid.peek(location), vec![Piece::new(Queen, White),]);
+        assert_eq!(
+            grid.peek(location).to_vec(),
+            vec![Piece::new(Queen, White),]
+        );
 
         location = location.apply(Direction::E);
         assert_eq!(
-            grid.peek(location),
+            grid.peek(location).to_vec(),
             vec![
                 Piece::new(Grasshopper, White),
                 Piece::new(Beetle, Black),
@@ -897,22 +955,31 @@ mod tests {
         );
 
         location = location.apply(Direction::E);
-        assert_eq!(grid.peek(location), vec![Piece::new(Grasshopper, Black)]);
+        assert_eq!(
+            grid.peek(location).to_vec(),
+            vec![Piece::new(Grasshopper, Black)]
+        );
 
         location = location.apply(Direction::SW);
-        assert_eq!(grid.peek(location), vec![Piece::new(Beetle, Black),]);
+        assert_eq!(
+            grid.peek(location).to_vec(),
+            vec![Piece::new(Beetle, Black),]
+        );
 
         location = location.apply(Direction::W);
-        assert_eq!(grid.peek(location), vec![Piece::new(Ant, White),]);
+        assert_eq!(grid.peek(location).to_vec(), vec![Piece::new(Ant, White),]);
 
         location = location.apply(Direction::SW);
         assert_eq!(
-            grid.peek(location),
+            grid.peek(location).to_vec(),
             vec![Piece::new(Ant, Black), Piece::new(Mosquito, White),]
         );
 
         location = location.apply(Direction::E).apply(Direction::E);
-        assert_eq!(grid.peek(location), vec![Piece::new(Mosquito, Black),]);
+        assert_eq!(
+            grid.peek(location).to_vec(),
+            vec![Piece::new(Mosquito, Black),]
+        );
 
         assert_eq!(grid.num_pieces(), 10);
     }
@@ -941,4 +1008,29 @@ mod tests {
 
         assert_eq!(selectors, expected);
     }
+
+    #[test]
+    pub fn test_parse_height_selector() {
+        let selector = concat!(
+            ". . . . .\n",
+            " . *0 A * .\n",
+            ". B *1 * .\n",
+            " . . *2 . .\n",
+            ". . . . .\n\n",
+            "start - [ 0 0 ]\n\n",
+        );
+
+        let selectors = Parser::parse_height_selector(selector).expect("Couldn't parse selectors");
+        let expected = vec![
+            (HexLocation::new(1, 1), Some(0)),
+            (HexLocation::new(3, 1), None),
+            (HexLocation::new(1, 2), Some(1)),
+            (HexLocation::new(2, 2), None),
+            (HexLocation::new(1, 3), Some(2)),
+        ];
+        assert_eq!(selectors, expected);
+
+        let locations = expected.iter().map(|(loc, _)| *loc).collect::<Vec<_>>();
+        assert_eq!(Parser::parse_selector(selector).unwrap(), locations);
+    }
 }
@@ -0,0 +1,171 @@
+//! Reads directories of game records in bulk, for building databases (see
+//! PositionDb::import_game()) from thousands of games.
+//!
+//! A record file either holds one UHP GameString per line, or a single game
+//! written as its GameTypeString followed by one MoveString per line. Blank
+//! lines and lines starting with # are skipped in both
+use crate::game::{GameDebugger, GameDebuggerError};
+use crate::uhp::GameType;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use thiserror::Error;
+
+/// The number of games read ahead of the consumer of import_dir()
+const READ_AHEAD: usize = 256;
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Could not read {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Invalid game at {0}:{1}: {2}")]
+    InvalidGame(String, usize, GameDebuggerError),
+}
+
+pub type Result<T> = std::result::Result<T, ImportError>;
+
+/// A game read from a record file, see import_dir()
+#[derive(Debug)]
+pub struct ImportedGame {
+    pub path: PathBuf,
+    /// The line of the file the game starts at, from 1
+    pub line: usize,
+    pub game: GameDebugger,
+}
+
+/// Reads the games of every file in *directory* (non recursively) on
+/// *threads* threads, checking every move to be legal.
+///
+/// Games are sent as soon as they are read, those of a file in order but
+/// files in any order. Files that cannot be read and invalid games are sent
+/// as errors without stopping the import, which ends when every file was
+/// read or the receiver is dropped
+pub fn import_dir(
+    directory: &Path,
+    threads: usize,
+) -> Result<mpsc::Receiver<Result<ImportedGame>>> {
+    let io_error = |e| ImportError::Io(directory.display().to_string(), e);
+    let mut paths = std::fs::read_dir(directory)
+        .map_err(io_error)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let paths = Arc::new(Mutex::new(paths.into_iter()));
+    let (sender, receiver) = mpsc::sync_channel(READ_AHEAD);
+    for _ in 0..threads.max(1) {
+        let paths = paths.clone();
+        let sender = sender.clone();
+        std::thread::spawn(move || loop {
+            let Some(path) = paths.lock().unwrap().next() else {
+                return;
+            };
+            let games = match std::fs::read_to_string(&path) {
+                Ok(input) => read_games(&path, &input),
+                Err(e) => vec![Err(ImportError::Io(path.display().to_string(), e))],
+            };
+            for game in games {
+                if sender.send(game).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    Ok(receiver)
+}
+
+/// The games of the record file at *path* holding *input*
+pub fn read_games(path: &Path, input: &str) -> Vec<Result<ImportedGame>> {
+    let lines = input
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect::<Vec<_>>();
+    let imported = |line, game: std::result::Result<GameDebugger, GameDebuggerError>| {
+        game.map(|game| ImportedGame {
+            path: path.to_path_buf(),
+            line,
+            game,
+        })
+        .map_err(|e| ImportError::InvalidGame(path.display().to_string(), line, e))
+    };
+
+    let notation = lines
+        .first()
+        .and_then(|(line, first)| Some((*line, GameType::try_from(*first).ok()?)));
+    match notation {
+        Some((line, game_type)) => {
+            let moves = lines[1..].iter().map(|(_, mv)| *mv).collect::<Vec<_>>();
+            vec![imported(
+                line,
+                GameDebugger::from_move_list(&moves, game_type),
+            )]
+        }
+        None => lines
+            .into_iter()
+            .map(|(line, game_string)| imported(line, GameDebugger::from_game_string(game_string)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+
+    #[test]
+    pub fn test_import_dir() {
+        let directory = temp_path("import");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("games.txt"),
+            concat!(
+                "# Two games and an illegal one\n",
+                "Base;InProgress;White[2];wA1;bG1 -wA1\n",
+                "\n",
+                "Base;InProgress;White[2];wA1;bG1 wA1\n",
+                "Base+M;InProgress;Black[3];wA1;bG1 -wA1;wQ wA1-\n",
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("notation.txt"),
+            "Base+MLP\nwA1\nbG1 -wA1\nwQ1 wA1-\nbQ /bG1\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(directory.join("nested")).unwrap();
+
+        let mut results = import_dir(&directory, 3)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 4);
+        results.sort_by_key(|result| match result {
+            Ok(imported) => (imported.path.clone(), imported.line),
+            Err(ImportError::InvalidGame(path, line, _)) => (PathBuf::from(path), *line),
+            Err(e) => panic!("Unexpected error {}", e),
+        });
+
+        let lines = results
+            .iter()
+            .map(|result| result.as_ref().map(|imported| imported.line).ok())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [Some(2), None, Some(5), Some(1)]);
+        assert!(matches!(
+            results[1],
+            Err(ImportError::InvalidGame(
+                _,
+                4,
+                GameDebuggerError::IllegalMove(_)
+            ))
+        ));
+        let notation = results[3].as_ref().unwrap();
+        assert_eq!(notation.game.game_type(), GameType::MLP);
+        assert_eq!(notation.game.positions().count(), 5);
+        assert_eq!(results[2].as_ref().unwrap().game.game_type(), GameType::M);
+
+        assert!(import_dir(&directory.join("missing"), 2).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+//! Rules every move generator has to respect, whatever the position.
+//!
+//! Unlike the rules module, which explains why a given move is illegal, the
+//! checks here are properties of the set of moves generated for a position,
+//! meant for property based tests (e.g. with proptest or quickcheck) of this
+//! crate's generators and of any generator built on top of the crate
+use crate::generator::debug::{PositionGenerator, ReferenceGenerator};
+use crate::hex_grid::*;
+use crate::inventory::Inventory;
+use crate::moves::Move;
+use crate::uhp::GameType;
+use thiserror::Error;
+
+/// A property broken by a position or by a generated move, see check_moves()
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum InvariantError {
+    #[error("The position is not valid: {0:?}")]
+    InvalidPosition(Vec<Violation>),
+    #[error("A generated position is not reached by a single move:\n{0}")]
+    NotAMove(String),
+    #[error("{0:?} splits the hive into {1} groups")]
+    BreaksHive(Move, usize),
+    #[error("{0:?} places a piece the player has none left of")]
+    PiecesNotConserved(Move),
+    #[error("{0:?} places a piece of the player not to move")]
+    WrongColor(Move),
+    #[error("{0:?} does not end on the outside of the hive")]
+    OffFrontier(Move),
+    #[error("{0:?} moves a pinned piece")]
+    MovesPinned(Move),
+}
+
+pub type Result<T> = std::result::Result<T, InvariantError>;
+
+/// Checks that *grid* could occur in a game of *game_type*, see
+/// HexGrid::validate()
+pub fn check_position(grid: &HexGrid, game_type: GameType) -> Result<()> {
+    grid.validate(game_type)
+        .map_err(InvariantError::InvalidPosition)
+}
+
+/// Checks the move from *before* to *after* made by *color*, returning it:
+///
+/// - pieces are only ever added by placing one from the hand of *color*,
+///   never more than the set of *game_type* contains, and never removed
+/// - pieces end next to the hive or on top of it
+/// - pieces whose removal would split the hive stay where they are
+/// - the hive is still One Hive afterwards
+///
+/// Covered pieces cannot move either, as a move of one is no single move.
+/// The position before the move is not checked, see check_position()
+pub fn check_move(
+    before: &HexGrid,
+    after: &HexGrid,
+    color: PieceColor,
+    game_type: GameType,
+) -> Result<Move> {
+    let mv = before
+        .diff(after)
+        .ok_or_else(|| InvariantError::NotAMove(after.to_dsl()))?;
+
+    match mv {
+        Move::Pass => {}
+        Move::Place { piece, to } => {
+            if piece.color != color {
+                return Err(InvariantError::WrongColor(mv));
+            }
+            let placed = before
+                .pieces_of(color)
+                .iter()
+                .filter(|(other, _, _)| *other == piece)
+                .count();
+            if placed >= Inventory::full(color, game_type).count(piece.piece_type) {
+                return Err(InvariantError::PiecesNotConserved(mv));
+            }
+            // Only the first piece has nothing to touch
+            if before.height(to) > 0 || (!before.is_empty() && before.get_neighbors(to).is_empty())
+            {
+                return Err(InvariantError::OffFrontier(mv));
+            }
+        }
+        Move::Movement { from, to, .. } => {
+            if before.height(from) == 1 && before.pinned().contains(&from) {
+                return Err(InvariantError::MovesPinned(mv));
+            }
+            // The moved piece does not count as a neighbor of its destination
+            let mut rest = before.clone();
+            rest.remove(from);
+            if from == to || (rest.height(to) == 0 && rest.get_neighbors(to).is_empty()) {
+                return Err(InvariantError::OffFrontier(mv));
+            }
+        }
+    }
+
+    // Implied by the checks above for valid positions, but generators may
+    // be given any position
+    let groups = after.groups();
+    if groups > 1 {
+        return Err(InvariantError::BreaksHive(mv, groups));
+    }
+    Ok(mv)
+}
+
+/// Checks every position of *afters*, generated from *before* for *color*
+/// to move, see check_move()
+pub fn check_moves<'a, I>(
+    before: &HexGrid,
+    afters: I,
+    color: PieceColor,
+    game_type: GameType,
+) -> Result<()>
+where
+    I: IntoIterator<Item = &'a HexGrid>,
+{
+    for after in afters {
+        check_move(before, after, color, game_type)?;
+    }
+    Ok(())
+}
+
+/// Checks the moves the ReferenceGenerator finds for *color* in *grid*,
+/// where *last_move* is the destination of the piece moved in the previous
+/// turn. Returns the number of moves checked
+pub fn check_generator(
+    grid: &HexGrid,
+    game_type: GameType,
+    last_move: Option<HexLocation>,
+    color: PieceColor,
+) -> Result<usize> {
+    let mut generator = ReferenceGenerator::from_hex_grid(grid, game_type, last_move);
+    let positions = generator.generate_positions_for(color);
+    check_moves(grid, positions.iter(), color, game_type)?;
+    Ok(positions.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameDebugger;
+    use crate::testing::fuzz::play_moves;
+    use crate::testing::positions::*;
+    use PieceColor::*;
+
+    #[test]
+    pub fn test_generated_moves() {
+        // The pieces to move in these positions are white
+        let dsls = SPIDER_MOVES
+            .iter()
+            .chain(BEETLE_MOVES.iter())
+            .chain(PILLBUG_SWAPS.iter())
+            .chain(MOSQUITO_MOVES.iter());
+        for dsl in dsls {
+            let grid = HexGrid::from_dsl(dsl);
+            check_generator(&grid, GameType::MLP, None, White).unwrap();
+        }
+
+        let choices = (0..30).map(|i| (i * 37 % 251) as u8).collect::<Vec<_>>();
+        let game = play_moves(&[&[7][..], &choices].concat());
+        let mut replay = GameDebugger::from_move_list(&[], GameType::MLP).unwrap();
+        for position in game.positions().skip(1) {
+            check_position(replay.position(), GameType::MLP).unwrap();
+            let checked = check_generator(
+                replay.position(),
+                GameType::MLP,
+                replay.last_move(),
+                replay.player_to_move(),
+            )
+            .unwrap();
+            assert_eq!(checked, replay.legal_positions().len());
+            replay.append_position(position).unwrap();
+        }
+    }
+
+    #[test]
+    pub fn test_broken_invariants() {
+        let before = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a Q G .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let check =
+            |after: &str| check_move(&before, &HexGrid::from_dsl(after), White, GameType::MLP);
+
+        let split = check(concat!(
+            " . . . . .\n",
+            ". a . G Q\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert!(matches!(split, Err(InvariantError::MovesPinned(_))));
+
+        let far = check(concat!(
+            " . . . . . A\n",
+            ". a Q G . .\n",
+            " . . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert!(matches!(far, Err(InvariantError::OffFrontier(_))));
+
+        let theirs = check(concat!(
+            " . . . . .\n",
+            ". a Q G b\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert!(matches!(theirs, Err(InvariantError::WrongColor(_))));
+
+        let second_queen = check(concat!(
+            " . . . . .\n",
+            ". a Q G Q\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert!(matches!(
+            second_queen,
+            Err(InvariantError::PiecesNotConserved(..))
+        ));
+
+        let two_moves = check(concat!(
+            " . . . . .\n",
+            ". . Q G A\n",
+            " . . . . A\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert!(matches!(two_moves, Err(InvariantError::NotAMove(_))));
+
+        // Pinned pieces may not move even where the hive stays together,
+        // while pieces on top of them may
+        let ring = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a Q B .\n",
+            " . G . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let around = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a . B .\n",
+            " . G Q . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert!(matches!(
+            check_move(&ring, &around, White, GameType::MLP),
+            Err(InvariantError::MovesPinned(_))
+        ));
+        let climbed = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a 2 . .\n",
+            " . G . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ Q B ]\n",
+        ));
+        assert!(check_move(&ring, &climbed, White, GameType::MLP).is_ok());
+        let climbed_off = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a Q . .\n",
+            " . G B . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert!(check_move(&climbed, &climbed_off, White, GameType::MLP).is_ok());
+        assert_eq!(
+            check_move(&ring, &ring, White, GameType::MLP),
+            Ok(Move::Pass)
+        );
+    }
+}
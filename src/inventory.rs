@@ -0,0 +1,215 @@
+use crate::hex_grid::*;
+use crate::uhp::GameType;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryError {
+    #[error("The {} is not part of this game type", .0.name())]
+    NotInGame(PieceType),
+    #[error("More pieces of type {} are on the board than the set contains", .0.name())]
+    TooManyPlaced(PieceType),
+    #[error("There are no more pieces of type {} left in hand", .0.name())]
+    NoneLeft(PieceType),
+}
+
+pub type Result<T> = std::result::Result<T, InventoryError>;
+
+/// The pieces a single player has yet to place on the board,
+/// for the piece types used by a given GameType
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Inventory {
+    color: PieceColor,
+    /// Remaining number of pieces for every piece type in the game,
+    /// in the order given by PieceType::all()
+    counts: Vec<(PieceType, usize)>,
+}
+
+impl Inventory {
+    /// The inventory of a player that has not placed any pieces yet
+    pub fn full(color: PieceColor, game_type: GameType) -> Inventory {
+        let counts = PieceType::all(game_type)
+            .into_iter()
+            .map(|piece_type| (piece_type, piece_type.count()))
+            .collect();
+        Inventory { color, counts }
+    }
+
+    /// The inventory of the player of the given *color* after the pieces
+    /// on the *grid* have been placed.
+    ///
+    /// Fails if the grid contains pieces that are not part of the game type,
+    /// or more pieces of a type than the official counts allow
+    pub fn from_grid(
+        grid: &impl IntoPieces,
+        color: PieceColor,
+        game_type: GameType,
+    ) -> Result<Inventory> {
+        let mut inventory = Inventory::full(color, game_type);
+        for (stack, _) in grid.pieces() {
+            for piece in stack.into_iter().filter(|piece| piece.color == color) {
+                inventory.take(piece.piece_type).map_err(|e| match e {
+                    InventoryError::NoneLeft(piece_type) => {
+                        InventoryError::TooManyPlaced(piece_type)
+                    }
+                    e => e,
+                })?;
+            }
+        }
+        Ok(inventory)
+    }
+
+    /// Like from_grid(), but for grids that may break the piece counts (such
+    /// as positions written by hand or fuzzed): a piece type with more pieces
+    /// on the board than the set contains has none left, and pieces that are
+    /// not part of the game type are ignored
+    pub fn saturating_from_grid(
+        grid: &impl IntoPieces,
+        color: PieceColor,
+        game_type: GameType,
+    ) -> Inventory {
+        let mut inventory = Inventory::full(color, game_type);
+        for (stack, _) in grid.pieces() {
+            for piece in stack.into_iter().filter(|piece| piece.color == color) {
+                let _ = inventory.take(piece.piece_type);
+            }
+        }
+        inventory
+    }
+
+    pub fn color(&self) -> PieceColor {
+        self.color
+    }
+
+    /// The number of pieces of the given type left in hand
+    pub fn count(&self, piece_type: PieceType) -> usize {
+        self.counts
+            .iter()
+            .find(|(t, _)| *t == piece_type)
+            .map(|(_, count)| *count)
+            .unwrap_or(0)
+    }
+
+    /// Returns true if a piece of the given type can still be placed
+    pub fn contains(&self, piece_type: PieceType) -> bool {
+        self.count(piece_type) > 0
+    }
+
+    /// Removes a single piece of the given type from the inventory,
+    /// returning the piece that is to be placed on the board
+    pub fn take(&mut self, piece_type: PieceType) -> Result<Piece> {
+        let (_, count) = self
+            .counts
+            .iter_mut()
+            .find(|(t, _)| *t == piece_type)
+            .ok_or(InventoryError::NotInGame(piece_type))?;
+        if *count == 0 {
+            return Err(InventoryError::NoneLeft(piece_type));
+        }
+        *count -= 1;
+        Ok(Piece::new(piece_type, self.color))
+    }
+
+    /// The distinct pieces that can still be placed,
+    /// in the order given by PieceType::all()
+    pub fn pieces(&self) -> Vec<Piece> {
+        self.counts
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(piece_type, _)| Piece::new(*piece_type, self.color))
+            .collect()
+    }
+
+    /// The total number of pieces left in hand
+    pub fn len(&self) -> usize {
+        self.counts.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_inventory_from_grid() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a A G .\n",
+            " . 2 q . .\n",
+            ". . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ a b ]\n",
+        ));
+
+        let white = Inventory::from_grid(&grid, White, GameType::Standard).unwrap();
+        assert_eq!(white.count(Ant), 2);
+        assert_eq!(white.count(Grasshopper), 2);
+        assert_eq!(white.count(Beetle), 2);
+        assert!(!white.contains(Pillbug));
+        assert_eq!(white.len(), 11 - 2);
+
+        let black = Inventory::from_grid(&grid, Black, GameType::Standard).unwrap();
+        assert!(!black.contains(Queen));
+        assert_eq!(black.count(Beetle), 1);
+        assert_eq!(black.count(Ant), 1);
+        assert!(!black.pieces().contains(&Piece::new(Queen, Black)));
+
+        assert_eq!(
+            Inventory::full(White, GameType::MLP).len(),
+            Inventory::full(White, GameType::Standard).len() + 3
+        );
+
+        // Four white ants and a pillbug outside the game type
+        let crowded = HexGrid::from_dsl(concat!(
+            " . . . . . . . .\n",
+            ". A A A A P Q .\n",
+            " . . . . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert_eq!(
+            Inventory::from_grid(&crowded, White, GameType::Standard),
+            Err(InventoryError::TooManyPlaced(Ant))
+        );
+        let saturated = Inventory::saturating_from_grid(&crowded, White, GameType::Standard);
+        assert_eq!(saturated.count(Ant), 0);
+        assert!(!saturated.contains(Queen) && !saturated.contains(Pillbug));
+        assert_eq!(saturated.len(), 11 - 3 - 1);
+    }
+
+    #[test]
+    pub fn test_inventory_validation() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". L Q Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        assert_eq!(
+            Inventory::from_grid(&grid, White, GameType::L),
+            Err(InventoryError::TooManyPlaced(Queen))
+        );
+        assert_eq!(
+            Inventory::from_grid(&grid, White, GameType::Standard)
+                .unwrap_err()
+                .to_string(),
+            "The ladybug is not part of this game type"
+        );
+
+        let mut inventory = Inventory::full(Black, GameType::Standard);
+        assert_eq!(inventory.take(Queen), Ok(Piece::new(Queen, Black)));
+        assert_eq!(inventory.take(Queen), Err(InventoryError::NoneLeft(Queen)));
+        assert_eq!(
+            inventory.take(Mosquito),
+            Err(InventoryError::NotInGame(Mosquito))
+        );
+    }
+}
@@ -0,0 +1,52 @@
+#[cfg(feature = "uhp")]
+pub mod arbiter;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod bitgrid;
+pub mod board;
+pub mod board_view;
+#[cfg(feature = "book")]
+pub mod book;
+pub mod chunked_map;
+pub mod clock;
+pub mod constants;
+#[cfg(feature = "engine")]
+pub mod data_analysis;
+pub mod encoding;
+#[cfg(feature = "engine")]
+pub mod engine;
+pub mod explorer;
+pub mod game;
+pub mod generator;
+pub mod heatmap;
+pub mod hex_grid;
+pub mod hex_grid_dsl;
+pub mod import;
+pub mod inventory;
+pub mod invariants;
+pub mod location;
+pub mod moves;
+pub mod openings;
+pub mod perft;
+pub mod piece;
+pub mod position_db;
+#[cfg(feature = "engine")]
+pub mod prover;
+pub mod queens;
+pub mod replay;
+pub mod rules;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "engine")]
+pub mod solver;
+pub mod sparse_hex_grid;
+pub mod structures;
+pub mod testing;
+#[cfg(feature = "uhp")]
+pub mod tournament;
+pub mod uhp;
+#[cfg(feature = "uhp")]
+pub mod uhp_client;
+#[cfg(feature = "uhp")]
+pub mod uhp_interface;
+pub mod zobrist;
@@ -1,4 +1,6 @@
-#[derive(Copy, Clone, Debug)]
+use crate::hex_grid::{HexGridError, Result};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Direction {
     NW,
     NE,
@@ -8,16 +10,183 @@ pub enum Direction {
     W,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+/// A line of hexes through the center, see HexLocation::reflect()
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    /// Through the west and east neighbors of the center
+    EastWest,
+    /// Through the north-east and south-west neighbors of the center
+    NorthEast,
+    /// Through the north-west and south-east neighbors of the center
+    NorthWest,
+}
+
+/// An optional reflection in the east-west axis, then a clockwise rotation
+/// around the center, then a translation, see HexGrid::canonical_transform()
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transform {
+    pub reflected: bool,
+    /// The number of 60 degree rotations, see HexLocation::rotate60()
+    pub rotation: usize,
+    pub offset: HexLocation,
+}
+
+impl Transform {
+    pub fn apply(&self, location: HexLocation) -> HexLocation {
+        let location = match self.reflected {
+            true => location.reflect(Axis::EastWest),
+            false => location,
+        };
+        location.rotate60(self.rotation).add(self.offset)
+    }
+
+    /// Undoes apply()
+    pub fn invert(&self, location: HexLocation) -> HexLocation {
+        let location = location - self.offset;
+        let location = location.rotate60(6 - self.rotation % 6);
+        match self.reflected {
+            true => location.reflect(Axis::EastWest),
+            false => location,
+        }
+    }
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl std::str::FromStr for Direction {
+    type Err = HexGridError;
+
+    /// Parses the abbreviation (e.g. "NW") or english name (e.g.
+    /// "north-west") of a direction, ignoring case
+    fn from_str(s: &str) -> Result<Direction> {
+        let s = s.to_lowercase();
+        Direction::ALL
+            .into_iter()
+            .find(|direction| direction.to_str().to_lowercase() == s || direction.name() == s)
+            .ok_or(HexGridError::DirectionError(s))
+    }
+}
+
+/// The type of the axial coordinates of a HexLocation, wide enough that
+/// games drifting across the board and translations of whole grids do not
+/// overflow
+pub type Coordinate = i16;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct HexLocation {
-    pub x: i8,
-    pub y: i8,
+    pub x: Coordinate,
+    pub y: Coordinate,
 }
 
 impl Direction {
-    pub fn all() -> Vec<Direction> {
+    /// Every direction, clockwise starting from NW
+    pub const ALL: [Direction; 6] = {
+        use Direction::*;
+        [NW, NE, E, SE, SW, W]
+    };
+
+    pub const fn all() -> [Direction; 6] {
+        Direction::ALL
+    }
+
+    /// The next direction clockwise, so that NW becomes NE, consistent
+    /// with HexLocation::rotate60()
+    pub fn rotate_cw(&self) -> Direction {
+        Direction::ALL[(*self as usize + 1) % 6]
+    }
+
+    /// The next direction counter-clockwise, so that NW becomes W
+    pub fn rotate_ccw(&self) -> Direction {
+        Direction::ALL[(*self as usize + 5) % 6]
+    }
+
+    /// The direction pointing the other way, so that NW becomes SE
+    pub fn opposite(&self) -> Direction {
+        Direction::ALL[(*self as usize + 3) % 6]
+    }
+
+    /// The abbreviation of the direction, e.g. "NW"
+    pub fn to_str(&self) -> &str {
         use Direction::*;
-        vec![NW, NE, E, SE, SW, W]
+        match self {
+            NW => "NW",
+            NE => "NE",
+            E => "E",
+            SE => "SE",
+            SW => "SW",
+            W => "W",
+        }
+    }
+
+    /// The glyph standing for this direction in UHP MoveStrings,
+    /// see Direction::annotate()
+    pub fn glyph(&self) -> char {
+        use Direction::*;
+        match self {
+            E | W => '-',
+            NE | SW => '/',
+            NW | SE => '\\',
+        }
+    }
+
+    /// Writes the hex in this direction of the *anchor* piece the way UHP
+    /// MoveStrings do, with the glyph before the anchor for the western
+    /// directions and after it for the eastern ones (e.g. "-wA1" is west of
+    /// wA1 and "wA1\" south-east of it)
+    pub fn annotate(&self, anchor: &str) -> String {
+        use Direction::*;
+        match self {
+            W | SW | NW => format!("{}{}", self.glyph(), anchor),
+            E | NE | SE => format!("{}{}", anchor, self.glyph()),
+        }
+    }
+
+    /// Splits an anchor of a UHP MoveString into the direction of the
+    /// destination from the anchor piece (None if on top of it) and the
+    /// anchor PieceString, undoing annotate()
+    pub fn parse_anchor(anchor: &str) -> (Option<Direction>, &str) {
+        for direction in Direction::ALL {
+            let rest = match direction {
+                Direction::W | Direction::SW | Direction::NW => {
+                    anchor.strip_prefix(direction.glyph())
+                }
+                _ => anchor.strip_suffix(direction.glyph()),
+            };
+            if let Some(rest) = rest {
+                return (Some(direction), rest);
+            }
+        }
+        (None, anchor)
+    }
+
+    /// The lowercase english name of the direction, e.g. "north-west"
+    pub fn name(&self) -> &str {
+        use Direction::*;
+        match self {
+            NW => "north-west",
+            NE => "north-east",
+            E => "east",
+            SE => "south-east",
+            SW => "south-west",
+            W => "west",
+        }
+    }
+
+    /// The change in axial coordinates of moving one hex in this direction
+    pub fn offset(&self) -> (Coordinate, Coordinate) {
+        use Direction::*;
+        match self {
+            NW => (0, -1),
+            NE => (1, -1),
+            E => (1, 0),
+            SE => (0, 1),
+            SW => (-1, 1),
+            W => (-1, 0),
+        }
     }
 
     /// Returns the two directions that are adjacent to this one.
@@ -45,37 +214,132 @@ impl Direction {
 }
 
 impl HexLocation {
-    pub fn new(x: i8, y: i8) -> HexLocation {
+    pub fn new(x: Coordinate, y: Coordinate) -> HexLocation {
         HexLocation { x, y }
     }
 
     /// Applies a direction to this location,
     /// returning the new location. This function is deterministic.
+    ///
+    /// Coordinates wrap around at the ends of the Coordinate range, see checked_apply()
     pub fn apply(&self, direction: Direction) -> Self {
-        use Direction::*;
+        let (dx, dy) = direction.offset();
+        HexLocation::new(self.x.wrapping_add(dx), self.y.wrapping_add(dy))
+    }
+
+    /// Applies a direction to this location like apply(), or returns None if
+    /// the new location cannot be represented
+    pub fn checked_apply(&self, direction: Direction) -> Option<Self> {
+        let (dx, dy) = direction.offset();
+        Some(HexLocation::new(
+            self.x.checked_add(dx)?,
+            self.y.checked_add(dy)?,
+        ))
+    }
+
+    pub fn add(&self, other: HexLocation) -> HexLocation {
+        *self + other
+    }
+
+    /// The coordinates of this location if they fit in the single bytes
+    /// locations used to be stored in, see From<(i8, i8)>
+    pub fn to_i8(&self) -> Option<(i8, i8)> {
+        Some((self.x.try_into().ok()?, self.y.try_into().ok()?))
+    }
+
+    /// Returns the direction leading from this location to *other*,
+    /// None if the locations are not neighbors
+    pub fn direction_to(&self, other: HexLocation) -> Option<Direction> {
+        Direction::all()
+            .into_iter()
+            .find(|&direction| self.apply(direction) == other)
+    }
+
+    /// Rotates this location clockwise around the center by *n* times 60
+    /// degrees, so that after one rotation E becomes SE
+    pub fn rotate60(&self, n: usize) -> HexLocation {
         let (mut x, mut y) = (self.x, self.y);
-        match direction {
-            NW => y -= 1,
-            E => x += 1,
-            W => x -= 1,
-            SE => y += 1,
-            NE => {
-                x += 1;
-                y -= 1
-            }
-            SW => {
-                x -= 1;
-                y += 1
-            }
+        for _ in 0..n % 6 {
+            (x, y) = (-y, x + y);
         }
         HexLocation::new(x, y)
     }
 
-    pub fn add(&self, other: HexLocation) -> HexLocation {
+    /// Mirrors this location in the given axis through the center
+    pub fn reflect(&self, axis: Axis) -> HexLocation {
+        // Mirroring in the other axes is the same as mirroring in the
+        // east-west axis and rotating by twice the angle between the axes
+        let mirrored = HexLocation::new(self.x + self.y, -self.y);
+        match axis {
+            Axis::EastWest => mirrored,
+            Axis::NorthEast => mirrored.rotate60(4),
+            Axis::NorthWest => mirrored.rotate60(2),
+        }
+    }
+}
+
+impl std::ops::Add for HexLocation {
+    type Output = HexLocation;
+
+    fn add(self, other: HexLocation) -> HexLocation {
         HexLocation::new(self.x + other.x, self.y + other.y)
     }
 }
 
+impl std::ops::Sub for HexLocation {
+    type Output = HexLocation;
+
+    fn sub(self, other: HexLocation) -> HexLocation {
+        HexLocation::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl std::ops::Neg for HexLocation {
+    type Output = HexLocation;
+
+    fn neg(self) -> HexLocation {
+        HexLocation::new(-self.x, -self.y)
+    }
+}
+
+impl std::ops::Mul<Coordinate> for HexLocation {
+    type Output = HexLocation;
+
+    /// Scales the location away from the center, so that *n* steps in a
+    /// direction from the center lead to the location of the direction times *n*
+    fn mul(self, n: Coordinate) -> HexLocation {
+        HexLocation::new(self.x * n, self.y * n)
+    }
+}
+
+impl std::ops::AddAssign for HexLocation {
+    fn add_assign(&mut self, other: HexLocation) {
+        *self = *self + other;
+    }
+}
+
+impl std::ops::SubAssign for HexLocation {
+    fn sub_assign(&mut self, other: HexLocation) {
+        *self = *self - other;
+    }
+}
+
+impl From<Direction> for HexLocation {
+    /// The neighbor of the center in the *direction*
+    fn from(direction: Direction) -> HexLocation {
+        let (x, y) = direction.offset();
+        HexLocation::new(x, y)
+    }
+}
+
+impl From<(i8, i8)> for HexLocation {
+    /// The location with the coordinates of the narrower type used before
+    /// Coordinate was widened
+    fn from((x, y): (i8, i8)) -> HexLocation {
+        HexLocation::new(x.into(), y.into())
+    }
+}
+
 impl Shiftable for HexLocation {
     fn shift_west(&self) -> HexLocation {
         self.apply(Direction::W)
@@ -146,3 +410,80 @@ pub trait Shiftable {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_direction_rotation() {
+        let center = HexLocation::new(0, 0);
+        for direction in Direction::ALL {
+            assert_eq!(
+                center.apply(direction).rotate60(1),
+                center.apply(direction.rotate_cw())
+            );
+            assert_eq!(direction.rotate_cw().rotate_ccw(), direction);
+            assert_eq!(center.apply(direction).apply(direction.opposite()), center);
+            assert_eq!(direction.opposite().opposite(), direction);
+        }
+        assert_eq!(Direction::NW.rotate_ccw(), Direction::W);
+        assert_eq!(
+            HexLocation::from(Direction::E).rotate60(1),
+            Direction::SE.into()
+        );
+        assert_eq!(Direction::all(), Direction::ALL);
+    }
+
+    #[test]
+    pub fn test_direction_notation() {
+        for direction in Direction::ALL {
+            let anchor = direction.annotate("wA1");
+            assert_eq!(Direction::parse_anchor(&anchor), (Some(direction), "wA1"));
+            assert_eq!(
+                direction.to_string().parse::<Direction>().unwrap(),
+                direction
+            );
+            assert_eq!(direction.name().parse::<Direction>().unwrap(), direction);
+        }
+        assert_eq!(Direction::W.annotate("bQ"), "-bQ");
+        assert_eq!(Direction::SE.annotate("bQ"), r"bQ\");
+        assert_eq!(Direction::parse_anchor("bQ"), (None, "bQ"));
+        assert_eq!("ne".parse::<Direction>().unwrap(), Direction::NE);
+        assert!("up".parse::<Direction>().is_err());
+    }
+
+    #[test]
+    pub fn test_location_arithmetic() {
+        let a = HexLocation::new(3, -2);
+        let b = HexLocation::new(-1, 5);
+        assert_eq!(a + b, HexLocation::new(2, 3));
+        assert_eq!(a - b, HexLocation::new(4, -7));
+        assert_eq!(-a, HexLocation::new(-3, 2));
+        assert_eq!(a * 3, HexLocation::new(9, -6));
+        assert_eq!(a.add(b), a + b);
+
+        let mut c = a;
+        c += b;
+        c -= a;
+        assert_eq!(c, b);
+
+        // Walking in a direction is adding its offset
+        for direction in Direction::ALL {
+            assert_eq!(a.apply(direction), a + direction.into());
+            assert_eq!(
+                (0..4).fold(a, |location, _| location.apply(direction)),
+                a + HexLocation::from(direction) * 4
+            );
+        }
+
+        // Far beyond the range of the coordinates of old
+        let far = HexLocation::new(100, 0) * 5;
+        assert_eq!(far.apply(Direction::E), HexLocation::new(501, 0));
+        assert_eq!(far.to_i8(), None);
+        assert_eq!(
+            HexLocation::from((-128i8, 127i8)).to_i8(),
+            Some((-128, 127))
+        );
+    }
+}
@@ -1,17 +1,11 @@
-mod bitgrid;
-mod constants;
-mod data_analysis;
-mod game;
-mod generator;
-mod hex_grid;
-mod hex_grid_dsl;
-mod location;
-mod piece;
-mod testing_utils;
-mod uhp;
-
-use clap::{Parser, Subcommand};
-use uhp::UHPInterface;
+use anansii::clock::TimeControl;
+use anansii::uhp::{CommandError, ErrorCode, GameType};
+use anansii::uhp_interface::UHPInterface;
+use anansii::uhp_client::EngineLimits;
+use anansii::{arbiter, bitgrid, book, data_analysis, engine, perft, testing, tournament};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -28,8 +22,250 @@ enum MainCommands {
     /// Runs a script to analyze the certain statistics for hive positions
     Analyze,
 
+    /// Searches a position and prints its evaluation, best line and best moves
+    Search {
+        /// UHP GameString of the position, or a file containing one
+        #[arg(long)]
+        position: String,
+
+        /// Number of plies to search
+        #[arg(long, default_value_t = 3)]
+        depth: usize,
+
+        /// Number of candidate moves to show
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+
+        /// Breaks ties between moves the same way on every run
+        #[arg(long)]
+        deterministic: bool,
+
+        /// Evaluation weights to use, the name of a built-in profile
+        /// (default, aggressive, positional) or a profile file
+        #[arg(long, default_value = "default")]
+        profile: String,
+
+        /// File to write the search tree to, as DOT if it ends in .dot
+        /// and as JSON otherwise
+        #[arg(long)]
+        tree: Option<PathBuf>,
+
+        /// Maximum number of positions written to the search tree
+        #[arg(long, default_value_t = 10000)]
+        tree_limit: usize,
+
+        /// File to keep the transposition table in between runs, read
+        /// before the search if it exists and written after it
+        #[arg(long)]
+        cache: Option<PathBuf>,
+    },
+
+    /// Searches every position of a game and flags the moves that lose the most
+    Review {
+        /// UHP GameString of the game, or a file containing one
+        #[arg(long)]
+        game: String,
+
+        /// Number of plies to search before each move
+        #[arg(long, default_value_t = 2)]
+        depth: usize,
+
+        /// Smallest loss against the best move that is flagged
+        #[arg(long, default_value_t = 100)]
+        threshold: i32,
+
+        /// Evaluation weights to use, the name of a built-in profile
+        /// (default, aggressive, positional) or a profile file
+        #[arg(long, default_value = "default")]
+        profile: String,
+    },
+
+    /// Fits the evaluation weights to the outcomes of recorded games
+    Tune {
+        /// Number of recorded games to learn from
+        #[arg(long, default_value_t = 1000)]
+        games: usize,
+
+        /// Maximum number of passes over the weights
+        #[arg(long, default_value_t = 100)]
+        rounds: usize,
+
+        /// File the tuned weights are written to
+        #[arg(long, default_value = "weights.toml")]
+        output: PathBuf,
+    },
+
+    /// Referees a game between two external UHP engines, validating every move
+    Arbiter {
+        /// Command that runs the engine playing white
+        white: String,
+
+        /// Command that runs the engine playing black
+        black: String,
+
+        /// GameTypeString of the game
+        #[arg(long, default_value = "Base+MLP")]
+        game_type: String,
+
+        /// Thinking time of each player for the whole game, in seconds
+        #[arg(long, default_value_t = 300)]
+        time: u64,
+
+        /// Thinking time added after each move, in seconds
+        #[arg(long, default_value_t = 0)]
+        increment: u64,
+
+        /// Time control replacing --time and --increment: a preset (bullet,
+        /// blitz, rapid, classical), minutes+increment such as 10+5, or
+        /// minutes+periods x seconds of byo-yomi such as 10+3x30
+        #[arg(long)]
+        time_control: Option<String>,
+
+        /// Number of moves after which the game is stopped undecided
+        #[arg(long, default_value_t = 300)]
+        max_moves: usize,
+
+        /// Draws the game once the same board is reached this many times,
+        /// whoever is to move
+        #[arg(long)]
+        shuffle_repetitions: Option<usize>,
+
+        /// Adjudicates a win once the arbiter's search scores the position
+        /// at least this much for the same player for --eval-moves moves
+        #[arg(long)]
+        eval_threshold: Option<i32>,
+
+        /// Number of moves in a row the --eval-threshold has to be reached
+        #[arg(long, default_value_t = 8)]
+        eval_moves: usize,
+
+        /// Compares the valid moves each engine reports with the legal moves every turn
+        #[arg(long)]
+        check_valid_moves: bool,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        /// File the GameString of the game is written to
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Runs a tournament between external UHP engines, refereeing every game
+    Tournament {
+        /// Commands that run the engines, best seed first
+        #[arg(required = true, num_args = 2..)]
+        engines: Vec<String>,
+
+        /// Pairing of the players: round-robin, swiss or knockout
+        #[arg(long, default_value = "round-robin")]
+        format: String,
+
+        /// Number of rounds of a swiss tournament, by default as many as it
+        /// takes to leave at most one player unbeaten
+        #[arg(long)]
+        rounds: Option<usize>,
+
+        /// GameTypeString of the games
+        #[arg(long, default_value = "Base+MLP")]
+        game_type: String,
+
+        /// Time control of every game: a preset (bullet, blitz, rapid,
+        /// classical), minutes+increment or minutes+periods x seconds
+        #[arg(long, default_value = "blitz")]
+        time_control: String,
+
+        /// Number of moves after which a game is stopped undecided
+        #[arg(long, default_value_t = 300)]
+        max_moves: usize,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+
     /// Interprets a number as an Axial and prints the bitboard
     Bitboard { number: u64 },
+
+    /// Mines recorded games for forced queen surround puzzles and prints them
+    Puzzles {
+        /// Maximum number of moves the player to move may take to surround
+        #[arg(default_value_t = 1)]
+        moves: usize,
+
+        /// Number of recorded games to search
+        #[arg(long, default_value_t = 100)]
+        games: usize,
+    },
+
+    /// Counts the positions reachable at each depth, for validating the move generator
+    Perft {
+        /// Number of turns to search
+        depth: usize,
+
+        /// UHP GameString or GameTypeString of the starting position, Base if not given
+        #[arg(long)]
+        game_string: Option<String>,
+
+        /// Splits the count at the final depth by the first move
+        #[arg(long)]
+        divide: bool,
+
+        /// Compares every ruleset against the Mzinga perft tables up to the depth instead,
+        /// from the starting position
+        #[arg(long, conflicts_with_all = ["game_string", "divide"])]
+        mzinga: bool,
+    },
+
+    /// Compiles a position database into an opening book
+    Book {
+        /// File of the position database
+        database: PathBuf,
+
+        /// File the book is written to
+        output: PathBuf,
+
+        /// Fewest visits of a position for its best move to be included
+        #[arg(long, default_value_t = 2)]
+        min_visits: u64,
+    },
+
+    /// Serves REST and WebSocket endpoints for playing and analyzing games
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
+
+        /// Deepest search a client may request
+        #[arg(long, default_value_t = 4)]
+        max_depth: usize,
+    },
+}
+
+/// The resources external engines may use
+#[derive(Args)]
+struct LimitArgs {
+    /// Memory each engine may map, in megabytes (Unix only)
+    #[arg(long)]
+    memory_limit: Option<u64>,
+
+    /// Processor time each engine may use per game, in seconds (Unix only)
+    #[arg(long)]
+    cpu_limit: Option<u64>,
+
+    /// Directory the engines run in
+    #[arg(long)]
+    working_dir: Option<PathBuf>,
+}
+
+impl LimitArgs {
+    fn limits(self) -> EngineLimits {
+        EngineLimits {
+            memory: self.memory_limit.map(|megabytes| megabytes << 20),
+            cpu_time: self.cpu_limit.map(Duration::from_secs),
+            working_dir: self.working_dir,
+        }
+    }
 }
 
 pub fn run_uhp() {
@@ -40,8 +276,16 @@ pub fn run_uhp() {
 
     loop {
         input.clear();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let output = uhp.command(&input);
+        let output = match std::io::stdin().read_line(&mut input) {
+            // The input was closed
+            Ok(0) => return,
+            Ok(_) => uhp.command(&input),
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                let error = CommandError::new(ErrorCode::InvalidArgument, "Commands must be UTF-8");
+                error.to_uhp() + "\nok\n"
+            }
+            Err(_) => return,
+        };
         print!("{}", output);
     }
 }
@@ -51,10 +295,140 @@ pub fn main() {
     match args.command {
         Some(MainCommands::Uhp) => run_uhp(),
         Some(MainCommands::Analyze) => data_analysis::check_positions(),
+        Some(MainCommands::Search {
+            position,
+            depth,
+            top,
+            deterministic,
+            profile,
+            tree,
+            tree_limit,
+            cache,
+        }) => engine::analysis::print_analysis(
+            &position,
+            depth,
+            top,
+            deterministic,
+            &profile,
+            tree.as_deref().map(|tree| (tree, tree_limit)),
+            cache.as_deref(),
+        ),
+        Some(MainCommands::Review {
+            game,
+            depth,
+            threshold,
+            profile,
+        }) => engine::review::print_review(&game, depth, threshold, &profile),
+        Some(MainCommands::Tune {
+            games,
+            rounds,
+            output,
+        }) => engine::tuner::print_tuning(games, rounds, &output),
+        Some(MainCommands::Arbiter {
+            white,
+            black,
+            game_type,
+            time,
+            increment,
+            time_control,
+            max_moves,
+            shuffle_repetitions,
+            eval_threshold,
+            eval_moves,
+            check_valid_moves,
+            limits,
+            output,
+        }) => {
+            let time_control = match time_control {
+                Some(time_control) => TimeControl::try_from(time_control.as_str()),
+                None => Ok(TimeControl::fischer(
+                    Duration::from_secs(time),
+                    Duration::from_secs(increment),
+                )),
+            };
+            match (GameType::try_from(game_type.as_str()), time_control) {
+                (Ok(game_type), Ok(time_control)) => {
+                    let config = arbiter::ArbiterConfig {
+                        game_type,
+                        time_control,
+                        max_moves,
+                        shuffle_repetitions,
+                        eval_adjudication: eval_threshold.map(|threshold| {
+                            arbiter::EvalAdjudication {
+                                threshold,
+                                moves: eval_moves,
+                                ..Default::default()
+                            }
+                        }),
+                        limits: limits.limits(),
+                        check_valid_moves,
+                    };
+                    arbiter::print_match(&white, &black, config, output.as_deref())
+                }
+                (Err(e), _) => println!("{}", e),
+                (_, Err(e)) => println!("{}", e),
+            }
+        }
+        Some(MainCommands::Tournament {
+            engines,
+            format,
+            rounds,
+            game_type,
+            time_control,
+            max_moves,
+            limits,
+        }) => {
+            let format = match tournament::Format::try_from(format.as_str()) {
+                Ok(tournament::Format::Swiss { .. }) => Ok(tournament::Format::Swiss { rounds }),
+                format => format,
+            };
+            match (
+                format,
+                GameType::try_from(game_type.as_str()),
+                TimeControl::try_from(time_control.as_str()),
+            ) {
+                (Ok(format), Ok(game_type), Ok(time_control)) => {
+                    let config = arbiter::ArbiterConfig {
+                        game_type,
+                        time_control,
+                        max_moves,
+                        limits: limits.limits(),
+                        ..Default::default()
+                    };
+                    tournament::print_tournament(engines, format, config)
+                }
+                (Err(e), _, _) => println!("{}", e),
+                (_, Err(e), _) => println!("{}", e),
+                (_, _, Err(e)) => println!("{}", e),
+            }
+        }
         Some(MainCommands::Bitboard { number }) => {
             let bitboard = bitgrid::board::AxialBitboard::from_u64(number);
             println!("{}", bitboard);
         }
+        Some(MainCommands::Puzzles { moves, games }) => {
+            data_analysis::puzzles::print_puzzles(moves, games)
+        }
+        Some(MainCommands::Perft {
+            depth,
+            game_string,
+            divide,
+            mzinga,
+        }) => match mzinga {
+            true => testing::mzinga::print_mzinga_check(depth),
+            false => perft::print_perft(depth, game_string, divide),
+        },
+        Some(MainCommands::Book {
+            database,
+            output,
+            min_visits,
+        }) => book::print_build(&database, &output, min_visits),
+        #[cfg(feature = "server")]
+        Some(MainCommands::Serve { address, max_depth }) => {
+            if let Err(e) = anansii::server::serve(&address, max_depth) {
+                println!("{}", e);
+            }
+        }
 
         None => run_uhp(),
     }
@@ -0,0 +1,225 @@
+use crate::game::GameDebugger;
+use crate::hex_grid::{HexGrid, HexLocation};
+use crate::location::Direction;
+use crate::piece::{Piece, PieceId, PieceType};
+
+/// A single turn taken by a player in a game of Hive.
+///
+/// Moves describe what happened to the board, not how a player would
+/// write it down, see the Annotator for notation concerns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Move {
+    /// A piece from the player's hand is placed on top of the board
+    Place { piece: Piece, to: HexLocation },
+    /// A piece on top of a stack is moved to the top of another stack
+    Movement {
+        piece: Piece,
+        from: HexLocation,
+        to: HexLocation,
+    },
+    /// No changes are made to the board
+    Pass,
+}
+
+impl Move {
+    /// Returns the piece that was placed or moved, if any
+    pub fn piece(&self) -> Option<Piece> {
+        match self {
+            Move::Place { piece, .. } | Move::Movement { piece, .. } => Some(*piece),
+            Move::Pass => None,
+        }
+    }
+
+    /// Returns the destination of the piece that was placed or moved, if any
+    pub fn destination(&self) -> Option<HexLocation> {
+        match self {
+            Move::Place { to, .. } | Move::Movement { to, .. } => Some(*to),
+            Move::Pass => None,
+        }
+    }
+
+    /// Returns the location the piece moved away from, if the move was a movement
+    pub fn origin(&self) -> Option<HexLocation> {
+        match self {
+            Move::Movement { from, .. } => Some(*from),
+            _ => None,
+        }
+    }
+
+    /// Returns the same move with every location replaced by *f*, e.g.
+    /// to follow the grid through a Transform
+    pub fn map_locations(&self, f: impl Fn(HexLocation) -> HexLocation) -> Move {
+        match *self {
+            Move::Place { piece, to } => Move::Place { piece, to: f(to) },
+            Move::Movement { piece, from, to } => Move::Movement {
+                piece,
+                from: f(from),
+                to: f(to),
+            },
+            Move::Pass => Move::Pass,
+        }
+    }
+
+    /// Applies this move to a copy of the given grid and returns the result.
+    ///
+    /// Does not check the legality of the move.
+    pub fn apply(&self, grid: &HexGrid) -> HexGrid {
+        let mut new_grid = grid.clone();
+        match *self {
+            Move::Place { piece, to } => new_grid.add(piece, to),
+            Move::Movement { from, to, .. } => {
+                let piece = new_grid.remove(from).expect("Expected a piece to move");
+                new_grid.add(piece, to);
+            }
+            Move::Pass => {}
+        }
+        new_grid
+    }
+
+    /// Like apply(), but returns None instead of panicking when the move
+    /// does not fit the grid: a piece placed on an occupied hex or moved
+    /// from a hex it is not on top of, e.g. a move taken from a
+    /// transposition table entry of a different position.
+    ///
+    /// Does not otherwise check the legality of the move.
+    pub fn try_apply(&self, grid: &HexGrid) -> Option<HexGrid> {
+        let fits = match *self {
+            Move::Place { to, .. } => grid.top(to).is_none(),
+            Move::Movement { piece, from, .. } => grid.top(from) == Some(piece),
+            Move::Pass => true,
+        };
+        fits.then(|| self.apply(grid))
+    }
+
+    /// Describes this move, played from the current position of *game*, in
+    /// plain english, e.g. "Black Ant 2 slides around to the hex north-east
+    /// of White Queen". Pieces are named as in the UHP MoveString of the move
+    pub fn describe(&self, game: &GameDebugger) -> String {
+        let color = game.player_to_move();
+        let player = color.to_str();
+        let Ok(move_string) = game.move_to_uhp(self) else {
+            return format!("{} cannot play {:?}", player, self);
+        };
+
+        let mut tokens = move_string.split_whitespace();
+        let (Some(piece), anchor) = (tokens.next(), tokens.next()) else {
+            return format!("{} passes", player);
+        };
+        let name = |token: &str| {
+            PieceId::from_uhp(token)
+                .map(|id| id.name())
+                .unwrap_or_else(|_| token.to_string())
+        };
+        let destination = match anchor.map(Direction::parse_anchor) {
+            None => String::new(),
+            Some((None, anchor)) => format!(" on top of {}", name(anchor)),
+            Some((Some(direction), anchor)) => {
+                format!(" to the hex {} of {}", direction.name(), name(anchor))
+            }
+        };
+
+        let verb = match *self {
+            Move::Pass => return format!("{} passes", player),
+            Move::Place { .. } => "is placed",
+            Move::Movement { piece, from, .. } => {
+                let climbs = game.position().height(from) > 1 || destination.starts_with(" on top");
+                match piece.piece_type {
+                    // Only a pillbug can move the pieces of the other player
+                    _ if piece.color != color => "is thrown by a pillbug",
+                    _ if climbs => "climbs",
+                    PieceType::Ant => "slides around",
+                    PieceType::Spider => "crawls",
+                    PieceType::Grasshopper => "jumps",
+                    PieceType::Ladybug => "walks over the hive",
+                    _ => "moves",
+                }
+            }
+        };
+        format!("{} {}{}", name(piece), verb, destination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceColor::*;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_describe() {
+        let moves = ["wS1", "bG1 -wS1", "wB1 wS1-", "bQ -bG1", r"wQ wS1\"];
+        let mut game = GameDebugger::from_move_list(&moves, GameType::MLP).unwrap();
+        let describe = |game: &GameDebugger, move_string: &str| {
+            game.uhp_to_move(move_string).unwrap().describe(game)
+        };
+
+        assert_eq!(
+            describe(&game, "bA1 -bQ"),
+            "Black Ant 1 is placed to the hex west of Black Queen"
+        );
+        game.make_move("bA1 -bQ").unwrap();
+
+        assert_eq!(
+            describe(&game, "wB1 wS1"),
+            "White Beetle 1 climbs on top of White Spider 1"
+        );
+        assert_eq!(
+            describe(&game, r"wQ wB1\"),
+            "White Queen moves to the hex south-east of White Beetle 1"
+        );
+        game.make_move("wB1 wS1").unwrap();
+        assert_eq!(
+            describe(&game, r"bA1 wQ\"),
+            "Black Ant 1 slides around to the hex south-east of White Queen"
+        );
+        assert_eq!(Move::Pass.describe(&game), "Black passes");
+
+        let first = GameDebugger::from_move_list(&[], GameType::MLP).unwrap();
+        assert_eq!(describe(&first, "wG1"), "White Grasshopper 1 is placed");
+
+        let moved_under = Move::Movement {
+            piece: Piece::new(PieceType::Spider, White),
+            from: game
+                .position()
+                .find(Piece::new(PieceType::Spider, White))
+                .unwrap()
+                .0,
+            to: HexLocation::new(5, 5),
+        };
+        assert!(moved_under.describe(&game).starts_with("Black cannot play"));
+    }
+
+    #[test]
+    pub fn test_try_apply() {
+        let game = GameDebugger::from_move_list(&["wS1", "bG1 -wS1"], GameType::MLP).unwrap();
+        let grid = game.position();
+        let spider = Piece::new(PieceType::Spider, White);
+        let (from, _) = grid.find(spider).unwrap();
+
+        let ant = Piece::new(PieceType::Ant, White);
+        let to = HexLocation::new(5, 5);
+        let place = Move::Place { piece: ant, to };
+        assert_eq!(place.try_apply(grid), Some(place.apply(grid)));
+        assert_eq!(Move::Pass.try_apply(grid), Some(grid.clone()));
+        let onto = Move::Place {
+            piece: ant,
+            to: from,
+        };
+        assert_eq!(onto.try_apply(grid), None);
+
+        let moved = Move::Movement {
+            piece: spider,
+            from,
+            to,
+        };
+        assert_eq!(moved.try_apply(grid), Some(moved.apply(grid)));
+        let ant_move = Move::Movement {
+            piece: ant,
+            from,
+            to,
+        };
+        assert_eq!(ant_move.try_apply(grid), None);
+        let vacated = moved.apply(grid);
+        assert_eq!(moved.try_apply(&vacated), None);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::game::GameDebugger;
+use crate::location::{Direction, HexLocation};
+use crate::moves::Move;
+
+/// The shape formed by the first four pieces of a game.
+///
+/// The second piece of each player has to be placed next to their first,
+/// so the opening is a chain of four hexes (second white, first white,
+/// first black, second black) classified by how it bends at the first
+/// pieces. Shapes are the same up to rotation and reflection.
+///
+/// ```text
+/// I:  w W B b
+///
+/// L:  w W B
+///          b
+///
+/// C:   W B
+///     w   b
+///
+/// Z:  w
+///      W B
+///         b
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Opening {
+    /// A straight line
+    I,
+    /// A line bent once, next to either player's first piece
+    L,
+    /// Bent twice in the same direction, curving around an empty hex
+    C,
+    /// Bent twice in opposite directions, a zig-zag
+    Z,
+}
+
+impl Opening {
+    pub fn name(&self) -> &str {
+        match self {
+            Opening::I => "I",
+            Opening::L => "L",
+            Opening::C => "C",
+            Opening::Z => "Z",
+        }
+    }
+
+    /// Classifies the opening of *game* from its first four moves, None if
+    /// fewer were played or they do not form a chain of placements
+    pub fn classify(game: &GameDebugger) -> Option<Opening> {
+        let positions = game.positions().take(5).collect::<Vec<_>>();
+        let mut placed = vec![];
+        for pair in positions.windows(2) {
+            match pair[0].diff(pair[1])? {
+                Move::Place { to, .. } => placed.push(to),
+                _ => return None,
+            }
+        }
+        let [white, black, second_white, second_black] = placed[..] else {
+            return None;
+        };
+
+        let chain = [second_white, white, black, second_black];
+        let turns = chain
+            .windows(3)
+            .map(|hexes| turn(hexes[0], hexes[1], hexes[2]))
+            .collect::<Option<Vec<_>>>()?;
+        match turns[..] {
+            [0, 0] => Some(Opening::I),
+            [0, 1 | -1] | [1 | -1, 0] => Some(Opening::L),
+            [1, 1] | [-1, -1] => Some(Opening::C),
+            [1, -1] | [-1, 1] => Some(Opening::Z),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Opening {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} opening", self.name())
+    }
+}
+
+/// The number of clockwise 60 degree turns from the step *a* -> *b* to
+/// the step *b* -> *c*, between -2 and 3, None if the steps are not
+/// between neighbors
+fn turn(a: HexLocation, b: HexLocation, c: HexLocation) -> Option<i32> {
+    let index = |direction| {
+        Direction::all()
+            .iter()
+            .position(|&d| d == direction)
+            .unwrap() as i32
+    };
+    let (first, second) = (a.direction_to(b)?, b.direction_to(c)?);
+    let turn = (index(second) - index(first)).rem_euclid(6);
+    Some(if turn > 3 { turn - 6 } else { turn })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_classify() {
+        let classify = |moves: &[&str]| {
+            let game = GameDebugger::from_move_list(moves, GameType::MLP).unwrap();
+            Opening::classify(&game)
+        };
+
+        let line = ["wG1", "bG1 wG1-", "wS1 -wG1", "bS1 bG1-"];
+        assert_eq!(classify(&line), Some(Opening::I));
+        assert_eq!(
+            classify(&["wG1", "bG1 wG1-", "wS1 -wG1", r"bS1 bG1\"]),
+            Some(Opening::L)
+        );
+        assert_eq!(
+            classify(&["wG1", "bG1 wG1-", r"wS1 \wG1", r"bS1 bG1\"]),
+            Some(Opening::Z)
+        );
+        assert_eq!(
+            classify(&["wG1", "bG1 wG1-", "wS1 /wG1", r"bS1 bG1\"]),
+            Some(Opening::C)
+        );
+
+        // Too early, and openings that have moved on are still classified
+        assert_eq!(classify(&line[..3]), None);
+        let longer = [&line[..], &["wQ wS1/"]].concat();
+        assert_eq!(classify(&longer), Some(Opening::I));
+        assert_eq!(Opening::C.to_string(), "C opening");
+    }
+}
@@ -0,0 +1,165 @@
+use crate::game::{board_result, GameDebugger};
+use crate::generator::debug::{PositionGenerator, ReferenceGenerator};
+use crate::hex_grid::*;
+use crate::uhp::GameType;
+use std::time::Instant;
+
+/// Counts the leaves of the game tree of the given *depth* below *grid*,
+/// with *color* to move and *last_move* the destination of the piece moved
+/// in the previous turn, using the ReferenceGenerator.
+///
+/// Moves that lead to the same position (such as placing either of two
+/// identical pieces) are counted once, passing counts as a move and
+/// finished games have no continuations.
+pub fn perft(
+    grid: &HexGrid,
+    game_type: GameType,
+    color: PieceColor,
+    last_move: Option<HexLocation>,
+    depth: usize,
+) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+    if board_result(grid).is_some() {
+        return 0;
+    }
+
+    let mut generator = ReferenceGenerator::from_hex_grid(grid, game_type, last_move);
+    let positions = generator.generate_positions_for(color);
+    if depth == 1 {
+        return positions.len();
+    }
+
+    positions
+        .iter()
+        .map(|position| {
+            let last_move = grid.diff(position).and_then(|mv| mv.destination());
+            perft(position, game_type, color.opposite(), last_move, depth - 1)
+        })
+        .sum()
+}
+
+/// Splits perft() by the first move, returning the position after every
+/// legal move together with the perft count of *depth* - 1 below it
+pub fn divide(
+    grid: &HexGrid,
+    game_type: GameType,
+    color: PieceColor,
+    last_move: Option<HexLocation>,
+    depth: usize,
+) -> Vec<(HexGrid, usize)> {
+    if depth == 0 || board_result(grid).is_some() {
+        return vec![];
+    }
+
+    let mut generator = ReferenceGenerator::from_hex_grid(grid, game_type, last_move);
+    generator
+        .generate_positions_for(color)
+        .into_iter()
+        .map(|position| {
+            let last_move = grid.diff(&position).and_then(|mv| mv.destination());
+            let count = perft(&position, game_type, color.opposite(), last_move, depth - 1);
+            (position, count)
+        })
+        .collect()
+}
+
+/// Prints the perft counts of every depth up to *depth*, with timings, from the
+/// position given by a UHP GameString or GameTypeString (Base if None).
+///
+/// With *split* the count of the last depth is divided by first move instead
+pub fn print_perft(depth: usize, game_string: Option<String>, split: bool) {
+    let game_string = game_string.unwrap_or("Base".to_string());
+    let game = match GameDebugger::from_game_string(&game_string) {
+        Ok(game) => game,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let game_type = game.game_type();
+
+    let grid = game.position();
+    let color = game.player_to_move();
+    let last_move = game.last_move();
+
+    if split {
+        let start = Instant::now();
+        let mut counts = divide(grid, game_type, color, last_move, depth)
+            .into_iter()
+            .map(|(position, count)| {
+                let move_string = game
+                    .move_string(&position)
+                    .unwrap_or_else(|_| "unknown".to_string());
+                (move_string, count)
+            })
+            .collect::<Vec<_>>();
+        counts.sort();
+        for (move_string, count) in counts.iter() {
+            println!("{}: {}", move_string, count);
+        }
+        let total = counts.iter().map(|(_, count)| count).sum();
+        print_timing(depth, total, start);
+        return;
+    }
+
+    for depth in 1..=depth {
+        let start = Instant::now();
+        let count = perft(grid, game_type, color, last_move, depth);
+        print_timing(depth, count, start);
+    }
+}
+
+fn print_timing(depth: usize, count: usize, start: Instant) {
+    let seconds = start.elapsed().as_secs_f64();
+    let nps = match seconds > 0.0 {
+        true => (count as f64 / seconds) as usize,
+        false => 0,
+    };
+    println!(
+        "perft({}) = {} in {:.3}s ({} nps)",
+        depth, count, seconds, nps
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_perft_opening() {
+        use PieceColor::*;
+
+        let grid = HexGrid::new();
+        // The queen may not be placed on the first turn
+        assert_eq!(perft(&grid, GameType::Standard, White, None, 1), 4);
+        // Every piece but the queen, on every side of the first piece
+        assert_eq!(perft(&grid, GameType::Standard, White, None, 2), 4 * 4 * 6);
+        assert_eq!(perft(&grid, GameType::MLP, White, None, 1), 7);
+    }
+
+    #[test]
+    pub fn test_divide() {
+        use PieceColor::*;
+
+        let moves = [r"wA1", r"bA1 wA1-", r"wQ -wA1"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        let game = GameDebugger::from_moves_custom(&moves, GameType::Standard).unwrap();
+        let grid = game.position();
+        let last_move = game.last_move();
+
+        let split = divide(grid, GameType::Standard, Black, last_move, 2);
+        let total = split.iter().map(|(_, count)| count).sum::<usize>();
+        assert_eq!(total, perft(grid, GameType::Standard, Black, last_move, 2));
+        assert_eq!(
+            split.len(),
+            perft(grid, GameType::Standard, Black, last_move, 1)
+        );
+        for (position, _) in split {
+            assert!(game.move_string(&position).is_ok());
+        }
+    }
+}
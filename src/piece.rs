@@ -15,7 +15,7 @@ pub const PIECE_COUNTS: [(PieceType, usize); 8] = [
     (PieceType::Mosquito, 1),
 ];
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum PieceType {
     Queen,
     Grasshopper,
@@ -42,6 +42,30 @@ impl PieceType {
         }
     }
 
+    /// The lowercase english name of the piece type, e.g. "grasshopper"
+    pub fn name(&self) -> &str {
+        use PieceType::*;
+        match self {
+            Queen => "queen",
+            Grasshopper => "grasshopper",
+            Spider => "spider",
+            Beetle => "beetle",
+            Ant => "ant",
+            Pillbug => "pillbug",
+            Ladybug => "ladybug",
+            Mosquito => "mosquito",
+        }
+    }
+
+    /// The number of pieces of this type in a player's set
+    pub fn count(&self) -> usize {
+        let (_, count) = PIECE_COUNTS
+            .iter()
+            .find(|(piece_type, _)| piece_type == self)
+            .unwrap();
+        *count
+    }
+
     pub fn all(game_type: GameType) -> Vec<PieceType> {
         use GameType::*;
         use PieceType::*;
@@ -66,6 +90,23 @@ impl PieceType {
         }
     }
 
+    /// The piece type with the given index in declaration order, the inverse
+    /// of `piece_type as u8`
+    pub fn from_index(index: u8) -> Result<PieceType> {
+        use PieceType::*;
+        match index {
+            0 => Ok(Queen),
+            1 => Ok(Grasshopper),
+            2 => Ok(Spider),
+            3 => Ok(Beetle),
+            4 => Ok(Ant),
+            5 => Ok(Pillbug),
+            6 => Ok(Ladybug),
+            7 => Ok(Mosquito),
+            _ => Err(HexGridError::PieceError),
+        }
+    }
+
     fn try_from_char(c: &char) -> Result<PieceType> {
         let string = c.to_string();
         PieceType::try_from_str(&string)
@@ -100,7 +141,7 @@ impl TryFrom<&str> for PieceType {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum PieceColor {
     Black,
     White,
@@ -122,7 +163,10 @@ impl PieceColor {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// The bit of Piece::to_byte() set for white pieces
+const COLOR_BIT: u8 = 0x80;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Piece {
     pub piece_type: PieceType,
     pub color: PieceColor,
@@ -162,14 +206,40 @@ impl Piece {
         }
     }
 
+    /// Packs the piece into a single byte that is never 0, with the piece
+    /// type in the low bits and the color in the high bit, so that 0 is
+    /// free to stand for an empty hex, see from_byte()
+    pub fn to_byte(&self) -> u8 {
+        let piece_type = self.piece_type as u8 + 1;
+        match self.color {
+            PieceColor::White => piece_type | COLOR_BIT,
+            PieceColor::Black => piece_type,
+        }
+    }
+
+    /// Unpacks a byte made by to_byte(), where 0 is an empty hex
+    pub fn from_byte(byte: u8) -> Result<Option<Piece>> {
+        if byte == 0 {
+            return Ok(None);
+        }
+        let color = match byte & COLOR_BIT {
+            0 => PieceColor::Black,
+            _ => PieceColor::White,
+        };
+        let index = (byte & !COLOR_BIT).checked_sub(1);
+        let piece_type = PieceType::from_index(index.ok_or(HexGridError::PieceError)?)?;
+        Ok(Some(Piece::new(piece_type, color)))
+    }
+
     pub fn from_uhp(uhp: &str) -> Result<Piece> {
-        let color = match &uhp[0..1] {
-            "w" => PieceColor::White,
-            "b" => PieceColor::Black,
+        let mut chars = uhp.chars();
+        let color = match chars.next() {
+            Some('w') => PieceColor::White,
+            Some('b') => PieceColor::Black,
             _ => return Err(HexGridError::PieceError),
         };
 
-        let piece = PieceType::try_from_char(&uhp.chars().nth(1).unwrap())?;
+        let piece = PieceType::try_from_char(&chars.next().ok_or(HexGridError::PieceError)?)?;
         Ok(Piece::new(piece, color))
     }
 }
@@ -181,6 +251,68 @@ impl std::hash::Hash for Piece {
     }
 }
 
+/// A specific physical piece from a player's set, distinguishing between
+/// pieces of the same type by the order in which they were placed
+/// (e.g. the second black spider)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PieceId {
+    pub piece: Piece,
+    pub id: u8,
+}
+
+impl PieceId {
+    pub fn new(piece: Piece, id: u8) -> PieceId {
+        PieceId { piece, id }
+    }
+
+    /// The UHP PieceString for this piece, pieces that only come
+    /// in a single copy have no id appended to them (e.g. wQ, bA2)
+    pub fn to_uhp(&self) -> String {
+        let uhp = self.piece.to_uhp(self.id);
+        match self.piece.piece_type.count() {
+            1 => uhp[..2].to_string(),
+            _ => uhp,
+        }
+    }
+
+    /// The english name of the piece, pieces that only come in a single
+    /// copy have no number (e.g. "Black Ant 2", "White Queen")
+    pub fn name(&self) -> String {
+        let mut letters = self.piece.piece_type.name().chars();
+        let first = letters.next().unwrap().to_ascii_uppercase();
+        let name = format!(
+            "{} {}{}",
+            self.piece.color.to_str(),
+            first,
+            letters.as_str()
+        );
+        match self.piece.piece_type.count() {
+            1 => name,
+            _ => format!("{} {}", name, self.id),
+        }
+    }
+
+    /// Parses a UHP PieceString, pieces without an id are
+    /// assumed to be the first of their type (e.g. wQ is wQ1)
+    pub fn from_uhp(uhp: &str) -> Result<PieceId> {
+        if uhp.len() < 2 {
+            return Err(HexGridError::PieceError);
+        }
+        let piece = Piece::from_uhp(uhp)?;
+        let id = match &uhp[2..] {
+            "" => 1,
+            id => id.parse().map_err(|_| HexGridError::PieceError)?,
+        };
+        Ok(PieceId::new(piece, id))
+    }
+}
+
+impl std::fmt::Display for PieceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_uhp())
+    }
+}
+
 pub trait IntoPieces {
     /// Returns a list of pieces and their locations in "board order", that
     /// is first by row top to bottom then by column left to right,
@@ -194,3 +326,27 @@ pub trait IntoPieces {
         HexGrid::from_pieces(self.pieces())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    pub fn test_piece_bytes() {
+        let mut bytes = HashSet::new();
+        for piece_type in PieceType::all(GameType::MLP) {
+            for color in [PieceColor::White, PieceColor::Black] {
+                let piece = Piece::new(piece_type, color);
+                let byte = piece.to_byte();
+                assert_ne!(byte, 0);
+                assert!(bytes.insert(byte));
+                assert_eq!(Piece::from_byte(byte).unwrap(), Some(piece));
+            }
+        }
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(Piece::from_byte(0).unwrap(), None);
+        assert!(Piece::from_byte(COLOR_BIT).is_err());
+        assert!(Piece::from_byte(9).is_err());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::encoding::{self, EncodingError};
+use crate::game::{GameDebugger, GameResult};
+use crate::hex_grid::HexGrid;
+use crate::moves::Move;
+use crate::piece::PieceColor;
+use crate::zobrist;
+use smallvec::SmallVec;
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// The first bytes of a saved PositionDb
+const MAGIC: &[u8; 4] = b"APDB";
+
+#[derive(Error, Debug)]
+pub enum PositionDbError {
+    #[error("Could not access {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Not a position database")]
+    NotADatabase,
+    #[error("{0} is not a valid player to move")]
+    InvalidColor(u8),
+    #[error("Corrupt position database, {0}")]
+    Encoding(#[from] EncodingError),
+}
+
+pub type Result<T> = std::result::Result<T, PositionDbError>;
+
+/// What is known about a position, see PositionDb
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PositionStats {
+    /// The number of times the position was recorded
+    pub visits: u64,
+    pub white_wins: u64,
+    pub black_wins: u64,
+    pub draws: u64,
+    pub best_move: Option<Move>,
+}
+
+impl PositionStats {
+    /// The share of the decided and drawn games won by *color*, counting
+    /// draws as half a win, None if no results were recorded
+    pub fn score(&self, color: PieceColor) -> Option<f64> {
+        let games = self.white_wins + self.black_wins + self.draws;
+        let wins = match color {
+            PieceColor::White => self.white_wins,
+            PieceColor::Black => self.black_wins,
+        };
+        match games {
+            0 => None,
+            games => Some((wins as f64 + self.draws as f64 / 2.0) / games as f64),
+        }
+    }
+}
+
+/// Statistics of positions, stored once for every position that is the
+/// same up to translation, rotation and reflection.
+///
+/// Positions are stored in their canonical form (see HexGrid::canonical())
+/// together with the player to move, under their key(). A position is
+/// looked up by comparing it with the positions stored under its key, so
+/// positions whose keys collide are kept apart. Moves are stored relative
+/// to the canonical form, so they are translated back when read through a
+/// symmetric position.
+#[derive(Clone, Debug, Default)]
+pub struct PositionDb {
+    /// The positions stored under each key, more than one only for
+    /// positions whose keys collide
+    entries: HashMap<u64, SmallVec<[(HexGrid, PieceColor, PositionStats); 1]>>,
+    len: usize,
+}
+
+impl PositionDb {
+    pub fn new() -> PositionDb {
+        PositionDb::default()
+    }
+
+    /// The key of the canonical form *canonical* with *color* to move
+    pub fn key(canonical: &HexGrid, color: PieceColor) -> u64 {
+        zobrist::state_key(canonical, None, color)
+    }
+
+    /// The number of distinct positions stored
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the statistics of *grid* with *color* to move, with the best
+    /// move given relative to *grid*
+    pub fn get(&self, grid: &HexGrid, color: PieceColor) -> Option<PositionStats> {
+        let transform = grid.canonical_transform();
+        let canonical = grid.apply_transform(&transform);
+        let (_, _, stats) = self
+            .entries
+            .get(&PositionDb::key(&canonical, color))?
+            .iter()
+            .find(|(position, stored, _)| *stored == color && *position == canonical)?;
+        let best_move = stats
+            .best_move
+            .map(|mv| mv.map_locations(|location| transform.invert(location)));
+        Some(PositionStats {
+            best_move,
+            ..*stats
+        })
+    }
+
+    /// Counts a visit of *grid* with *color* to move, in a game that ended
+    /// with *result* (None if it is unknown or the game is unfinished)
+    pub fn record(&mut self, grid: &HexGrid, color: PieceColor, result: Option<GameResult>) {
+        let stats = self.entry(grid, color);
+        stats.visits += 1;
+        match result.map(|result| result.winner()) {
+            Some(Some(PieceColor::White)) => stats.white_wins += 1,
+            Some(Some(PieceColor::Black)) => stats.black_wins += 1,
+            Some(None) => stats.draws += 1,
+            None => {}
+        }
+    }
+
+    /// Records every position of *game* with the result of the game,
+    /// positions repeated in the game are counted once per occurrence
+    pub fn import_game(&mut self, game: &GameDebugger) {
+        let result = game.game_result();
+        let mut color = PieceColor::White;
+        for position in game.positions() {
+            self.record(position, color, result.clone());
+            color = color.opposite();
+        }
+    }
+
+    /// Stores *mv*, a move played by *color* from *grid*, as the best move
+    /// of the position
+    pub fn set_best_move(&mut self, grid: &HexGrid, color: PieceColor, mv: Move) {
+        let transform = grid.canonical_transform();
+        let best_move = mv.map_locations(|location| transform.apply(location));
+        self.entry(grid, color).best_move = Some(best_move);
+    }
+
+    /// Iterates over every stored position in its canonical form with the
+    /// player to move, in no particular order
+    pub fn positions(&self) -> impl Iterator<Item = (&HexGrid, PieceColor, &PositionStats)> {
+        self.entries
+            .values()
+            .flatten()
+            .map(|(position, color, stats)| (position, *color, stats))
+    }
+
+    /// Returns the stats of *grid* with *color* to move, inserting empty
+    /// ones if it is not stored yet
+    fn entry(&mut self, grid: &HexGrid, color: PieceColor) -> &mut PositionStats {
+        let canonical = grid.canonical();
+        let entries = self
+            .entries
+            .entry(PositionDb::key(&canonical, color))
+            .or_default();
+        let index = match entries
+            .iter()
+            .position(|(position, stored, _)| *stored == color && *position == canonical)
+        {
+            Some(index) => index,
+            None => {
+                entries.push((canonical, color, PositionStats::default()));
+                self.len += 1;
+                entries.len() - 1
+            }
+        };
+        &mut entries[index].2
+    }
+
+    /// Serializes the database as the magic bytes and the number of
+    /// entries (u64, little endian), then for each entry the position (see
+    /// encoding::encode_position()), the player to move (0 for white, 1 for
+    /// black), the visits, white wins, black wins and draws (u64 each, little
+    /// endian) and the best move (0 if there is none, otherwise 1 followed by
+    /// the move, see encoding::write_move()).
+    ///
+    /// Entries are written in the order of their keys, so that the same
+    /// database is always written the same way
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries = self
+            .entries
+            .iter()
+            .flat_map(|(key, entries)| {
+                entries.iter().map(|(position, color, stats)| {
+                    (*key, encoding::encode_position(position), *color, stats)
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| (a.0, &a.1, a.2).cmp(&(b.0, &b.1, b.2)));
+
+        let mut output = MAGIC.to_vec();
+        output.extend((entries.len() as u64).to_le_bytes());
+        for (_, position, color, stats) in entries {
+            output.extend(position);
+            output.push(match color {
+                PieceColor::White => 0,
+                PieceColor::Black => 1,
+            });
+            for count in [
+                stats.visits,
+                stats.white_wins,
+                stats.black_wins,
+                stats.draws,
+            ] {
+                output.extend(count.to_le_bytes());
+            }
+            match stats.best_move {
+                Some(mv) => {
+                    output.push(1);
+                    encoding::write_move(&mv, &mut output);
+                }
+                None => output.push(0),
+            }
+        }
+        output
+    }
+
+    /// Reads a database made by to_bytes()
+    pub fn from_bytes(bytes: &[u8]) -> Result<PositionDb> {
+        let Some(bytes) = bytes.strip_prefix(MAGIC.as_slice()) else {
+            return Err(PositionDbError::NotADatabase);
+        };
+        let mut offset = 0;
+        let mut db = PositionDb::new();
+        for _ in 0..read_u64(bytes, &mut offset)? {
+            let (position, length) = encoding::read_position(&bytes[offset..])?;
+            offset += length;
+            let color = match *bytes.get(offset).ok_or(EncodingError::Truncated)? {
+                0 => PieceColor::White,
+                1 => PieceColor::Black,
+                byte => return Err(PositionDbError::InvalidColor(byte)),
+            };
+            offset += 1;
+
+            let mut counts = [0; 4];
+            for count in counts.iter_mut() {
+                *count = read_u64(bytes, &mut offset)?;
+            }
+            let [visits, white_wins, black_wins, draws] = counts;
+
+            let has_move = *bytes.get(offset).ok_or(EncodingError::Truncated)?;
+            offset += 1;
+            let best_move = match has_move {
+                0 => None,
+                _ => {
+                    let (mv, length) = encoding::read_move(&bytes[offset..])?;
+                    offset += length;
+                    Some(mv)
+                }
+            };
+
+            *db.entry(&position, color) = PositionStats {
+                visits,
+                white_wins,
+                black_wins,
+                draws,
+                best_move,
+            };
+        }
+
+        match bytes.len() - offset {
+            0 => Ok(db),
+            left_over => Err(EncodingError::TrailingBytes(left_over).into()),
+        }
+    }
+
+    /// Writes the database to *path*, see to_bytes()
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| PositionDbError::Io(path.display().to_string(), e))
+    }
+
+    /// Reads a database written by save()
+    pub fn load(path: &Path) -> Result<PositionDb> {
+        let bytes =
+            std::fs::read(path).map_err(|e| PositionDbError::Io(path.display().to_string(), e))?;
+        PositionDb::from_bytes(&bytes)
+    }
+}
+
+/// Reads a little endian u64 at *offset*, moving the offset past it
+fn read_u64(bytes: &[u8], offset: &mut usize) -> Result<u64> {
+    let end = *offset + 8;
+    let value = bytes.get(*offset..end).ok_or(EncodingError::Truncated)?;
+    *offset = end;
+    Ok(u64::from_le_bytes(value.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::{Axis, HexLocation};
+    use crate::piece::{Piece, PieceType};
+    use PieceColor::*;
+
+    #[test]
+    pub fn test_position_db() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . Q 2 a .\n",
+            ". . g . .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ b B ]\n",
+        ));
+        let symmetric = grid
+            .reflect(Axis::NorthEast)
+            .rotate60(2)
+            .translate(HexLocation::new(3, -1));
+        assert_eq!(grid.canonical(), symmetric.canonical());
+
+        let mut db = PositionDb::new();
+        db.record(&grid, White, Some(GameResult::WhiteWins));
+        db.record(&symmetric, White, Some(GameResult::Stalemate));
+        db.record(&symmetric, White, None);
+        db.record(&HexGrid::new(), White, Some(GameResult::BlackWins));
+        assert_eq!(db.len(), 2);
+
+        let stats = db.get(&grid, White).unwrap();
+        assert_eq!(stats.visits, 3);
+        assert_eq!((stats.white_wins, stats.black_wins, stats.draws), (1, 0, 1));
+        assert_eq!(stats.score(White), Some(0.75));
+        assert_eq!(
+            db.get(&grid.translate(HexLocation::new(0, 1)), White),
+            Some(stats)
+        );
+        assert_eq!(db.get(&grid.rotate60(1).rotate60(5), White), Some(stats));
+
+        // The same board with the other player to move is another position
+        assert_eq!(db.get(&grid, Black), None);
+        db.record(&grid, Black, None);
+        assert_eq!(db.len(), 3);
+        assert_eq!(db.get(&grid, Black).unwrap().visits, 1);
+        assert_eq!(db.get(&grid, White), Some(stats));
+
+        // The best move follows the position through its symmetries
+        let ant = Piece::new(PieceType::Ant, Black);
+        let (from, _) = grid.find(ant).unwrap();
+        let mv = Move::Movement {
+            piece: ant,
+            from,
+            to: from.apply(crate::location::Direction::SE),
+        };
+        db.set_best_move(&grid, Black, mv);
+        assert_eq!(db.get(&grid, Black).unwrap().best_move, Some(mv));
+        assert_eq!(db.get(&grid, White).unwrap().best_move, None);
+        let transform = symmetric.canonical_transform();
+        let from_symmetric = db.get(&symmetric, Black).unwrap().best_move.unwrap();
+        assert_eq!(
+            from_symmetric.map_locations(|location| transform.apply(location)),
+            mv.map_locations(|location| grid.canonical_transform().apply(location))
+        );
+        assert_eq!(
+            mv.apply(&grid).canonical(),
+            from_symmetric.apply(&symmetric).canonical()
+        );
+
+        let loaded = PositionDb::from_bytes(&db.to_bytes()).unwrap();
+        assert_eq!(loaded.len(), db.len());
+        for color in [White, Black] {
+            assert_eq!(loaded.get(&symmetric, color), db.get(&symmetric, color));
+            assert_eq!(
+                loaded.get(&HexGrid::new(), color),
+                db.get(&HexGrid::new(), color)
+            );
+        }
+        assert_eq!(loaded.to_bytes(), db.to_bytes());
+
+        let bytes = db.to_bytes();
+        assert!(matches!(
+            PositionDb::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(PositionDbError::Encoding(EncodingError::Truncated))
+        ));
+        assert!(matches!(
+            PositionDb::from_bytes(b"nope"),
+            Err(PositionDbError::NotADatabase)
+        ));
+    }
+
+    #[test]
+    pub fn test_colliding_positions() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . . q . .\n",
+            ". . A Q .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let empty = HexGrid::new();
+        let mut db = PositionDb::new();
+        db.record(&grid, White, Some(GameResult::WhiteWins));
+
+        // Another position stored under the same key is kept apart
+        let key = PositionDb::key(&grid.canonical(), White);
+        db.entries
+            .get_mut(&key)
+            .unwrap()
+            .insert(0, (empty.clone(), White, PositionStats::default()));
+        db.len += 1;
+        assert_eq!(db.get(&grid, White).unwrap().white_wins, 1);
+        db.record(&grid, White, None);
+        assert_eq!(db.get(&grid, White).unwrap().visits, 2);
+        assert_eq!(db.len(), 2);
+        assert_eq!(db.positions().count(), 2);
+
+        // The empty board was stored under the key of another position, and
+        // is found under its own key once loaded
+        assert_eq!(db.get(&empty, White), None);
+        let loaded = PositionDb::from_bytes(&db.to_bytes()).unwrap();
+        assert_eq!(loaded.get(&empty, White), Some(PositionStats::default()));
+        assert_eq!(loaded.get(&grid, White), db.get(&grid, White));
+
+        // The order positions are recorded in does not change the bytes
+        let mut forward = PositionDb::new();
+        let mut backward = PositionDb::new();
+        let positions = [grid.clone(), empty.clone(), grid.rotate60(1)];
+        for (position, color) in positions.iter().zip([White, Black, Black]) {
+            forward.record(position, color, None);
+        }
+        for (position, color) in positions.iter().zip([White, Black, Black]).rev() {
+            backward.record(position, color, None);
+        }
+        assert_eq!(forward.to_bytes(), backward.to_bytes());
+    }
+}
@@ -0,0 +1,260 @@
+use crate::game::GameDebugger;
+use crate::generator::debug::{PositionGenerator, ReferenceGenerator};
+use crate::hex_grid::*;
+use crate::moves::Move;
+use crate::uhp::GameType;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+/// How far from the center of the grid a piece may wander during the search,
+/// histories that drift further are not explored
+const MAX_DRIFT: Coordinate = 20;
+
+/// The outcome of trying to prove a position reachable
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reachability {
+    /// The positions of a legal game starting from the empty board and
+    /// ending in the position
+    Reachable(Vec<HexGrid>),
+    /// The position breaks the rules of Hive outright, see HexGrid::validate()
+    Invalid(Vec<Violation>),
+    /// Every possible history of the position was searched, and none of them
+    /// start from the empty board
+    Unreachable,
+    /// The search gave up before finding an answer
+    Unknown,
+}
+
+/// Attempts to prove that positions (typically written by hand in the DSL)
+/// can be reached from the empty board by a legal game.
+///
+/// The search runs backwards from the position, undoing placements and moves.
+/// Every undone move is checked forwards with the ReferenceGenerator, and the
+/// resulting game is replayed by a GameDebugger before being reported.
+#[derive(Clone, Debug)]
+pub struct ReachabilityProver {
+    game_type: GameType,
+    node_limit: usize,
+}
+
+/// A position explored during the search, arrived at by a move of *mover*
+struct Node {
+    grid: HexGrid,
+    mover: PieceColor,
+    /// The node this position was reached from, that is, the position after it
+    next: Option<usize>,
+    /// The number of turns undone to reach this position
+    turns: usize,
+}
+
+impl ReachabilityProver {
+    /// Creates a prover that explores at most *node_limit* earlier positions
+    /// before giving up
+    pub fn new(game_type: GameType, node_limit: usize) -> ReachabilityProver {
+        ReachabilityProver {
+            game_type,
+            node_limit,
+        }
+    }
+
+    /// Searches for a legal game that ends in the given position.
+    ///
+    /// Earlier positions are explored best first, preferring positions that
+    /// need the fewest turns in total to be reached from the empty board.
+    pub fn prove(&self, grid: &HexGrid) -> Reachability {
+        if let Err(violations) = grid.validate(self.game_type) {
+            return Reachability::Invalid(violations);
+        }
+        if grid.is_empty() {
+            return Reachability::Reachable(vec![HexGrid::new()]);
+        }
+
+        let mut nodes = vec![];
+        let mut visited = HashSet::new();
+        let mut frontier = BinaryHeap::new();
+        let mut complete = true;
+
+        // Either player may have made the last move
+        for mover in [PieceColor::White, PieceColor::Black] {
+            visited.insert((grid.normalized(), mover));
+            frontier.push(Reverse((grid.num_pieces(), grid.num_pieces(), nodes.len())));
+            nodes.push(Node {
+                grid: grid.clone(),
+                mover,
+                next: None,
+                turns: 0,
+            });
+        }
+
+        let mut explored = 0;
+        while let Some(Reverse((_, _, index))) = frontier.pop() {
+            explored += 1;
+            if explored > self.node_limit {
+                return Reachability::Unknown;
+            }
+
+            let mover = nodes[index].mover;
+            let turns = nodes[index].turns + 1;
+            for previous in self.predecessors(&nodes[index].grid, mover, &mut complete) {
+                if previous.is_empty() {
+                    // White always makes the first move
+                    if mover != PieceColor::White {
+                        continue;
+                    }
+                    let history = history(&nodes, index);
+                    if self.replays(&history) {
+                        return Reachability::Reachable(history);
+                    }
+                    complete = false;
+                    continue;
+                }
+
+                if !visited.insert((previous.normalized(), mover.opposite())) {
+                    continue;
+                }
+                let pieces = previous.num_pieces();
+                frontier.push(Reverse((turns + pieces, pieces, nodes.len())));
+                nodes.push(Node {
+                    grid: previous,
+                    mover: mover.opposite(),
+                    next: Some(index),
+                    turns,
+                });
+            }
+        }
+
+        match complete {
+            true => Reachability::Unreachable,
+            false => Reachability::Unknown,
+        }
+    }
+
+    /// Returns the positions from which *mover* could have arrived at *grid*
+    /// in a single turn, placements first as they lead towards the empty board
+    fn predecessors(&self, grid: &HexGrid, mover: PieceColor, complete: &mut bool) -> Vec<HexGrid> {
+        let mut predecessors = vec![];
+
+        let pinned = grid.pinned();
+        for (_, location, height) in grid.pieces_of(mover) {
+            if height != 0 || grid.is_covered(location) || pinned.contains(&location) {
+                continue;
+            }
+            let mut previous = grid.clone();
+            previous.remove(location);
+            if self.leads_to(&previous, grid, mover) {
+                predecessors.push(previous);
+            }
+        }
+
+        // Moves in Hive can always be made in reverse, so the positions the
+        // mover could have come from are the ones they could move to
+        let mut generator = ReferenceGenerator::from_hex_grid(grid, self.game_type, None);
+        let positions = generator.generate_positions_for(mover);
+        if positions.len() == 1 && positions.contains(grid) {
+            // No moves available, the mover passed
+            predecessors.push(grid.clone());
+            return predecessors;
+        }
+
+        for previous in positions {
+            if !matches!(grid.diff(&previous), Some(Move::Movement { .. })) {
+                continue;
+            }
+            if drifted(&previous) {
+                *complete = false;
+                continue;
+            }
+            if self.leads_to(&previous, grid, mover) {
+                predecessors.push(previous);
+            }
+        }
+
+        predecessors
+    }
+
+    /// Returns true if *color* can move from *from* to *to* in a single turn,
+    /// ignoring pieces immobilized by the pillbug
+    fn leads_to(&self, from: &HexGrid, to: &HexGrid, color: PieceColor) -> bool {
+        let mut generator = ReferenceGenerator::from_hex_grid(from, self.game_type, None);
+        generator.generate_positions_for(color).contains(to)
+    }
+
+    /// Returns true if the *history* is a legal game
+    fn replays(&self, history: &Vec<HexGrid>) -> bool {
+        GameDebugger::from_positions_custom(history, self.game_type).is_ok()
+    }
+}
+
+/// The positions of the game from the empty board, through the node at *index*,
+/// up to the position the search started from
+fn history(nodes: &[Node], index: usize) -> Vec<HexGrid> {
+    let mut history = vec![HexGrid::new()];
+    let mut current = Some(index);
+    while let Some(index) = current {
+        history.push(nodes[index].grid.clone());
+        current = nodes[index].next;
+    }
+    history
+}
+
+fn drifted(grid: &HexGrid) -> bool {
+    grid.occupied()
+        .any(|(location, _)| location.x.abs() > MAX_DRIFT || location.y.abs() > MAX_DRIFT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::Replay;
+
+    #[test]
+    pub fn test_reachable() {
+        // Ends with the white beetle on top of the white queen
+        let moves = [
+            r"wL",
+            r"bP wL-",
+            r"wQ \wL",
+            r"bQ bP/",
+            r"wB1 /wQ",
+            r"bA1 bQ-",
+            r"wB1 wQ",
+            r"bA1 -wB1",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        assert!(GameDebugger::from_moves_custom(&moves, GameType::MLP).is_ok());
+        let mut replay = Replay::new(&moves);
+        let position = replay.seek(moves.len()).unwrap().clone();
+
+        let prover = ReachabilityProver::new(GameType::MLP, 1000);
+        let Reachability::Reachable(history) = prover.prove(&position) else {
+            panic!("Expected the position to be reachable");
+        };
+        assert!(history.first().unwrap().is_empty());
+        assert_eq!(*history.last().unwrap(), position);
+        assert!(GameDebugger::from_positions_custom(&history, GameType::MLP).is_ok());
+    }
+
+    #[test]
+    pub fn test_unreachable() {
+        // Black must have placed a piece before white could place a second one
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". . Q A .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let prover = ReachabilityProver::new(GameType::Standard, 1000);
+        assert_eq!(prover.prove(&grid), Reachability::Unreachable);
+
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". . 2 a .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [G A]\n",
+        ));
+        assert!(matches!(prover.prove(&grid), Reachability::Invalid(_)));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::game::GameDebugger;
+use crate::generator::debug::ReferenceGenerator;
+use crate::hex_grid::*;
+use crate::uhp::GameType;
+
+/// How close a queen is to being surrounded, for danger meters and as
+/// support for evaluation terms, see QueenMetrics::of()
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct QueenMetrics {
+    pub color: PieceColor,
+    pub location: HexLocation,
+    /// The empty hexes around the queen, the queen is surrounded at zero
+    pub liberties: usize,
+    /// Neighboring stacks topped by a piece of the queen's color
+    pub friendly_neighbors: usize,
+    /// Neighboring stacks topped by a piece of the opponent
+    pub enemy_neighbors: usize,
+    /// Whether a beetle or mosquito sits on top of the queen
+    pub covered: bool,
+    /// The number of hexes the queen could move to on her player's turn,
+    /// by herself or thrown by a friendly pillbug
+    pub destinations: usize,
+}
+
+impl QueenMetrics {
+    /// Measures the queen of *color* in *grid*, None if she was not placed
+    /// yet. *last_move* is the destination of the piece moved in the
+    /// previous turn, which a pillbug cannot throw
+    pub fn of(
+        grid: &HexGrid,
+        color: PieceColor,
+        game_type: GameType,
+        last_move: Option<HexLocation>,
+    ) -> Option<QueenMetrics> {
+        let (location, height) = grid.find(Piece::new(PieceType::Queen, color))?;
+        let (mut friendly_neighbors, mut enemy_neighbors) = (0, 0);
+        for neighbor in grid.get_neighbors(location) {
+            match grid.top(neighbor) {
+                Some(piece) if piece.color == color => friendly_neighbors += 1,
+                _ => enemy_neighbors += 1,
+            }
+        }
+        let generator = ReferenceGenerator::from_hex_grid(grid, game_type, last_move);
+        Some(QueenMetrics {
+            color,
+            location,
+            liberties: grid.get_empty_neighbors(location).len(),
+            friendly_neighbors,
+            enemy_neighbors,
+            covered: height + 1 < grid.height(location),
+            destinations: generator.legal_destinations(color, location).len(),
+        })
+    }
+
+    /// The pieces around the queen, six when she is surrounded
+    pub fn neighbors(&self) -> usize {
+        self.friendly_neighbors + self.enemy_neighbors
+    }
+
+    pub fn is_surrounded(&self) -> bool {
+        self.liberties == 0
+    }
+
+    /// Whether the queen can still escape on her own turn
+    pub fn can_move(&self) -> bool {
+        self.destinations > 0
+    }
+}
+
+/// Measures both queens of the current position of *game*, white first,
+/// see QueenMetrics::of()
+pub fn queen_metrics(game: &GameDebugger) -> [Option<QueenMetrics>; 2] {
+    [PieceColor::White, PieceColor::Black]
+        .map(|color| QueenMetrics::of(game.position(), color, game.game_type(), game.last_move()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_queen_metrics() {
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a Q b .\n",
+            " . G A . .\n",
+            ". . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let white = QueenMetrics::of(&grid, PieceColor::White, GameType::MLP, None).unwrap();
+        assert_eq!(white.liberties, 2);
+        assert_eq!(white.friendly_neighbors, 2);
+        assert_eq!(white.enemy_neighbors, 2);
+        assert_eq!(white.neighbors(), 4);
+        assert!(!white.covered);
+        assert!(!white.is_surrounded());
+        assert!(white.can_move());
+        assert!(QueenMetrics::of(&grid, PieceColor::Black, GameType::MLP, None).is_none());
+
+        // Pinned between the ants she cannot move at all
+        let pinned = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". a Q b .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let white = QueenMetrics::of(&pinned, PieceColor::White, GameType::MLP, None).unwrap();
+        assert_eq!((white.liberties, white.enemy_neighbors), (4, 2));
+        assert!(!white.can_move());
+
+        let covered = HexGrid::from_dsl(concat!(
+            " . . . .\n",
+            ". a 2 .\n",
+            " . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ Q b ]\n",
+        ));
+        let white = QueenMetrics::of(&covered, PieceColor::White, GameType::MLP, None).unwrap();
+        assert!(white.covered);
+        assert_eq!(white.liberties, 5);
+        assert!(!white.can_move());
+
+        let game =
+            GameDebugger::from_move_list(&["wA1", "bG1 -wA1", "wQ wA1-", "bQ /bG1"], GameType::MLP)
+                .unwrap();
+        let [white, black] = queen_metrics(&game);
+        let (white, black) = (white.unwrap(), black.unwrap());
+        assert_eq!((white.friendly_neighbors, white.enemy_neighbors), (1, 0));
+        assert_eq!((black.friendly_neighbors, black.enemy_neighbors), (1, 0));
+        assert!(white.can_move() && black.can_move());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::hex_grid::HexGrid;
+use crate::uhp::{Annotator, UHPError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("Ply {0} is past the end of the replay")]
+    OutOfRange(usize),
+    #[error("Could not replay move {0}, {1}")]
+    MoveError(String, UHPError),
+}
+
+pub type Result<T> = std::result::Result<T, ReplayError>;
+
+/// Steps through a list of UHP MoveStrings, starting from the empty board.
+///
+/// Positions are reconstructed lazily and cached, so moving backwards or
+/// seeking to an already visited ply is free, and moving forwards only
+/// replays the moves that have not been seen yet.
+///
+/// Ply 0 is the empty board, ply n is the position after the nth move.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    moves: Vec<String>,
+    /// annotations[i] holds the position after i moves have been made
+    annotations: Vec<Annotator>,
+    ply: usize,
+}
+
+impl Replay {
+    pub fn new(moves: &[String]) -> Replay {
+        Replay {
+            moves: moves.to_vec(),
+            annotations: vec![Annotator::new()],
+            ply: 0,
+        }
+    }
+
+    /// The number of moves that can be replayed
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// The ply the replay is currently positioned at
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// The position at the current ply
+    pub fn current_position(&self) -> &HexGrid {
+        self.annotations[self.ply].position()
+    }
+
+    /// The UHP MoveString that led to the current position, if any
+    pub fn last_move(&self) -> Option<&str> {
+        match self.ply {
+            0 => None,
+            ply => Some(&self.moves[ply - 1]),
+        }
+    }
+
+    /// Makes sure every position up to and including the given ply is cached
+    fn reconstruct(&mut self, ply: usize) -> Result<()> {
+        if ply > self.len() {
+            return Err(ReplayError::OutOfRange(ply));
+        }
+
+        while self.annotations.len() <= ply {
+            let move_string = &self.moves[self.annotations.len() - 1];
+            let annotator = self
+                .annotations
+                .last()
+                .unwrap()
+                .next_uhp_move(move_string)
+                .map_err(|e| ReplayError::MoveError(move_string.clone(), e))?;
+            self.annotations.push(annotator);
+        }
+
+        Ok(())
+    }
+
+    /// Moves the replay to the given ply and returns the position there
+    pub fn seek(&mut self, ply: usize) -> Result<&HexGrid> {
+        self.reconstruct(ply)?;
+        self.ply = ply;
+        Ok(self.current_position())
+    }
+
+    /// Moves the replay back by a single ply and returns the position there,
+    /// or None if already at the start
+    pub fn prev(&mut self) -> Option<&HexGrid> {
+        if self.ply == 0 {
+            return None;
+        }
+        self.ply -= 1;
+        Some(self.current_position())
+    }
+
+    /// Returns the position at the given ply without moving the replay
+    pub fn snapshot(&mut self, ply: usize) -> Result<HexGrid> {
+        self.reconstruct(ply)?;
+        Ok(self.annotations[ply].position().clone())
+    }
+}
+
+impl Iterator for Replay {
+    type Item = HexGrid;
+
+    /// Moves the replay forward by a single ply and returns the position there,
+    /// or None if already at the end or the next move cannot be replayed
+    fn next(&mut self) -> Option<HexGrid> {
+        self.seek(self.ply + 1).ok().cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves() -> Vec<String> {
+        [r"wL", r"bP wL-", r"wA1 \wL", r"bB1 bP/", r"wQ /wA1", r"bQ bB1\"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    pub fn test_replay_step_through() {
+        let mut replay = Replay::new(&moves());
+        assert_eq!(replay.len(), 6);
+        assert!(replay.current_position().is_empty());
+        assert!(replay.prev().is_none());
+
+        let first = replay.next().unwrap();
+        assert_eq!(first.num_pieces(), 1);
+        assert_eq!(replay.last_move(), Some("wL"));
+
+        let positions = replay.by_ref().collect::<Vec<_>>();
+        assert_eq!(positions.len(), 5);
+        assert_eq!(replay.ply(), 6);
+        assert_eq!(replay.current_position().num_pieces(), 6);
+
+        let previous = replay.prev().unwrap().clone();
+        assert_eq!(previous, positions[3]);
+        assert_eq!(replay.ply(), 5);
+    }
+
+    #[test]
+    pub fn test_replay_seek() {
+        let final_position = HexGrid::from_dsl(concat!(
+            ". . . . . .\n",
+            " . A . b . .\n",
+            ". Q L p q .\n",
+            " . . . . . .\n\n",
+            "start - [ -1 -2 ]\n\n"
+        ));
+
+        let mut replay = Replay::new(&moves());
+        assert_eq!(*replay.seek(6).unwrap(), final_position);
+        assert_eq!(replay.seek(2).unwrap().num_pieces(), 2);
+        assert_eq!(replay.snapshot(6).unwrap(), final_position);
+        assert_eq!(replay.ply(), 2);
+        assert!(matches!(replay.seek(7), Err(ReplayError::OutOfRange(7))));
+        assert!(replay.seek(0).unwrap().is_empty());
+    }
+}
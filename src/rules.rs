@@ -0,0 +1,318 @@
+use crate::generator::debug::ReferenceGenerator;
+use crate::hex_grid::*;
+use crate::inventory::Inventory;
+use crate::moves::Move;
+use crate::uhp::GameType;
+use thiserror::Error;
+
+/// The reason a move breaks the rules of Hive.
+///
+/// The error messages are intended to be shown to players as is.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalMoveReason {
+    #[error("the game is already over")]
+    GameOver,
+    #[error("passing is only allowed when no other move can be made")]
+    PassNotAllowed,
+    #[error("that piece belongs to your opponent")]
+    WrongColor,
+    #[error("there is no piece there to move")]
+    EmptyOrigin,
+    #[error("this {} is covered by another piece and cannot move", .0.name())]
+    PieceCovered(PieceType),
+    #[error("there are no more pieces of type {} left in hand", .0.name())]
+    NotInHand(PieceType),
+    #[error("pieces can only be placed on empty hexes")]
+    DestinationOccupied,
+    #[error("the queen may not be placed on the first turn")]
+    QueenFirstTurn,
+    #[error("the queen must be placed by the fourth turn")]
+    QueenDeadline,
+    #[error("pieces cannot move until the queen has been placed")]
+    QueenNotPlaced,
+    #[error("placed pieces must touch the hive")]
+    PlacementDisconnected,
+    #[error("placed pieces may not touch an opponent's piece")]
+    PlacementTouchesOpponent,
+    #[error("moving this {} would split the hive", .0.name())]
+    BreaksHive(PieceType),
+    #[error("this gap is a gate and the {} cannot squeeze through", .0.name())]
+    Gate(PieceType),
+    #[error("this piece moved last turn and cannot be moved by a pillbug")]
+    Immobilized,
+    #[error("a pillbug that moved last turn cannot move other pieces")]
+    ThrowerImmobilized,
+    #[error("pieces in a stack cannot be moved by a pillbug")]
+    ThrownFromStack,
+    #[error("the {} cannot reach that hex", .0.name())]
+    Unreachable(PieceType),
+}
+
+/// Determines why a move that is known to be illegal breaks the rules,
+/// when made by the player of the given *color* on the given *grid*.
+///
+/// *last_move* is the destination of the piece moved in the previous turn,
+/// and *tournament_opening* forbids placing the queen on the first turn.
+///
+/// Reasons are checked from the most basic (wrong piece, wrong turn) to the
+/// most specific (gates, reachability), the first one that applies is returned.
+pub fn illegal_reason(
+    grid: &HexGrid,
+    game_type: GameType,
+    tournament_opening: bool,
+    color: PieceColor,
+    last_move: Option<HexLocation>,
+    mv: &Move,
+) -> IllegalMoveReason {
+    use IllegalMoveReason::*;
+
+    let friendly_count = grid.pieces_of(color).len();
+    let queen_placed = grid.find(Piece::new(PieceType::Queen, color)).is_some();
+
+    match *mv {
+        Move::Pass => PassNotAllowed,
+        Move::Place { piece, to } => {
+            if piece.color != color {
+                return WrongColor;
+            }
+            if !Inventory::saturating_from_grid(grid, color, game_type).contains(piece.piece_type) {
+                return NotInHand(piece.piece_type);
+            }
+            if grid.height(to) > 0 {
+                return DestinationOccupied;
+            }
+            if tournament_opening && piece.piece_type == PieceType::Queen && friendly_count == 0 {
+                return QueenFirstTurn;
+            }
+            if piece.piece_type != PieceType::Queen && !queen_placed && friendly_count == 3 {
+                return QueenDeadline;
+            }
+            if !grid.is_empty() && grid.get_neighbors(to).is_empty() {
+                return PlacementDisconnected;
+            }
+            let touches_opponent = grid
+                .get_neighbors(to)
+                .into_iter()
+                .any(|loc| grid.top(loc).unwrap().color != color);
+            if grid.num_pieces() > 1 && touches_opponent {
+                return PlacementTouchesOpponent;
+            }
+            Unreachable(piece.piece_type)
+        }
+        Move::Movement { piece, from, to } => {
+            let stack = grid.stack(from);
+            if !stack.contains(&piece) {
+                return EmptyOrigin;
+            }
+            if stack.last() != Some(&piece) {
+                return PieceCovered(piece.piece_type);
+            }
+            if piece.color != color {
+                // Only a pillbug (or mosquito acting as one) may move an opponent's piece
+                let throwers: Vec<_> = grid
+                    .get_neighbors(from)
+                    .into_iter()
+                    .filter(|loc| {
+                        let stack = grid.peek(*loc);
+                        stack.len() == 1
+                            && stack[0].color == color
+                            && matches!(
+                                stack[0].piece_type,
+                                PieceType::Pillbug | PieceType::Mosquito
+                            )
+                    })
+                    .collect();
+                if throwers.is_empty() {
+                    return WrongColor;
+                }
+                if throwers.iter().all(|thrower| last_move == Some(*thrower)) {
+                    return ThrowerImmobilized;
+                }
+                if stack.len() > 1 {
+                    return ThrownFromStack;
+                }
+                if last_move == Some(from) {
+                    return Immobilized;
+                }
+            }
+            if !queen_placed {
+                return QueenNotPlaced;
+            }
+            if !grid.is_covered(from) && grid.pinned().contains(&from) {
+                return BreaksHive(piece.piece_type);
+            }
+
+            let gated = Direction::all()
+                .into_iter()
+                .find(|direction| from.apply(*direction) == to)
+                .map(|direction| {
+                    let (left, right) = direction.adjacent();
+                    let left_height = grid.height(from.apply(left));
+                    let right_height = grid.height(from.apply(right));
+                    let effective_height = stack.len().max(grid.height(to) + 1);
+                    effective_height <= left_height.min(right_height)
+                })
+                .unwrap_or(false);
+            if gated {
+                return Gate(piece.piece_type);
+            }
+
+            Unreachable(piece.piece_type)
+        }
+    }
+}
+
+/// A piece that cannot move by itself and why, see frozen_pieces()
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrozenPiece {
+    pub piece: Piece,
+    pub location: HexLocation,
+    /// The number of pieces below it in its stack
+    pub height: Height,
+    /// One of PieceCovered, QueenNotPlaced, BreaksHive and, for pieces that
+    /// are free to leave but have nowhere to go, Gate
+    pub reason: IllegalMoveReason,
+}
+
+/// Returns every piece on *grid* that cannot move by itself on its player's
+/// turn, in board order and from the bottom of each stack up, so that a UI
+/// can gray them out. Being thrown by a pillbug is not considered.
+///
+/// Reasons are checked in the order of illegal_reason(): covered pieces
+/// are reported as covered even when their stack is pinned
+pub fn frozen_pieces(grid: &HexGrid, game_type: GameType) -> Vec<FrozenPiece> {
+    use IllegalMoveReason::*;
+
+    let pinned = grid.pinned();
+    let generator = ReferenceGenerator::from_hex_grid(grid, game_type, None);
+    let mut frozen = vec![];
+    for color in [PieceColor::White, PieceColor::Black] {
+        let queen_placed = grid.find(Piece::new(PieceType::Queen, color)).is_some();
+        for (piece, location, height) in grid.pieces_of(color) {
+            let reason = if height + 1 < grid.height(location) {
+                PieceCovered(piece.piece_type)
+            } else if !queen_placed {
+                QueenNotPlaced
+            } else if grid.height(location) == 1 && pinned.contains(&location) {
+                BreaksHive(piece.piece_type)
+            } else if generator.destinations(location).is_empty() {
+                Gate(piece.piece_type)
+            } else {
+                continue;
+            };
+            frozen.push(FrozenPiece {
+                piece,
+                location,
+                height,
+                reason,
+            });
+        }
+    }
+    frozen.sort_by_key(|frozen| (HexGrid::board_order(frozen.location), frozen.height));
+    frozen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_pillbug_immunity() {
+        use IllegalMoveReason::*;
+        use PieceColor::*;
+        use PieceType::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". Q P a .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+        let (pillbug, _) = grid.find(Piece::new(Pillbug, White)).unwrap();
+        let (ant, _) = grid.find(Piece::new(Ant, Black)).unwrap();
+        let throw = Move::Movement {
+            piece: Piece::new(Ant, Black),
+            from: ant,
+            to: pillbug.apply(Direction::NW),
+        };
+        let reason =
+            |last_move| illegal_reason(&grid, GameType::MLP, false, White, last_move, &throw);
+        assert_eq!(reason(Some(ant)), Immobilized);
+        assert_eq!(reason(Some(pillbug)), ThrowerImmobilized);
+        assert_eq!(
+            illegal_reason(&grid, GameType::MLP, false, Black, None, &throw),
+            QueenNotPlaced
+        );
+
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . .\n",
+            ". Q P 2 .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ a b ]\n",
+        ));
+        let throw = Move::Movement {
+            piece: Piece::new(Beetle, Black),
+            from: ant,
+            to: pillbug.apply(Direction::NW),
+        };
+        assert_eq!(
+            illegal_reason(&grid, GameType::MLP, false, White, None, &throw),
+            ThrownFromStack
+        );
+        assert_eq!(
+            ThrownFromStack.to_string(),
+            "pieces in a stack cannot be moved by a pillbug"
+        );
+    }
+
+    #[test]
+    pub fn test_frozen_pieces() {
+        use IllegalMoveReason::*;
+        let piece = |name: &str| Piece::from_uhp(name).unwrap();
+
+        // The white queen sits in a pocket of five pieces, the black ant at
+        // its second hex holds the white ant on the outside
+        let center = HexLocation::new(0, 0);
+        let ring = Direction::all().map(|direction| center.apply(direction));
+        let mut grid = HexGrid::new();
+        grid.add(piece("wQ"), center);
+        for (location, name) in ring.iter().zip(["bQ", "bA", "bS", "bA", "wG"]) {
+            grid.add(piece(name), *location);
+        }
+        grid.add(piece("wB"), ring[2]);
+        grid.add(piece("wA"), ring[1].apply(Direction::all()[1]));
+
+        let frozen = frozen_pieces(&grid, GameType::MLP);
+        let reason = |location: HexLocation, height: Height| {
+            frozen
+                .iter()
+                .find(|frozen| frozen.location == location && frozen.height == height)
+                .map(|frozen| frozen.reason)
+        };
+        assert_eq!(reason(center, 0), Some(Gate(PieceType::Queen)));
+        assert_eq!(reason(ring[1], 0), Some(BreaksHive(PieceType::Ant)));
+        assert_eq!(reason(ring[2], 0), Some(PieceCovered(PieceType::Spider)));
+        assert_eq!(reason(ring[2], 1), None);
+        assert_eq!(reason(ring[4], 0), None);
+        assert!(frozen.windows(2).all(|pair| {
+            let key = |frozen: &FrozenPiece| (HexGrid::board_order(frozen.location), frozen.height);
+            key(&pair[0]) < key(&pair[1])
+        }));
+
+        // Without their queen no black piece may move
+        grid.remove(ring[0]);
+        let frozen = frozen_pieces(&grid, GameType::MLP);
+        assert!(frozen
+            .iter()
+            .filter(|frozen| frozen.piece.color == PieceColor::Black && frozen.height == 0)
+            .all(|frozen| frozen.reason == QueenNotPlaced || frozen.location == ring[2]));
+        assert_eq!(
+            frozen
+                .iter()
+                .filter(|frozen| frozen.reason == QueenNotPlaced)
+                .count(),
+            2
+        );
+    }
+}
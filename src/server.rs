@@ -0,0 +1,633 @@
+use crate::engine::analysis::{format_score, line_strings};
+use crate::engine::batch::default_threads;
+use crate::engine::search::Searcher;
+use crate::engine::series::EvalSeries;
+use crate::game::GameDebugger;
+use crate::uhp::{CommandError, ErrorCode, GameType};
+use crate::uhp_interface::UHPInterface;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::panic::AssertUnwindSafe;
+use std::sync::{mpsc, Arc, Mutex};
+use thiserror::Error;
+use tiny_http::{Header, Method, Request, Response};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::{Role, WebSocket};
+use tungstenite::Message;
+
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("No game with id {0}")]
+    GameNotFound(usize),
+    #[error("No endpoint for {0} {1}")]
+    NotFound(String, String),
+    #[error("Invalid depth {0}, expected 1 to {1}")]
+    InvalidDepth(String, usize),
+    #[error("The game is over")]
+    GameOver,
+    #[error("{0}")]
+    Uhp(#[from] CommandError),
+    #[error("Could not start the server: {0}")]
+    Bind(String),
+    #[error("Every worker is busy, try again later")]
+    Busy,
+}
+
+pub type Result<T> = std::result::Result<T, ServerError>;
+
+/// Most games an AnalysisServer keeps, creating another one forgets the game
+/// least recently used
+pub const MAX_GAMES: usize = 1024;
+/// Most evaluation series an AnalysisServer keeps, one per game type and depth
+pub const MAX_SERIES: usize = 8;
+/// Requests serve() queues for its workers before turning them away as Busy
+pub const QUEUED_REQUESTS: usize = 64;
+
+impl ServerError {
+    /// The HTTP status code reported for this error
+    pub fn status(&self) -> u16 {
+        match self {
+            ServerError::GameNotFound(_) | ServerError::NotFound(_, _) => 404,
+            ServerError::Bind(_) => 500,
+            ServerError::Busy => 503,
+            _ => 400,
+        }
+    }
+}
+
+/// The games played through the server, each a UHPInterface addressed by id
+///
+/// Endpoints (bodies are plain text, responses are JSON):
+///
+/// ```text
+/// POST   /games                      new game, body: GameTypeString or GameString
+/// GET    /games/<id>                 current GameString
+/// DELETE /games/<id>                 forgets the game
+/// POST   /games/<id>/play            plays a move, body: UHP MoveString
+/// GET    /games/<id>/validmoves      UHP MoveStrings of every legal move
+/// GET    /games/<id>/bestmove?depth= searches the position, see Searcher
+/// GET    /games/<id>/eval?depth=     WebSocket, one message per depth searched
+/// GET    /games/<id>/evaluations?depth= score for white after every ply, see EvalSeries
+/// GET    /games/<id>/hint?depth=     best move with the reasons for it, see hint()
+/// ```
+///
+/// At most MAX_GAMES games and MAX_SERIES evaluation series are kept, the
+/// least recently used are forgotten first
+pub struct AnalysisServer {
+    games: Recent<usize, UHPInterface>,
+    next_id: usize,
+    max_depth: usize,
+    /// Shared by every game, so that games with the same opening reuse
+    /// each other's scores
+    series: Recent<(GameType, usize), Arc<Mutex<EvalSeries>>>,
+}
+
+/// A search requested from an AnalysisServer, see Search::run()
+pub enum Search {
+    BestMove(GameDebugger, usize),
+    Hint(GameDebugger, usize),
+    Evaluations {
+        id: usize,
+        game: GameDebugger,
+        depth: usize,
+        series: Arc<Mutex<EvalSeries>>,
+    },
+}
+
+/// The answer to a request, either right away or after a search
+pub enum Reply {
+    Done(Value),
+    Search(Box<Search>),
+}
+
+impl AnalysisServer {
+    /// Creates a server whose searches are limited to *max_depth* plies
+    pub fn new(max_depth: usize) -> AnalysisServer {
+        AnalysisServer {
+            games: Recent::new(MAX_GAMES),
+            next_id: 1,
+            max_depth,
+            series: Recent::new(MAX_SERIES),
+        }
+    }
+
+    /// Answers a REST request, *url* may include a query string
+    pub fn handle(&mut self, method: &str, url: &str, body: &str) -> Result<Value> {
+        match self.request(method, url, body)? {
+            Reply::Done(value) => Ok(value),
+            Reply::Search(search) => search.run(),
+        }
+    }
+
+    /// Answers a REST request, leaving the searches it asks for to the
+    /// caller so that they can run on another thread
+    pub fn request(&mut self, method: &str, url: &str, body: &str) -> Result<Reply> {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+        let not_found = || ServerError::NotFound(method.to_string(), path.to_string());
+
+        match (method, segments.as_slice()) {
+            ("POST", ["games"]) => self.new_game(body.trim()).map(Reply::Done),
+            (method, ["games", id, rest @ ..]) => {
+                let id = id.parse::<usize>().map_err(|_| not_found())?;
+                if self.games.get(&id).is_none() {
+                    return Err(ServerError::GameNotFound(id));
+                }
+                let reply = match (method, rest) {
+                    ("GET", []) => {
+                        let game_string = self.game(id)?.game_string();
+                        json!({ "id": id, "game_string": game_string })
+                    }
+                    ("DELETE", []) => {
+                        self.games.remove(&id);
+                        json!({ "id": id })
+                    }
+                    ("POST", ["play"]) => {
+                        let game_string = self.command(id, &format!("play {}", body.trim()))?;
+                        json!({ "id": id, "game_string": game_string })
+                    }
+                    ("GET", ["validmoves"]) => {
+                        let moves = self.command(id, "validmoves")?;
+                        let moves = moves
+                            .split(';')
+                            .filter(|m| !m.is_empty())
+                            .collect::<Vec<_>>();
+                        json!({ "id": id, "moves": moves })
+                    }
+                    ("GET", ["bestmove"]) => {
+                        let depth = self.depth(query)?;
+                        let game = self.game(id)?.game_debugger();
+                        return Ok(Reply::Search(Box::new(Search::BestMove(game, depth))));
+                    }
+                    ("GET", ["hint"]) => {
+                        let depth = self.depth(query)?;
+                        let game = self.game(id)?.game_debugger();
+                        return Ok(Reply::Search(Box::new(Search::Hint(game, depth))));
+                    }
+                    ("GET", ["evaluations"]) => {
+                        let depth = self.depth(query)?;
+                        let game = self.game(id)?.game_debugger();
+                        let key = (game.game_type(), depth);
+                        if self.series.get(&key).is_none() {
+                            let series = EvalSeries::new(game.game_type(), depth);
+                            self.series.insert(key, Arc::new(Mutex::new(series)));
+                        }
+                        let series = self.series.get(&key).unwrap().clone();
+                        return Ok(Reply::Search(Box::new(Search::Evaluations {
+                            id,
+                            game,
+                            depth,
+                            series,
+                        })));
+                    }
+                    _ => return Err(not_found()),
+                };
+                Ok(Reply::Done(reply))
+            }
+            _ => Err(not_found()),
+        }
+    }
+
+    /// Returns the game and search depth of an eval stream request
+    pub fn eval_request(&mut self, url: &str) -> Result<(GameDebugger, usize)> {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+        let ["games", id, "eval"] = segments.as_slice() else {
+            return Err(ServerError::NotFound("GET".to_string(), path.to_string()));
+        };
+        let id = id
+            .parse::<usize>()
+            .map_err(|_| ServerError::NotFound("GET".to_string(), path.to_string()))?;
+        let game = self.game(id)?.game_debugger();
+        Ok((game, self.depth(query)?))
+    }
+
+    fn new_game(&mut self, body: &str) -> Result<Value> {
+        let mut uhp = UHPInterface::new();
+        let input = match body.is_empty() {
+            true => "newgame".to_string(),
+            false => format!("newgame {}", body),
+        };
+        let game_string = uhp.execute(&input)?;
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.games.insert(id, uhp);
+        Ok(json!({ "id": id, "game_string": game_string }))
+    }
+
+    fn game(&mut self, id: usize) -> Result<&mut UHPInterface> {
+        self.games.get(&id).ok_or(ServerError::GameNotFound(id))
+    }
+
+    /// Runs a UHP command on the game *id*
+    fn command(&mut self, id: usize, input: &str) -> Result<String> {
+        Ok(self.game(id)?.execute(input)?)
+    }
+
+    /// Reads the depth of a search from a query string, 3 (or the maximum
+    /// depth if lower) if not given
+    fn depth(&self, query: &str) -> Result<usize> {
+        let Some(value) = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("depth="))
+        else {
+            return Ok(self.max_depth.min(3));
+        };
+        match value.parse::<usize>() {
+            Ok(depth) if (1..=self.max_depth).contains(&depth) => Ok(depth),
+            _ => Err(ServerError::InvalidDepth(value.to_string(), self.max_depth)),
+        }
+    }
+}
+
+impl Search {
+    /// Runs the search, giving the body of the response to the request
+    /// that asked for it
+    pub fn run(self) -> Result<Value> {
+        match self {
+            Search::BestMove(game, depth) => best_move(&game, depth),
+            Search::Hint(game, depth) => {
+                let hint = game.hint(depth).ok_or(ServerError::GameOver)?;
+                let reasons = hint
+                    .reasons
+                    .iter()
+                    .map(|reason| reason.to_string())
+                    .collect::<Vec<_>>();
+                Ok(json!({
+                    "depth": depth,
+                    "move": hint.move_string,
+                    "score": hint.score,
+                    "evaluation": format_score(hint.score),
+                    "reasons": reasons,
+                }))
+            }
+            Search::Evaluations {
+                id,
+                game,
+                depth,
+                series,
+            } => {
+                let scores = series.lock().unwrap().evaluate_game(&game);
+                let evaluations = scores
+                    .iter()
+                    .map(|score| format_score(*score))
+                    .collect::<Vec<_>>();
+                Ok(json!({
+                    "id": id,
+                    "depth": depth,
+                    "scores": scores,
+                    "evaluations": evaluations,
+                }))
+            }
+        }
+    }
+}
+
+/// Searches the current position of *game* to *depth* plies
+pub fn best_move(game: &GameDebugger, depth: usize) -> Result<Value> {
+    if game.game_result().is_some() {
+        return Err(ServerError::GameOver);
+    }
+    let mut searcher = Searcher::new(game.game_type());
+    let analysis = searcher.analyze(
+        game.position(),
+        game.last_move(),
+        game.player_to_move(),
+        depth,
+    );
+    let best_line = line_strings(game, &analysis.best_line);
+    Ok(json!({
+        "depth": depth,
+        "move": best_line.first(),
+        "score": analysis.score,
+        "evaluation": format_score(analysis.score),
+        "best_line": best_line,
+        "nodes": analysis.stats.nodes,
+        "nps": analysis.stats.nps(),
+    }))
+}
+
+/// Values by key, forgetting the least recently used one when a value is
+/// inserted past the limit
+struct Recent<K, V> {
+    entries: HashMap<K, (V, u64)>,
+    limit: usize,
+    /// Counts the uses of every value, each stored with the count at its
+    /// last use
+    uses: u64,
+}
+
+impl<K: Copy + Eq + Hash, V> Recent<K, V> {
+    fn new(limit: usize) -> Recent<K, V> {
+        Recent {
+            entries: HashMap::new(),
+            limit,
+            uses: 0,
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<&mut V> {
+        self.uses += 1;
+        let (value, last_use) = self.entries.get_mut(key)?;
+        *last_use = self.uses;
+        Some(value)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.entries.len() >= self.limit && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_use))| *last_use)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.uses += 1;
+        self.entries.insert(key, (value, self.uses));
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.entries.remove(key).map(|(value, _)| value)
+    }
+}
+
+/// A fixed number of threads doing the jobs sent to them, so that no number
+/// of requests can start more threads than that
+struct Workers<T> {
+    jobs: mpsc::SyncSender<T>,
+}
+
+impl<T: Send + 'static> Workers<T> {
+    /// Starts *threads* threads calling *work* on every job, with up to
+    /// *queue* jobs waiting for one of them
+    fn new(threads: usize, queue: usize, work: fn(T)) -> Workers<T> {
+        let (jobs, receiver) = mpsc::sync_channel::<T>(queue);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads.max(1) {
+            let receiver = receiver.clone();
+            std::thread::spawn(move || loop {
+                let Ok(job) = receiver.lock().unwrap().recv() else {
+                    return;
+                };
+                // A job that panics must not take the worker down with it
+                let _ = std::panic::catch_unwind(AssertUnwindSafe(|| work(job)));
+            });
+        }
+        Workers { jobs }
+    }
+
+    /// Queues *job*, giving it back if every worker is busy and the queue
+    /// is full
+    fn send(&self, job: T) -> std::result::Result<(), T> {
+        self.jobs.try_send(job).map_err(|error| match error {
+            mpsc::TrySendError::Full(job) | mpsc::TrySendError::Disconnected(job) => job,
+        })
+    }
+}
+
+/// The requests serve() leaves to its workers
+enum Job {
+    Search(Request, Search),
+    /// A WebSocket upgrade request with its key, see stream_eval()
+    Eval(Request, String, GameDebugger, usize),
+}
+
+impl Job {
+    fn run(self) {
+        match self {
+            Job::Search(request, search) => respond(request, search.run()),
+            Job::Eval(request, key, game, depth) => stream_eval(request, &key, game, depth),
+        }
+    }
+
+    fn request(self) -> Request {
+        match self {
+            Job::Search(request, _) | Job::Eval(request, ..) => request,
+        }
+    }
+}
+
+/// Serves the REST and WebSocket endpoints of an AnalysisServer on *address*
+/// until the process is stopped
+///
+/// Searches and eval streams run on one worker thread per core, with up to
+/// QUEUED_REQUESTS more waiting for a worker, further ones are answered
+/// with ServerError::Busy. Every other request is answered right away
+pub fn serve(address: &str, max_depth: usize) -> Result<()> {
+    let server = tiny_http::Server::http(address).map_err(|e| ServerError::Bind(e.to_string()))?;
+    let mut games = AnalysisServer::new(max_depth);
+    let workers = Workers::new(default_threads(), QUEUED_REQUESTS, Job::run);
+    let queue = |job: Job| {
+        if let Err(job) = workers.send(job) {
+            respond(job.request(), Err(ServerError::Busy));
+        }
+    };
+    println!("Listening on http://{}", address);
+
+    for mut request in server.incoming_requests() {
+        let key = header(&request, "Sec-WebSocket-Key");
+        if let (Method::Get, Some(key)) = (request.method(), key) {
+            match games.eval_request(request.url()) {
+                Ok((game, depth)) => queue(Job::Eval(request, key, game, depth)),
+                Err(error) => respond(request, Err(error)),
+            }
+            continue;
+        }
+
+        let mut body = String::new();
+        if request.as_reader().read_to_string(&mut body).is_err() {
+            respond(
+                request,
+                Err(CommandError::new(ErrorCode::InvalidArgument, "Body is not UTF-8").into()),
+            );
+            continue;
+        }
+        let method = request.method().to_string();
+        match games.request(&method, request.url(), &body) {
+            Ok(Reply::Search(search)) => queue(Job::Search(request, *search)),
+            Ok(Reply::Done(value)) => respond(request, Ok(value)),
+            Err(error) => respond(request, Err(error)),
+        }
+    }
+    Ok(())
+}
+
+fn header(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.to_string())
+}
+
+fn respond(request: Request, result: Result<Value>) {
+    let (status, value) = match result {
+        Ok(value) => (200, value),
+        Err(ServerError::Uhp(error)) => (
+            400,
+            json!({ "error": error.message, "code": error.code.to_str() }),
+        ),
+        Err(error) => (error.status(), json!({ "error": error.to_string() })),
+    };
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(content_type);
+    // The client may have gone away, there is nobody left to tell
+    let _ = request.respond(response);
+}
+
+/// Completes the WebSocket handshake and sends the search result of every
+/// depth up to *depth* as it finishes
+fn stream_eval(request: Request, key: &str, game: GameDebugger, depth: usize) {
+    let accept =
+        Header::from_bytes("Sec-WebSocket-Accept", derive_accept_key(key.as_bytes())).unwrap();
+    let response = Response::empty(101).with_header(accept);
+    let stream = request.upgrade("websocket", response);
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    for depth in 1..=depth {
+        let message = match best_move(&game, depth) {
+            Ok(value) => value,
+            Err(error) => json!({ "error": error.to_string() }),
+        };
+        if socket.send(Message::Text(message.to_string())).is_err() {
+            return;
+        }
+    }
+    let _ = socket.close(None);
+    // Wait for the client to acknowledge the close
+    while socket.read().is_ok() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_server_game() {
+        let mut server = AnalysisServer::new(2);
+
+        let game = server.handle("POST", "/games", "Base+M").unwrap();
+        assert_eq!(game["id"], 1);
+        assert_eq!(game["game_string"], "Base+M;NotStarted;White[1]");
+
+        let moves = server.handle("GET", "/games/1/validmoves", "").unwrap();
+        assert_eq!(moves["moves"].as_array().unwrap().len(), 5);
+
+        let played = server.handle("POST", "/games/1/play", "wA1\n").unwrap();
+        assert_eq!(played["game_string"], "Base+M;InProgress;Black[1];wA1");
+        let game = server.handle("GET", "/games/1", "").unwrap();
+        assert_eq!(game["game_string"], played["game_string"]);
+
+        let best = server
+            .handle("GET", "/games/1/bestmove?depth=1", "")
+            .unwrap();
+        assert!(best["move"].as_str().unwrap().starts_with('b'));
+        assert_eq!(best["best_line"].as_array().unwrap().len(), 1);
+
+        let hint = server.handle("GET", "/games/1/hint?depth=1", "").unwrap();
+        assert_eq!(hint["score"], best["score"]);
+        assert!(hint["reasons"].is_array());
+
+        let series = server
+            .handle("GET", "/games/1/evaluations?depth=1", "")
+            .unwrap();
+        assert_eq!(series["scores"].as_array().unwrap().len(), 1);
+        assert_eq!(series["evaluations"].as_array().unwrap().len(), 1);
+
+        let (game, depth) = server.eval_request("/games/1/eval?depth=2").unwrap();
+        assert_eq!(game.player_to_move(), crate::hex_grid::PieceColor::Black);
+        assert_eq!(depth, 2);
+
+        assert_eq!(server.handle("DELETE", "/games/1", "").unwrap()["id"], 1);
+        assert!(matches!(
+            server.handle("GET", "/games/1", ""),
+            Err(ServerError::GameNotFound(1))
+        ));
+    }
+
+    #[test]
+    pub fn test_server_errors() {
+        let mut server = AnalysisServer::new(2);
+        server.handle("POST", "/games", "").unwrap();
+
+        let chess = server.handle("POST", "/games", "Chess");
+        assert!(matches!(chess, Err(ServerError::Uhp(_))));
+        assert_eq!(chess.unwrap_err().status(), 400);
+
+        // Illegal moves are errors too
+        server.handle("POST", "/games/1/play", "wA1").unwrap();
+        let illegal = server.handle("POST", "/games/1/play", "bQ wA1-");
+        assert!(matches!(
+            illegal,
+            Err(ServerError::Uhp(CommandError {
+                code: ErrorCode::InvalidMove,
+                ..
+            }))
+        ));
+
+        let deep = server.handle("GET", "/games/1/bestmove?depth=3", "");
+        assert!(matches!(deep, Err(ServerError::InvalidDepth(_, 2))));
+
+        let unknown = server.handle("GET", "/games/1/moves", "");
+        assert_eq!(unknown.unwrap_err().status(), 404);
+        assert!(server.handle("GET", "/games/x", "").is_err());
+        assert!(server.eval_request("/games/2/eval").is_err());
+    }
+
+    #[test]
+    pub fn test_forget_games() {
+        let mut server = AnalysisServer::new(1);
+        for _ in 0..MAX_GAMES {
+            server.handle("POST", "/games", "").unwrap();
+        }
+        // Game 2 is the least recently used once game 1 was played
+        server.handle("POST", "/games/1/play", "wA1").unwrap();
+        let game = server.handle("POST", "/games", "").unwrap();
+        assert_eq!(game["id"], MAX_GAMES + 1);
+
+        assert!(server.handle("GET", "/games/1", "").is_ok());
+        assert!(matches!(
+            server.handle("GET", "/games/2", ""),
+            Err(ServerError::GameNotFound(2))
+        ));
+        assert!(server.handle("GET", "/games/3", "").is_ok());
+    }
+
+    #[test]
+    pub fn test_workers() {
+        let workers = Workers::new(1, 0, |job: mpsc::Receiver<()>| {
+            let _ = job.recv();
+        });
+        // Waits for the worker to start
+        let (finish, mut job) = mpsc::channel();
+        while let Err(returned) = workers.send(job) {
+            job = returned;
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        // The only worker is busy and nothing may wait for it
+        let (_, waiting) = mpsc::channel();
+        let mut waiting = match workers.send(waiting) {
+            Ok(()) => panic!("The job should be turned away"),
+            Err(waiting) => waiting,
+        };
+
+        finish.send(()).unwrap();
+        while let Err(returned) = workers.send(waiting) {
+            waiting = returned;
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+}
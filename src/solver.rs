@@ -0,0 +1,175 @@
+use crate::game::board_result;
+use crate::generator::debug::{PositionGenerator, ReferenceGenerator};
+use crate::hex_grid::*;
+use crate::uhp::GameType;
+
+/// Exhaustive solver for forced queen surrounds.
+///
+/// A position is a forced surround in N for the attacker if the attacker
+/// can surround the opposing queen with at most N of their own moves,
+/// no matter how the defender replies. Surrounding both queens at once
+/// is a draw and does not count as a win.
+///
+/// The search is a plain AND/OR tree search over the ReferenceGenerator,
+/// so it is only practical for small N.
+#[derive(Clone, Debug)]
+pub struct SurroundSolver {
+    game_type: GameType,
+}
+
+impl SurroundSolver {
+    pub fn new(game_type: GameType) -> SurroundSolver {
+        SurroundSolver { game_type }
+    }
+
+    /// Returns every position reachable by the *attacker* in a single move
+    /// from *grid* that forces a surround of the opposing queen within
+    /// *moves* attacker moves (counting this one).
+    ///
+    /// *last_move* is the destination of the piece moved in the previous turn,
+    /// which cannot be moved by a pillbug this turn.
+    pub fn winning_moves(
+        &self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        attacker: PieceColor,
+        moves: usize,
+    ) -> Vec<HexGrid> {
+        if !self.surround_possible(grid, attacker, moves) {
+            return vec![];
+        }
+
+        self.children(grid, last_move, attacker)
+            .into_iter()
+            .filter(|(child, destination)| self.move_wins(child, *destination, attacker, moves - 1))
+            .map(|(child, _)| child)
+            .collect()
+    }
+
+    /// Returns true if the *attacker* to move can force a surround of the
+    /// opposing queen within *moves* attacker moves
+    pub fn has_forced_surround(
+        &self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        attacker: PieceColor,
+        moves: usize,
+    ) -> bool {
+        if !self.surround_possible(grid, attacker, moves) {
+            return false;
+        }
+
+        self.children(grid, last_move, attacker)
+            .into_iter()
+            .any(|(child, destination)| self.move_wins(&child, destination, attacker, moves - 1))
+    }
+
+    /// Returns true if the attacker wins after having moved to *grid*, with
+    /// *moves* attacker moves left to finish the job
+    fn move_wins(
+        &self,
+        grid: &HexGrid,
+        destination: Option<HexLocation>,
+        attacker: PieceColor,
+        moves: usize,
+    ) -> bool {
+        if let Some(result) = board_result(grid) {
+            return result.winner() == Some(attacker);
+        }
+        if moves == 0 {
+            return false;
+        }
+
+        let defender = attacker.opposite();
+        for (reply, reply_destination) in self.children(grid, destination, defender) {
+            match board_result(&reply) {
+                Some(result) if result.winner() == Some(attacker) => continue,
+                Some(_) => return false,
+                None => {}
+            }
+            if !self.has_forced_surround(&reply, reply_destination, attacker, moves) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Every ply can fill at most one of the hexes around a queen, so a
+    /// queen with too many free neighbors cannot be surrounded in time
+    fn surround_possible(&self, grid: &HexGrid, attacker: PieceColor, moves: usize) -> bool {
+        if moves == 0 {
+            return false;
+        }
+        let queen = Piece::new(PieceType::Queen, attacker.opposite());
+        let Some((queen_location, _)) = grid.find(queen) else {
+            return false;
+        };
+        let free = grid.get_empty_neighbors(queen_location).len();
+        free < 2 * moves
+    }
+
+    /// Returns the positions reachable by *color* paired with the destination
+    /// of the piece that moved to reach them
+    fn children(
+        &self,
+        grid: &HexGrid,
+        last_move: Option<HexLocation>,
+        color: PieceColor,
+    ) -> Vec<(HexGrid, Option<HexLocation>)> {
+        let mut generator = ReferenceGenerator::from_hex_grid(grid, self.game_type, last_move);
+        generator
+            .generate_positions_for(color)
+            .into_iter()
+            .map(|child| {
+                let destination = grid.diff(&child).and_then(|mv| mv.destination());
+                (child, destination)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_surround_in_one() {
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . . .\n",
+            ". . a G . .\n",
+            " . a q a . .\n",
+            ". . a . Q A\n",
+            " . . . . . .\n",
+            ". . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+
+        let solver = SurroundSolver::new(GameType::Standard);
+        let wins = solver.winning_moves(&grid, None, PieceColor::White, 1);
+        assert!(!wins.is_empty());
+        for win in wins.iter() {
+            assert_eq!(board_result(win).unwrap().winner(), Some(PieceColor::White));
+        }
+
+        assert!(solver.has_forced_surround(&grid, None, PieceColor::White, 1));
+        assert!(!solver.has_forced_surround(&grid, None, PieceColor::Black, 1));
+    }
+
+    #[test]
+    pub fn test_no_surround_with_too_many_liberties() {
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . . .\n",
+            ". . a . . .\n",
+            " . . q . . .\n",
+            ". . . Q A .\n",
+            " . . . . . .\n\n",
+            "start - [0 0]\n\n",
+        ));
+
+        let solver = SurroundSolver::new(GameType::Standard);
+        assert!(!solver.has_forced_surround(&grid, None, PieceColor::White, 1));
+        assert!(solver
+            .winning_moves(&grid, None, PieceColor::White, 2)
+            .is_empty());
+    }
+}
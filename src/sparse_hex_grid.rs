@@ -0,0 +1,142 @@
+use crate::board::Board;
+pub use crate::board::Stack;
+use crate::generator::debug::Position;
+use crate::hex_grid::*;
+
+/// A board that only stores the occupied locations.
+///
+/// Unlike a HexGrid, locations are not confined to a window around the
+/// center, so the hive may drift anywhere in the range of a HexLocation.
+/// Convert to a HexGrid (with HexGrid::from()) for rendering, which
+/// requires the hive to fit inside the HexGrid window.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SparseHexGrid {
+    stacks: HashMap<HexLocation, Stack>,
+}
+
+impl SparseHexGrid {
+    pub fn new() -> SparseHexGrid {
+        SparseHexGrid::default()
+    }
+}
+
+impl Board for SparseHexGrid {
+    fn stack(&self, location: HexLocation) -> &[Piece] {
+        self.stacks
+            .get(&location)
+            .map_or(&[], |stack| stack.as_slice())
+    }
+
+    fn add(&mut self, piece: Piece, location: HexLocation) {
+        self.stacks.entry(location).or_default().push(piece);
+    }
+
+    fn remove(&mut self, location: HexLocation) -> Option<Piece> {
+        let stack = self.stacks.get_mut(&location)?;
+        let piece = stack.pop();
+        if stack.is_empty() {
+            self.stacks.remove(&location);
+        }
+        piece
+    }
+
+    fn occupied(&self) -> impl Iterator<Item = (HexLocation, &[Piece])> {
+        self.stacks
+            .iter()
+            .map(|(&location, stack)| (location, stack.as_slice()))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+}
+
+impl IntoPieces for SparseHexGrid {
+    fn pieces(&self) -> Vec<(Vec<Piece>, HexLocation)> {
+        let mut pieces = self
+            .stacks
+            .iter()
+            .map(|(location, stack)| (stack.to_vec(), *location))
+            .collect::<Vec<_>>();
+        pieces.sort_by_key(|(_, location)| HexGrid::board_order(*location));
+        pieces
+    }
+}
+
+impl std::hash::Hash for SparseHexGrid {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.pieces().hash(state);
+    }
+}
+
+impl HexGridConvertible for SparseHexGrid {}
+
+impl From<&HexGrid> for SparseHexGrid {
+    fn from(grid: &HexGrid) -> Self {
+        let mut sparse = SparseHexGrid::new();
+        for (stack, location) in grid.pieces() {
+            sparse.stacks.insert(location, Stack::from_vec(stack));
+        }
+        sparse
+    }
+}
+
+impl Position for SparseHexGrid {
+    fn new() -> Self {
+        SparseHexGrid::new()
+    }
+
+    fn from_hex_grid(grid: &HexGrid) -> std::result::Result<Self, String> {
+        Ok(SparseHexGrid::from(grid))
+    }
+
+    fn to_hex_grid(&self) -> HexGrid {
+        HexGrid::from_pieces(self.pieces())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_sparse_hex_grid() {
+        use PieceColor::*;
+        use PieceType::*;
+
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . Q 2 a .\n",
+            ". . g . .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ b B ]\n",
+        ));
+        let sparse = SparseHexGrid::from(&grid);
+        assert_eq!(Board::num_pieces(&sparse), grid.num_pieces());
+        assert_eq!(sparse.pieces(), grid.pieces());
+        assert_eq!(HexGrid::from(sparse.clone()), grid);
+        assert_eq!(Position::to_hex_grid(&sparse), grid);
+
+        for (location, stack) in grid.occupied() {
+            assert_eq!(sparse.stack(location), stack);
+            assert_eq!(
+                sparse.neighbors(location).collect::<Vec<_>>(),
+                grid.neighbors(location).collect::<Vec<_>>()
+            );
+        }
+
+        // Locations far outside of the HexGrid window
+        let mut sparse = SparseHexGrid::new();
+        let far = HexLocation::new(100, -120);
+        sparse.add(Piece::new(Queen, White), far);
+        sparse.add(Piece::new(Beetle, Black), far);
+        assert_eq!(sparse.height(far), 2);
+        assert_eq!(sparse.top(far), Some(Piece::new(Beetle, Black)));
+        assert_eq!(sparse.remove(far), Some(Piece::new(Beetle, Black)));
+        assert_eq!(sparse.remove(far), Some(Piece::new(Queen, White)));
+        assert_eq!(sparse.remove(far), None);
+        assert!(sparse.is_empty());
+        assert_eq!(sparse, SparseHexGrid::new());
+    }
+}
@@ -0,0 +1,220 @@
+//! The structures of the hive that shape where sliding pieces can go.
+//!
+//! A gate is a pair of stacks on either side of a step between two hexes,
+//! both tall enough that a piece cannot squeeze between them. A door is a
+//! hex that sliding pieces can pass, and the only way into a pocket of the
+//! hive: blocking it shuts the pocket. Hexes that sliding pieces cannot reach
+//! at all are enclosed
+use crate::hex_grid::*;
+use std::collections::{HashSet, VecDeque};
+
+/// A step from *from* to the empty hex *to* blocked by the stacks on
+/// either side of it, see gates_around()
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Gate {
+    pub from: HexLocation,
+    pub to: HexLocation,
+    pub flanks: (HexLocation, HexLocation),
+    /// The height of the lower flank, pieces moving at this height or below
+    /// cannot pass
+    pub height: Height,
+}
+
+/// The entrance of a pocket of the hive, see doors()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Door {
+    pub location: HexLocation,
+    /// The empty hexes next to the hive only reachable through the door,
+    /// in board order
+    pub pocket: Vec<HexLocation>,
+}
+
+/// Returns the gates a piece on top of *location* (or a piece on the ground
+/// if it is empty) cannot slide through to an empty neighbor, in the order
+/// of Direction::all(). Climbing onto other stacks is not considered
+pub fn gates_around(grid: &HexGrid, location: HexLocation) -> Vec<Gate> {
+    let level = grid.height(location).max(1);
+    Direction::all()
+        .into_iter()
+        .filter_map(|direction| {
+            let to = location.apply(direction);
+            let (left, right) = direction.adjacent();
+            let flanks = (location.apply(left), location.apply(right));
+            let height = grid.height(flanks.0).min(grid.height(flanks.1));
+            (grid.height(to) == 0 && height >= level).then_some(Gate {
+                from: location,
+                to,
+                flanks,
+                height,
+            })
+        })
+        .collect()
+}
+
+/// Returns every gate between two empty hexes next to the hive, each once
+/// and in board order of the hex it is stepped from
+pub fn gates(grid: &HexGrid) -> Vec<Gate> {
+    let mut gates = vec![];
+    for from in sorted(grid.outside()) {
+        for gate in gates_around(grid, from) {
+            let reverse = gates
+                .iter()
+                .any(|other: &Gate| other.from == gate.to && other.to == from);
+            if grid.is_outside(gate.to) && !reverse {
+                gates.push(gate);
+            }
+        }
+    }
+    gates
+}
+
+/// Returns the doors of the hive, in board order
+pub fn doors(grid: &HexGrid) -> Vec<Door> {
+    let Some(start) = open_hex(grid) else {
+        return vec![];
+    };
+    let reachable = slide_region(grid, start, None);
+    sorted(grid.outside())
+        .into_iter()
+        .filter(|location| reachable.contains(location))
+        .filter_map(|location| {
+            let without = slide_region(grid, start, Some(location));
+            let pocket = sorted(
+                grid.outside()
+                    .into_iter()
+                    .filter(|other| {
+                        *other != location && reachable.contains(other) && !without.contains(other)
+                    })
+                    .collect(),
+            );
+            (!pocket.is_empty()).then_some(Door { location, pocket })
+        })
+        .collect()
+}
+
+/// Returns the empty hexes next to the hive that no piece can slide to from
+/// the outside, in board order
+pub fn enclosed(grid: &HexGrid) -> Vec<HexLocation> {
+    let Some(start) = open_hex(grid) else {
+        return vec![];
+    };
+    let reachable = slide_region(grid, start, None);
+    sorted(
+        grid.outside()
+            .into_iter()
+            .filter(|location| !reachable.contains(location))
+            .collect(),
+    )
+}
+
+/// An empty hex that is certainly outside of the hive: west of the
+/// westernmost hex next to it
+fn open_hex(grid: &HexGrid) -> Option<HexLocation> {
+    grid.outside()
+        .into_iter()
+        .min_by_key(|location| (location.x, location.y))
+        .map(|location| location.apply(Direction::W))
+}
+
+/// The empty hexes at most two steps from the hive that a piece on the
+/// ground can slide to from *start*, never passing *blocked*
+fn slide_region(
+    grid: &HexGrid,
+    start: HexLocation,
+    blocked: Option<HexLocation>,
+) -> HashSet<HexLocation> {
+    let near = |location: HexLocation| {
+        grid.is_outside(location)
+            || Direction::all()
+                .into_iter()
+                .any(|direction| grid.is_outside(location.apply(direction)))
+    };
+    let mut visited = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(location) = queue.pop_front() {
+        let gated = gates_around(grid, location)
+            .into_iter()
+            .map(|gate| gate.to)
+            .collect::<Vec<_>>();
+        for direction in Direction::all() {
+            let next = location.apply(direction);
+            if grid.height(next) > 0
+                || gated.contains(&next)
+                || Some(next) == blocked
+                || !near(next)
+                || !visited.insert(next)
+            {
+                continue;
+            }
+            queue.push_back(next);
+        }
+    }
+    visited
+}
+
+fn sorted(locations: HashSet<HexLocation>) -> Vec<HexLocation> {
+    let mut locations = locations.into_iter().collect::<Vec<_>>();
+    locations.sort_by_key(|location| HexGrid::board_order(*location));
+    locations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_gates_and_doors() {
+        // The spider sits in a hole it cannot leave, see test_spider_gate()
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . . . .\n",
+            ". . . a . . .\n",
+            " . a S a . . .\n",
+            ". . a a . . .\n",
+            " . . . . . . .\n",
+            ". . . . . . .\n\n",
+            "start - [0 0]\n\n"
+        ));
+        let (spider, _) = grid
+            .find(Piece::new(PieceType::Spider, PieceColor::White))
+            .unwrap();
+        let around = gates_around(&grid, spider);
+        assert_eq!(around.len(), 1);
+        for gate in around.iter() {
+            assert_eq!(gate.height, 1);
+            assert!(grid.height(gate.flanks.0) > 0 && grid.height(gate.flanks.1) > 0);
+        }
+        assert!(doors(&grid).is_empty());
+
+        let mut hole = grid.clone();
+        hole.remove(spider);
+        assert_eq!(enclosed(&hole), vec![spider]);
+        let hole_gates = gates(&hole);
+        assert_eq!(hole_gates.len(), 1);
+        assert!([hole_gates[0].from, hole_gates[0].to].contains(&spider));
+
+        // The pocket west of the spider is entered through a single hex,
+        // see test_spider_door()
+        let grid = HexGrid::from_dsl(concat!(
+            " . . . . . . .\n",
+            ". . a a . . .\n",
+            " . a . a S . .\n",
+            ". a . . . . .\n",
+            " . a a . . . .\n",
+            ". . . . . . .\n\n",
+            "start - [0 0]\n\n"
+        ));
+        assert!(gates(&grid).is_empty());
+        let found = doors(&grid);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].pocket.len(), 2);
+        assert!(enclosed(&grid).is_empty());
+
+        // Shutting the door encloses the pocket
+        let mut shut = grid.clone();
+        shut.add(
+            Piece::new(PieceType::Ant, PieceColor::Black),
+            found[0].location,
+        );
+        assert_eq!(enclosed(&shut), found[0].pocket);
+    }
+}
@@ -1,10 +1,15 @@
+#[cfg(feature = "seal")]
+use crate::encoding::{self, Digest, Signer, Verifier};
 use crate::hex_grid::*;
 use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Compares the moves of a piece at a start location to the expected moves:
 ///
 /// start_location - location of the piece to move
 /// expected - a "selector" string of destiation locations that are valid moves, see HexGrid::selector
+/// destinations marked with a height (e.g. "*1") must land on that many pieces
 ///
 /// original_position - the original game position that contians this piece
 /// test_positions - a list of game positions that resulted from the move generator being applied.
@@ -15,14 +20,23 @@ pub fn compare_moves(
     original_position: &HexGrid,
     test_positions: &Vec<HexGrid>,
 ) {
-    let expected_locations = HexGrid::selector(expected);
+    let expected_locations = HexGrid::height_selector(expected);
     let mut original_position = original_position.clone();
     let piece = original_position
         .remove(start_location)
         .expect("Expected piece at start location");
     let mut expected_positions = Vec::new();
 
-    for location in expected_locations {
+    for (location, height) in expected_locations {
+        if let Some(height) = height {
+            assert_eq!(
+                original_position.height(location),
+                height,
+                "Selector expects a landing height of {} at {:?}",
+                height,
+                location
+            );
+        }
         let mut new_position = original_position.clone();
         new_position.add(piece, location);
         expected_positions.push(new_position);
@@ -37,7 +51,9 @@ pub fn compare_moves(
 
     assert_eq!(expected_positions.len(), test_positions.len());
     for position in expected_positions {
-        assert!(test_positions.contains(&position));
+        assert!(test_positions
+            .iter()
+            .any(|test_position| test_position.eq_up_to_translation(&position)));
     }
 }
 
@@ -126,3 +142,32 @@ pub fn is_localized<H: Shiftable + FromHex>(
         distance,
     )
 }
+
+/// Signs with the digest of a secret key followed by the digest signed,
+/// standing in for a signature scheme in tests
+#[cfg(feature = "seal")]
+pub struct KeyedSigner(pub [u8; 4]);
+
+#[cfg(feature = "seal")]
+impl Signer for KeyedSigner {
+    fn sign(&self, digest: &Digest) -> Vec<u8> {
+        encoding::digest(&[&self.0[..], digest].concat()).to_vec()
+    }
+}
+
+#[cfg(feature = "seal")]
+impl Verifier for KeyedSigner {
+    fn verify(&self, digest: &Digest, signature: &[u8]) -> bool {
+        self.sign(digest) == signature
+    }
+}
+
+/// A path in the temporary directory ending in *name* that no other call,
+/// in this process or in another test run, returns, so that tests running
+/// at the same time do not overwrite each other's files
+pub fn temp_path(name: &str) -> PathBuf {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let call = CALLS.fetch_add(1, Ordering::Relaxed);
+    let name = format!("anansii-{}-{}-{}", std::process::id(), call, name);
+    std::env::temp_dir().join(name)
+}
@@ -0,0 +1,167 @@
+//! Entry points for the cargo fuzz targets in fuzz/, which take arbitrary
+//! bytes and panic only when they find a bug.
+//!
+//! Inputs that are not valid are rejected without panicking, valid ones are
+//! checked for consistency between the parser, the rules and the notation
+use crate::game::GameDebugger;
+use crate::hex_grid::*;
+use crate::hex_grid_dsl::Parser;
+use crate::uhp::GameType;
+
+const GAME_TYPES: [GameType; 8] = [
+    GameType::Standard,
+    GameType::M,
+    GameType::L,
+    GameType::P,
+    GameType::ML,
+    GameType::MP,
+    GameType::LP,
+    GameType::MLP,
+];
+
+/// Parses *data* as a DSL string, returning the grid if it is valid. Valid
+/// grids must survive a round trip through HexGrid::to_dsl()
+pub fn parse_dsl(data: &[u8]) -> Option<HexGrid> {
+    let input = std::str::from_utf8(data).ok()?;
+    let grid = Parser::parse_hex_grid(input).ok()?;
+    let reparsed = Parser::parse_hex_grid(&grid.to_dsl())
+        .unwrap_or_else(|e| panic!("Could not parse the DSL of a parsed grid: {}", e));
+    assert!(
+        reparsed.eq_up_to_translation(&grid),
+        "The DSL round trip changed the grid:\n{}",
+        grid.to_dsl()
+    );
+    Some(grid)
+}
+
+/// Parses *data* as a UHP GameString, returning the game if it is valid
+pub fn parse_game_string(data: &[u8]) -> Option<GameDebugger> {
+    let input = std::str::from_utf8(data).ok()?;
+    GameDebugger::from_game_string(input).ok()
+}
+
+/// Plays the lines of *data* as MoveStrings from the empty board, skipping
+/// the ones that are not legal. Rejected moves must leave the game as it
+/// was, and accepted ones must be written back the same way
+pub fn apply_move_strings(data: &[u8]) -> GameDebugger {
+    let mut game =
+        GameDebugger::from_move_list(&[], GameType::MLP).expect("The empty game should be valid");
+    for line in String::from_utf8_lossy(data).lines() {
+        let before = game.position().clone();
+        match game.make_move(line.trim()) {
+            Ok(()) => check_move_string(&game, &before),
+            Err(_) => assert_eq!(
+                game.position(),
+                &before,
+                "The rejected move {:?} changed the game",
+                line
+            ),
+        }
+    }
+    game
+}
+
+/// Plays a game chosen by *data*: its first byte selects the game type, and
+/// every other byte one of the legal moves of the position, until the game
+/// ends. Every move played must be written and read back to the same
+/// position, and taking it back must restore the position before it
+pub fn play_moves(data: &[u8]) -> GameDebugger {
+    let Some((first, choices)) = data.split_first() else {
+        return GameDebugger::from_move_list(&[], GameType::MLP)
+            .expect("The empty game should be valid");
+    };
+    let game_type = GAME_TYPES[*first as usize % GAME_TYPES.len()];
+    let mut game =
+        GameDebugger::from_move_list(&[], game_type).expect("The empty game should be valid");
+    for choice in choices {
+        if game.game_result().is_some() {
+            break;
+        }
+        // Sorted so that the same bytes always play the same game
+        let mut moves = game
+            .legal_positions()
+            .into_iter()
+            .map(|position| {
+                let move_string = game
+                    .move_string(&position)
+                    .unwrap_or_else(|e| panic!("A legal move has no MoveString: {}", e));
+                (move_string, position)
+            })
+            .collect::<Vec<_>>();
+        moves.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let Some((_, position)) = moves.get(*choice as usize % moves.len().max(1)) else {
+            break;
+        };
+
+        let before = game.position().clone();
+        game.append_position(position)
+            .unwrap_or_else(|e| panic!("A legal position was rejected: {}", e));
+        check_move_string(&game, &before);
+
+        let mut undone = game.clone();
+        undone.undo_move().expect("A move was just played");
+        assert_eq!(
+            undone.position(),
+            &before,
+            "Undoing a move changed the game"
+        );
+    }
+    game
+}
+
+/// Checks that the last move of *game*, played from *before*, is read back
+/// from its MoveString to the same position
+fn check_move_string(game: &GameDebugger, before: &HexGrid) {
+    let mut replay = game.clone();
+    replay.undo_move().expect("A move was just played");
+    let move_string = replay
+        .move_string(game.position())
+        .unwrap_or_else(|e| panic!("A legal move has no MoveString: {}", e));
+    replay.make_move(&move_string).unwrap_or_else(|e| {
+        panic!(
+            "The MoveString {} of a legal move was rejected: {}\n{}",
+            move_string,
+            e,
+            before.to_dsl()
+        )
+    });
+    assert_eq!(
+        replay.position(),
+        game.position(),
+        "The MoveString {} leads elsewhere",
+        move_string
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_fuzz_entry_points() {
+        let grid = parse_dsl(b" . a .\n. . Q\n\nstart - [0 0]\n\n").unwrap();
+        assert_eq!(grid.pieces().len(), 2);
+        for garbage in [
+            &b""[..],
+            b"\xff\xfe",
+            b"start - [0 0]",
+            b". . [ 9 ]\n",
+            b"Qq\n\n\n",
+        ] {
+            assert!(parse_dsl(garbage).is_none());
+            assert!(parse_game_string(garbage).is_none());
+        }
+        assert!(parse_game_string(b"Base;InProgress;White[2];wA1;bG1 -wA1").is_some());
+
+        let game = apply_move_strings(b"wA1\nwQ\nbG1 -wA1\n\xff\nbG1 wA1-\nwQ wA1-");
+        assert_eq!(game.position().pieces().len(), 3);
+
+        let game = play_moves(&[7, 0, 200, 13, 255, 1, 99, 42, 5, 17, 3]);
+        assert_eq!(game.game_type(), GameType::MLP);
+        assert_eq!(game.positions().count(), 11);
+        assert_eq!(
+            game.position(),
+            play_moves(&[7, 0, 200, 13, 255, 1, 99, 42, 5, 17, 3]).position()
+        );
+    }
+}
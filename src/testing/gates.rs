@@ -0,0 +1,168 @@
+//! Enumerates the stack heights around a single step of a beetle and checks
+//! the generators against the height gate rule, the most error prone rule of
+//! Hive move generation.
+//!
+//! The beetle steps from the center in the direction of the case. The step
+//! is blocked when both stacks flanking it are at least as tall as the
+//! higher end of the step, with the beetle counted on top of its stack:
+//!
+//! ```text
+//!  min(left, right) >= max(from, to + 1)
+//! ```
+//!
+//! A beetle on the ground stepping down to the ground must also slide along
+//! one of the flanks, or it would leave the hive on the way
+use crate::board::Board;
+use crate::generator::debug::{FromHexGrid, ReferenceGenerator};
+use crate::hex_grid::*;
+
+/// The heights of the stacks around one step of a beetle, see gate_cases()
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GateCase {
+    pub direction: Direction,
+    /// The height of the stack the beetle steps from, the beetle included
+    pub from: Height,
+    /// The height of the stack the beetle steps onto
+    pub to: Height,
+    /// The heights of the stacks on either side of the step, in the order
+    /// of Direction::adjacent()
+    pub flanks: (Height, Height),
+}
+
+impl GateCase {
+    pub fn from_location(&self) -> HexLocation {
+        HexLocation::center()
+    }
+
+    pub fn to_location(&self) -> HexLocation {
+        self.from_location().apply(self.direction)
+    }
+
+    pub fn flank_locations(&self) -> (HexLocation, HexLocation) {
+        let (left, right) = self.direction.adjacent();
+        (
+            self.from_location().apply(left),
+            self.from_location().apply(right),
+        )
+    }
+
+    /// Whether the beetle may take the step, according to the height gate
+    /// rule written out above
+    pub fn is_reachable(&self) -> bool {
+        let (left, right) = self.flanks;
+        if left.min(right) >= self.from.max(self.to + 1) {
+            return false;
+        }
+        self.from > 1 || self.to > 0 || left.max(right) > 0
+    }
+
+    /// The position of the case: the beetle on top of its stack, the stacks
+    /// of the step, and the other neighbors of the beetle surrounded by a ring
+    /// of ants holding the hive together whatever the heights
+    pub fn grid(&self) -> HexGrid {
+        let from = self.from_location();
+        let filler = Piece::new(PieceType::Ant, PieceColor::Black);
+        let mut grid = HexGrid::new();
+
+        let (left, right) = self.flank_locations();
+        let stacks = [
+            (left, self.flanks.0),
+            (right, self.flanks.1),
+            (self.to_location(), self.to),
+        ];
+        for (location, height) in stacks {
+            for _ in 0..height {
+                grid.add(filler, location);
+            }
+        }
+        let neighbors = Direction::all().map(|direction| from.apply(direction));
+        for neighbor in neighbors {
+            if stacks.iter().all(|(location, _)| *location != neighbor) {
+                grid.add(filler, neighbor);
+            }
+        }
+        for neighbor in neighbors {
+            for ring in Direction::all().map(|direction| neighbor.apply(direction)) {
+                if ring != from && !neighbors.contains(&ring) && grid.height(ring) == 0 {
+                    grid.add(filler, ring);
+                }
+            }
+        }
+
+        for _ in 1..self.from {
+            grid.add(filler, from);
+        }
+        grid.add(Piece::new(PieceType::Beetle, PieceColor::White), from);
+        grid
+    }
+}
+
+/// Returns every case with stacks of at most *max_height* pieces, the
+/// beetle's own stack holding at least the beetle, in every direction
+pub fn gate_cases(max_height: Height) -> Vec<GateCase> {
+    let mut cases = vec![];
+    for direction in Direction::all() {
+        for from in 1..=max_height {
+            for to in 0..=max_height {
+                for left in 0..=max_height {
+                    for right in 0..=max_height {
+                        cases.push(GateCase {
+                            direction,
+                            from,
+                            to,
+                            flanks: (left, right),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    cases
+}
+
+/// Asserts that the ReferenceGenerator running on the board *B* lets the
+/// beetle of *case* step exactly when GateCase::is_reachable() does
+pub fn assert_gate_case<B: Board>(case: &GateCase) {
+    let grid = case.grid();
+    let generator = ReferenceGenerator::<B>::from_default(&grid);
+    let reached = generator
+        .destinations(case.from_location())
+        .contains(&case.to_location());
+    assert_eq!(
+        reached,
+        case.is_reachable(),
+        "The generator disagrees with the height gate rule for {:?}\n{}",
+        case,
+        grid.to_dsl()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sparse_hex_grid::SparseHexGrid;
+
+    #[test]
+    pub fn test_gate_matrix() {
+        let cases = gate_cases(3);
+        assert_eq!(cases.len(), 6 * 3 * 4 * 4 * 4);
+        for case in cases.iter() {
+            assert_gate_case::<HexGrid>(case);
+            assert_gate_case::<SparseHexGrid>(case);
+        }
+
+        // The cases of test_beetle_gate_lower_level(), then an upper level
+        // gate blocking a slide on top of the hive
+        let case = |from, to, flanks| GateCase {
+            direction: Direction::E,
+            from,
+            to,
+            flanks,
+        };
+        assert!(!case(1, 0, (1, 1)).is_reachable());
+        assert!(case(1, 1, (1, 1)).is_reachable());
+        assert!(case(2, 0, (1, 1)).is_reachable());
+        assert!(!case(1, 0, (0, 0)).is_reachable());
+        assert!(!case(2, 1, (2, 3)).is_reachable());
+    }
+}
@@ -0,0 +1,403 @@
+use crate::generator::debug::ReferenceGenerator;
+use crate::hex_grid::*;
+use crate::hex_grid_dsl::{Parser, ParserError};
+use crate::perft::perft;
+use crate::uhp::GameType;
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum GoldenError {
+    #[error("Could not read {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("{name}, line {line}: {message}")]
+    Syntax {
+        name: String,
+        line: usize,
+        message: String,
+    },
+    #[error("{name}: invalid {section}: {source}")]
+    Dsl {
+        name: String,
+        section: String,
+        source: ParserError,
+    },
+    #[error("{name}: there is no {piece} on the board")]
+    PieceNotFound { name: String, piece: String },
+    #[error("{name}: moves of {piece} differ, missing {missing:?}, unexpected {unexpected:?}")]
+    MovesMismatch {
+        name: String,
+        piece: String,
+        missing: Vec<HexLocation>,
+        unexpected: Vec<HexLocation>,
+    },
+    #[error("{name}: {piece} would land at height {actual} on {location:?}, expected {expected}")]
+    HeightMismatch {
+        name: String,
+        piece: String,
+        location: HexLocation,
+        expected: Height,
+        actual: Height,
+    },
+    #[error("{name}: perft({depth}) is {actual}, expected {expected}")]
+    PerftMismatch {
+        name: String,
+        depth: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, GoldenError>;
+
+/// A regression test case read from a golden file, see GoldenCase::parse()
+#[derive(Clone, Debug)]
+pub struct GoldenCase {
+    pub name: String,
+    pub game_type: GameType,
+    pub to_move: PieceColor,
+    /// Destination of the piece moved in the previous turn, if any
+    pub last_move: Option<HexLocation>,
+    pub position: HexGrid,
+    /// The piece whose moves are checked (as written in the DSL) and a
+    /// selector string of its expected destinations
+    pub moves: Vec<(String, String)>,
+    /// Expected perft counts by depth, see perft()
+    pub perft: Vec<(usize, usize)>,
+}
+
+impl GoldenCase {
+    /// Parses a golden file, made of sections each starting with a header line
+    /// of the form "<key>: <value>", for example:
+    ///
+    /// ```text
+    /// # The pillbug throws the ant onto any empty hex around it
+    /// game: Base+P
+    /// to_move: white
+    ///
+    /// position:
+    /// . . . .
+    ///  . a . .
+    /// . Q P .
+    ///  . q . .
+    /// . . . .
+    ///
+    /// start - [0 0]
+    ///
+    /// moves a:
+    /// . . . .
+    ///  . a * .
+    /// . Q P *
+    ///  . q * .
+    /// . . . .
+    ///
+    /// start - [0 0]
+    ///
+    /// perft: 1 21
+    /// ```
+    ///
+    /// "position" and every "moves <piece>" section hold a DSL string, the
+    /// moves of the first <piece> found in board order are compared to the
+    /// selector (which may use landing heights). "perft" lists comma separated
+    /// "<depth> <count>" pairs. "game" defaults to the default GameType,
+    /// "to_move" to white, and lines starting with "#" are ignored.
+    pub fn parse(name: &str, input: &str) -> Result<GoldenCase> {
+        let header = Regex::new(r"^([a-z_]+)( \S+)?:\s*(.*)$").unwrap();
+        let syntax = |line: usize, message: &str| GoldenError::Syntax {
+            name: name.to_string(),
+            line: line + 1,
+            message: message.to_string(),
+        };
+
+        // (header line, key, argument, value or body)
+        let mut sections: Vec<(usize, String, Option<String>, String)> = vec![];
+        for (line, text) in input.lines().enumerate() {
+            if text.starts_with('#') {
+                continue;
+            }
+            if let Some(captures) = header.captures(text) {
+                let argument = captures.get(2).map(|arg| arg.as_str().trim().to_string());
+                sections.push((
+                    line,
+                    captures[1].to_string(),
+                    argument,
+                    captures[3].to_string(),
+                ));
+                continue;
+            }
+            match sections.last_mut() {
+                Some((_, _, _, body)) => {
+                    body.push_str(text);
+                    body.push('\n');
+                }
+                None if text.trim().is_empty() => {}
+                None => return Err(syntax(line, "expected a section header")),
+            }
+        }
+
+        let mut case = GoldenCase {
+            name: name.to_string(),
+            game_type: GameType::default(),
+            to_move: PieceColor::White,
+            last_move: None,
+            position: HexGrid::new(),
+            moves: vec![],
+            perft: vec![],
+        };
+        let mut has_position = false;
+
+        for (line, key, argument, body) in sections {
+            let value = body.trim();
+            match (key.as_str(), argument) {
+                ("game", None) => {
+                    case.game_type =
+                        GameType::try_from(value).map_err(|_| syntax(line, "unknown game type"))?;
+                }
+                ("to_move", None) => {
+                    case.to_move = match value {
+                        "white" => PieceColor::White,
+                        "black" => PieceColor::Black,
+                        _ => return Err(syntax(line, "expected white or black")),
+                    };
+                }
+                ("last_move", None) => {
+                    let coordinates = value
+                        .split_whitespace()
+                        .map(|n| n.parse::<Coordinate>())
+                        .collect::<std::result::Result<Vec<_>, _>>();
+                    case.last_move = match coordinates.as_deref() {
+                        Ok([x, y]) => Some(HexLocation::new(*x, *y)),
+                        _ => return Err(syntax(line, "expected two coordinates")),
+                    };
+                }
+                ("position", None) => {
+                    let dsl = normalized_dsl(&body);
+                    case.position =
+                        Parser::parse_hex_grid(&dsl).map_err(|source| GoldenError::Dsl {
+                            name: name.to_string(),
+                            section: key.clone(),
+                            source,
+                        })?;
+                    has_position = true;
+                }
+                ("moves", Some(piece)) => {
+                    case.moves.push((piece, normalized_dsl(&body)));
+                }
+                ("perft", None) => {
+                    for entry in value.split(',') {
+                        let numbers = entry
+                            .split_whitespace()
+                            .map(|n| n.parse::<usize>())
+                            .collect::<std::result::Result<Vec<_>, _>>();
+                        match numbers.as_deref() {
+                            Ok([depth, count]) => case.perft.push((*depth, *count)),
+                            _ => return Err(syntax(line, "expected <depth> <count> pairs")),
+                        }
+                    }
+                }
+                _ => return Err(syntax(line, "unknown section")),
+            }
+        }
+
+        if !has_position {
+            return Err(syntax(0, "missing position section"));
+        }
+        Ok(case)
+    }
+
+    /// Checks the expected moves and perft counts against the ReferenceGenerator,
+    /// reporting every difference found
+    pub fn check(&self) -> Vec<GoldenError> {
+        let mut errors = vec![];
+
+        for (piece, selector) in self.moves.iter() {
+            if let Err(error) = self.check_moves(piece, selector) {
+                errors.push(error);
+            }
+        }
+
+        for &(depth, expected) in self.perft.iter() {
+            let actual = perft(
+                &self.position,
+                self.game_type,
+                self.to_move,
+                self.last_move,
+                depth,
+            );
+            if actual != expected {
+                errors.push(GoldenError::PerftMismatch {
+                    name: self.name.clone(),
+                    depth,
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        errors
+    }
+
+    fn check_moves(&self, piece: &str, selector: &str) -> Result<()> {
+        let not_found = || GoldenError::PieceNotFound {
+            name: self.name.clone(),
+            piece: piece.to_string(),
+        };
+        let mut letters = piece.chars();
+        let (Some(letter), None) = (letters.next(), letters.next()) else {
+            return Err(not_found());
+        };
+        let color = match letter.is_lowercase() {
+            true => PieceColor::Black,
+            false => PieceColor::White,
+        };
+        let piece_type = PieceType::try_from(letter).map_err(|_| not_found())?;
+        let (location, _) = self
+            .position
+            .find(Piece::new(piece_type, color))
+            .ok_or_else(not_found)?;
+
+        let expected =
+            Parser::parse_height_selector(selector).map_err(|source| GoldenError::Dsl {
+                name: self.name.clone(),
+                section: format!("moves {}", piece),
+                source,
+            })?;
+
+        let mut removed = self.position.clone();
+        removed.remove(location);
+        for &(destination, height) in expected.iter() {
+            let Some(height) = height else {
+                continue;
+            };
+            if removed.height(destination) != height {
+                return Err(GoldenError::HeightMismatch {
+                    name: self.name.clone(),
+                    piece: piece.to_string(),
+                    location: destination,
+                    expected: height,
+                    actual: removed.height(destination),
+                });
+            }
+        }
+
+        let generator =
+            ReferenceGenerator::from_hex_grid(&self.position, self.game_type, self.last_move);
+        let actual = generator.legal_destinations(self.to_move, location);
+        let expected = expected
+            .into_iter()
+            .map(|(destination, _)| destination)
+            .collect::<HashSet<_>>();
+
+        if actual != expected {
+            let mut missing = expected.difference(&actual).cloned().collect::<Vec<_>>();
+            let mut unexpected = actual.difference(&expected).cloned().collect::<Vec<_>>();
+            missing.sort_by_key(|loc| (loc.y, loc.x));
+            unexpected.sort_by_key(|loc| (loc.y, loc.x));
+            return Err(GoldenError::MovesMismatch {
+                name: self.name.clone(),
+                piece: piece.to_string(),
+                missing,
+                unexpected,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Reads every golden file in *directory* (non recursively), in file name order
+pub fn load_dir(directory: &Path) -> Result<Vec<GoldenCase>> {
+    let io_error = |path: &Path, e| GoldenError::Io(path.display().to_string(), e);
+    let mut paths = std::fs::read_dir(directory)
+        .map_err(|e| io_error(directory, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let input = std::fs::read_to_string(path).map_err(|e| io_error(path, e))?;
+            let name = path.file_name().unwrap().to_string_lossy();
+            GoldenCase::parse(&name, &input)
+        })
+        .collect()
+}
+
+/// Loads and checks every golden file in *directory*, returning all
+/// parse errors and differences found
+pub fn run_dir(directory: &Path) -> Vec<GoldenError> {
+    match load_dir(directory) {
+        Ok(cases) => cases.iter().flat_map(|case| case.check()).collect(),
+        Err(error) => vec![error],
+    }
+}
+
+/// The DSL expects the start line to be followed by a blank line,
+/// and the stack lines (if any) to end in a single newline
+fn normalized_dsl(body: &str) -> String {
+    // Rows may start with a space, so only blank lines are trimmed
+    let body = body.trim_start_matches('\n').trim_end();
+    match body.lines().last() {
+        Some(line) if line.trim_start().starts_with("start") => format!("{}\n\n", body),
+        _ => format!("{}\n", body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_golden_files() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/testing/golden");
+        let cases = load_dir(&directory).unwrap();
+        assert!(!cases.is_empty());
+
+        let errors = run_dir(&directory);
+        assert!(
+            errors.is_empty(),
+            "{}",
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    #[test]
+    pub fn test_golden_mismatch() {
+        let input = concat!(
+            "game: Base\n",
+            "position:\n",
+            " . . . .\n",
+            ". Q q .\n",
+            " . . . .\n\n",
+            "start - [0 0]\n\n",
+            "moves Q:\n",
+            " . . . .\n",
+            ". Q q *\n",
+            " . . . .\n\n",
+            "start - [0 0]\n\n",
+            "perft: 1 3\n",
+        );
+        let case = GoldenCase::parse("mismatch", input).unwrap();
+        assert_eq!(case.game_type, GameType::Standard);
+
+        let errors = case.check();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], GoldenError::MovesMismatch { .. }));
+        assert!(matches!(
+            errors[1],
+            GoldenError::PerftMismatch { expected: 3, .. }
+        ));
+
+        assert!(matches!(
+            GoldenCase::parse("bad", "foo\ngame: Base\n"),
+            Err(GoldenError::Syntax { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,9 @@
+mod funcs;
+pub mod fuzz;
+pub mod gates;
+pub mod golden;
+pub mod mzinga;
+pub mod positions;
+
+pub use crate::hex_grid_dsl::{Parser, ParserError};
+pub use funcs::*;
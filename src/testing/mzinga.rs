@@ -0,0 +1,113 @@
+use crate::hex_grid::*;
+use crate::perft::perft;
+use crate::uhp::GameType;
+
+/// The perft tables to check against: the Base row is published by Mzinga,
+/// the expansion rows are derived locally from the placement rules, see
+/// mzinga_perft.txt and perft_tables()
+pub const MZINGA_PERFT: &str = include_str!("mzinga_perft.txt");
+
+/// Perft counts from the starting position of a ruleset
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerftTable {
+    pub game_type: GameType,
+    /// The count at depth d is at index d - 1
+    pub counts: Vec<usize>,
+}
+
+/// The first depth at which perft() disagrees with a PerftTable
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub depth: usize,
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl PerftTable {
+    /// Runs perft() at every depth of the table up to *max_depth*,
+    /// stopping at the first count that differs
+    pub fn first_divergence(&self, max_depth: usize) -> Option<Divergence> {
+        let grid = HexGrid::new();
+        self.counts
+            .iter()
+            .take(max_depth)
+            .enumerate()
+            .find_map(|(index, &expected)| {
+                let depth = index + 1;
+                let actual = perft(&grid, self.game_type, PieceColor::White, None, depth);
+                (actual != expected).then_some(Divergence {
+                    depth,
+                    expected,
+                    actual,
+                })
+            })
+    }
+}
+
+/// Parses the bundled MZINGA_PERFT tables, lines hold a GameTypeString
+/// followed by the counts from depth 1 onwards
+pub fn perft_tables() -> Vec<PerftTable> {
+    MZINGA_PERFT
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            let game_type = fields.next().unwrap();
+            PerftTable {
+                game_type: GameType::try_from(game_type).expect("Unknown game type in table"),
+                counts: fields
+                    .map(|count| count.parse().expect("Invalid count in table"))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Checks every ruleset against the Mzinga tables up to *max_depth* and
+/// prints the first depth of divergence of each
+pub fn print_mzinga_check(max_depth: usize) {
+    for table in perft_tables() {
+        let name = table.game_type.to_str().to_string();
+        match table.first_divergence(max_depth) {
+            None => {
+                let depth = max_depth.min(table.counts.len());
+                println!("{}: ok up to depth {}", name, depth);
+            }
+            Some(divergence) => println!(
+                "{}: diverges at depth {}, expected {} found {}",
+                name, divergence.depth, divergence.expected, divergence.actual
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_mzinga_perft() {
+        let tables = perft_tables();
+        assert_eq!(tables.len(), 8);
+        assert_eq!(tables[0].game_type, GameType::Standard);
+
+        for table in tables.iter() {
+            assert_eq!(table.first_divergence(3), None, "{:?}", table.game_type);
+        }
+
+        let wrong = PerftTable {
+            game_type: GameType::Standard,
+            counts: vec![4, 95, 1440],
+        };
+        assert_eq!(
+            wrong.first_divergence(3),
+            Some(Divergence {
+                depth: 2,
+                expected: 95,
+                actual: 96
+            })
+        );
+        assert_eq!(wrong.first_divergence(1), None);
+    }
+}
@@ -3,7 +3,7 @@ use crate::hex_grid::{HexGrid, HexLocation};
 use crate::piece::*;
 use crate::uhp::GameType;
 
-const SPIDER_MOVES: [&'static str; 8] = [
+pub const SPIDER_MOVES: [&str; 8] = [
     concat!(
         " . . . . . . .\n",
         ". . . a . . .\n",
@@ -1,4 +0,0 @@
-mod funcs;
-pub mod positions;
-
-pub use funcs::*;
@@ -0,0 +1,583 @@
+//! Tournaments between many UHP engines, each game refereed by an Arbiter.
+//!
+//! Players are numbered from 0 in the order they are entered, which is also
+//! their seed: earlier players are considered stronger when pairing the
+//! first round of a knockout and when breaking ties. Rounds are paired one
+//! at a time from the results so far, see Tournament::next_round()
+use crate::arbiter::{Arbiter, ArbiterConfig};
+use crate::piece::PieceColor;
+use std::collections::HashSet;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TournamentError {
+    #[error("Invalid tournament format {0}, expected round-robin, swiss or knockout")]
+    InvalidFormat(String),
+}
+
+pub type Result<T> = std::result::Result<T, TournamentError>;
+
+/// How the players of a Tournament are paired
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Every player meets every other once
+    RoundRobin,
+    /// Players meet others with about the same points, never the same
+    /// player twice if it can be helped. Without a number of rounds, as
+    /// many are played as it takes to leave at most one player unbeaten
+    Swiss { rounds: Option<usize> },
+    /// Players meet in a bracket seeded by their number, each match being
+    /// two games with the colors swapped. The loser of a match is out, a
+    /// tied match goes to the better seed
+    Knockout,
+}
+
+impl TryFrom<&str> for Format {
+    type Error = TournamentError;
+
+    fn try_from(input: &str) -> Result<Format> {
+        match input {
+            "round-robin" => Ok(Format::RoundRobin),
+            "swiss" => Ok(Format::Swiss { rounds: None }),
+            "knockout" => Ok(Format::Knockout),
+            _ => Err(TournamentError::InvalidFormat(input.to_string())),
+        }
+    }
+}
+
+/// A game between the players numbered *white* and *black*
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pairing {
+    pub white: usize,
+    pub black: usize,
+}
+
+/// The games of a round of a Tournament
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Round {
+    pub pairings: Vec<Pairing>,
+    /// The winner of each game of pairings once played, None for a draw
+    /// and any other game without a winner
+    pub results: Vec<Option<PieceColor>>,
+    /// The players without a game this round, scored as a win
+    pub byes: Vec<usize>,
+}
+
+/// The results of a player in a Tournament, see Tournament::standings()
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub player: usize,
+    /// 1 for a win or a bye, 0.5 for a draw
+    pub points: f64,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub byes: usize,
+    /// The points of every opponent met, for breaking ties
+    pub buchholz: f64,
+    /// The number of knockout rounds the player took part in, 0 for other
+    /// formats
+    pub stage: usize,
+}
+
+/// A tournament, paired round by round from its results, see the module
+/// documentation
+#[derive(Clone, Debug)]
+pub struct Tournament {
+    format: Format,
+    players: Vec<String>,
+    rounds: Vec<Round>,
+}
+
+impl Tournament {
+    /// A tournament of *format* between *players*, best seed first
+    pub fn new(format: Format, players: Vec<String>) -> Tournament {
+        Tournament {
+            format,
+            players,
+            rounds: vec![],
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn players(&self) -> &[String] {
+        &self.players
+    }
+
+    /// The rounds played so far
+    pub fn rounds(&self) -> &[Round] {
+        &self.rounds
+    }
+
+    /// Pairs the next round from the results so far, without any result.
+    /// None once the tournament is over
+    pub fn next_round(&self) -> Option<Round> {
+        match self.format {
+            Format::RoundRobin => self.round_robin_round(),
+            Format::Swiss { rounds } => self.swiss_round(rounds),
+            Format::Knockout => self.knockout_round(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_round().is_none()
+    }
+
+    /// Adds a round paired by next_round() with the result of every game
+    pub fn add_round(&mut self, round: Round) {
+        self.rounds.push(round);
+    }
+
+    /// Plays the remaining rounds, calling *play* with the numbers of the
+    /// white and the black player of every game for its winner. Stops at
+    /// the first error, keeping the rounds completed before
+    pub fn run<E>(
+        &mut self,
+        mut play: impl FnMut(usize, usize) -> std::result::Result<Option<PieceColor>, E>,
+    ) -> std::result::Result<(), E> {
+        while let Some(mut round) = self.next_round() {
+            for pairing in round.pairings.iter() {
+                round.results.push(play(pairing.white, pairing.black)?);
+            }
+            self.add_round(round);
+        }
+        Ok(())
+    }
+
+    /// The results of every player, best first: by knockout round reached,
+    /// then points, then Buchholz, then seed
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings = (0..self.players.len())
+            .map(|player| Standing {
+                player,
+                points: 0.0,
+                wins: 0,
+                draws: 0,
+                losses: 0,
+                byes: 0,
+                buchholz: 0.0,
+                stage: 0,
+            })
+            .collect::<Vec<_>>();
+        for round in self.rounds.iter() {
+            for bye in round.byes.iter() {
+                standings[*bye].byes += 1;
+                standings[*bye].points += 1.0;
+            }
+            for (pairing, winner) in round.pairings.iter().zip(round.results.iter()) {
+                let (white, black) = (pairing.white, pairing.black);
+                match winner {
+                    Some(PieceColor::White) => {
+                        standings[white].wins += 1;
+                        standings[black].losses += 1;
+                    }
+                    Some(PieceColor::Black) => {
+                        standings[black].wins += 1;
+                        standings[white].losses += 1;
+                    }
+                    None => {
+                        standings[white].draws += 1;
+                        standings[black].draws += 1;
+                    }
+                }
+            }
+        }
+        for standing in standings.iter_mut() {
+            standing.points += standing.wins as f64 + standing.draws as f64 / 2.0;
+        }
+        let points = standings
+            .iter()
+            .map(|standing| standing.points)
+            .collect::<Vec<_>>();
+        for pairing in self.pairings() {
+            standings[pairing.white].buchholz += points[pairing.black];
+            standings[pairing.black].buchholz += points[pairing.white];
+        }
+        if self.format == Format::Knockout {
+            for field in self.knockout_fields() {
+                for player in field.into_iter().flatten() {
+                    standings[player].stage += 1;
+                }
+            }
+        }
+
+        standings.sort_by(|a, b| {
+            b.stage
+                .cmp(&a.stage)
+                .then(b.points.total_cmp(&a.points))
+                .then(b.buchholz.total_cmp(&a.buchholz))
+                .then(a.player.cmp(&b.player))
+        });
+        standings
+    }
+
+    /// Every game paired so far
+    fn pairings(&self) -> impl Iterator<Item = &Pairing> {
+        self.rounds.iter().flat_map(|round| round.pairings.iter())
+    }
+
+    /// Pairs players by the circle method: the last player stays in place
+    /// while the others rotate, with a bye for the player facing the
+    /// missing one when there is an odd number of players
+    fn round_robin_round(&self) -> Option<Round> {
+        let slots = self.players.len() + self.players.len() % 2;
+        let index = self.rounds.len();
+        if self.players.len() < 2 || index >= slots - 1 {
+            return None;
+        }
+        let rotated = |slot: usize| match slot {
+            slot if slot == slots - 1 => slots - 1,
+            slot => (slot + index) % (slots - 1),
+        };
+
+        let mut round = Round::default();
+        for slot in 0..slots / 2 {
+            let (mut white, mut black) = (rotated(slot), rotated(slots - 1 - slot));
+            // Alternates the colors of the player staying in place
+            if slot == 0 && index % 2 == 1 {
+                std::mem::swap(&mut white, &mut black);
+            }
+            match (white < self.players.len(), black < self.players.len()) {
+                (true, true) => round.pairings.push(Pairing { white, black }),
+                (true, false) => round.byes.push(white),
+                (false, _) => round.byes.push(black),
+            }
+        }
+        Some(round)
+    }
+
+    /// Pairs players from the most points down, each with the next player
+    /// they have not met yet. The player ranked lowest without a bye sits
+    /// out when there is an odd number of players
+    fn swiss_round(&self, rounds: Option<usize>) -> Option<Round> {
+        let rounds = rounds.unwrap_or_else(|| {
+            // Halving the unbeaten players every round
+            (usize::BITS - self.players.len().saturating_sub(1).leading_zeros()) as usize
+        });
+        if self.players.len() < 2 || self.rounds.len() >= rounds {
+            return None;
+        }
+
+        let standings = self.standings();
+        let mut order = standings
+            .iter()
+            .map(|standing| standing.player)
+            .collect::<Vec<_>>();
+        let mut round = Round::default();
+        if order.len() % 2 == 1 {
+            let bye = (0..order.len())
+                .rev()
+                .find(|index| standings[*index].byes == 0)
+                .unwrap_or(order.len() - 1);
+            round.byes.push(order.remove(bye));
+        }
+
+        let met = self
+            .pairings()
+            .flat_map(|pairing| {
+                [
+                    (pairing.white, pairing.black),
+                    (pairing.black, pairing.white),
+                ]
+            })
+            .collect::<HashSet<_>>();
+        let pairs = pair_unmet(&order, &met)
+            .unwrap_or_else(|| order.chunks(2).map(|pair| (pair[0], pair[1])).collect());
+        let whites = |player: usize| {
+            self.pairings()
+                .filter(|pairing| pairing.white == player)
+                .count()
+        };
+        for (higher, lower) in pairs {
+            round.pairings.push(match whites(higher) > whites(lower) {
+                true => Pairing {
+                    white: lower,
+                    black: higher,
+                },
+                false => Pairing {
+                    white: higher,
+                    black: lower,
+                },
+            });
+        }
+        Some(round)
+    }
+
+    /// Pairs the players left in the bracket with their neighbors in it,
+    /// each match played both ways, a player without a neighbor moving on
+    /// with a bye
+    fn knockout_round(&self) -> Option<Round> {
+        let field = self.knockout_fields().pop().unwrap();
+        if field.len() < 2 {
+            return None;
+        }
+        let mut round = Round::default();
+        for pair in field.chunks(2) {
+            match (pair[0], pair[1]) {
+                (Some(white), Some(black)) => {
+                    round.pairings.push(Pairing { white, black });
+                    round.pairings.push(Pairing {
+                        white: black,
+                        black: white,
+                    });
+                }
+                (Some(player), None) | (None, Some(player)) => round.byes.push(player),
+                (None, None) => {}
+            }
+        }
+        Some(round)
+    }
+
+    /// The bracket before every knockout round played and after the last
+    /// one, by position, None for an empty spot of the first round
+    fn knockout_fields(&self) -> Vec<Vec<Option<usize>>> {
+        let size = self.players.len().max(1).next_power_of_two();
+        // Seeds 0 and 1 only meet in the final, 0 to 3 in the semifinals...
+        let mut seeds = vec![0];
+        while seeds.len() < size {
+            let length = seeds.len() * 2;
+            seeds = seeds
+                .iter()
+                .flat_map(|seed| [*seed, length - 1 - seed])
+                .collect();
+        }
+        let mut field = seeds
+            .into_iter()
+            .map(|seed| (seed < self.players.len()).then_some(seed))
+            .collect::<Vec<_>>();
+
+        let mut fields = vec![];
+        for round in self.rounds.iter() {
+            let next = field
+                .chunks(2)
+                .map(|pair| match (pair[0], pair[1]) {
+                    (Some(a), Some(b)) => Some(match_winner(round, a, b)),
+                    (player, None) | (None, player) => player,
+                })
+                .collect();
+            fields.push(std::mem::replace(&mut field, next));
+        }
+        fields.push(field);
+        fields
+    }
+}
+
+/// The winner of the knockout match between *a* and *b* in *round*, the
+/// better seed if they scored the same
+fn match_winner(round: &Round, a: usize, b: usize) -> usize {
+    let mut score = 0;
+    for (pairing, winner) in round.pairings.iter().zip(round.results.iter()) {
+        let winner = match winner {
+            Some(PieceColor::White) => pairing.white,
+            Some(PieceColor::Black) => pairing.black,
+            None => continue,
+        };
+        match winner {
+            winner if winner == a => score += 1,
+            winner if winner == b => score -= 1,
+            _ => {}
+        }
+    }
+    match score {
+        0 => a.min(b),
+        score if score > 0 => a,
+        _ => b,
+    }
+}
+
+/// Pairs *order* from the first player down, each with the first player
+/// after them they have not *met*, backtracking when the players left
+/// cannot be paired that way. None if there is no such pairing
+fn pair_unmet(order: &[usize], met: &HashSet<(usize, usize)>) -> Option<Vec<(usize, usize)>> {
+    let Some((first, rest)) = order.split_first() else {
+        return Some(vec![]);
+    };
+    for (index, opponent) in rest.iter().enumerate() {
+        if met.contains(&(*first, *opponent)) {
+            continue;
+        }
+        let left = [&rest[..index], &rest[index + 1..]].concat();
+        if let Some(mut pairs) = pair_unmet(&left, met) {
+            pairs.insert(0, (*first, *opponent));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+impl std::fmt::Display for Tournament {
+    /// Writes the standings as a table
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>4}  {:<30} {:>6} {:>4} {:>4} {:>4} {:>8}",
+            "Rank", "Player", "Points", "Won", "Drew", "Lost", "Buchholz"
+        )?;
+        for (rank, standing) in self.standings().iter().enumerate() {
+            write!(
+                f,
+                "\n{:>4}  {:<30} {:>6.1} {:>4} {:>4} {:>4} {:>8.1}",
+                rank + 1,
+                self.players[standing.player],
+                standing.points,
+                standing.wins,
+                standing.draws,
+                standing.losses,
+                standing.buchholz
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a tournament of *format* between the engines run by *engines*,
+/// printing the result of every game and the standings after every round
+pub fn print_tournament(engines: Vec<String>, format: Format, config: ArbiterConfig) {
+    let arbiter = Arbiter::new(config);
+    let mut tournament = Tournament::new(format, engines.clone());
+    while let Some(mut round) = tournament.next_round() {
+        println!("Round {}", tournament.rounds().len() + 1);
+        for pairing in round.pairings.iter() {
+            let (white, black) = (&engines[pairing.white], &engines[pairing.black]);
+            let winner = match arbiter.play(white, black) {
+                Ok(record) => {
+                    println!("{} - {}: {}", white, black, record.outcome);
+                    record.outcome.winner()
+                }
+                // Counted as a draw, the engines could not be started
+                Err(e) => {
+                    println!("{} - {}: {}", white, black, e);
+                    None
+                }
+            };
+            round.results.push(winner);
+        }
+        for bye in round.byes.iter() {
+            println!("{}: bye", engines[*bye]);
+        }
+        tournament.add_round(round);
+        println!("{}\n", tournament);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|player| format!("engine{}", player))
+            .collect()
+    }
+
+    /// The better seed always wins
+    fn by_seed(white: usize, black: usize) -> std::result::Result<Option<PieceColor>, ()> {
+        Ok(Some(match white < black {
+            true => PieceColor::White,
+            false => PieceColor::Black,
+        }))
+    }
+
+    #[test]
+    pub fn test_round_robin() {
+        let mut tournament = Tournament::new(Format::RoundRobin, players(5));
+        tournament.run(by_seed).unwrap();
+        assert_eq!(tournament.rounds().len(), 5);
+        let mut met = tournament
+            .pairings()
+            .map(|pairing| {
+                (
+                    pairing.white.min(pairing.black),
+                    pairing.white.max(pairing.black),
+                )
+            })
+            .collect::<Vec<_>>();
+        met.sort();
+        met.dedup();
+        assert_eq!(met.len(), 10);
+        assert!(tournament
+            .rounds()
+            .iter()
+            .all(|round| round.byes.len() == 1 && round.pairings.len() == 2));
+
+        let standings = tournament.standings();
+        let order = standings
+            .iter()
+            .map(|standing| standing.player)
+            .collect::<Vec<_>>();
+        assert_eq!(order, [0, 1, 2, 3, 4]);
+        assert_eq!((standings[0].points, standings[0].wins), (5.0, 4));
+        assert_eq!((standings[4].points, standings[4].losses), (1.0, 4));
+        assert!(tournament.to_string().contains("engine0"));
+        assert!(Tournament::new(Format::RoundRobin, players(1)).is_finished());
+    }
+
+    #[test]
+    pub fn test_swiss() {
+        let mut tournament = Tournament::new(Format::Swiss { rounds: None }, players(7));
+        tournament.run(by_seed).unwrap();
+        assert_eq!(tournament.rounds().len(), 3);
+        let mut met = HashSet::new();
+        for pairing in tournament.pairings() {
+            assert!(met.insert((
+                pairing.white.min(pairing.black),
+                pairing.white.max(pairing.black)
+            )));
+        }
+        let byes = tournament
+            .rounds()
+            .iter()
+            .flat_map(|round| round.byes.iter().copied())
+            .collect::<Vec<_>>();
+        assert_eq!(byes.len(), 3);
+        assert_eq!(byes.iter().collect::<HashSet<_>>().len(), 3);
+
+        let standings = tournament.standings();
+        assert_eq!((standings[0].player, standings[0].points), (0, 3.0));
+        // The leaders met in the last round
+        let last = tournament.rounds().last().unwrap();
+        assert!(last
+            .pairings
+            .iter()
+            .any(|pairing| [pairing.white, pairing.black].contains(&0)
+                && [pairing.white, pairing.black].contains(&standings[1].player)));
+
+        // Draws are half a point each
+        let mut tournament = Tournament::new(Format::Swiss { rounds: Some(2) }, players(4));
+        tournament.run(|_, _| Ok::<_, ()>(None)).unwrap();
+        assert!(tournament
+            .standings()
+            .iter()
+            .all(|standing| standing.points == 1.0 && standing.draws == 2));
+    }
+
+    #[test]
+    pub fn test_knockout() {
+        let mut tournament = Tournament::new(Format::Knockout, players(6));
+        let round = tournament.next_round().unwrap();
+        // The two best seeds skip the first round of a bracket of 8
+        assert_eq!(round.byes, [0, 1]);
+        assert!(round.pairings.contains(&Pairing { white: 3, black: 4 }));
+        assert!(round.pairings.contains(&Pairing { white: 5, black: 2 }));
+
+        // The worse seed wins every game as white, so matches are tied
+        let upsets = |white: usize, black: usize| match white > black {
+            true => Ok::<_, ()>(Some(PieceColor::White)),
+            false => by_seed(white, black),
+        };
+        tournament.run(upsets).unwrap();
+        assert_eq!(tournament.rounds().len(), 3);
+        let standings = tournament.standings();
+        let order = standings
+            .iter()
+            .map(|standing| (standing.player, standing.stage))
+            .collect::<Vec<_>>();
+        assert_eq!(order, [(0, 4), (1, 3), (2, 2), (3, 2), (4, 1), (5, 1)]);
+
+        assert_eq!(Format::try_from("knockout"), Ok(Format::Knockout));
+        assert!(Format::try_from("ladder").is_err());
+    }
+}
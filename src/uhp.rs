@@ -1,6 +1,6 @@
-use crate::constants::*;
-use crate::game::*;
+use crate::constants::{ENGINE_NAME, VERSION};
 use crate::hex_grid::*;
+use crate::moves::Move;
 use std::collections::HashMap;
 use thiserror::Error;
 
@@ -15,10 +15,125 @@ pub enum UHPError {
 
     #[error("Found more than two position differences")]
     TooManyDiffs,
+    #[error("Unable to interpret GameTypeString")]
+    GameTypeError,
+    #[error("Invalid MoveString {move_string}: {reason}")]
+    MoveStringError { move_string: String, reason: String },
 }
 
 pub type Result<T> = std::result::Result<T, UHPError>;
-pub type CommandResult = std::result::Result<String, String>;
+pub type CommandResult = std::result::Result<String, CommandError>;
+
+/// Starts the response to a move that breaks the rules, instead of "err"
+pub const INVALID_MOVE: &str = "invalidmove";
+
+/// What went wrong with a command, the machine readable part of an error
+/// response, see CommandError::to_uhp()
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The command is not one of the protocol
+    UnknownCommand,
+    /// The arguments of the command could not be read
+    InvalidArgument,
+    /// A GameTypeString of a game type the engine does not play
+    InvalidGameType,
+    /// A GameString whose moves, GameStateString or TurnString do not agree
+    InvalidGameString,
+    /// A MoveString that does not describe a move of the current position
+    InvalidMoveString,
+    /// A move that breaks the rules
+    InvalidMove,
+    /// The command cannot be run in the current state of the game, such as
+    /// a move once the game is over
+    InvalidState,
+    /// An option the engine does not have
+    UnknownOption,
+    /// The engine failed to run the command, a bug
+    Internal,
+}
+
+impl ErrorCode {
+    const ALL: [ErrorCode; 9] = [
+        ErrorCode::UnknownCommand,
+        ErrorCode::InvalidArgument,
+        ErrorCode::InvalidGameType,
+        ErrorCode::InvalidGameString,
+        ErrorCode::InvalidMoveString,
+        ErrorCode::InvalidMove,
+        ErrorCode::InvalidState,
+        ErrorCode::UnknownOption,
+        ErrorCode::Internal,
+    ];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            ErrorCode::UnknownCommand => "UnknownCommand",
+            ErrorCode::InvalidArgument => "InvalidArgument",
+            ErrorCode::InvalidGameType => "InvalidGameType",
+            ErrorCode::InvalidGameString => "InvalidGameString",
+            ErrorCode::InvalidMoveString => "InvalidMoveString",
+            ErrorCode::InvalidMove => "InvalidMove",
+            ErrorCode::InvalidState => "InvalidState",
+            ErrorCode::UnknownOption => "UnknownOption",
+            ErrorCode::Internal => "Internal",
+        }
+    }
+}
+
+impl TryFrom<&str> for ErrorCode {
+    type Error = ();
+
+    fn try_from(input: &str) -> std::result::Result<ErrorCode, ()> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|code| code.to_str() == input)
+            .ok_or(())
+    }
+}
+
+/// The error response to a command, see UHPInterface::command()
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[error("{message}")]
+pub struct CommandError {
+    pub code: ErrorCode,
+    /// Why the command failed, for people to read
+    pub message: String,
+}
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> CommandError {
+        CommandError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// The response line, "err" followed by the code and the message, such
+    /// as "err InvalidState: The game is over". Moves that break the rules
+    /// are answered with "invalidmove" and the rule instead, as the UHP asks
+    pub fn to_uhp(&self) -> String {
+        match self.code {
+            ErrorCode::InvalidMove => format!("{} {}", INVALID_MOVE, self.message),
+            code => format!("err {}: {}", code.to_str(), self.message),
+        }
+    }
+
+    /// Reads an error response line written by to_uhp(). Errors of other
+    /// engines without a known code are read as Internal ones
+    pub fn from_uhp(line: &str) -> Option<CommandError> {
+        if let Some(reason) = line.strip_prefix(INVALID_MOVE) {
+            return Some(CommandError::new(ErrorCode::InvalidMove, reason.trim()));
+        }
+        let message = line.strip_prefix("err")?.trim();
+        let error = message
+            .split_once(": ")
+            .and_then(|(code, message)| {
+                Some(CommandError::new(ErrorCode::try_from(code).ok()?, message))
+            })
+            .unwrap_or_else(|| CommandError::new(ErrorCode::Internal, message));
+        Some(error)
+    }
+}
 type StackIds = Vec<Option<u8>>;
 
 /// Responsible for annotation of moves in UHP format
@@ -167,15 +282,10 @@ impl Annotator {
         let e = destination.apply(Direction::E);
         let w = destination.apply(Direction::W);
 
+        /// The anchor of the destination, given the direction of the anchor
+        /// from the destination
         fn relative_direction(direction: Direction, uhp: &str) -> String {
-            match direction {
-                Direction::E => format!("-{}", uhp),
-                Direction::W => format!("{}-", uhp),
-                Direction::NW => format!(r"{}\", uhp),
-                Direction::SW => format!(r"{}/", uhp),
-                Direction::SE => format!(r"\{}", uhp),
-                Direction::NE => format!(r"/{}", uhp),
-            }
+            direction.opposite().annotate(uhp)
         }
 
         return if !hex_grid.peek(nw).is_empty() {
@@ -392,6 +502,28 @@ impl Annotator {
         Some(location)
     }
 
+    /// Returns which physical piece sits at the given height of the stack at
+    /// *location*, or None if there is no piece there
+    pub fn piece_id(&self, location: HexLocation, height: Height) -> Option<PieceId> {
+        let piece = *self.prev_grid.peek(location).get(height)?;
+        let id = (*self.ids.get(&location)?.get(height)?)?;
+        Some(PieceId::new(piece, id))
+    }
+
+    /// Returns every piece on the board with its identity, location and height,
+    /// in "board order" (see IntoPieces::pieces())
+    pub fn piece_ids(&self) -> Vec<(PieceId, HexLocation, Height)> {
+        self.prev_grid
+            .pieces()
+            .into_iter()
+            .flat_map(|(stack, location)| (0..stack.len()).map(move |height| (location, height)))
+            .filter_map(|(location, height)| {
+                let piece_id = self.piece_id(location, height)?;
+                Some((piece_id, location, height))
+            })
+            .collect()
+    }
+
     /// Assuming an valid annotator, find the piece, location and height
     /// uniquely described by the given piece string.
     ///
@@ -423,6 +555,10 @@ impl Annotator {
     /// Returns the resulting state of the annotator after the move is applied.
     pub fn next_standard_move(&self, move_string: &str) -> Result<Annotator> {
         debug_assert!(move_string.trim() == move_string);
+        let invalid = |reason: &str| UHPError::MoveStringError {
+            move_string: move_string.to_string(),
+            reason: reason.to_string(),
+        };
 
         if move_string == "pass" {
             return self.next_state(&self.prev_grid);
@@ -430,38 +566,66 @@ impl Annotator {
 
         let mut parts = move_string.split_whitespace();
 
-        let piece_string = parts.next().expect("Expected a piece");
-        let new_piece = Piece::from_uhp(piece_string).expect("Expected a valid piece");
+        let piece_string = parts.next().ok_or_else(|| invalid("expected a piece"))?;
+        let new_piece = PieceId::from_uhp(piece_string)
+            .map_err(|_| invalid("expected a valid piece"))?
+            .piece;
 
-        if move_string.len() <= 3 {
-            let mut new_grid = HexGrid::new();
-            new_grid.add(new_piece, HexLocation::new(0, 0));
-            return self.next_state(&new_grid);
-        }
+        let new_grid = match (parts.next(), parts.next()) {
+            (None, _) if self.prev_grid.is_empty() => {
+                let mut new_grid = HexGrid::new();
+                new_grid.add(new_piece, HexLocation::new(0, 0));
+                new_grid
+            }
+            (None, _) => return Err(invalid("expected an anchor position")),
+            (Some(anchor_piece_string), None) => self
+                .moved_grid(piece_string, new_piece, anchor_piece_string)
+                .map_err(invalid)?,
+            (Some(_), Some(_)) => return Err(invalid("expected a piece and an anchor position")),
+        };
 
-        let anchor_piece_string = parts.next().expect("Expected an anchor position");
+        let annotator = self.next_state(&new_grid)?;
 
-        let length = anchor_piece_string.len();
+        // A placed piece must use the next id of its type
+        let inferred = annotator
+            .move_strings
+            .last()
+            .and_then(|mv| mv.split(' ').next());
+        if inferred != Some(piece_string) {
+            return Err(invalid(
+                "the piece id does not match the pieces placed so far",
+            ));
+        }
 
-        let first_last = (
-            anchor_piece_string.chars().next().unwrap(),
-            anchor_piece_string.chars().last().unwrap(),
-        );
+        // Replace last move with verbatim move string so annotator
+        // uhp move strings are predicable given string input
+        let mut moves = annotator.move_strings.clone();
+        moves.pop();
+        moves.push(move_string.to_string());
+        Ok(Annotator {
+            move_strings: moves,
+            ..annotator
+        })
+    }
 
+    /// Returns the board after the piece named *piece_string* is placed or
+    /// moved next to the anchor piece, or on top of it without a direction
+    /// (a climb, the anchor being the piece covered), or why it cannot be
+    fn moved_grid(
+        &self,
+        piece_string: &str,
+        new_piece: Piece,
+        anchor_piece_string: &str,
+    ) -> std::result::Result<HexGrid, &'static str> {
         // direction either at front, end or neither
-        let (direction, anchor_piece_string) = match first_last {
-            ('-', _) => (Some(Direction::W), &anchor_piece_string[1..]),
-            (_, '-') => (Some(Direction::E), &anchor_piece_string[..length - 1]),
-            ('\\', _) => (Some(Direction::NW), &anchor_piece_string[1..]),
-            ('/', _) => (Some(Direction::SW), &anchor_piece_string[1..]),
-            (_, '/') => (Some(Direction::NE), &anchor_piece_string[..length - 1]),
-            (_, '\\') => (Some(Direction::SE), &anchor_piece_string[..length - 1]),
-            _ => (None, anchor_piece_string),
-        };
+        let (direction, anchor_piece_string) = Direction::parse_anchor(anchor_piece_string);
+        if anchor_piece_string == piece_string {
+            return Err("a piece cannot be its own anchor");
+        }
 
         let (_, mut final_loc, _) = self
             .find(anchor_piece_string)
-            .expect(format!("Could not find anchor piece: {}", anchor_piece_string).as_str());
+            .ok_or("the anchor piece is not on the board")?;
 
         if let Some(direction) = direction {
             final_loc = final_loc.apply(direction);
@@ -472,28 +636,20 @@ impl Annotator {
         // Account for either a existing piece being moved or one being placed
         match self.find(piece_string) {
             Some((piece, old_loc, height)) => {
+                if height + 1 != self.prev_grid.peek(old_loc).len() {
+                    return Err("the piece is covered by another");
+                }
+                if old_loc == final_loc {
+                    return Err("the piece does not move");
+                }
                 new_grid.remove(old_loc);
-                debug_assert!(new_grid.peek(old_loc).len() == height);
                 new_grid.add(piece, final_loc);
             }
             None => {
                 new_grid.add(new_piece, final_loc);
             }
         }
-
-        let result = self.next_state(&new_grid);
-
-        // Replace last move with verbatim move string so annotator
-        // uhp move strings are predicable given string input
-        result.map(|annotator| {
-            let mut moves = annotator.move_strings.clone();
-            moves.pop();
-            moves.push(move_string.to_string());
-            Annotator {
-                move_strings: moves,
-                ..annotator
-            }
-        })
+        Ok(new_grid)
     }
 
     /// Add a new state the annotator, representing a UHP move string with identifiers
@@ -502,9 +658,11 @@ impl Annotator {
     /// The move must represent a legal Hive move from the last state of the board
     /// to the current state.
     ///
+    /// Move strings in the standard form (see next_standard_move()) are accepted too.
+    ///
     /// Returns the resulting state of the annotator after the move is applied
     pub fn next_uhp_move(&self, move_string: &str) -> Result<Annotator> {
-        let move_string = Annotator::uhp_to_standard(move_string);
+        let move_string = Annotator::uhp_to_standard(&Annotator::standard_to_uhp(move_string));
         self.next_standard_move(&move_string)
     }
 
@@ -549,6 +707,33 @@ impl Annotator {
             .collect()
     }
 
+    /// Returns the UHP MoveString of *mv* played from the current position,
+    /// such as "wS1", "bG1 -wS1", "wB1 wQ" for a climb, or "pass".
+    ///
+    /// Does not check that the move is legal
+    pub fn move_to_uhp(&self, mv: &Move) -> Result<String> {
+        if let Move::Movement { piece, from, .. } = mv {
+            if self.prev_grid.top(*from) != Some(*piece) {
+                return Err(UHPError::MoveStringError {
+                    move_string: format!("{:?}", mv),
+                    reason: "the piece is not on top of its stack".to_string(),
+                });
+            }
+        }
+        self.annotate(&mv.apply(&self.prev_grid))
+    }
+
+    /// Parses a UHP (or standard) MoveString played from the current position,
+    /// the inverse of move_to_uhp().
+    ///
+    /// Does not check that the move is legal
+    pub fn uhp_to_move(&self, move_string: &str) -> Result<Move> {
+        let next = self.next_uhp_move(move_string.trim())?;
+        self.prev_grid
+            .diff(next.position())
+            .ok_or(UHPError::TooManyDiffs)
+    }
+
     pub fn annotate(&self, position: &HexGrid) -> Result<String> {
         let next = self.next_state(position)?;
         next.uhp_move_strings()
@@ -562,7 +747,7 @@ impl Annotator {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub enum GameType {
     Standard,
     M,
@@ -590,39 +775,13 @@ impl GameType {
     }
 }
 
-pub struct UHPInterface {
-    annotations: Vec<Annotator>,
-    game_type: GameType,
-    game: GameDebugger,
-    player_to_move: PieceColor,
-}
-
-impl UHPInterface {
-    pub fn new() -> UHPInterface {
-        UHPInterface {
-            annotations: vec![Annotator::new()],
-            game_type: GameType::Standard,
-            game: GameDebugger::from_moves(&[]).unwrap(),
-            player_to_move: PieceColor::White,
-        }
-    }
-
-    pub fn game_debugger(&self) -> GameDebugger {
-        self.game.clone()
-    }
-
-    fn info(&self) -> CommandResult {
-        Ok("id ".to_string() + ENGINE_NAME + " v" + VERSION + "\n" + "Mosquito;Ladybug;Pillbug")
-    }
-
-    fn unknown(&self) -> CommandResult {
-        Err("Unknown command, cannot parse".to_string())
-    }
+impl TryFrom<&str> for GameType {
+    type Error = UHPError;
 
-    /// Parse a GameTypeString (see Universal Hive Protocol wiki)
-    /// and set the game type accordingly
-    fn set_game_type(&mut self, input: &str) -> CommandResult {
-        self.game_type = match input {
+    /// Parse a GameTypeString (see Universal Hive Protocol wiki),
+    /// expansions may be listed in any order
+    fn try_from(input: &str) -> Result<GameType> {
+        let game_type = match input {
             "Base" => GameType::Standard,
             "Base+M" => GameType::M,
             "Base+L" => GameType::L,
@@ -633,263 +792,138 @@ impl UHPInterface {
             "Base+MLP" | "Base+MPL" | "Base+LMP" | "Base+LPM" | "Base+PML" | "Base+PLM" => {
                 GameType::MLP
             }
-            _ => return Err("Unable to interpret GameTypeString".to_string()),
+            _ => return Err(UHPError::GameTypeError),
         };
-
-        // Also update underlying move generator
-        self.game = GameDebugger::from_moves_custom(
-            &self.annotations.last().unwrap().uhp_move_strings(),
-            self.game_type,
-        )
-        .unwrap();
-
-        Ok("".to_string())
+        Ok(game_type)
     }
+}
 
-    /// Parse a newgame command,
-    /// commands must be of the form:
-    ///
-    /// newgame
-    /// newgame GameTypeString
-    /// newgame GameString
-    ///
-    /// See the Universal Hive Protocol wiki for more information
-    fn new_game(&mut self, input: &str) -> CommandResult {
-        self.annotations = vec![Annotator::new()];
-        self.player_to_move = PieceColor::White;
-        if input == "newgame" {
-            self.set_game_type("Base")?;
-            return Ok(self.game_string());
-        }
-
-        if input.len() < 10 {
-            return Err("Invalid game type".to_string());
-        }
-
-        if !input.contains(';') {
-            self.set_game_type(&input[8..])?;
-        } else {
-            let rest = input[8..].to_string();
-            let mut delimited = rest.split(';');
-
-            let base = delimited
-                .next()
-                .ok_or_else(|| String::from("Invalid GameString"))?;
-            self.set_game_type(base)?;
-
-            let game_state = delimited
-                .next()
-                .ok_or_else(|| String::from("Invalid GameString"))?;
-
-            let turn_string = delimited
-                .next()
-                .ok_or_else(|| String::from("Invalid GameString"))?;
-
-            if turn_string.len() < 8 {
-                return Err("Expected TurnString at position 2 of GameString".to_string());
-            }
-
-            let num = turn_string[6..turn_string.len() - 1].to_string();
-            let _ = num
-                .parse::<u8>()
-                .map_err(|_| "Expected number at position 2 of GameString")?;
-
-            for move_string in delimited {
-                self.make_move(move_string)?;
-            }
+/// The capabilities an engine lists in its info response besides the
+/// expansions it plays
+const PONDER: &str = "Ponder";
+const MULTI_PV: &str = "MultiPV";
+
+const EXPANSIONS: [PieceType; 3] = [PieceType::Mosquito, PieceType::Ladybug, PieceType::Pillbug];
+
+/// What an engine reports about itself in response to info, for GUIs and
+/// clients to adapt to what it supports
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EngineInfo {
+    pub name: String,
+    pub version: String,
+    /// The expansion bugs the engine plays with, among the mosquito, the
+    /// ladybug and the pillbug
+    pub expansions: Vec<PieceType>,
+    /// Whether the engine thinks on its opponent's time
+    pub ponder: bool,
+    /// Whether the engine reports several best lines
+    pub multi_pv: bool,
+}
 
-            debug_assert!(
-                game_state == self.game_result(),
-                "Game did not end as expected"
-            );
+impl EngineInfo {
+    /// The info of this engine
+    pub fn anansii() -> EngineInfo {
+        EngineInfo {
+            name: ENGINE_NAME.to_string(),
+            version: VERSION.to_string(),
+            expansions: EXPANSIONS.to_vec(),
+            ponder: false,
+            multi_pv: false,
         }
-
-        Ok(self.game_string())
     }
 
-    fn game_result(&self) -> &str {
-        let result = self.game.game_result();
+    /// Whether the engine plays every expansion bug of *game_type*
+    pub fn supports(&self, game_type: GameType) -> bool {
+        let expansions = game_type
+            .to_str()
+            .trim_start_matches("Base")
+            .trim_start_matches('+');
+        expansions.chars().all(|c| {
+            PieceType::try_from(c).is_ok_and(|piece_type| self.expansions.contains(&piece_type))
+        })
+    }
 
-        match (self.annotations.len(), result) {
-            (1, None) => "NotStarted",
-            (_, None) => "InProgress",
-            (_, Some(GameResult::Draw)) => "Draw",
-            (_, Some(GameResult::WhiteWins)) => "WhiteWins",
-            (_, Some(GameResult::BlackWins)) => "BlackWins",
+    /// The info response, the id followed by the capabilities separated by
+    /// semicolons, e.g. "id Anansii v0.1\nMosquito;Ladybug;Pillbug"
+    pub fn to_uhp(&self) -> String {
+        let mut capabilities = self
+            .expansions
+            .iter()
+            .map(|piece_type| capitalize(piece_type.name()))
+            .collect::<Vec<_>>();
+        if self.ponder {
+            capabilities.push(PONDER.to_string());
         }
-    }
-    /// Returns the current GameString according to the Universal Hive Protocol
-    /// wiki
-    fn game_string(&self) -> String {
-        let turn_number = ((self.annotations.len() - 1) / 2) + 1;
-        let moves = self
-            .annotations
-            .last()
-            .unwrap()
-            .uhp_move_strings()
-            .join(";");
-        let game_type = self.game_type.to_str();
-        let color = self.player_to_move.to_str();
-        let game_result = self.game_result();
-        if game_result == "NotStarted" {
-            return format!("{};{};{}[{}]", game_type, game_result, color, turn_number);
+        if self.multi_pv {
+            capabilities.push(MULTI_PV.to_string());
         }
         format!(
-            "{};{};{}[{}];{}",
-            game_type, game_result, color, turn_number, moves
+            "id {} v{}\n{}",
+            self.name,
+            self.version,
+            capabilities.join(";")
         )
     }
 
-    /// Parses a move string in the UHP protocol form and stores
-    /// it directly for construction of the GameString. Checks the that
-    /// move is legal assuming self.game_type is updated
-    fn make_move(&mut self, move_string: &str) -> CommandResult {
-        let annotator = self.annotations.last().unwrap();
-
-        let annotator = annotator
-            .next_uhp_move(move_string)
-            .map_err(|e| e.to_string())?;
-
-        self.game
-            .make_move(move_string)
-            .map_err(|e| e.to_string())?;
-        self.annotations.push(annotator);
-        self.player_to_move = self.player_to_move.opposite();
-        Ok(self.game_string())
-    }
-
-    /// Parses a play command in the UHP protocol form and stores
-    /// the move string directly for access in the GameString
-    fn play(&mut self, input: &str) -> CommandResult {
-        if input.len() < 7 {
-            return Err("Invalid move string for play command".to_string());
-        }
-
-        self.make_move(input[5..].trim())
-    }
-
-    /// Returns a list of all valid moves for the current player
-    /// in compliance with the UHP protocol
-    fn valid_moves(&mut self) -> CommandResult {
-        let positions = self.game.legal_positions();
-        let annotator = self.annotations.last().unwrap();
-
-        let mut moves = vec![];
-        for position in positions {
-            let move_string = annotator
-                .annotate(&position)
-                .map_err(|_| "Cannot create move string")?;
-            moves.push(move_string);
-        }
-
-        Ok(moves.join(";"))
-    }
-
-    fn pass(&mut self) -> CommandResult {
-        self.make_move("pass")
-    }
-
-    fn best_move(&mut self, _input: &str) -> CommandResult {
-        todo!()
-    }
-
-    /// Undoes a single move and updates the game state if possible
-    fn undo_one(&mut self) -> CommandResult {
-        if self.annotations.len() == 1 {
-            return Err("Cannot undo past the first move".to_string());
-        }
-        self.annotations.pop();
-        self.player_to_move = self.player_to_move.opposite();
-        self.game.undo_move().map_err(|e| e.to_string())?;
-        Ok(self.game_string())
-    }
-
-    /// Undo command,
-    /// Must be one of the following forms
-    ///
-    /// undo
-    /// undo number
-    ///
-    /// See the Universal Hive Protocol wiki for more information
-    fn undo(&mut self, input: &str) -> CommandResult {
-        if input == "undo" {
-            return self.undo_one();
-        }
-
-        let num = input[5..]
-            .trim()
-            .parse::<u8>()
-            .map_err(|_| "Invalid number for undo command".to_string())?;
-        if num as usize >= self.annotations.len() {
-            return Err("Cannot undo past the first move".to_string());
-        }
-        if num < 1 {
-            return Err("Invalid number for undo command".to_string());
-        }
-
-        let mut game_string = Ok("".to_string());
-        for _ in 0..num {
-            game_string = self.undo_one();
+    /// Reads the *lines* of an info response, ignoring capabilities it does
+    /// not know. The version is empty if the id does not end with one
+    pub fn from_uhp(lines: &[String]) -> EngineInfo {
+        let mut info = EngineInfo {
+            name: String::new(),
+            version: String::new(),
+            expansions: vec![],
+            ponder: false,
+            multi_pv: false,
+        };
+        for line in lines {
+            if let Some(id) = line.strip_prefix("id ") {
+                let id = id.trim();
+                match id.rsplit_once(" v") {
+                    Some((name, version)) if version.starts_with(|c: char| c.is_ascii_digit()) => {
+                        info.name = name.to_string();
+                        info.version = version.to_string();
+                    }
+                    _ => info.name = id.to_string(),
+                }
+                continue;
+            }
+            for capability in line.split(';').map(str::trim) {
+                match capability.to_lowercase().as_str() {
+                    "ponder" => info.ponder = true,
+                    "multipv" => info.multi_pv = true,
+                    name => {
+                        let expansion = EXPANSIONS
+                            .into_iter()
+                            .find(|piece_type| piece_type.name() == name);
+                        match expansion {
+                            Some(expansion) if !info.expansions.contains(&expansion) => {
+                                info.expansions.push(expansion)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
         }
-        game_string
-    }
-
-    fn options(&mut self, _input: &str) -> CommandResult {
-        todo!()
+        info
     }
+}
 
-    pub fn current_position(&self) -> &HexGrid {
-        self.annotations
-            .last()
-            .expect("There should always be a current position")
-            .position()
-    }
-
-    /// Parses commands according to the Universal Hive Protocol
-    /// and returns the appropriate response
-    ///
-    /// All responses end with "ok\n"
-    ///
-    /// If the command encounters an error, the string returned will
-    /// begin with "err" in accordance with the UHP
-    pub fn command(&mut self, input: &str) -> String {
-        let response = match input.trim() {
-            "info" => self.info(),
-            "validmoves" => self.valid_moves(),
-            "pass" => self.pass(),
-            a if a.starts_with("bestmove") => self.best_move(a),
-            a if a.starts_with("newgame") => self.new_game(a),
-            a if a.starts_with("play") => self.play(a),
-            a if a.starts_with("undo") => self.undo(a),
-            a if a.starts_with("options") => self.options(a),
-            _ => self.unknown(),
-        };
-        let response = match response {
-            Ok(response) => response,
-            Err(response) => "err ".to_string() + &response,
-        };
-
-        debug_assert!(
-            if !response.is_empty() {
-                !response.ends_with('\n')
-            } else {
-                true
-            },
-            "Non-empty response should not end with a newline"
-        );
-        if response.is_empty() {
-            "ok\n".to_string()
-        } else {
-            response + "\nok\n"
-        }
+/// *word* with its first letter in uppercase
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Annotator;
-    use super::UHPInterface;
     use super::*;
+    use crate::game::GameDebugger;
+    use std::collections::HashSet;
 
     #[test]
     pub fn test_annotator_empty() {
@@ -1030,6 +1064,48 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn test_annotator_piece_ids() {
+        let mut annotator = Annotator::new();
+        for move_string in [r"wA1", r"bS1 wA1-", r"wA2 -wA1", r"bS2 bS1-", r"wQ \wA1"] {
+            annotator = annotator.next_uhp_move(move_string).unwrap();
+        }
+
+        let ids = annotator
+            .piece_ids()
+            .into_iter()
+            .map(|(piece_id, _, _)| piece_id.to_uhp())
+            .collect::<HashSet<String>>();
+        let expected = ["wA1", "wA2", "wQ", "bS1", "bS2"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<HashSet<String>>();
+        assert_eq!(ids, expected);
+
+        for (piece_id, location, height) in annotator.piece_ids() {
+            assert_eq!(annotator.piece_id(location, height), Some(piece_id));
+            assert_eq!(PieceId::from_uhp(&piece_id.to_string()).unwrap(), piece_id);
+        }
+
+        let (_, location, height) = annotator
+            .piece_ids()
+            .into_iter()
+            .find(|(piece_id, _, _)| piece_id.to_uhp() == "bS2")
+            .unwrap();
+        let annotator = annotator.next_uhp_move(r"wA2 bS2-").unwrap();
+        assert_eq!(
+            annotator.piece_id(location, height).unwrap().to_uhp(),
+            "bS2"
+        );
+        let (_, moved, _) = annotator
+            .piece_ids()
+            .into_iter()
+            .find(|(piece_id, _, _)| piece_id.to_uhp() == "wA2")
+            .unwrap();
+        assert_eq!(Some(moved), annotator.last_move());
+        assert!(annotator.piece_id(moved, 1).is_none());
+    }
+
     #[test]
     pub fn test_annotator_climb() {
         let mut annotator = Annotator::new();
@@ -1641,6 +1717,100 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_move_strings_round_trip() {
+        // Random games of every game type, checking every legal move of every
+        // position rather than only the one played
+        let mut games = (0..8u8)
+            .map(|seed| {
+                let choices = (0..40u8)
+                    .map(|i| seed.wrapping_mul(37) ^ i.wrapping_mul(11))
+                    .collect::<Vec<_>>();
+                crate::testing::fuzz::play_moves(&[&[seed][..], &choices].concat())
+            })
+            .collect::<Vec<_>>();
+        // The white pillbug may throw its queen, "wQ wP/" being out of the
+        // queen's own reach
+        let moves = ["wP", "bA1 wP-", "wQ -wP", "bQ bA1-"];
+        games.push(GameDebugger::from_move_list(&moves, GameType::P).unwrap());
+
+        let (mut climbs, mut drops) = (0, 0);
+        let mut move_strings = HashSet::new();
+        for game in games {
+            let mut replay = GameDebugger::from_move_list(&[], game.game_type()).unwrap();
+            let mut annotator = Annotator::new();
+            let mut next = game.positions().skip(1);
+            loop {
+                for legal in replay.legal_positions() {
+                    let move_string = annotator.annotate(&legal).unwrap();
+                    let played = annotator.next_uhp_move(&move_string).unwrap();
+                    assert_eq!(played.position(), &legal, "{}", move_string);
+                    let mv = annotator.uhp_to_move(&move_string).unwrap();
+                    assert_eq!(annotator.move_to_uhp(&mv).unwrap(), move_string);
+
+                    if let Move::Movement { from, to, .. } = mv {
+                        climbs += (legal.peek(to).len() > 1) as usize;
+                        drops += (annotator.position().peek(from).len() > 1) as usize;
+                    }
+                    move_strings.insert(move_string);
+                }
+                let Some(position) = next.next() else {
+                    break;
+                };
+                annotator = annotator.next_state(position).unwrap();
+                replay.append_position(position).unwrap();
+            }
+        }
+        assert!(
+            climbs > 0 && drops > 0,
+            "{} climbs, {} drops",
+            climbs,
+            drops
+        );
+        assert!(move_strings.contains("wQ wP/"));
+    }
+
+    #[test]
+    pub fn test_annotator_invalid_stacked_moves() {
+        let mut annotator = Annotator::new();
+        for move_string in [r"wL", r"bP wL-", r"wA1 \wL", r"bB1 bP/", r"bB1 bP"] {
+            annotator = annotator.next_uhp_move(move_string).unwrap();
+        }
+        // The black beetle covers the black pillbug
+        let reason =
+            |annotator: &Annotator, move_string: &str| match annotator.next_uhp_move(move_string) {
+                Err(UHPError::MoveStringError { reason, .. }) => reason,
+                result => panic!("{} was not rejected: {:?}", move_string, result.map(|_| ())),
+            };
+        assert_eq!(
+            reason(&annotator, r"bP wL\"),
+            "the piece is covered by another"
+        );
+        assert_eq!(
+            reason(&annotator, r"bB1 bB1-"),
+            "a piece cannot be its own anchor"
+        );
+        assert_eq!(reason(&annotator, r"bB1 bP"), "the piece does not move");
+        assert_eq!(
+            reason(&annotator, r"bB1 wQ"),
+            "the anchor piece is not on the board"
+        );
+
+        // Dropping off the stack, and back on with the covered pillbug as the anchor
+        let dropped = annotator.next_uhp_move(r"bB1 bP-").unwrap();
+        assert_eq!(
+            dropped
+                .position()
+                .peek(annotator.find("bP1").unwrap().1)
+                .len(),
+            1
+        );
+        let climbed = dropped.next_uhp_move(r"bB1 bP").unwrap();
+        assert_eq!(climbed.position(), annotator.position());
+        let mv = dropped.uhp_to_move(r"bB1 bP").unwrap();
+        assert_eq!(dropped.move_to_uhp(&mv).unwrap(), r"bB1 bP");
+    }
+
     #[test]
     pub fn test_annotator_uhp_move_string_interpretation_with_climbing() {
         let legal_moves = vec![
@@ -1795,199 +1965,81 @@ mod tests {
     }
 
     #[test]
-    pub fn test_uhp_interface_newgame() {
-        let mut uhp = UHPInterface::new();
-        let result = uhp.command("newgame");
-        assert_eq!(result, "Base;NotStarted;White[1]\nok\n")
-    }
-
-    #[test]
-    pub fn test_uhp_interface_newgame_game_type_strings() {
-        let mut uhp = UHPInterface::new();
-        let result = uhp.command("newgame Base+M");
-        assert_eq!(result, "Base+M;NotStarted;White[1]\nok\n");
-        let result = uhp.command("newgame Base+ML");
-        assert!(
-            result == "Base+ML;NotStarted;White[1]\nok\n"
-                || result == "Base+LM;NotStarted;White[1]\nok\n"
-        );
-
-        let result = uhp.command("newgame Base+LM");
-        assert!(
-            result == "Base+LM;NotStarted;White[1]\nok\n"
-                || result == "Base+ML;NotStarted;White[1]\nok\n"
-        );
-        let result = uhp.command("newgame Base+MLP");
-        assert!(
-            result == "Base+MLP;NotStarted;White[1]\nok\n"
-                || result == "Base+MPL;NotStarted;White[1]\nok\n"
-                || result == "Base+PLM;NotStarted;White[1]\nok\n"
-                || result == "Base+PML;NotStarted;White[1]\nok\n"
-                || result == "Base+LMP;NotStarted;White[1]\nok\n"
-                || result == "Base+LPM;NotStarted;White[1]\nok\n"
-        );
-    }
+    pub fn test_engine_info() {
+        let info = EngineInfo::anansii();
+        let response = info.to_uhp();
+        assert_eq!(response, "id Anansii v0.1\nMosquito;Ladybug;Pillbug");
+        let lines = response.lines().map(str::to_string).collect::<Vec<_>>();
+        assert_eq!(EngineInfo::from_uhp(&lines), info);
+        assert!(info.supports(GameType::MLP) && info.supports(GameType::Standard));
+
+        let info = EngineInfo {
+            expansions: vec![PieceType::Pillbug],
+            ponder: true,
+            multi_pv: true,
+            ..info
+        };
+        assert_eq!(info.to_uhp(), "id Anansii v0.1\nPillbug;Ponder;MultiPV");
+        assert!(info.supports(GameType::P) && !info.supports(GameType::LP));
 
-    #[test]
-    pub fn test_uhp_interface_some_moves() {
-        let moves = [
-            r"wL", r"bP wL-", r"wA1 \wL", r"bB1 bP/", r"wQ /wA1", r"bQ bB1\",
+        let lines = [
+            "id Other Engine".to_string(),
+            "Ladybug;Unknown;ladybug".to_string(),
         ];
-        let final_position = HexGrid::from_dsl(concat!(
-            ". . . . . .\n",
-            " . A . b . .\n",
-            ". Q L p q .\n",
-            " . . . . . .\n\n",
-            "start - [ -1 -2 ]\n\n"
-        ));
-
-        let moves = moves.join(";");
-        let newgame = format!("newgame Base+LP;InProgress;White[4];{}", moves);
-
-        let mut uhp = UHPInterface::new();
-        let output = uhp.command(&newgame);
-
-        println!("OUTPUT: {}", output);
-        assert!(
-            output == format!("Base+LP;InProgress;White[4];{}\nok\n", moves)
-                || output == format!("Base+PL;InProgress;White[4];{}\nok\n", moves)
+        let other = EngineInfo::from_uhp(&lines);
+        assert_eq!(
+            (other.name.as_str(), other.version.as_str()),
+            ("Other Engine", "")
         );
-        println!("{}", uhp.current_position().to_dsl());
-        println!("{}", final_position.to_dsl());
-        assert!(*uhp.current_position() == final_position);
+        assert_eq!(other.expansions, [PieceType::Ladybug]);
+        assert!(!other.ponder && !other.multi_pv);
     }
 
     #[test]
-    pub fn test_uhp_interface_play() {
-        let moves = [
-            r"wL", r"bP wL-", r"wA1 \wL", r"bB1 bP/", r"wQ /wA1", r"bQ bB1\",
-        ];
-        let final_position = HexGrid::from_dsl(concat!(
-            ". . . . . .\n",
-            " . A . b . .\n",
-            ". Q L p q .\n",
-            " . . . . . .\n\n",
-            "start - [ -1 -2 ]\n\n"
-        ));
-
-        let mut uhp = UHPInterface::new();
-        uhp.command("newgame Base+PL");
-
-        for (i, move_string) in moves.iter().enumerate() {
-            let turn_number = (i + 1) / 2 + 1;
-            let color = match (i + 1) % 2 {
-                0 => "White",
-                _ => "Black",
-            };
-            let output = uhp.command(&format!("play {}", move_string));
-            let moves = moves
-                .iter()
-                .take(i + 1)
-                .cloned()
-                .collect::<Vec<&str>>()
-                .join(";");
-
-            println!("GOT TO THIS OUTPUT {}", output);
-            assert!(
-                output
-                    == format!(
-                        "Base+PL;InProgress;{}[{}];{}\nok\n",
-                        color, turn_number, moves
-                    )
-                    || output
-                        == format!(
-                            "Base+LP;InProgress;{}[{}];{}\nok\n",
-                            color, turn_number, moves
-                        )
-            );
-        }
-
-        assert!(*uhp.current_position() == final_position);
-    }
+    pub fn test_move_conversion() {
+        use PieceColor::*;
 
-    #[test]
-    pub fn test_uhp_interface_undo() {
         let moves = [
-            r"wL", r"bP wL-", r"wA1 \wL", r"bB1 bP/", r"wQ /wA1", r"bQ bB1\",
+            "wS1", "bG1 -wS1", "wB1 wS1-", "bQ -bG1", r"wQ wS1\", "bA1 -bQ",
         ];
+        let mut game = GameDebugger::from_move_list(&moves, GameType::MLP).unwrap();
+        let annotator = Annotator::new();
+        let annotator = moves.iter().fold(annotator, |annotator, mv| {
+            annotator.next_uhp_move(mv).unwrap()
+        });
+        assert_eq!(annotator.position(), game.position());
+
+        let grid = game.position().clone();
+        let (beetle, _) = grid.find(Piece::new(PieceType::Beetle, White)).unwrap();
+        let (spider, _) = grid.find(Piece::new(PieceType::Spider, White)).unwrap();
+        let climb = Move::Movement {
+            piece: Piece::new(PieceType::Beetle, White),
+            from: beetle,
+            to: spider,
+        };
+        assert_eq!(annotator.move_to_uhp(&climb).unwrap(), "wB1 wS1");
+        assert_eq!(annotator.uhp_to_move("wB1 wS1").unwrap(), climb);
+
+        let place = annotator.uhp_to_move(r"wA1 \wQ1").unwrap();
+        assert!(matches!(place, Move::Place { .. }));
+        assert_eq!(annotator.move_to_uhp(&Move::Pass).unwrap(), "pass");
+        assert_eq!(annotator.uhp_to_move("pass").unwrap(), Move::Pass);
+
+        // Every legal move survives the round trip
+        for position in game.legal_positions() {
+            let mv = grid.diff(&position).unwrap();
+            let move_string = annotator.move_to_uhp(&mv).unwrap();
+            assert_eq!(move_string, game.move_string(&position).unwrap());
+            assert_eq!(annotator.uhp_to_move(&move_string).unwrap(), mv);
+        }
 
-        let mut uhp = UHPInterface::new();
-        uhp.command("newgame Base+PML;NotStarted;White[1]");
-        uhp.command(&format!("play {}", moves[0]));
-        uhp.command(&format!("play {}", moves[1]));
-        uhp.command(&format!("play {}", moves[2]));
-        uhp.command(&format!("play {}", moves[3]));
-        let output = uhp.command("undo");
-        assert_eq!(
-            &output[8..],
-            ";InProgress;Black[2];wL;bP wL-;wA1 \\wL\nok\n"
-        );
-
-        let output = uhp.command("undo");
-        assert_eq!(&output[8..], ";InProgress;White[2];wL;bP wL-\nok\n");
-
-        uhp.command(&format!("play {}", moves[2]));
-        let output = uhp.command("undo 2");
-        assert_eq!(&output[8..], ";InProgress;Black[1];wL\nok\n");
-    }
-
-    #[test]
-    pub fn test_game_states_output() {
-        let draw_before = r"Base;InProgress;Black[6];wA1;bA1 wA1-;wQ -wA1;bQ bA1-;wQ \wA1;bQ bA1/;wQ -wA1;bQ bA1-;wQ /wA1;bQ bA1\;wQ -wA1";
-        let draw_last_move = r"bQ bA1-";
-        let draw_complete = r"Base;Draw;White[7];wA1;bA1 wA1-;wQ -wA1;bQ bA1-;wQ \wA1;bQ bA1/;wQ -wA1;bQ bA1-;wQ /wA1;bQ bA1\;wQ -wA1;bQ bA1-";
-
-        let black_wins_before = r"Base+LMP;InProgress;White[8];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bA2 bQ/;wB1 wP;bA1 /wA1;wB1 wP\;bA2 bA1\;wA2 \wP;bA3 bQ\";
-        let black_wins_last_move = r"wA2 wQ\";
-        let black_wins_complete = r"Base+LMP;BlackWins;Black[8];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bA2 bQ/;wB1 wP;bA1 /wA1;wB1 wP\;bA2 bA1\;wA2 \wP;bA3 bQ\;wA2 wQ\";
-
-        let white_wins_before = r"Base+PL;InProgress;White[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/";
-        let white_wins_last_move = r"wB1 \bL";
-        let white_wins_complete = r"Base+PL;WhiteWins;Black[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/;wB1 \bL";
-
-        let mut uhp = UHPInterface::new();
-
-        uhp.command(&format!("newgame {}", draw_before));
-        let output = uhp.command(&format!("play {}", draw_last_move));
-        println!("{}", output);
-        println!("{}", format!("{}\nok\n", draw_complete));
-        assert!(output == format!("{}\nok\n", draw_complete));
-
-        uhp.command(&format!("newgame {}", black_wins_before));
-        let output = uhp.command(&format!("play {}", black_wins_last_move));
-        let output = output.to_string();
-        let black_wins_complete = black_wins_complete.to_string();
-        println!("{}", &output[8..]);
-        println!("{}", &format!("{}\nok\n", black_wins_complete)[8..]);
-        assert!(output[8..] == format!("{}\nok\n", black_wins_complete)[8..]);
-
-        uhp.command(&format!("newgame {}", white_wins_before));
-        let output = uhp.command(&format!("play {}", white_wins_last_move));
-        println!("{}", output);
-        println!("{}", format!("{}\nok\n", white_wins_complete));
-        assert!(output[7..] == format!("{}\nok\n", white_wins_complete)[7..]);
-    }
-
-    #[test]
-    pub fn test_game_states_input() {
-        let mut uhp = UHPInterface::new();
-        let draw_game_string = r"Base;Draw;White[7];wA1;bA1 wA1-;wQ -wA1;bQ bA1-;wQ \wA1;bQ bA1/;wQ -wA1;bQ bA1-;wQ /wA1;bQ bA1\;wQ -wA1;bQ bA1-";
-        let output = uhp.command(&format!("newgame {}", draw_game_string));
-        println!("{}", output);
-        println!("{}", format!("{}\nok\n", draw_game_string));
-        assert!(output == format!("{}\nok\n", draw_game_string));
-
-        let black_wins = r"Base+LMP;BlackWins;Black[8];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bA2 bQ/;wB1 wP;bA1 /wA1;wB1 wP\;bA2 bA1\;wA2 \wP;bA3 bQ\;wA2 wQ\";
-        let output = uhp.command(&format!("newgame {}", black_wins));
-        println!("{}", output);
-        println!("{}", format!("{}\nok\n", black_wins));
-        assert!(output[8..] == format!("{}\nok\n", black_wins)[8..]);
-
-        let white_wins = r"Base+PL;WhiteWins;Black[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/;wB1 \bL";
-        let output = uhp.command(&format!("newgame {}", white_wins));
-        println!("{}", output);
-        println!("{}", format!("{}\nok\n", white_wins));
-        assert!(output[7..] == format!("{}\nok\n", white_wins)[7..]);
+        let moved_under = Move::Movement {
+            piece: Piece::new(PieceType::Queen, White),
+            from: beetle,
+            to: spider,
+        };
+        assert!(annotator.move_to_uhp(&moved_under).is_err());
+        assert!(annotator.uhp_to_move("wB1 bL1-").is_err());
     }
 
     #[ignore = "be sure to manually test using nokamute uhp test suite"]
@@ -0,0 +1,676 @@
+use crate::engine::engines::Engine;
+use crate::game::GameDebugger;
+use crate::hex_grid::HexGrid;
+use crate::uhp::{CommandError, EngineInfo, ErrorCode};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// How long an engine may take to start up or to answer a command other
+/// than bestmove, unless set otherwise with UhpClient::set_timeout()
+pub const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an engine that stopped answering gets to exit before it is
+/// considered to have exited cleanly, see UhpClientError::Crashed
+const EXIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Error, Debug)]
+pub enum UhpClientError {
+    #[error("No engine command given")]
+    EmptyCommand,
+    #[error("Could not start {0}: {1}")]
+    Spawn(String, std::io::Error),
+    #[error("{0} did not answer in time")]
+    Timeout(String),
+    #[error("{0} exited")]
+    Exited(String),
+    /// The engine was killed by a signal or exited with an error status,
+    /// such as when it ran into one of its EngineLimits
+    #[error("{0} crashed: {1}")]
+    Crashed(String, String),
+    #[error("Could not talk to {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("{engine} rejected \"{command}\": {message}")]
+    Rejected {
+        engine: String,
+        command: String,
+        message: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, UhpClientError>;
+
+impl UhpClientError {
+    /// The code of the error the engine answered with, see
+    /// CommandError::from_uhp()
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            UhpClientError::Rejected { message, .. } => {
+                CommandError::from_uhp(message).map(|error| error.code)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How long the engine may think about a bestmove command
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BestMoveLimit {
+    /// Search for about the given time, the engine gets the response
+    /// timeout on top of it to answer
+    Time(Duration),
+    /// Search to the given depth, however long that takes
+    Depth(usize),
+    /// Search about the given number of positions, an extension of the UHP
+    /// understood by this crate's UHPInterface but not every engine
+    Nodes(usize),
+}
+
+impl BestMoveLimit {
+    /// The arguments of the bestmove command, the time as hh:mm:ss rounded
+    /// up to whole seconds so that a short budget is not sent as none at all.
+    /// No time at all is sent as a search to depth 1
+    pub fn to_uhp(&self) -> String {
+        match self {
+            BestMoveLimit::Time(duration) if duration.is_zero() => "depth 1".to_string(),
+            BestMoveLimit::Time(duration) => {
+                let seconds = duration.as_secs() + (duration.subsec_nanos() > 0) as u64;
+                format!(
+                    "time {:02}:{:02}:{:02}",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60
+                )
+            }
+            BestMoveLimit::Depth(depth) => format!("depth {}", depth),
+            BestMoveLimit::Nodes(nodes) => format!("nodes {}", nodes),
+        }
+    }
+}
+
+/// Limits on the process of an external engine, so that a misbehaving
+/// engine cannot take down the machine it runs on. None leaves a resource
+/// unlimited.
+///
+/// The memory and processor time are resource limits of the operating
+/// system, only set on Unix. An engine going over them is killed or fails to
+/// allocate, which is reported as UhpClientError::Crashed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineLimits {
+    /// The most memory the engine may map, in bytes
+    pub memory: Option<u64>,
+    /// The processor time the engine may use in total, rounded up to whole
+    /// seconds, see cpu_seconds()
+    pub cpu_time: Option<Duration>,
+    /// The directory the engine runs in, instead of the current one
+    pub working_dir: Option<PathBuf>,
+}
+
+impl EngineLimits {
+    /// The processor time limit in the whole seconds the operating system
+    /// counts, rounded up so that the engine never gets less than asked for
+    pub fn cpu_seconds(&self) -> Option<u64> {
+        self.cpu_time.map(|time| {
+            let seconds = time.as_secs() + u64::from(time.subsec_nanos() > 0);
+            seconds.max(1)
+        })
+    }
+
+    /// Sets the limits on *process* before it is started
+    pub fn apply(&self, process: &mut Command) {
+        if let Some(directory) = &self.working_dir {
+            process.current_dir(directory);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            let memory = self.memory;
+            let cpu_time = self.cpu_seconds();
+            let set = |resource, soft: u64, hard: u64| {
+                let limit = libc::rlimit {
+                    rlim_cur: soft as libc::rlim_t,
+                    rlim_max: hard as libc::rlim_t,
+                };
+                // Safety: setrlimit only reads the limit it is given
+                match unsafe { libc::setrlimit(resource, &limit) } {
+                    0 => Ok(()),
+                    _ => Err(std::io::Error::last_os_error()),
+                }
+            };
+            // Safety: the closure only calls setrlimit, which is async
+            // signal safe, between the fork and the exec of the engine
+            unsafe {
+                process.pre_exec(move || {
+                    if let Some(bytes) = memory {
+                        set(libc::RLIMIT_AS, bytes, bytes)?;
+                    }
+                    // The engine gets a SIGXCPU it can report at the soft
+                    // limit, and is killed a second later
+                    if let Some(seconds) = cpu_time {
+                        set(libc::RLIMIT_CPU, seconds, seconds + 1)?;
+                    }
+                    Ok(())
+                });
+            }
+        }
+    }
+}
+
+/// Why an engine exited with *status*, in words
+fn describe_exit(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return match signal {
+                libc::SIGXCPU => "out of processor time".to_string(),
+                libc::SIGSEGV => "segmentation fault".to_string(),
+                libc::SIGABRT => "aborted, possibly out of memory".to_string(),
+                libc::SIGKILL => "killed".to_string(),
+                signal => format!("killed by signal {}", signal),
+            };
+        }
+    }
+    status.to_string()
+}
+
+/// Drives another UHP engine as a separate process over its standard input
+/// and output, sending it commands the way a UHP viewer would.
+///
+/// Responses are read on a separate thread so that every command can time
+/// out, and the engine is told to exit and killed when the client is dropped
+#[derive(Debug)]
+pub struct UhpClient {
+    name: String,
+    /// The lines of the info response after the id, usually the expansions
+    /// the engine supports
+    capabilities: Vec<String>,
+    /// The info response as understood by the client
+    info: EngineInfo,
+    timeout: Duration,
+    /// The BestMoveLimit used when playing as an Engine
+    limit: BestMoveLimit,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    /// The number of commands that timed out, whose responses are still to
+    /// come before the response to the next command
+    late: usize,
+}
+
+impl UhpClient {
+    /// Starts *command*, a program followed by its arguments separated by
+    /// whitespace, see start()
+    pub fn spawn(command: &str) -> Result<UhpClient> {
+        UhpClient::spawn_with(command, &EngineLimits::default())
+    }
+
+    /// Like spawn(), running the engine within *limits*
+    pub fn spawn_with(command: &str, limits: &EngineLimits) -> Result<UhpClient> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or(UhpClientError::EmptyCommand)?;
+        let mut process = Command::new(program);
+        process.args(words);
+        limits.apply(&mut process);
+        UhpClient::start(process, command)
+    }
+
+    /// Starts *process* with its input and output connected to the client
+    /// and waits for the info response it prints on startup, *name* is used
+    /// in errors until the engine reports its own
+    pub fn start(mut process: Command, name: &str) -> Result<UhpClient> {
+        let mut child = process
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| UhpClientError::Spawn(name.to_string(), e))?;
+        let stdin = child.stdin.take().expect("The input should be piped");
+        let stdout = child.stdout.take().expect("The output should be piped");
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut client = UhpClient {
+            name: name.to_string(),
+            capabilities: vec![],
+            info: EngineInfo::from_uhp(&[]),
+            timeout: RESPONSE_TIMEOUT,
+            limit: BestMoveLimit::Depth(3),
+            child,
+            stdin,
+            lines,
+            late: 0,
+        };
+        let info = client.response("info", Some(RESPONSE_TIMEOUT))?;
+        client.read_info(info);
+        Ok(client)
+    }
+
+    fn read_info(&mut self, info: Vec<String>) {
+        self.info = EngineInfo::from_uhp(&info);
+        self.capabilities.clear();
+        for line in info {
+            match line.strip_prefix("id ") {
+                Some(id) => self.name = id.trim().to_string(),
+                None => self.capabilities.push(line),
+            }
+        }
+    }
+
+    /// The id the engine reported, or the command that started it
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The lines of the info response after the id
+    pub fn capabilities(&self) -> &[String] {
+        &self.capabilities
+    }
+
+    /// The name, version, expansions and optional capabilities the engine
+    /// reported
+    pub fn engine_info(&self) -> &EngineInfo {
+        &self.info
+    }
+
+    /// Sets how long the engine may take to answer commands,
+    /// RESPONSE_TIMEOUT by default
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sets how long the engine thinks about each move when it plays
+    /// as an Engine, a depth of 3 by default
+    pub fn set_limit(&mut self, limit: BestMoveLimit) {
+        self.limit = limit;
+    }
+
+    /// Sends *command* and returns the lines of the response before "ok".
+    ///
+    /// Fails if the response is not complete within *timeout* (if given)
+    /// or starts with "err" or "invalidmove". The response to a command that
+    /// timed out is skipped when it comes, before the next command is sent
+    pub fn send(&mut self, command: &str, timeout: Option<Duration>) -> Result<Vec<String>> {
+        self.skip_late()?;
+        if let Err(e) = writeln!(self.stdin, "{}", command).and_then(|_| self.stdin.flush()) {
+            return Err(match e.kind() {
+                std::io::ErrorKind::BrokenPipe => self.exit_error(),
+                _ => UhpClientError::Io(self.name.clone(), e),
+            });
+        }
+        let response = self.response(command, timeout);
+        if let Err(UhpClientError::Timeout(_)) = response {
+            self.late += 1;
+        }
+        response
+    }
+
+    /// Reads and drops the responses to the commands that timed out, so that
+    /// they are not taken for the response to the next command. Fails if
+    /// they do not come within the response timeout
+    fn skip_late(&mut self) -> Result<()> {
+        while self.late > 0 {
+            let timeout = Some(self.timeout);
+            match self.response("", timeout) {
+                Ok(_) | Err(UhpClientError::Rejected { .. }) => self.late -= 1,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends *command* and returns the single line of its response
+    fn send_line(&mut self, command: &str) -> Result<String> {
+        let timeout = Some(self.timeout);
+        Ok(self.send(command, timeout)?.join("\n"))
+    }
+
+    fn response(&mut self, command: &str, timeout: Option<Duration>) -> Result<Vec<String>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut lines = vec![];
+        loop {
+            let line = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    self.lines.recv_timeout(left)
+                }
+                None => self
+                    .lines
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let line = match line {
+                Ok(line) => line.trim().to_string(),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(UhpClientError::Timeout(self.name.clone()))
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(self.exit_error()),
+            };
+            if line == "ok" {
+                break;
+            }
+            lines.push(line);
+        }
+        let error = lines
+            .iter()
+            .find(|line| line.starts_with("err") || line.starts_with("invalidmove"));
+        match error {
+            Some(message) => Err(UhpClientError::Rejected {
+                engine: self.name.clone(),
+                command: command.to_string(),
+                message: message.clone(),
+            }),
+            None => Ok(lines),
+        }
+    }
+
+    /// The error for an engine whose output closed, Crashed if its process
+    /// ended abnormally
+    fn exit_error(&mut self) -> UhpClientError {
+        let deadline = Instant::now() + EXIT_TIMEOUT;
+        loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) if !status.success() => {
+                    return UhpClientError::Crashed(self.name.clone(), describe_exit(status))
+                }
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                _ => return UhpClientError::Exited(self.name.clone()),
+            }
+        }
+    }
+
+    /// Asks the engine for its id and capabilities again, see capabilities()
+    pub fn info(&mut self) -> Result<()> {
+        let timeout = Some(self.timeout);
+        let info = self.send("info", timeout)?;
+        self.read_info(info);
+        Ok(())
+    }
+
+    /// Starts a new game from a GameTypeString or a GameString and
+    /// returns the GameString the engine reports
+    pub fn new_game(&mut self, game: &str) -> Result<String> {
+        self.send_line(&format!("newgame {}", game))
+    }
+
+    /// Plays the UHP MoveString *move_string* and returns the GameString
+    /// the engine reports
+    pub fn play(&mut self, move_string: &str) -> Result<String> {
+        self.send_line(&format!("play {}", move_string))
+    }
+
+    pub fn pass(&mut self) -> Result<String> {
+        self.send_line("pass")
+    }
+
+    /// Takes back the last *moves* moves and returns the GameString the
+    /// engine reports
+    pub fn undo(&mut self, moves: usize) -> Result<String> {
+        self.send_line(&format!("undo {}", moves))
+    }
+
+    /// The MoveStrings of every move the engine considers valid
+    pub fn valid_moves(&mut self) -> Result<Vec<String>> {
+        let moves = self.send_line("validmoves")?;
+        Ok(moves
+            .split(';')
+            .filter(|move_string| !move_string.is_empty())
+            .map(|move_string| move_string.to_string())
+            .collect())
+    }
+
+    /// The MoveString of the move the engine would play within *limit*
+    pub fn best_move(&mut self, limit: BestMoveLimit) -> Result<String> {
+        let timeout = match limit {
+            BestMoveLimit::Time(time) => Some(time + self.timeout),
+            BestMoveLimit::Depth(_) | BestMoveLimit::Nodes(_) => None,
+        };
+        let command = format!("bestmove {}", limit.to_uhp());
+        Ok(self.send(&command, timeout)?.join("\n"))
+    }
+
+    /// The engine's options, one line per option as the engine reports them
+    pub fn options(&mut self) -> Result<Vec<String>> {
+        let timeout = Some(self.timeout);
+        self.send("options", timeout)
+    }
+
+    /// Sets the option *name* to *value*
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<()> {
+        let timeout = Some(self.timeout);
+        self.send(&format!("options set {} {}", name, value), timeout)?;
+        Ok(())
+    }
+}
+
+impl Drop for UhpClient {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "exit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The UHP GameString of the current position of *game*
+pub fn game_string(game: &GameDebugger) -> String {
+    let mut replay = GameDebugger::from_move_list(&[], game.game_type())
+        .expect("The empty game should be valid");
+    let mut moves = vec![];
+    for position in game.positions().skip(1) {
+        let move_string = replay
+            .move_string(position)
+            .expect("Every position of a game should follow the one before");
+        replay
+            .append_position(position)
+            .expect("Every position of a game should follow the one before");
+        moves.push(move_string);
+    }
+
+    let state = match game.game_result() {
+        _ if moves.is_empty() => "NotStarted",
+        Some(result) => result.game_state(),
+        None => "InProgress",
+    };
+    let mut game_string = format!(
+        "{};{};{}[{}]",
+        game.game_type().to_str(),
+        state,
+        game.player_to_move().to_str(),
+        moves.len() / 2 + 1
+    );
+    for move_string in moves {
+        game_string += ";";
+        game_string += &move_string;
+    }
+    game_string
+}
+
+/// Lets an external engine play wherever an engine of this crate can, by
+/// starting a new game from the position of each move and asking for the
+/// best move within the limit set with set_limit().
+///
+/// Moves that fail or are illegal are reported as no move
+impl Engine for UhpClient {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn best_move(&mut self, game: &mut GameDebugger) -> Option<HexGrid> {
+        if game.game_result().is_some() {
+            return None;
+        }
+        self.new_game(&game_string(game)).ok()?;
+        let move_string = UhpClient::best_move(self, self.limit).ok()?;
+        let mut after = game.clone();
+        after.make_move(&move_string).ok()?;
+        Some(after.position().clone())
+    }
+}
+
+// The tests run shell scripts as engines
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::piece::PieceType;
+    use crate::uhp::GameType;
+
+    #[test]
+    pub fn test_cpu_seconds() {
+        let limits = |cpu_time| EngineLimits {
+            cpu_time,
+            ..Default::default()
+        };
+        assert_eq!(limits(None).cpu_seconds(), None);
+        assert_eq!(limits(Some(Duration::ZERO)).cpu_seconds(), Some(1));
+        assert_eq!(
+            limits(Some(Duration::from_millis(200))).cpu_seconds(),
+            Some(1)
+        );
+        assert_eq!(limits(Some(Duration::from_secs(2))).cpu_seconds(), Some(2));
+        assert_eq!(
+            limits(Some(Duration::from_millis(2500))).cpu_seconds(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    pub fn test_uhp_client() {
+        // An engine that answers bestmove with wA1, echoes every other command
+        // and rejects undo
+        let script = concat!(
+            "echo 'id echo v1'; echo 'Mosquito;MultiPV'; echo ok; ",
+            "while read line; do case $line in ",
+            "bestmove*) echo wA1;; undo*) echo err InvalidState: no undo;; *) echo $line;; ",
+            "esac; echo ok; done",
+        );
+        let mut process = Command::new("sh");
+        process.args(["-c", script]);
+        let mut client = UhpClient::start(process, "echo").unwrap();
+        assert_eq!(client.name(), "echo v1");
+        assert_eq!(client.capabilities(), ["Mosquito;MultiPV"]);
+        let info = client.engine_info();
+        assert_eq!((info.name.as_str(), info.version.as_str()), ("echo", "1"));
+        assert_eq!(info.expansions, [PieceType::Mosquito]);
+        assert!(info.multi_pv && !info.ponder);
+        assert!(info.supports(GameType::M) && !info.supports(GameType::MP));
+
+        assert_eq!(client.new_game("Base").unwrap(), "newgame Base");
+        assert_eq!(client.play("wA1").unwrap(), "play wA1");
+        assert_eq!(client.valid_moves().unwrap(), ["validmoves"]);
+        assert_eq!(
+            client
+                .best_move(BestMoveLimit::Time(Duration::from_secs(75)))
+                .unwrap(),
+            "wA1"
+        );
+        assert_eq!(
+            BestMoveLimit::Time(Duration::from_secs(3725)).to_uhp(),
+            "time 01:02:05"
+        );
+        assert_eq!(BestMoveLimit::Nodes(5000).to_uhp(), "nodes 5000");
+        assert_eq!(
+            BestMoveLimit::Time(Duration::from_millis(300)).to_uhp(),
+            "time 00:00:01"
+        );
+        assert_eq!(BestMoveLimit::Time(Duration::ZERO).to_uhp(), "depth 1");
+        let undo = client.undo(1);
+        assert!(matches!(undo, Err(UhpClientError::Rejected { .. })));
+        assert_eq!(undo.unwrap_err().code(), Some(ErrorCode::InvalidState));
+        client.set_option("MaxHelperThreads", "1").unwrap();
+
+        let mut game = GameDebugger::from_move_list(&[], GameType::Standard).unwrap();
+        assert_eq!(game_string(&game), "Base;NotStarted;White[1]");
+        let position = Engine::best_move(&mut client, &mut game).unwrap();
+        game.append_position(&position).unwrap();
+        assert_eq!(game_string(&game), "Base;InProgress;Black[1];wA1");
+        assert!(Engine::best_move(&mut client, &mut game).is_none());
+
+        assert!(matches!(
+            UhpClient::spawn(" "),
+            Err(UhpClientError::EmptyCommand)
+        ));
+    }
+
+    #[test]
+    pub fn test_late_response() {
+        // An engine that answers bestmove a second late and echoes every
+        // other command
+        let script = concat!(
+            "echo 'id late'; echo ok; ",
+            "while read line; do case $line in ",
+            "bestmove*) sleep 1; echo wA1;; *) echo $line;; ",
+            "esac; echo ok; done",
+        );
+        let mut process = Command::new("sh");
+        process.args(["-c", script]);
+        let mut client = UhpClient::start(process, "late").unwrap();
+        let timeout = client.send("bestmove depth 1", Some(Duration::from_millis(100)));
+        assert!(matches!(timeout, Err(UhpClientError::Timeout(_))));
+
+        // The late wA1 is not taken for the response to the next commands
+        assert_eq!(client.new_game("Base").unwrap(), "newgame Base");
+        assert_eq!(client.play("wA1").unwrap(), "play wA1");
+
+        // An engine that is still late by the next command times out again
+        client
+            .send("bestmove depth 1", Some(Duration::from_millis(100)))
+            .unwrap_err();
+        client.set_timeout(Duration::from_millis(100));
+        let timeout = client.play("wA1");
+        assert!(matches!(timeout, Err(UhpClientError::Timeout(_))));
+        client.set_timeout(RESPONSE_TIMEOUT);
+        assert_eq!(client.play("wA1").unwrap(), "play wA1");
+    }
+
+    #[test]
+    pub fn test_engine_limits() {
+        // An engine that reports the directory it runs in and crashes on
+        // its first command
+        let script = "echo \"id $(pwd)\"; echo ok; read line; kill -SEGV $$";
+        let mut process = Command::new("sh");
+        process.args(["-c", script]);
+        let directory = std::env::temp_dir().canonicalize().unwrap();
+        let limits = EngineLimits {
+            working_dir: Some(directory.clone()),
+            ..Default::default()
+        };
+        limits.apply(&mut process);
+        let mut client = UhpClient::start(process, "crashing").unwrap();
+        assert_eq!(client.name(), directory.display().to_string());
+        let crash = client.new_game("Base").unwrap_err();
+        assert!(
+            matches!(&crash, UhpClientError::Crashed(_, reason) if reason == "segmentation fault"),
+            "{}",
+            crash
+        );
+
+        // Thinking forever runs out of processor time
+        let script = "echo 'id busy'; echo ok; read line; while :; do :; done";
+        let mut process = Command::new("sh");
+        process.args(["-c", script]);
+        let limits = EngineLimits {
+            cpu_time: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        limits.apply(&mut process);
+        let mut client = UhpClient::start(process, "busy").unwrap();
+        let timeout = client.send("bestmove depth 3", Some(Duration::from_secs(20)));
+        assert!(
+            matches!(&timeout, Err(UhpClientError::Crashed(_, reason)) if reason == "out of processor time"),
+            "{:?}",
+            timeout
+        );
+    }
+}
@@ -0,0 +1,815 @@
+use crate::engine::handle::{search, SearchLimits, MAX_DEPTH};
+use crate::engine::search::Searcher;
+use crate::game::*;
+use crate::hex_grid::*;
+use crate::uhp::*;
+
+/// Parses the arguments of a bestmove command, see UHPInterface::best_move()
+fn parse_search_limits(input: &str) -> std::result::Result<SearchLimits, String> {
+    let words = input.split_whitespace().collect::<Vec<_>>();
+    if words.is_empty() {
+        return Ok(SearchLimits::default());
+    }
+    if words.len() % 2 != 0 {
+        return Err("Expected bestmove depth, time or nodes followed by a value".to_string());
+    }
+    let mut limits = SearchLimits {
+        depth: MAX_DEPTH,
+        time: None,
+        nodes: None,
+    };
+    for pair in words.chunks(2) {
+        let number = || pair[1].parse::<usize>().ok().filter(|number| *number > 0);
+        match pair[0] {
+            "depth" => limits.depth = number().ok_or("Invalid depth for bestmove command")?,
+            "nodes" => limits.nodes = Some(number().ok_or("Invalid nodes for bestmove command")?),
+            "time" => {
+                let parts = pair[1]
+                    .split(':')
+                    .map(|part| part.parse::<u64>())
+                    .collect::<std::result::Result<Vec<_>, _>>();
+                let seconds = match parts.as_deref() {
+                    Ok([hours, minutes, seconds]) => hours * 3600 + minutes * 60 + seconds,
+                    _ => return Err("Expected time as hh:mm:ss for bestmove command".to_string()),
+                };
+                limits.time = Some(std::time::Duration::from_secs(seconds));
+            }
+            limit => return Err(format!("Unknown bestmove limit {}", limit)),
+        }
+    }
+    Ok(limits)
+}
+
+/// Plays games through the text commands of the Universal Hive Protocol
+#[derive(Clone)]
+pub struct UHPInterface {
+    annotations: Vec<Annotator>,
+    game_type: GameType,
+    game: GameDebugger,
+    player_to_move: PieceColor,
+}
+
+impl UHPInterface {
+    pub fn new() -> UHPInterface {
+        UHPInterface {
+            annotations: vec![Annotator::new()],
+            game_type: GameType::Standard,
+            game: GameDebugger::from_moves(&[]).unwrap(),
+            player_to_move: PieceColor::White,
+        }
+    }
+
+    pub fn game_debugger(&self) -> GameDebugger {
+        self.game.clone()
+    }
+
+    fn info(&self) -> CommandResult {
+        Ok(EngineInfo::anansii().to_uhp())
+    }
+
+    fn unknown(&self, input: &str) -> CommandResult {
+        Err(CommandError::new(
+            ErrorCode::UnknownCommand,
+            format!("Unknown command {}", input),
+        ))
+    }
+
+    /// Parse a GameTypeString (see Universal Hive Protocol wiki)
+    /// and set the game type accordingly
+    fn set_game_type(&mut self, input: &str) -> CommandResult {
+        let game_type = GameType::try_from(input)
+            .map_err(|e| CommandError::new(ErrorCode::InvalidGameType, e.to_string()))?;
+
+        // Also update underlying move generator
+        self.game = GameDebugger::from_moves_custom(
+            &self.annotations.last().unwrap().uhp_move_strings(),
+            game_type,
+        )
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+        self.game_type = game_type;
+
+        Ok("".to_string())
+    }
+
+    /// Parse a newgame command,
+    /// commands must be of the form:
+    ///
+    /// newgame
+    /// newgame GameTypeString
+    /// newgame GameString
+    ///
+    /// A GameString restores a game with its moves, whose GameStateString
+    /// and TurnString must agree with them. The current game is only
+    /// replaced once the new one is set up, so a failed newgame leaves it
+    /// as it was.
+    ///
+    /// See the Universal Hive Protocol wiki for more information
+    fn new_game(&mut self, input: &str) -> CommandResult {
+        let argument = input["newgame".len()..].trim();
+        let mut restored = UHPInterface::new();
+        match argument {
+            "" => restored.set_game_type("Base")?,
+            _ if !argument.contains(';') => restored.set_game_type(argument)?,
+            _ => restored.restore(argument)?,
+        };
+
+        self.annotations = restored.annotations;
+        self.game_type = restored.game_type;
+        self.game = restored.game;
+        self.player_to_move = restored.player_to_move;
+        Ok(self.game_string())
+    }
+
+    /// Plays the moves of *game_string* from the start of its game type
+    fn restore(&mut self, game_string: &str) -> CommandResult {
+        let invalid = |message: String| CommandError::new(ErrorCode::InvalidGameString, message);
+        let mut fields = game_string.split(';').map(str::trim);
+        self.set_game_type(fields.next().unwrap_or_default())?;
+        let game_state = fields.next().ok_or_else(|| {
+            invalid("Expected GameStateString at position 1 of GameString".to_string())
+        })?;
+        let turn_string = fields.next().ok_or_else(|| {
+            invalid("Expected TurnString at position 2 of GameString".to_string())
+        })?;
+
+        for move_string in fields.filter(|move_string| !move_string.is_empty()) {
+            self.make_move(move_string).map_err(|e| {
+                invalid(format!(
+                    "Cannot play {} of the GameString: {}",
+                    move_string, e.message
+                ))
+            })?;
+        }
+
+        // A result the board does not show was decided by the players
+        self.game
+            .apply_game_state(game_state)
+            .map_err(|e| invalid(e.to_string()))?;
+        if game_state != self.game_result() {
+            return Err(invalid(format!(
+                "Expected GameStateString {} for the moves of the GameString, found {}",
+                self.game_result(),
+                game_state
+            )));
+        }
+        if turn_string != self.turn_string() {
+            return Err(invalid(format!(
+                "Expected TurnString {} for the moves of the GameString, found {}",
+                self.turn_string(),
+                turn_string
+            )));
+        }
+        Ok("".to_string())
+    }
+
+    /// The TurnString of the current position, such as White[1]
+    fn turn_string(&self) -> String {
+        let turn_number = ((self.annotations.len() - 1) / 2) + 1;
+        format!("{}[{}]", self.player_to_move.to_str(), turn_number)
+    }
+
+    fn game_result(&self) -> &str {
+        let result = self.game.game_result();
+
+        match (self.annotations.len(), result) {
+            (1, None) => "NotStarted",
+            (_, None) => "InProgress",
+            (_, Some(result)) => result.game_state(),
+        }
+    }
+    /// Returns the current GameString according to the Universal Hive Protocol
+    /// wiki
+    pub fn game_string(&self) -> String {
+        let moves = self
+            .annotations
+            .last()
+            .unwrap()
+            .uhp_move_strings()
+            .join(";");
+        let game_type = self.game_type.to_str();
+        let turn_string = self.turn_string();
+        let game_result = self.game_result();
+        if game_result == "NotStarted" {
+            return format!("{};{};{}", game_type, game_result, turn_string);
+        }
+        format!("{};{};{};{}", game_type, game_result, turn_string, moves)
+    }
+
+    /// Parses a move string in the UHP protocol form and stores
+    /// it directly for construction of the GameString. Checks the that
+    /// move is legal assuming self.game_type is updated
+    fn make_move(&mut self, move_string: &str) -> CommandResult {
+        if self.game.game_result().is_some() {
+            return Err(CommandError::new(
+                ErrorCode::InvalidState,
+                "The game is over",
+            ));
+        }
+        let annotator = self.annotations.last().unwrap();
+
+        let annotator = annotator.next_uhp_move(move_string).map_err(|e| match e {
+            UHPError::IllegalMove { .. } => {
+                CommandError::new(ErrorCode::InvalidMove, e.to_string())
+            }
+            e => CommandError::new(ErrorCode::InvalidMoveString, e.to_string()),
+        })?;
+
+        self.game.make_move(move_string).map_err(|e| match e {
+            GameDebuggerError::IllegalMove(reason) => {
+                CommandError::new(ErrorCode::InvalidMove, reason.to_string())
+            }
+            GameDebuggerError::AnnotationError(UHPError::IllegalMove { .. }) => {
+                CommandError::new(ErrorCode::InvalidMove, e.to_string())
+            }
+            e => CommandError::new(ErrorCode::InvalidMoveString, e.to_string()),
+        })?;
+        self.annotations.push(annotator);
+        self.player_to_move = self.player_to_move.opposite();
+        Ok(self.game_string())
+    }
+
+    /// Parses a play command in the UHP protocol form and stores
+    /// the move string directly for access in the GameString
+    fn play(&mut self, input: &str) -> CommandResult {
+        match input["play".len()..].trim() {
+            "" => Err(CommandError::new(
+                ErrorCode::InvalidArgument,
+                "Expected a MoveString for play command",
+            )),
+            move_string => self.make_move(move_string),
+        }
+    }
+
+    /// Returns a list of all valid moves for the current player
+    /// in compliance with the UHP protocol
+    fn valid_moves(&mut self) -> CommandResult {
+        let positions = self.game.legal_positions();
+        let annotator = self.annotations.last().unwrap();
+
+        let mut moves = vec![];
+        for position in positions {
+            let move_string = annotator
+                .annotate(&position)
+                .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+            moves.push(move_string);
+        }
+
+        Ok(moves.join(";"))
+    }
+
+    fn pass(&mut self) -> CommandResult {
+        self.make_move("pass")
+    }
+
+    /// Bestmove command,
+    /// Must be one of the following forms
+    ///
+    /// bestmove
+    /// bestmove depth number
+    /// bestmove time hh:mm:ss
+    /// bestmove nodes number
+    ///
+    /// Limiting the whole search to *number* positions with nodes is an
+    /// extension of the UHP. Limits may be combined, the search stops at the
+    /// first one reached, and without any the default SearchLimits are used
+    ///
+    /// See the Universal Hive Protocol wiki for more information
+    fn best_move(&mut self, input: &str) -> CommandResult {
+        let limits = parse_search_limits(&input["bestmove".len()..])
+            .map_err(|e| CommandError::new(ErrorCode::InvalidArgument, e))?;
+        let game_over = || CommandError::new(ErrorCode::InvalidState, "The game is over");
+        if self.game.game_result().is_some() {
+            return Err(game_over());
+        }
+        let analysis =
+            search(&self.game, Searcher::new(self.game_type), limits).ok_or_else(game_over)?;
+        let position = match analysis.best_line.first() {
+            Some(position) => position.clone(),
+            // Passing is the only move
+            None => self.current_position().clone(),
+        };
+        self.annotations
+            .last()
+            .unwrap()
+            .annotate(&position)
+            .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+    }
+
+    /// Undoes a single move and updates the game state if possible
+    fn undo_one(&mut self) -> CommandResult {
+        if self.annotations.len() == 1 {
+            return Err(CommandError::new(
+                ErrorCode::InvalidState,
+                "Cannot undo past the first move",
+            ));
+        }
+        let internal = |e: GameDebuggerError| CommandError::new(ErrorCode::Internal, e.to_string());
+        // Undoing takes back the move along with the resignation or the
+        // agreed draw that ended the game after it
+        if matches!(
+            self.game.game_result(),
+            Some(GameResult::Resigned(_) | GameResult::DrawAgreed)
+        ) {
+            self.game.undo_move().map_err(internal)?;
+        }
+        self.game.undo_move().map_err(internal)?;
+        self.annotations.pop();
+        self.player_to_move = self.player_to_move.opposite();
+        Ok(self.game_string())
+    }
+
+    /// Undo command,
+    /// Must be one of the following forms
+    ///
+    /// undo
+    /// undo number
+    ///
+    /// Either every move asked for is undone or, if there are not that many
+    /// moves, none is.
+    ///
+    /// See the Universal Hive Protocol wiki for more information
+    fn undo(&mut self, input: &str) -> CommandResult {
+        let argument = input["undo".len()..].trim();
+        let invalid = || {
+            CommandError::new(
+                ErrorCode::InvalidArgument,
+                "Invalid number for undo command",
+            )
+        };
+        let num = match argument {
+            "" => 1,
+            _ => argument.parse::<usize>().map_err(|_| invalid())?,
+        };
+        if num < 1 {
+            return Err(invalid());
+        }
+        if num >= self.annotations.len() {
+            return Err(CommandError::new(
+                ErrorCode::InvalidState,
+                "Cannot undo past the first move",
+            ));
+        }
+
+        for _ in 1..num {
+            self.undo_one()?;
+        }
+        self.undo_one()
+    }
+
+    /// Options command,
+    /// Must be one of the following forms
+    ///
+    /// options
+    /// options get OptionName
+    /// options set OptionName OptionValue
+    ///
+    /// The engine has no options yet, so the list is empty and every option
+    /// asked for is unknown
+    ///
+    /// See the Universal Hive Protocol wiki for more information
+    fn options(&mut self, input: &str) -> CommandResult {
+        let words = input["options".len()..]
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        match words.as_slice() {
+            [] => Ok("".to_string()),
+            ["get", name] | ["set", name, _] => Err(CommandError::new(
+                ErrorCode::UnknownOption,
+                format!("Unknown option {}", name),
+            )),
+            _ => Err(CommandError::new(
+                ErrorCode::InvalidArgument,
+                "Expected options, options get OptionName or options set OptionName OptionValue",
+            )),
+        }
+    }
+
+    pub fn current_position(&self) -> &HexGrid {
+        self.annotations
+            .last()
+            .expect("There should always be a current position")
+            .position()
+    }
+
+    /// Runs a command of the Universal Hive Protocol, returning its response
+    /// without the trailing "ok", or why it failed.
+    ///
+    /// Every command checks its arguments before changing the game, so a
+    /// command that fails leaves the game as it was
+    pub fn execute(&mut self, input: &str) -> CommandResult {
+        let input = input.trim();
+        let (name, arguments) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        match (name, arguments.trim()) {
+            ("info", "") => self.info(),
+            ("validmoves", "") => self.valid_moves(),
+            ("pass", "") => self.pass(),
+            ("info" | "validmoves" | "pass", _) => Err(CommandError::new(
+                ErrorCode::InvalidArgument,
+                format!("The {} command takes no arguments", name),
+            )),
+            ("bestmove", _) => self.best_move(input),
+            ("newgame", _) => self.new_game(input),
+            ("play", _) => self.play(input),
+            ("undo", _) => self.undo(input),
+            ("options", _) => self.options(input),
+            _ => self.unknown(input),
+        }
+    }
+
+    /// Parses commands according to the Universal Hive Protocol
+    /// and returns the appropriate response
+    ///
+    /// All responses end with "ok\n"
+    ///
+    /// If the command encounters an error, the string returned will
+    /// begin with "err" followed by an ErrorCode in accordance with the UHP,
+    /// or with "invalidmove" followed by the rule broken if a move was not
+    /// legal, see CommandError::to_uhp()
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip(self, input), fields(command = input.trim()))
+    )]
+    pub fn command(&mut self, input: &str) -> String {
+        let response = self.execute(input).unwrap_or_else(|e| e.to_uhp());
+        #[cfg(feature = "tracing")]
+        tracing::debug!(response = %response, "answered");
+
+        debug_assert!(
+            if !response.is_empty() {
+                !response.ends_with('\n')
+            } else {
+                true
+            },
+            "Non-empty response should not end with a newline"
+        );
+        if response.is_empty() {
+            "ok\n".to_string()
+        } else {
+            response + "\nok\n"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_uhp_interface_newgame() {
+        let mut uhp = UHPInterface::new();
+        let result = uhp.command("newgame");
+        assert_eq!(result, "Base;NotStarted;White[1]\nok\n")
+    }
+
+    #[test]
+    pub fn test_uhp_interface_newgame_game_type_strings() {
+        let mut uhp = UHPInterface::new();
+        let result = uhp.command("newgame Base+M");
+        assert_eq!(result, "Base+M;NotStarted;White[1]\nok\n");
+        let result = uhp.command("newgame Base+ML");
+        assert!(
+            result == "Base+ML;NotStarted;White[1]\nok\n"
+                || result == "Base+LM;NotStarted;White[1]\nok\n"
+        );
+
+        let result = uhp.command("newgame Base+LM");
+        assert!(
+            result == "Base+LM;NotStarted;White[1]\nok\n"
+                || result == "Base+ML;NotStarted;White[1]\nok\n"
+        );
+        let result = uhp.command("newgame Base+MLP");
+        assert!(
+            result == "Base+MLP;NotStarted;White[1]\nok\n"
+                || result == "Base+MPL;NotStarted;White[1]\nok\n"
+                || result == "Base+PLM;NotStarted;White[1]\nok\n"
+                || result == "Base+PML;NotStarted;White[1]\nok\n"
+                || result == "Base+LMP;NotStarted;White[1]\nok\n"
+                || result == "Base+LPM;NotStarted;White[1]\nok\n"
+        );
+    }
+
+    #[test]
+    pub fn test_uhp_interface_some_moves() {
+        let moves = [
+            r"wL", r"bP wL-", r"wA1 \wL", r"bB1 bP/", r"wQ /wA1", r"bQ bB1\",
+        ];
+        let final_position = HexGrid::from_dsl(concat!(
+            ". . . . . .\n",
+            " . A . b . .\n",
+            ". Q L p q .\n",
+            " . . . . . .\n\n",
+            "start - [ -1 -2 ]\n\n"
+        ));
+
+        let moves = moves.join(";");
+        let newgame = format!("newgame Base+LP;InProgress;White[4];{}", moves);
+
+        let mut uhp = UHPInterface::new();
+        let output = uhp.command(&newgame);
+
+        println!("OUTPUT: {}", output);
+        assert!(
+            output == format!("Base+LP;InProgress;White[4];{}\nok\n", moves)
+                || output == format!("Base+PL;InProgress;White[4];{}\nok\n", moves)
+        );
+        println!("{}", uhp.current_position().to_dsl());
+        println!("{}", final_position.to_dsl());
+        assert!(*uhp.current_position() == final_position);
+    }
+
+    #[test]
+    pub fn test_uhp_interface_play() {
+        let moves = [
+            r"wL", r"bP wL-", r"wA1 \wL", r"bB1 bP/", r"wQ /wA1", r"bQ bB1\",
+        ];
+        let final_position = HexGrid::from_dsl(concat!(
+            ". . . . . .\n",
+            " . A . b . .\n",
+            ". Q L p q .\n",
+            " . . . . . .\n\n",
+            "start - [ -1 -2 ]\n\n"
+        ));
+
+        let mut uhp = UHPInterface::new();
+        uhp.command("newgame Base+PL");
+
+        for (i, move_string) in moves.iter().enumerate() {
+            let turn_number = (i + 1) / 2 + 1;
+            let color = match (i + 1) % 2 {
+                0 => "White",
+                _ => "Black",
+            };
+            let output = uhp.command(&format!("play {}", move_string));
+            let moves = moves
+                .iter()
+                .take(i + 1)
+                .cloned()
+                .collect::<Vec<&str>>()
+                .join(";");
+
+            println!("GOT TO THIS OUTPUT {}", output);
+            assert!(
+                output
+                    == format!(
+                        "Base+PL;InProgress;{}[{}];{}\nok\n",
+                        color, turn_number, moves
+                    )
+                    || output
+                        == format!(
+                            "Base+LP;InProgress;{}[{}];{}\nok\n",
+                            color, turn_number, moves
+                        )
+            );
+        }
+
+        assert!(*uhp.current_position() == final_position);
+
+        assert_eq!(
+            uhp.command("play wG1 bQ-"),
+            "invalidmove placed pieces may not touch an opponent's piece\nok\n"
+        );
+        assert!(uhp.command("play wG9 bQ-").starts_with("err "));
+        assert!(*uhp.current_position() == final_position);
+    }
+
+    #[test]
+    pub fn test_uhp_interface_undo() {
+        let moves = [
+            r"wL", r"bP wL-", r"wA1 \wL", r"bB1 bP/", r"wQ /wA1", r"bQ bB1\",
+        ];
+
+        let mut uhp = UHPInterface::new();
+        uhp.command("newgame Base+PML;NotStarted;White[1]");
+        uhp.command(&format!("play {}", moves[0]));
+        uhp.command(&format!("play {}", moves[1]));
+        uhp.command(&format!("play {}", moves[2]));
+        uhp.command(&format!("play {}", moves[3]));
+        let output = uhp.command("undo");
+        assert_eq!(
+            &output[8..],
+            ";InProgress;Black[2];wL;bP wL-;wA1 \\wL\nok\n"
+        );
+
+        let output = uhp.command("undo");
+        assert_eq!(&output[8..], ";InProgress;White[2];wL;bP wL-\nok\n");
+
+        uhp.command(&format!("play {}", moves[2]));
+        let output = uhp.command("undo 2");
+        assert_eq!(&output[8..], ";InProgress;Black[1];wL\nok\n");
+    }
+
+    #[test]
+    pub fn test_uhp_interface_best_move() {
+        let mut uhp = UHPInterface::new();
+        uhp.command("newgame Base;InProgress;White[2];wA1;bG1 -wA1");
+        for command in [
+            "bestmove",
+            "bestmove depth 1",
+            "bestmove time 00:00:01",
+            "bestmove nodes 200",
+            "bestmove depth 2 nodes 1",
+        ] {
+            let output = uhp.command(command);
+            let move_string = output.strip_suffix("\nok\n").unwrap();
+            let mut game = uhp.game_debugger();
+            assert!(game.make_move(move_string).is_ok(), "{}", output);
+        }
+        for command in [
+            "bestmove depth",
+            "bestmove depth 0",
+            "bestmove time 10",
+            "bestmove moves 3",
+        ] {
+            assert!(uhp.command(command).starts_with("err "), "{}", command);
+        }
+
+        let limits = parse_search_limits(" time 01:02:05 depth 4").unwrap();
+        assert_eq!(limits.time, Some(std::time::Duration::from_secs(3725)));
+        assert_eq!((limits.depth, limits.nodes), (4, None));
+
+        let white_wins = r"Base+PL;WhiteWins;Black[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/;wB1 \bL";
+        uhp.command(&format!("newgame {}", white_wins));
+        assert_eq!(
+            uhp.command("bestmove depth 1"),
+            "err InvalidState: The game is over\nok\n"
+        );
+    }
+
+    #[test]
+    pub fn test_uhp_interface_errors() {
+        let mut uhp = UHPInterface::new();
+        let code = |uhp: &mut UHPInterface, command: &str| uhp.execute(command).map_err(|e| e.code);
+        assert_eq!(code(&mut uhp, "hello"), Err(ErrorCode::UnknownCommand));
+        assert_eq!(code(&mut uhp, "playwA1"), Err(ErrorCode::UnknownCommand));
+        assert_eq!(code(&mut uhp, "play"), Err(ErrorCode::InvalidArgument));
+        assert_eq!(
+            code(&mut uhp, "bestmove depth"),
+            Err(ErrorCode::InvalidArgument)
+        );
+        assert_eq!(
+            code(&mut uhp, "newgame Chess"),
+            Err(ErrorCode::InvalidGameType)
+        );
+        assert_eq!(
+            code(&mut uhp, "play wZ1"),
+            Err(ErrorCode::InvalidMoveString)
+        );
+        assert_eq!(code(&mut uhp, "undo"), Err(ErrorCode::InvalidState));
+        assert_eq!(code(&mut uhp, "options"), Ok("".to_string()));
+        assert_eq!(
+            code(&mut uhp, "options get Foo"),
+            Err(ErrorCode::UnknownOption)
+        );
+        assert_eq!(
+            code(&mut uhp, "options sit Foo"),
+            Err(ErrorCode::InvalidArgument)
+        );
+        uhp.command("play wA1");
+        assert_eq!(code(&mut uhp, "play bQ wA1-"), Err(ErrorCode::InvalidMove));
+
+        // Not a single one panics
+        for command in [
+            "play é",
+            "play wA1 -",
+            "play wA1 wA1",
+            "play bG1 ##",
+            "play pass pass",
+            "play bQ1",
+            "play bG1 /wA2",
+            "undo -1",
+            "undo 99999999999999999999",
+            "bestmove time 1:2",
+            "bestmove nodes 0",
+            "newgame ;;;",
+            "newgame Base;",
+            "newgame Base;InProgress;White[1];é",
+            "validmoves now",
+            "\u{0}",
+        ] {
+            let error = uhp.execute(command).unwrap_err();
+            assert_ne!(error.code, ErrorCode::Internal, "{}: {}", command, error);
+        }
+        assert_eq!(uhp.game_string(), "Base;InProgress;Black[1];wA1");
+
+        let error = CommandError::new(ErrorCode::InvalidState, "The game is over");
+        assert_eq!(error.to_uhp(), "err InvalidState: The game is over");
+        assert_eq!(CommandError::from_uhp(&error.to_uhp()), Some(error));
+        let invalid_move = CommandError::from_uhp("invalidmove queen not placed").unwrap();
+        assert_eq!(invalid_move.code, ErrorCode::InvalidMove);
+        assert_eq!(invalid_move.message, "queen not placed");
+        let other = CommandError::from_uhp("err Something broke").unwrap();
+        assert_eq!(other.code, ErrorCode::Internal);
+        assert_eq!(CommandError::from_uhp("wA1"), None);
+    }
+
+    #[test]
+    pub fn test_uhp_interface_restore() {
+        let game_string = r"Base+M;InProgress;White[3];wA1;bG1 -wA1;wQ wA1-;bQ /bG1";
+        let mut uhp = UHPInterface::new();
+        let output = uhp.command(&format!("newgame {}", game_string));
+        assert_eq!(output, format!("{}\nok\n", game_string));
+
+        // Rewinding and restoring through the protocol
+        let output = uhp.command("undo 3");
+        assert_eq!(output, "Base+M;InProgress;Black[1];wA1\nok\n");
+        assert_eq!(
+            uhp.command("undo 2"),
+            "err InvalidState: Cannot undo past the first move\nok\n"
+        );
+        assert!(uhp.command("undo two").starts_with("err "));
+        assert!(uhp.command("undo 0").starts_with("err "));
+        uhp.command(&format!("newgame {}", game_string));
+        assert_eq!(uhp.game_string(), game_string);
+
+        // Invalid GameStrings leave the game as it was
+        let invalid = [
+            r"Base+M;InProgress;Black[3];wA1;bG1 -wA1;wQ wA1-;bQ /bG1",
+            r"Base+M;NotStarted;White[3];wA1;bG1 -wA1;wQ wA1-;bQ /bG1",
+            r"Base+M;InProgress;White[3];wA1;bG1 -wA1;wQ wA1-;bQ bG1",
+            r"Base+X;InProgress;White[3];wA1;bG1 -wA1;wQ wA1-;bQ /bG1",
+            r"Base+M;InProgress",
+        ];
+        for newgame in invalid {
+            let output = uhp.command(&format!("newgame {}", newgame));
+            assert!(output.starts_with("err "), "{}", output);
+            assert_eq!(uhp.game_string(), game_string);
+        }
+        let output = uhp.command("newgame Base+M;InProgress;Black[1];wA1;bQ");
+        assert!(
+            output.contains("Cannot play bQ of the GameString"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    pub fn test_game_states_output() {
+        let draw_before = r"Base;InProgress;Black[6];wA1;bA1 wA1-;wQ -wA1;bQ bA1-;wQ \wA1;bQ bA1/;wQ -wA1;bQ bA1-;wQ /wA1;bQ bA1\;wQ -wA1";
+        let draw_last_move = r"bQ bA1-";
+        let draw_complete = r"Base;Draw;White[7];wA1;bA1 wA1-;wQ -wA1;bQ bA1-;wQ \wA1;bQ bA1/;wQ -wA1;bQ bA1-;wQ /wA1;bQ bA1\;wQ -wA1;bQ bA1-";
+
+        let black_wins_before = r"Base+LMP;InProgress;White[8];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bA2 bQ/;wB1 wP;bA1 /wA1;wB1 wP\;bA2 bA1\;wA2 \wP;bA3 bQ\";
+        let black_wins_last_move = r"wA2 wQ\";
+        let black_wins_complete = r"Base+LMP;BlackWins;Black[8];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bA2 bQ/;wB1 wP;bA1 /wA1;wB1 wP\;bA2 bA1\;wA2 \wP;bA3 bQ\;wA2 wQ\";
+
+        let white_wins_before = r"Base+PL;InProgress;White[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/";
+        let white_wins_last_move = r"wB1 \bL";
+        let white_wins_complete = r"Base+PL;WhiteWins;Black[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/;wB1 \bL";
+
+        let mut uhp = UHPInterface::new();
+
+        uhp.command(&format!("newgame {}", draw_before));
+        let output = uhp.command(&format!("play {}", draw_last_move));
+        println!("{}", output);
+        println!("{}", format!("{}\nok\n", draw_complete));
+        assert!(output == format!("{}\nok\n", draw_complete));
+
+        uhp.command(&format!("newgame {}", black_wins_before));
+        let output = uhp.command(&format!("play {}", black_wins_last_move));
+        let output = output.to_string();
+        let black_wins_complete = black_wins_complete.to_string();
+        println!("{}", &output[8..]);
+        println!("{}", &format!("{}\nok\n", black_wins_complete)[8..]);
+        assert!(output[8..] == format!("{}\nok\n", black_wins_complete)[8..]);
+
+        uhp.command(&format!("newgame {}", white_wins_before));
+        let output = uhp.command(&format!("play {}", white_wins_last_move));
+        println!("{}", output);
+        println!("{}", format!("{}\nok\n", white_wins_complete));
+        assert!(output[7..] == format!("{}\nok\n", white_wins_complete)[7..]);
+    }
+
+    #[test]
+    pub fn test_game_states_input() {
+        let mut uhp = UHPInterface::new();
+        let draw_game_string = r"Base;Draw;White[7];wA1;bA1 wA1-;wQ -wA1;bQ bA1-;wQ \wA1;bQ bA1/;wQ -wA1;bQ bA1-;wQ /wA1;bQ bA1\;wQ -wA1;bQ bA1-";
+        let output = uhp.command(&format!("newgame {}", draw_game_string));
+        println!("{}", output);
+        println!("{}", format!("{}\nok\n", draw_game_string));
+        assert!(output == format!("{}\nok\n", draw_game_string));
+
+        let black_wins = r"Base+LMP;BlackWins;Black[8];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bA2 bQ/;wB1 wP;bA1 /wA1;wB1 wP\;bA2 bA1\;wA2 \wP;bA3 bQ\;wA2 wQ\";
+        let output = uhp.command(&format!("newgame {}", black_wins));
+        println!("{}", output);
+        println!("{}", format!("{}\nok\n", black_wins));
+        assert!(output[8..] == format!("{}\nok\n", black_wins)[8..]);
+
+        let white_wins = r"Base+PL;WhiteWins;Black[7];wP;bL wP-;wB1 \wP;bQ bL/;wA1 /wB1;bA1 \bQ;wQ wA1\;bB1 bQ/;wB1 wP;bG1 bB1\;wA1 bQ\;bG2 bG1/;wB1 \bL";
+        let output = uhp.command(&format!("newgame {}", white_wins));
+        println!("{}", output);
+        println!("{}", format!("{}\nok\n", white_wins));
+        assert!(output[7..] == format!("{}\nok\n", white_wins)[7..]);
+
+        // Games ended by resignation or an agreed draw before the end
+        for ended in [
+            "Base;BlackWins;White[2];wA1;bG1 -wA1",
+            "Base;Draw;Black[2];wA1;bG1 -wA1;wQ wA1-",
+        ] {
+            let output = uhp.command(&format!("newgame {}", ended));
+            assert_eq!(output, format!("{}\nok\n", ended));
+            assert!(uhp.command("play wQ wA1-").starts_with("err InvalidState"));
+        }
+        let output = uhp.command("undo");
+        assert_eq!(output, "Base;InProgress;White[2];wA1;bG1 -wA1\nok\n");
+    }
+}
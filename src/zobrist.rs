@@ -0,0 +1,103 @@
+use crate::hex_grid::Height;
+use crate::location::HexLocation;
+use crate::piece::{IntoPieces, Piece, PieceColor};
+
+/// The random key of a piece at a given location and height, the same
+/// on every run and for every location a HexLocation can hold
+pub fn key(piece: Piece, location: HexLocation, height: Height) -> u64 {
+    let seed = (piece.to_byte() as u64)
+        | (location.x as u16 as u64) << 8
+        | (location.y as u16 as u64) << 24
+        | (height as u64) << 40;
+    splitmix64(seed)
+}
+
+/// The Zobrist hash of a position, the xor of the key() of every piece on the
+/// board, so that it can be updated incrementally with update() as pieces move
+pub fn hash(position: &impl IntoPieces) -> u64 {
+    let mut hash = 0;
+    for (stack, location) in position.pieces() {
+        for (height, &piece) in stack.iter().enumerate() {
+            hash = update(hash, piece, location, height);
+        }
+    }
+    hash
+}
+
+/// The key of the state of a game, which unlike hash() tells apart the same
+/// board with a different player to move, or with a different piece the
+/// pillbug may not throw: *last_move* is the destination of the piece moved
+/// in the previous turn
+pub fn state_key(
+    position: &impl IntoPieces,
+    last_move: Option<HexLocation>,
+    color: PieceColor,
+) -> u64 {
+    let mut key = hash(position);
+    if color == PieceColor::Black {
+        key ^= splitmix64(u64::MAX);
+    }
+    if let Some(location) = last_move {
+        let seed = (location.x as u16 as u64) | (location.y as u16 as u64) << 16 | 1 << 32;
+        key ^= splitmix64(seed);
+    }
+    key
+}
+
+/// Adds or removes a piece at a given location and height from *hash*
+pub fn update(hash: u64, piece: Piece, location: HexLocation, height: Height) -> u64 {
+    hash ^ key(piece, location, height)
+}
+
+/// A well mixed 64 bit hash of *seed*, see
+/// https://prng.di.unimi.it/splitmix64.c
+pub(crate) fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex_grid::HexGrid;
+    use crate::piece::{PieceColor, PieceType};
+
+    #[test]
+    pub fn test_zobrist_hash() {
+        let grid = HexGrid::from_dsl(concat!(
+            ". . . . .\n",
+            " . Q 2 a .\n",
+            ". . g . .\n",
+            " . . . . .\n\n",
+            "start - [0 0]\n\n",
+            "2 - [ b B ]\n",
+        ));
+        assert_eq!(hash(&HexGrid::new()), 0);
+        assert_eq!(hash(&grid), hash(&grid.clone()));
+        assert_ne!(hash(&grid), hash(&grid.translate(HexLocation::new(1, 0))));
+        // Locations far apart have keys of their own
+        assert_ne!(hash(&grid), hash(&grid.translate(HexLocation::new(256, 0))));
+        assert_ne!(hash(&grid), hash(&grid.translate(HexLocation::new(0, -256))));
+
+        // Moving the ant one hex to the east, incrementally
+        let ant = Piece::new(PieceType::Ant, PieceColor::Black);
+        let (from, height) = grid.find(ant).unwrap();
+        let to = HexLocation::new(from.x + 1, from.y);
+        let mut moved = grid.clone();
+        moved.remove(from);
+        moved.add(ant, to);
+        let incremental = update(update(hash(&grid), ant, from, height), ant, to, 0);
+        assert_eq!(incremental, hash(&moved));
+
+        let white = state_key(&moved, Some(to), PieceColor::White);
+        assert_ne!(white, hash(&moved));
+        assert_ne!(white, state_key(&moved, Some(to), PieceColor::Black));
+        assert_ne!(white, state_key(&moved, None, PieceColor::White));
+        assert_ne!(white, state_key(&moved, Some(from), PieceColor::White));
+        let far = HexLocation::new(to.x + 256, to.y - 256);
+        assert_ne!(white, state_key(&moved, Some(far), PieceColor::White));
+        assert_eq!(state_key(&moved, None, PieceColor::White), hash(&moved));
+    }
+}